@@ -26,7 +26,8 @@ fn bench_string_operations(c: &mut Criterion) {
         b.to_async(&runtime).iter(|| async {
             let request = Request::Set { 
                 key: "testkey".to_string(), 
-                value: "small".to_string() 
+                value: "small".to_string(),
+                options: Default::default(),
             };
             executor.execute(black_box(request)).await.unwrap()
         })
@@ -38,7 +39,8 @@ fn bench_string_operations(c: &mut Criterion) {
         b.to_async(&runtime).iter(|| async {
             let request = Request::Set { 
                 key: "testkey".to_string(), 
-                value: kb_value.clone() 
+                value: kb_value.clone(),
+                options: Default::default(),
             };
             executor.execute(black_box(request)).await.unwrap()
         })
@@ -113,7 +115,7 @@ fn bench_list_operations(c: &mut Criterion) {
         runtime.block_on(async {
             let list_key = format!("list_{}", size);
             let values: Vec<String> = (0..size).map(|i| format!("item{}", i)).collect();
-            storage.set(&list_key, DataType::List(values)).await.unwrap();
+            storage.set(&list_key, DataType::List(values.into())).await.unwrap();
         });
         
         group.bench_with_input(
@@ -132,6 +134,32 @@ fn bench_list_operations(c: &mut Criterion) {
         );
     }
     
+    // Queue pattern on a large list in memory: push at the head, pop at the tail
+    for size in [10_000, 100_000] {
+        let mut list = DataType::List(Default::default());
+        list.rpush((0..size).map(|i| format!("item{}", i)).collect()).unwrap();
+        group.bench_with_input(
+            BenchmarkId::new("queue_lpush_rpop", size),
+            &size,
+            |b, _| {
+                b.iter(|| {
+                    list.lpush(vec![black_box("item".to_string())]).unwrap();
+                    list.rpop().unwrap()
+                })
+            }
+        );
+        group.bench_with_input(
+            BenchmarkId::new("queue_rpush_lpop", size),
+            &size,
+            |b, _| {
+                b.iter(|| {
+                    list.rpush(vec![black_box("item".to_string())]).unwrap();
+                    list.lpop().unwrap()
+                })
+            }
+        );
+    }
+    
     group.finish();
 }
 
@@ -234,7 +262,8 @@ fn bench_sorted_set_operations(c: &mut Criterion) {
         b.to_async(&runtime).iter(|| async {
             let request = Request::ZAdd { 
                 key: "myzset".to_string(), 
-                members: vec![("member".to_string(), 1.0)]
+                members: vec![(1.0, "member".to_string())],
+                flags: Default::default(),
             };
             executor.execute(black_box(request)).await.unwrap()
         })
@@ -291,7 +320,8 @@ fn bench_concurrent_operations(c: &mut Criterion) {
                             for i in 0..100 {
                                 let request = Request::Set {
                                     key: format!("key_{}_{}", t, i),
-                                    value: "value".to_string()
+                                    value: "value".to_string(),
+                                    options: Default::default(),
                                 };
                                 exec.execute(request).await.unwrap();
                             }
@@ -1,7 +1,7 @@
 use criterion::{criterion_group, criterion_main, Criterion, BenchmarkId};
 use diskdb::data_types::DataType;
 use diskdb::data_types_pooled::PooledStorageOps;
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 fn benchmark_string_allocation(c: &mut Criterion) {
     let mut group = c.benchmark_group("string_allocation");
@@ -52,7 +52,7 @@ fn benchmark_list_operations(c: &mut Criterion) {
             &size,
             |b, &size| {
                 b.iter(|| {
-                    let mut data = DataType::List(Vec::new());
+                    let mut data = DataType::List(VecDeque::new());
                     for i in 0..size {
                         data.lpush(vec![format!("item{}", i)]).unwrap();
                     }
@@ -117,7 +117,7 @@ fn benchmark_mixed_operations(c: &mut Criterion) {
             
             // Allocate lists
             for i in 0..50 {
-                let mut list = DataType::List(Vec::new());
+                let mut list = DataType::List(VecDeque::new());
                 list.lpush(vec![format!("item{}", i)]).unwrap();
                 results.push(list);
             }
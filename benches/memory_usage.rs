@@ -80,7 +80,7 @@ fn bench_memory_per_key_type(c: &mut Criterion) {
                 for i in 0..10 {
                     let key = format!("list_{}", i);
                     let values: Vec<String> = (0..100).map(|j| format!("item_{}", j)).collect();
-                    storage.set(&key, DataType::List(values)).await.unwrap();
+                    storage.set(&key, DataType::List(values.into())).await.unwrap();
                 }
                 
                 let after = get_allocated_bytes();
@@ -129,7 +129,7 @@ fn bench_allocation_patterns(c: &mut Criterion) {
                 
                 // Pre-create a list
                 let initial_list: Vec<String> = (0..100).map(|i| format!("item_{}", i)).collect();
-                storage.set("mylist", DataType::List(initial_list)).await.unwrap();
+                storage.set("mylist", DataType::List(initial_list.into())).await.unwrap();
                 
                 reset_alloc_counter();
                 
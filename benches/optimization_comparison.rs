@@ -1,7 +1,7 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId};
 use diskdb::protocol::Request;
 use diskdb::data_types::DataType;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 #[cfg(feature = "memory_pool")]
 use diskdb::data_types_pooled::PooledStorageOps;
@@ -82,7 +82,7 @@ fn benchmark_memory_allocation(c: &mut Criterion) {
             &size,
             |b, &size| {
                 b.iter(|| {
-                    let mut list = DataType::List(Vec::with_capacity(size));
+                    let mut list = DataType::List(VecDeque::with_capacity(size));
                     for i in 0..size {
                         list.lpush(vec![format!("item{}", i)]).unwrap();
                     }
@@ -146,7 +146,7 @@ fn benchmark_data_operations(c: &mut Criterion) {
         b.iter(|| {
             let mut zset = DataType::SortedSet(std::collections::BTreeMap::new());
             for i in 0..100 {
-                zset.zadd(vec![(i as f64, format!("member{}", i))]).unwrap();
+                zset.zadd(vec![(i as f64, format!("member{}", i))], Default::default()).unwrap();
             }
             let _ = zset.zrange(0, 50, false).unwrap();
             black_box(zset);
@@ -181,7 +181,7 @@ fn benchmark_combined_workload(c: &mut Criterion) {
             let mut string_data = DataType::String("value".to_string());
             string_data.incr(1).unwrap();
             
-            let mut list_data = DataType::List(Vec::new());
+            let mut list_data = DataType::List(VecDeque::new());
             list_data.lpush(vec!["item1".to_string(), "item2".to_string()]).unwrap();
             
             let mut hash_data = DataType::Hash(HashMap::new());
@@ -1,5 +1,6 @@
 use std::env;
 use std::path::PathBuf;
+use std::process::Command;
 
 fn main() {
     // Tell cargo to rerun this if the C files change
@@ -32,4 +33,37 @@ fn main() {
     
     // Link pthread for thread-local storage
     println!("cargo:rustc-link-lib=pthread");
+    
+    // Build metadata reported by the VERSION command
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=DISKDB_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=DISKDB_ROCKSDB_VERSION={}", rocksdb_requirement());
+}
+
+/// The rocksdb crate version required by the manifest, e.g. `0.21.0`
+fn rocksdb_requirement() -> String {
+    let manifest = std::fs::read_to_string("Cargo.toml").unwrap_or_default();
+    manifest.lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("rocksdb ") || line.starts_with("rocksdb="))
+        .and_then(|line| {
+            // Either `rocksdb = "x"` or `rocksdb = { version = "x", ... }`
+            let value = line.split_once('=')?.1;
+            let value = match value.find("version") {
+                Some(pos) => &value[pos..],
+                None => value,
+            };
+            value.split('"').nth(1).map(str::to_string)
+        })
+        .unwrap_or_else(|| "unknown".to_string())
 }
\ No newline at end of file
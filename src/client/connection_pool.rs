@@ -1,29 +1,90 @@
 use std::collections::VecDeque;
+use std::io;
 use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::task::{Context, Poll};
 use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tokio::net::TcpStream;
 use tokio::sync::{Mutex, Semaphore};
 use tokio::time::timeout;
+use tokio_native_tls::{TlsConnector, TlsStream};
+use crate::client::tls::TlsClientConfig;
 use crate::error::{Result, DiskDBError};
+use crate::protocol::Response;
+use crate::resp;
 
-const DEFAULT_POOL_SIZE: usize = 10;
-const DEFAULT_MIN_CONNECTIONS: usize = 2;
+pub(crate) const DEFAULT_POOL_SIZE: usize = 10;
+pub(crate) const DEFAULT_MIN_CONNECTIONS: usize = 2;
 const CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
 const IDLE_TIMEOUT: Duration = Duration::from_secs(300); // 5 minutes
 
+/// A client connection to the server, either plain TCP or TLS
+pub enum ClientStream {
+    Plain(TcpStream),
+    Tls(TlsStream<TcpStream>),
+}
+
+impl ClientStream {
+    /// The underlying TCP socket
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            ClientStream::Plain(stream) => stream,
+            ClientStream::Tls(stream) => stream.get_ref().get_ref().get_ref(),
+        }
+    }
+}
+
+impl AsyncRead for ClientStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            ClientStream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ClientStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ClientStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            ClientStream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            ClientStream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ClientStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            ClientStream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
 /// A pooled connection with metadata
 struct PooledConnection {
-    stream: TcpStream,
+    stream: ClientStream,
+    /// Whether the server switched the connection to RESP3 replies; None until
+    /// HELLO has been tried
+    framed: Option<bool>,
     created_at: Instant,
     last_used: Instant,
 }
 
 impl PooledConnection {
-    fn new(stream: TcpStream) -> Self {
+    fn new(stream: ClientStream, framed: Option<bool>) -> Self {
         let now = Instant::now();
         Self {
             stream,
+            framed,
             created_at: now,
             last_used: now,
         }
@@ -41,6 +102,10 @@ impl PooledConnection {
 /// Connection pool for DiskDB clients
 pub struct ConnectionPool {
     addr: SocketAddr,
+    /// Connector and server name used to wrap new connections in TLS
+    tls: Option<(TlsConnector, String)>,
+    /// Whether connections negotiate RESP3 with HELLO before use
+    resp3: Arc<AtomicBool>,
     connections: Arc<Mutex<VecDeque<PooledConnection>>>,
     semaphore: Arc<Semaphore>,
     max_size: usize,
@@ -55,8 +120,30 @@ impl ConnectionPool {
     
     /// Create a connection pool with custom configuration
     pub fn with_config(addr: SocketAddr, max_size: usize, min_connections: usize) -> Self {
+        Self::build(addr, None, max_size, min_connections)
+    }
+    
+    /// Create a connection pool whose connections are secured with TLS
+    pub fn with_tls(
+        addr: SocketAddr,
+        tls_config: &TlsClientConfig,
+        max_size: usize,
+        min_connections: usize,
+    ) -> Result<Self> {
+        let connector = tls_config.create_connector()?;
+        Ok(Self::build(addr, Some((connector, tls_config.domain.clone())), max_size, min_connections))
+    }
+    
+    fn build(
+        addr: SocketAddr,
+        tls: Option<(TlsConnector, String)>,
+        max_size: usize,
+        min_connections: usize,
+    ) -> Self {
         let pool = Self {
             addr,
+            tls,
+            resp3: Arc::new(AtomicBool::new(false)),
             connections: Arc::new(Mutex::new(VecDeque::with_capacity(max_size))),
             semaphore: Arc::new(Semaphore::new(max_size)),
             max_size,
@@ -72,8 +159,40 @@ impl ConnectionPool {
         pool
     }
     
+    /// Have connections ask for RESP3 replies with HELLO 3 before their next use.
+    /// RESP3 frames every reply, so one whose text form spans several lines, such
+    /// as an array, is still read as one. Servers that refuse HELLO stay on the
+    /// line protocol.
+    pub fn set_resp3(&self, enabled: bool) {
+        self.resp3.store(enabled, Ordering::Relaxed);
+    }
+    
     /// Get a connection from the pool
     pub async fn get(&self) -> Result<PooledTcpStream> {
+        let mut conn = self.checkout().await?;
+        if self.resp3.load(Ordering::Relaxed) && conn.framed.is_none() {
+            let stream = conn.stream_mut();
+            match timeout(CONNECTION_TIMEOUT, Self::hello(stream)).await {
+                Ok(Ok(framed)) => conn.framed = Some(framed),
+                // The reply may still be on its way, so the connection is not pooled again
+                Ok(Err(e)) => {
+                    drop(conn.into_inner());
+                    return Err(e);
+                }
+                Err(_) => {
+                    drop(conn.into_inner());
+                    return Err(DiskDBError::Io(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "HELLO timeout",
+                    )));
+                }
+            }
+        }
+        Ok(conn)
+    }
+    
+    /// Take an idle connection, or open a new one
+    async fn checkout(&self) -> Result<PooledTcpStream> {
         // Acquire permit
         let permit = self.semaphore.clone().acquire_owned().await
             .map_err(|_| DiskDBError::Protocol("Failed to acquire connection permit".to_string()))?;
@@ -92,6 +211,7 @@ impl ConnectionPool {
             if Self::is_connection_alive(&conn.stream).await {
                 return Ok(PooledTcpStream {
                     stream: Some(conn.stream),
+                    framed: conn.framed,
                     pool: self.connections.clone(),
                     _permit: permit,
                 });
@@ -102,29 +222,68 @@ impl ConnectionPool {
         let stream = self.create_connection().await?;
         Ok(PooledTcpStream {
             stream: Some(stream),
+            framed: None,
             pool: self.connections.clone(),
             _permit: permit,
         })
     }
     
     /// Create a new connection
-    async fn create_connection(&self) -> Result<TcpStream> {
-        match timeout(CONNECTION_TIMEOUT, TcpStream::connect(self.addr)).await {
+    async fn create_connection(&self) -> Result<ClientStream> {
+        let stream = match timeout(CONNECTION_TIMEOUT, TcpStream::connect(self.addr)).await {
             Ok(Ok(stream)) => {
                 // Set TCP_NODELAY
                 stream.set_nodelay(true)?;
-                Ok(stream)
+                stream
             }
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => return Err(DiskDBError::Io(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "Connection timeout",
+            ))),
+        };
+        
+        let Some((connector, domain)) = &self.tls else {
+            return Ok(ClientStream::Plain(stream));
+        };
+        
+        // The handshake shares the connect timeout
+        match timeout(CONNECTION_TIMEOUT, connector.connect(domain, stream)).await {
+            Ok(Ok(tls_stream)) => Ok(ClientStream::Tls(tls_stream)),
             Ok(Err(e)) => Err(e.into()),
             Err(_) => Err(DiskDBError::Io(std::io::Error::new(
                 std::io::ErrorKind::TimedOut,
-                "Connection timeout",
+                "TLS handshake timeout",
             ))),
         }
     }
     
+    /// Switch the connection to RESP3 replies. A server that only speaks the line
+    /// protocol refuses HELLO with one text line and the connection stays on it;
+    /// returns whether the switch was made.
+    async fn hello(stream: &mut ClientStream) -> Result<bool> {
+        stream.write_all(b"HELLO 3\r\n").await?;
+        let mut buf = Vec::new();
+        loop {
+            if stream.read_buf(&mut buf).await? == 0 {
+                return Err(DiskDBError::ConnectionClosed);
+            }
+            // RESP3 answers with a map of server details
+            if buf[0] != b'%' {
+                if buf.contains(&b'\n') {
+                    return Ok(false);
+                }
+                continue;
+            }
+            if resp::decode_reply(&buf)?.is_some() {
+                return Ok(true);
+            }
+        }
+    }
+    
     /// Check if a connection is still alive
-    async fn is_connection_alive(stream: &TcpStream) -> bool {
+    async fn is_connection_alive(stream: &ClientStream) -> bool {
+        let stream = stream.tcp();
         // Try to read with zero timeout - if connection is closed, it will return immediately
         let mut buf = [0u8; 1];
         match timeout(Duration::from_millis(1), stream.peek(&mut buf)).await {
@@ -141,7 +300,7 @@ impl ConnectionPool {
         
         for _ in 0..self.min_connections {
             match self.create_connection().await {
-                Ok(stream) => connections.push(PooledConnection::new(stream)),
+                Ok(stream) => connections.push(PooledConnection::new(stream, None)),
                 Err(e) => {
                     log::warn!("Failed to pre-warm connection: {}", e);
                     break;
@@ -174,6 +333,8 @@ impl Clone for ConnectionPool {
     fn clone(&self) -> Self {
         Self {
             addr: self.addr,
+            tls: self.tls.clone(),
+            resp3: self.resp3.clone(),
             connections: self.connections.clone(),
             semaphore: self.semaphore.clone(),
             max_size: self.max_size,
@@ -182,28 +343,63 @@ impl Clone for ConnectionPool {
     }
 }
 
-/// A TCP stream that returns to the pool when dropped
+/// A client stream that returns to the pool when dropped
 pub struct PooledTcpStream {
-    stream: Option<TcpStream>,
+    stream: Option<ClientStream>,
+    framed: Option<bool>,
     pool: Arc<Mutex<VecDeque<PooledConnection>>>,
     _permit: tokio::sync::OwnedSemaphorePermit,
 }
 
 impl PooledTcpStream {
     /// Get a reference to the inner stream
-    pub fn stream(&self) -> &TcpStream {
+    pub fn stream(&self) -> &ClientStream {
         self.stream.as_ref().expect("Stream already taken")
     }
     
     /// Get a mutable reference to the inner stream
-    pub fn stream_mut(&mut self) -> &mut TcpStream {
+    pub fn stream_mut(&mut self) -> &mut ClientStream {
         self.stream.as_mut().expect("Stream already taken")
     }
     
     /// Take ownership of the stream (removes from pool)
-    pub fn into_inner(mut self) -> TcpStream {
+    pub fn into_inner(mut self) -> ClientStream {
         self.stream.take().expect("Stream already taken")
     }
+    
+    /// Read exactly `count` replies: whole RESP3 frames, or on a line-protocol
+    /// connection one line each, which only keeps count of replies that fit on one
+    /// line. The server sends nothing unasked, so no bytes past the last reply are
+    /// consumed.
+    pub async fn read_replies(&mut self, count: usize) -> Result<Vec<Response>> {
+        let framed = self.framed == Some(true);
+        let stream = self.stream_mut();
+        let mut buf = Vec::with_capacity(4096);
+        let mut pos = 0;
+        let mut replies = Vec::with_capacity(count);
+        while replies.len() < count {
+            let next = if framed {
+                resp::decode_reply(&buf[pos..])?
+            } else {
+                match buf[pos..].iter().position(|&b| b == b'\n') {
+                    Some(end) => Some((Response::parse(&String::from_utf8_lossy(&buf[pos..pos + end]))?, end + 1)),
+                    None => None,
+                }
+            };
+            match next {
+                Some((reply, used)) => {
+                    replies.push(reply);
+                    pos += used;
+                }
+                None => {
+                    if stream.read_buf(&mut buf).await? == 0 {
+                        return Err(DiskDBError::ConnectionClosed);
+                    }
+                }
+            }
+        }
+        Ok(replies)
+    }
 }
 
 impl Drop for PooledTcpStream {
@@ -211,10 +407,11 @@ impl Drop for PooledTcpStream {
         if let Some(stream) = self.stream.take() {
             // Return to pool if healthy
             let pool = self.pool.clone();
+            let framed = self.framed;
             tokio::spawn(async move {
                 if ConnectionPool::is_connection_alive(&stream).await {
                     let mut connections = pool.lock().await;
-                    connections.push_back(PooledConnection::new(stream));
+                    connections.push_back(PooledConnection::new(stream, framed));
                 }
             });
         }
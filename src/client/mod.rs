@@ -1,5 +1,7 @@
 pub mod connection_pool;
 pub mod optimized_client;
+pub mod tls;
 
-pub use connection_pool::{ConnectionPool, PoolStats};
-pub use optimized_client::OptimizedClient;
\ No newline at end of file
+pub use connection_pool::{ClientStream, ConnectionPool, PoolStats};
+pub use optimized_client::OptimizedClient;
+pub use tls::TlsClientConfig;
\ No newline at end of file
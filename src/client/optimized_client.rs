@@ -1,20 +1,28 @@
-use crate::client::connection_pool::ConnectionPool;
+use crate::client::connection_pool::{ConnectionPool, DEFAULT_MIN_CONNECTIONS, DEFAULT_POOL_SIZE};
+use crate::client::tls::TlsClientConfig;
 use crate::error::{Result, DiskDBError};
-use crate::protocol::{Request, Response};
+use crate::protocol::{Request, Response, SetOptions};
 use crate::network::buffer_pool::GLOBAL_BUFFER_POOL;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::sync::Mutex;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{oneshot, Mutex};
 use tokio::time::{timeout, Duration};
 
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
 
-/// Optimized DiskDB client with connection pooling and pipelining
+/// A request waiting in the pipeline buffer, and where its reply goes
+type Pending = (Request, oneshot::Sender<Result<Response>>);
+
+/// Optimized DiskDB client with connection pooling and pipelining.
+///
+/// With pipelining enabled, requests from concurrent `execute` calls are
+/// buffered and sent together, up to `max_pipeline_size` at a time, and each
+/// caller gets the reply to its own request.
 pub struct OptimizedClient {
     pool: Arc<ConnectionPool>,
     pipeline_enabled: bool,
-    pipeline_buffer: Arc<Mutex<Vec<Request>>>,
+    pipeline_buffer: Arc<Mutex<Vec<Pending>>>,
     max_pipeline_size: usize,
 }
 
@@ -49,78 +57,70 @@ impl OptimizedClient {
         })
     }
     
+    /// Create a client whose pooled connections use TLS
+    pub async fn connect_tls(addr: &str, tls_config: &TlsClientConfig) -> Result<Self> {
+        let addr: SocketAddr = addr.parse()
+            .map_err(|e| DiskDBError::Config(format!("Invalid address: {}", e)))?;
+        
+        let pool = Arc::new(ConnectionPool::with_tls(addr, tls_config, DEFAULT_POOL_SIZE, DEFAULT_MIN_CONNECTIONS)?);
+        
+        Ok(Self {
+            pool,
+            pipeline_enabled: true,
+            pipeline_buffer: Arc::new(Mutex::new(Vec::with_capacity(100))),
+            max_pipeline_size: 100,
+        })
+    }
+    
     /// Execute a single command
     pub async fn execute(&self, request: Request) -> Result<Response> {
-        if self.pipeline_enabled {
-            // Add to pipeline
-            let mut buffer = self.pipeline_buffer.lock().await;
-            buffer.push(request.clone());
-            
-            // Execute pipeline if full or if this is a special command
-            if buffer.len() >= self.max_pipeline_size || self.should_flush(&request) {
-                drop(buffer);
-                let responses = self.flush_pipeline().await?;
-                // Return the last response (current request)
-                responses.into_iter().last()
-                    .ok_or_else(|| DiskDBError::Protocol("No response received".to_string()))
-            } else {
-                drop(buffer);
-                // For now, execute immediately
-                // In a real implementation, we might wait for more commands
-                self.execute_single(request).await
-            }
-        } else {
-            self.execute_single(request).await
+        if !self.pipeline_enabled {
+            return self.execute_single(request).await;
         }
+        
+        let (reply, receiver) = oneshot::channel();
+        let mut buffer = self.pipeline_buffer.lock().await;
+        buffer.push((request, reply));
+        // The first request into an empty buffer schedules its flush, which takes
+        // along everything buffered by the time it runs
+        if buffer.len() == 1 {
+            let pool = self.pool.clone();
+            let pipeline_buffer = self.pipeline_buffer.clone();
+            let max_pipeline_size = self.max_pipeline_size;
+            tokio::spawn(async move {
+                Self::flush_pipeline(&pool, &pipeline_buffer, max_pipeline_size).await;
+            });
+        }
+        drop(buffer);
+        
+        receiver.await
+            .map_err(|_| DiskDBError::Protocol("No response received".to_string()))?
     }
     
     /// Execute a single request without pipelining
     async fn execute_single(&self, request: Request) -> Result<Response> {
-        let mut conn = self.pool.get().await?;
-        let stream = conn.stream_mut();
-        
-        // Get buffer from pool
-        let mut write_buffer = GLOBAL_BUFFER_POOL.get(256).await;
-        write_buffer.as_mut().extend_from_slice(request.to_string().as_bytes());
-        write_buffer.as_mut().extend_from_slice(b"\n");
-        
-        // Send request
-        match timeout(REQUEST_TIMEOUT, stream.write_all(write_buffer.as_mut())).await {
-            Ok(Ok(_)) => {},
-            Ok(Err(e)) => return Err(e.into()),
-            Err(_) => return Err(DiskDBError::Io(std::io::Error::new(
-                std::io::ErrorKind::TimedOut,
-                "Request timeout",
-            ))),
-        }
-        
-        // Read response
-        let mut reader = BufReader::new(stream);
-        let mut line = String::new();
-        
-        match timeout(REQUEST_TIMEOUT, reader.read_line(&mut line)).await {
-            Ok(Ok(0)) => Err(DiskDBError::ConnectionClosed),
-            Ok(Ok(_)) => Response::parse(&line),
-            Ok(Err(e)) => Err(e.into()),
-            Err(_) => Err(DiskDBError::Io(std::io::Error::new(
-                std::io::ErrorKind::TimedOut,
-                "Response timeout",
-            ))),
-        }
+        self.execute_pipeline(vec![request]).await?
+            .pop()
+            .ok_or_else(|| DiskDBError::Protocol("No response received".to_string()))
     }
     
     /// Execute multiple requests in a pipeline
     pub async fn execute_pipeline(&self, requests: Vec<Request>) -> Result<Vec<Response>> {
+        Self::send_pipeline(&self.pool, &requests).await
+    }
+    
+    /// Send `requests` on one pooled connection and read a reply for each
+    async fn send_pipeline(pool: &ConnectionPool, requests: &[Request]) -> Result<Vec<Response>> {
         if requests.is_empty() {
             return Ok(Vec::new());
         }
         
-        let mut conn = self.pool.get().await?;
+        let mut conn = pool.get().await?;
         let stream = conn.stream_mut();
         
         // Build request buffer
         let mut write_buffer = GLOBAL_BUFFER_POOL.get(4096).await;
-        for request in &requests {
+        for request in requests {
             write_buffer.as_mut().extend_from_slice(request.to_string().as_bytes());
             write_buffer.as_mut().extend_from_slice(b"\n");
         }
@@ -135,48 +135,44 @@ impl OptimizedClient {
             ))),
         }
         
-        // Read all responses
-        let mut reader = BufReader::new(stream);
-        let mut responses = Vec::with_capacity(requests.len());
-        
-        for _ in 0..requests.len() {
-            let mut line = String::new();
-            match timeout(REQUEST_TIMEOUT, reader.read_line(&mut line)).await {
-                Ok(Ok(0)) => return Err(DiskDBError::ConnectionClosed),
-                Ok(Ok(_)) => {
-                    responses.push(Response::parse(&line)?);
-                }
-                Ok(Err(e)) => return Err(e.into()),
-                Err(_) => return Err(DiskDBError::Io(std::io::Error::new(
+        // Read one reply per request. A connection left part way through its
+        // replies would hand them to the next user, so it is not pooled again.
+        match timeout(REQUEST_TIMEOUT, conn.read_replies(requests.len())).await {
+            Ok(Ok(responses)) => Ok(responses),
+            Ok(Err(e)) => {
+                drop(conn.into_inner());
+                Err(e)
+            }
+            Err(_) => {
+                drop(conn.into_inner());
+                Err(DiskDBError::Io(std::io::Error::new(
                     std::io::ErrorKind::TimedOut,
                     "Pipeline response timeout",
-                ))),
+                )))
             }
         }
-        
-        Ok(responses)
     }
     
-    /// Flush the pipeline buffer
-    async fn flush_pipeline(&self) -> Result<Vec<Response>> {
-        let mut buffer = self.pipeline_buffer.lock().await;
-        if buffer.is_empty() {
-            return Ok(Vec::new());
+    /// Flush the pipeline buffer, handing each waiting caller its own reply
+    async fn flush_pipeline(pool: &ConnectionPool, buffer: &Mutex<Vec<Pending>>, max_pipeline_size: usize) {
+        let mut pending: Vec<Pending> = buffer.lock().await.drain(..).collect();
+        while !pending.is_empty() {
+            let rest = pending.split_off(pending.len().min(max_pipeline_size.max(1)));
+            let (requests, replies): (Vec<Request>, Vec<_>) = pending.into_iter().unzip();
+            match Self::send_pipeline(pool, &requests).await {
+                Ok(responses) => {
+                    for (reply, response) in replies.into_iter().zip(responses) {
+                        let _ = reply.send(Ok(response));
+                    }
+                }
+                Err(e) => {
+                    for reply in replies {
+                        let _ = reply.send(Err(copy_error(&e)));
+                    }
+                }
+            }
+            pending = rest;
         }
-        
-        let requests: Vec<Request> = buffer.drain(..).collect();
-        drop(buffer);
-        
-        self.execute_pipeline(requests).await
-    }
-    
-    /// Check if request should trigger pipeline flush
-    fn should_flush(&self, request: &Request) -> bool {
-        matches!(request, 
-            Request::FlushDb | 
-            Request::Info | 
-            Request::Ping
-        )
     }
     
     /// Enable or disable pipelining
@@ -189,6 +185,12 @@ impl OptimizedClient {
         self.max_pipeline_size = size;
     }
     
+    /// Ask the server for RESP3 replies on every connection, so replies that span
+    /// several lines in the text protocol, such as arrays, are read whole
+    pub fn set_resp3(&mut self, enabled: bool) {
+        self.pool.set_resp3(enabled);
+    }
+    
     /// Get connection pool statistics
     pub async fn pool_stats(&self) -> crate::client::connection_pool::PoolStats {
         self.pool.stats().await
@@ -197,11 +199,21 @@ impl OptimizedClient {
     /// Close all connections
     pub async fn close(&self) -> Result<()> {
         // Flush any pending pipeline requests
-        let _ = self.flush_pipeline().await;
+        Self::flush_pipeline(&self.pool, &self.pipeline_buffer, self.max_pipeline_size).await;
         Ok(())
     }
 }
 
+/// The error of a failed pipeline, repeated for each request that was in it
+fn copy_error(e: &DiskDBError) -> DiskDBError {
+    match e {
+        DiskDBError::Io(e) => DiskDBError::Io(std::io::Error::new(e.kind(), e.to_string())),
+        DiskDBError::Protocol(msg) => DiskDBError::Protocol(msg.clone()),
+        DiskDBError::ConnectionClosed => DiskDBError::ConnectionClosed,
+        other => DiskDBError::Protocol(other.to_string()),
+    }
+}
+
 // Convenience methods for common operations
 impl OptimizedClient {
     pub async fn get(&self, key: &str) -> Result<Option<String>> {
@@ -213,10 +225,16 @@ impl OptimizedClient {
         }
     }
     
+    /// GET, falling back to `default` when the key is missing; the default is not stored
+    pub async fn get_or(&self, key: &str, default: &str) -> Result<String> {
+        Ok(self.get(key).await?.unwrap_or_else(|| default.to_string()))
+    }
+    
     pub async fn set(&self, key: &str, value: &str) -> Result<()> {
         let response = self.execute(Request::Set { 
             key: key.to_string(), 
-            value: value.to_string() 
+            value: value.to_string(),
+            options: SetOptions::default(),
         }).await?;
         
         match response {
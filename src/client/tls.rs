@@ -0,0 +1,58 @@
+use crate::error::Result;
+use native_tls::{Certificate, TlsConnector};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// TLS settings for connecting to a TLS-enabled DiskDB server
+#[derive(Debug, Clone)]
+pub struct TlsClientConfig {
+    /// Name checked against the server certificate
+    pub domain: String,
+    /// Extra PEM root certificates trusted alongside the system store
+    pub root_certificates: Vec<PathBuf>,
+    /// Skip certificate and hostname verification
+    pub accept_invalid_certs: bool,
+}
+
+impl TlsClientConfig {
+    pub fn new(domain: &str) -> Self {
+        Self {
+            domain: domain.to_string(),
+            root_certificates: Vec::new(),
+            accept_invalid_certs: false,
+        }
+    }
+
+    /// Trust the PEM certificate at `path`, e.g. a private CA or a self-signed server cert
+    pub fn with_root_certificate(mut self, path: impl AsRef<Path>) -> Self {
+        self.root_certificates.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Disable certificate verification entirely.
+    ///
+    /// Only meant for development against self-signed certificates; the connection
+    /// is encrypted but the server's identity is not checked.
+    pub fn insecure(mut self) -> Self {
+        self.accept_invalid_certs = true;
+        self
+    }
+
+    pub(crate) fn create_connector(&self) -> Result<tokio_native_tls::TlsConnector> {
+        let mut builder = TlsConnector::builder();
+
+        for path in &self.root_certificates {
+            let mut contents = Vec::new();
+            File::open(path)?.read_to_end(&mut contents)?;
+            builder.add_root_certificate(Certificate::from_pem(&contents)?);
+        }
+
+        if self.accept_invalid_certs {
+            builder.danger_accept_invalid_certs(true);
+            builder.danger_accept_invalid_hostnames(true);
+        }
+
+        Ok(tokio_native_tls::TlsConnector::from(builder.build()?))
+    }
+}
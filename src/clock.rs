@@ -0,0 +1,59 @@
+//! Source of the current time for everything measured against deadlines:
+//! key and field expiry, LFU decay and generated stream IDs.
+//!
+//! Storage carries one [`Clock`] and the executor reads the time through it, so
+//! tests can swap in a [`MockClock`] and move time forward without sleeping.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub trait Clock: Send + Sync {
+    /// Milliseconds since the Unix epoch
+    fn now_millis(&self) -> u64;
+
+    /// The mock behind this clock, if it can be set by hand
+    fn as_mock(&self) -> Option<&MockClock> {
+        None
+    }
+}
+
+/// The wall clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u64 {
+        crate::storage::now_millis()
+    }
+}
+
+/// A clock that only moves when told to
+#[derive(Debug, Default)]
+pub struct MockClock {
+    millis: AtomicU64,
+}
+
+impl MockClock {
+    /// A clock stopped at `millis` since the Unix epoch
+    pub fn new(millis: u64) -> Self {
+        Self { millis: AtomicU64::new(millis) }
+    }
+
+    pub fn set(&self, millis: u64) {
+        self.millis.store(millis, Ordering::SeqCst);
+    }
+
+    /// Move the clock forward by `millis`, returning the new time
+    pub fn advance(&self, millis: u64) -> u64 {
+        self.millis.fetch_add(millis, Ordering::SeqCst).saturating_add(millis)
+    }
+}
+
+impl Clock for MockClock {
+    fn now_millis(&self) -> u64 {
+        self.millis.load(Ordering::SeqCst)
+    }
+
+    fn as_mock(&self) -> Option<&MockClock> {
+        Some(self)
+    }
+}
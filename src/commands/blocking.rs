@@ -0,0 +1,232 @@
+use crate::commands::CommandExecutor;
+use crate::data_types::ZAddFlags;
+use crate::error::{DiskDBError, Result};
+use crate::protocol::Response;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Where a blocked client hands its reply: taken by whichever key serves it
+/// first, or by the client itself once it stops waiting
+type Slot = Arc<Mutex<Option<oneshot::Sender<Vec<Response>>>>>;
+
+/// What a blocked client pops once one of its keys has something
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Pop {
+    /// BLPOP: the head of a list
+    Left,
+    /// BRPOP: the tail of a list
+    Right,
+    /// BZPOPMIN: the lowest-scored member of a sorted set
+    Min,
+    /// BZPOPMAX: the highest-scored member of a sorted set
+    Max,
+}
+
+struct Waiter {
+    pop: Pop,
+    slot: Slot,
+}
+
+/// Clients blocked in BLPOP/BRPOP/BZPOPMIN/BZPOPMAX, queued per key in the
+/// order they blocked.
+///
+/// A push or ZADD serves the queue of its key directly, so the longest-waiting
+/// client gets the element rather than whoever polls next.
+#[derive(Clone, Default)]
+pub struct BlockedClients {
+    queues: Arc<tokio::sync::Mutex<HashMap<String, VecDeque<Waiter>>>>,
+}
+
+impl BlockedClients {
+    async fn register(&self, keys: &[String], pop: Pop) -> (Slot, oneshot::Receiver<Vec<Response>>) {
+        let (sender, receiver) = oneshot::channel();
+        let slot: Slot = Arc::new(Mutex::new(Some(sender)));
+        let mut queues = self.queues.lock().await;
+        for key in keys {
+            queues.entry(key.clone()).or_default().push_back(Waiter { pop, slot: slot.clone() });
+        }
+        (slot, receiver)
+    }
+
+    async fn unregister(&self, keys: &[String], slot: &Slot) {
+        let mut queues = self.queues.lock().await;
+        for key in keys {
+            if let Some(queue) = queues.get_mut(key) {
+                queue.retain(|waiter| !Arc::ptr_eq(&waiter.slot, slot));
+                if queue.is_empty() {
+                    queues.remove(key);
+                }
+            }
+        }
+    }
+}
+
+impl CommandExecutor {
+    /// BLPOP/BRPOP/BZPOPMIN/BZPOPMAX: pop from the first non-empty key, or wait
+    /// up to `timeout_ms` (0 waits forever) for a write to one of them. Replies
+    /// `[key, element]` or `[key, member, score]`, or a null array on timeout.
+    ///
+    /// The shared lock is held only while checking and registering, so a blocked
+    /// client never stalls scripts or the pushes it is waiting for. Once `hangup`
+    /// resolves the client is gone: it stops waiting, and an element popped for it
+    /// at that moment goes back to its key.
+    pub(super) async fn blocking_pop(
+        &self,
+        keys: Vec<String>,
+        pop: Pop,
+        timeout_ms: u64,
+        hangup: impl Future<Output = ()>,
+    ) -> Result<Response> {
+        let (slot, mut receiver) = {
+            let _shared = self.script_lock.read().await;
+            let _keys = self.key_locks.lock(&keys.iter().map(String::as_str).collect::<Vec<_>>()).await;
+            if let Some(reply) = self.pop_first(&keys, pop).await? {
+                if let Response::Array(pair) = &reply {
+                    if let Some(Response::String(Some(key))) = pair.first() {
+                        self.tracking.invalidate(&[key]);
+                    }
+                }
+                return Ok(reply);
+            }
+            let registered = self.blocked.register(&keys, pop).await;
+            // A write may have landed between the check and registering
+            for key in &keys {
+                self.serve_blocked(key).await?;
+            }
+            registered
+        };
+
+        // Resolves with whether the client hung up, rather than the timeout passing
+        let stop = async {
+            let timeout = async {
+                match timeout_ms {
+                    0 => std::future::pending().await,
+                    ms => tokio::time::sleep(Duration::from_millis(ms)).await,
+                }
+            };
+            tokio::select! {
+                _ = timeout => false,
+                _ = hangup => true,
+            }
+        };
+        let received = tokio::select! {
+            received = &mut receiver => received.ok(),
+            hung_up = stop => {
+                // Nobody claimed the slot, so nothing can be sent any more
+                if slot.lock().unwrap().take().is_some() {
+                    None
+                } else {
+                    // A write claimed it just as the wait ended; its reply is on the way
+                    let late = receiver.await.ok();
+                    match late {
+                        Some(reply) if hung_up => {
+                            if let Some(Response::String(Some(key))) = reply.first() {
+                                let key = key.clone();
+                                let _shared = self.script_lock.read().await;
+                                let _key = self.key_locks.lock(&[&key]).await;
+                                self.unpop(&key, pop, reply).await?;
+                            }
+                            None
+                        }
+                        late => late,
+                    }
+                }
+            }
+        };
+        self.blocked.unregister(&keys, &slot).await;
+
+        if let Some(Response::String(Some(key))) = received.as_ref().and_then(|reply| reply.first()) {
+            self.tracking.invalidate(&[key]);
+        }
+        Ok(received.map_or(Response::NullArray, Response::Array))
+    }
+
+    /// Non-blocking form of the blocking pops: `[key, element]` or
+    /// `[key, member, score]` from the first non-empty key, a WRONGTYPE error,
+    /// or None when every key is empty
+    pub(super) async fn pop_first(&self, keys: &[String], pop: Pop) -> Result<Option<Response>> {
+        let reply = match pop {
+            Pop::Left | Pop::Right => self.execute_mpop(keys, "list", |data| {
+                let values = data.lpop_count(pop == Pop::Left, 1)?;
+                Ok(values.into_iter().map(|v| Response::String(Some(v))).collect())
+            }).await?,
+            Pop::Min | Pop::Max => self.execute_mpop(keys, "zset", |data| {
+                let members = data.zpop(pop == Pop::Min, 1)?;
+                Ok(members.into_iter()
+                    .flat_map(|(member, score)| [Response::String(Some(member)), Response::String(Some(score.to_string()))])
+                    .collect())
+            }).await?,
+        };
+        Ok(match reply {
+            Response::NullArray => None,
+            Response::Array(mut pair) => match pair.pop() {
+                Some(Response::Array(values)) => {
+                    pair.extend(values);
+                    Some(Response::Array(pair))
+                }
+                _ => None,
+            },
+            other => Some(other),
+        })
+    }
+
+    /// Hand elements of `key` to its blocked clients, oldest first, until the
+    /// key or the queue runs out. Called after every push and ZADD.
+    pub(super) async fn serve_blocked(&self, key: &str) -> Result<()> {
+        let mut queues = self.blocked.queues.lock().await;
+        let Some(queue) = queues.get_mut(key) else {
+            return Ok(());
+        };
+
+        while let Some(waiter) = queue.front() {
+            // Already served through another key, or stopped waiting
+            if waiter.slot.lock().unwrap().as_ref().is_none_or(|sender| sender.is_closed()) {
+                queue.pop_front();
+                continue;
+            }
+            let pop = waiter.pop;
+            let reply = match self.pop_first(&[key.to_string()], pop).await? {
+                Some(Response::Array(reply)) => reply,
+                _ => break,
+            };
+
+            let sender = queue.pop_front().and_then(|waiter| waiter.slot.lock().unwrap().take());
+            let undelivered = match sender {
+                Some(sender) => sender.send(reply).err(),
+                None => Some(reply),
+            };
+            // The client went away after the element was popped: put it back where it was
+            if let Some(reply) = undelivered {
+                self.unpop(key, pop, reply).await?;
+            }
+        }
+
+        if queue.is_empty() {
+            queues.remove(key);
+        }
+        Ok(())
+    }
+
+    /// Undo a pop whose reply `[key, ...]` could not be delivered
+    async fn unpop(&self, key: &str, pop: Pop, reply: Vec<Response>) -> Result<()> {
+        match (pop, reply.as_slice()) {
+            (Pop::Left | Pop::Right, [_, Response::String(Some(value))]) => {
+                let mut data = self.storage.get_or_create_list(key).await?;
+                let pushed = if pop == Pop::Left { data.lpush(vec![value.clone()]) } else { data.rpush(vec![value.clone()]) };
+                pushed.map_err(DiskDBError::Database)?;
+                self.storage.set(key, data).await?;
+            }
+            (Pop::Min | Pop::Max, [_, Response::String(Some(member)), Response::String(Some(score))]) => {
+                let score = score.parse::<f64>().map_err(|e| DiskDBError::Database(e.to_string()))?;
+                let mut data = self.storage.get_or_create_sorted_set(key).await?;
+                data.zadd(vec![(score, member.clone())], ZAddFlags::default()).map_err(DiskDBError::Database)?;
+                self.storage.set(key, data).await?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
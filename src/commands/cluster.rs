@@ -0,0 +1,47 @@
+use crate::commands::CommandExecutor;
+use crate::protocol::Response;
+use sha1::{Digest, Sha1};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Node ID reported by CLUSTER MYID, generated once per process
+static NODE_ID: OnceLock<String> = OnceLock::new();
+
+impl CommandExecutor {
+    /// CLUSTER INFO, SLOTS, SHARDS and MYID, answered as a lone node with
+    /// clustering disabled so that cluster-aware clients fall back to it
+    pub(super) fn cluster(subcommand: &str, args: &[String]) -> Response {
+        match (subcommand, args) {
+            ("INFO", []) => Response::String(Some(
+                [
+                    "cluster_enabled:0",
+                    "cluster_state:ok",
+                    "cluster_slots_assigned:0",
+                    "cluster_slots_ok:0",
+                    "cluster_slots_pfail:0",
+                    "cluster_slots_fail:0",
+                    "cluster_known_nodes:1",
+                    "cluster_size:0",
+                    "cluster_current_epoch:0",
+                    "cluster_my_epoch:0",
+                ]
+                .join("\n"),
+            )),
+            ("SLOTS" | "SHARDS", []) => Response::Array(Vec::new()),
+            ("MYID", []) => Response::String(Some(node_id().to_string())),
+            ("INFO" | "SLOTS" | "SHARDS" | "MYID", _) => Response::Error(format!(
+                "ERR wrong number of arguments for 'cluster|{}' command",
+                subcommand.to_lowercase(),
+            )),
+            _ => Response::Error(format!("ERR unknown subcommand '{}'. Try CLUSTER HELP.", subcommand)),
+        }
+    }
+}
+
+/// 40 hex characters, like a Redis cluster node ID
+fn node_id() -> &'static str {
+    NODE_ID.get_or_init(|| {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+        format!("{:x}", Sha1::digest(format!("{}:{}", std::process::id(), nanos).as_bytes()))
+    })
+}
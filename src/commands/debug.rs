@@ -0,0 +1,138 @@
+use crate::commands::CommandExecutor;
+use crate::data_types::DataType;
+use crate::error::{DiskDBError, Result};
+use crate::protocol::Response;
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Reply for DEBUG when the server was started without enabling it
+pub const DEBUG_DISABLED: &str = "ERR DEBUG command not allowed. Set DISKDB_ENABLE_DEBUG_COMMAND to enable it.";
+/// Reply to SET-TIME and ADVANCE-TIME when storage runs on the wall clock
+pub const NO_MOCK_CLOCK: &str = "ERR the server is not running on a mock clock";
+
+/// Keys DEBUG POPULATE writes per storage batch, bounding the memory one batch holds
+const POPULATE_BATCH: u64 = 10_000;
+
+/// A key as saved by DEBUG RELOAD: the encoded value and its expiry state
+struct Record {
+    key: String,
+    value: Vec<u8>,
+    deadline: Option<u64>,
+    field_deadlines: BTreeMap<String, u64>,
+}
+
+impl CommandExecutor {
+    /// DEBUG RELOAD: encode every key, delete them all, decode and write them
+    /// back, then compare a checksum of the keyspace taken before and after.
+    ///
+    /// The snapshot is held in memory, and the caller holds the exclusive lock
+    /// so no other command sees the keyspace half reloaded.
+    pub(super) async fn debug_reload(&self) -> Result<Response> {
+        if !self.debug_enabled {
+            return Ok(Response::Error(DEBUG_DISABLED.to_string()));
+        }
+
+        let snapshot = self.snapshot().await?;
+        let before = checksum(&snapshot)?;
+
+        let keys: Vec<String> = snapshot.iter().map(|record| record.key.clone()).collect();
+        self.storage.delete_multiple(&keys).await?;
+        for record in &snapshot {
+            let value: DataType = bincode::deserialize(&record.value)
+                .map_err(|e| DiskDBError::Database(format!("Deserialization error: {}", e)))?;
+            self.storage.set(&record.key, value).await?;
+            if let Some(deadline) = record.deadline {
+                self.storage.expire_at(&record.key, deadline).await?;
+            }
+            if !record.field_deadlines.is_empty() {
+                let deadlines = record.field_deadlines.clone().into_iter().collect();
+                self.storage.set_field_deadlines(&record.key, &deadlines).await?;
+            }
+        }
+
+        let after = checksum(&self.snapshot().await?)?;
+        if before == after {
+            Ok(Response::Ok)
+        } else {
+            Ok(Response::Error(format!(
+                "ERR DEBUG RELOAD changed the dataset: checksum {} before, {} after",
+                before, after,
+            )))
+        }
+    }
+
+    /// DEBUG POPULATE: create `<prefix>:<n>` holding `value:<n>` for each n below
+    /// `count` whose key is missing, like Redis. With `size`, each value is
+    /// padded with zero bytes or truncated to exactly that length.
+    pub(super) async fn debug_populate(&self, count: u64, prefix: &str, size: Option<usize>) -> Result<Response> {
+        if !self.debug_enabled {
+            return Ok(Response::Error(DEBUG_DISABLED.to_string()));
+        }
+        if size.is_some_and(|size| self.value_too_large(size)) {
+            return Ok(Response::Error(super::VALUE_TOO_LARGE.to_string()));
+        }
+
+        let mut start = 0;
+        while start < count {
+            let end = count.min(start + POPULATE_BATCH);
+            let entries = (start..end)
+                .map(|n| {
+                    let mut value = format!("value:{}", n);
+                    if let Some(size) = size {
+                        value.truncate(size);
+                        value.extend(std::iter::repeat_n('\0', size - value.len()));
+                    }
+                    (format!("{}:{}", prefix, n), DataType::String(value))
+                })
+                .collect();
+            self.storage.insert_missing(entries).await?;
+            start = end;
+        }
+        Ok(Response::Ok)
+    }
+
+    /// Every live key with its encoded value and deadlines, ordered by key
+    async fn snapshot(&self) -> Result<Vec<Record>> {
+        let mut records = Vec::new();
+        let mut cursor = 0;
+        loop {
+            let (next, keys) = self.storage.scan(cursor, 1000, None).await?;
+            for key in keys {
+                let Some(value) = self.storage.get(&key).await? else { continue };
+                let value = bincode::serialize(&value)
+                    .map_err(|e| DiskDBError::Database(format!("Serialization error: {}", e)))?;
+                let deadline = self.storage.expiry(&key).await?;
+                let field_deadlines = self.storage.field_deadlines(&key).await?.into_iter().collect();
+                records.push(Record { key, value, deadline, field_deadlines });
+            }
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        records.sort_by(|a, b| a.key.cmp(&b.key));
+        records.dedup_by(|a, b| a.key == b.key);
+        Ok(records)
+    }
+}
+
+/// Hex SHA1 over the keyspace. Sets and hashes are hashed in sorted order,
+/// since their encoding follows the iteration order of the in-memory map.
+fn checksum(records: &[Record]) -> Result<String> {
+    let mut hasher = Sha1::new();
+    for record in records {
+        let value: DataType = bincode::deserialize(&record.value)
+            .map_err(|e| DiskDBError::Database(format!("Deserialization error: {}", e)))?;
+        let canonical = match &value {
+            DataType::Set(members) => bincode::serialize(&members.iter().collect::<BTreeSet<_>>()),
+            DataType::Hash(fields) => bincode::serialize(&fields.iter().collect::<BTreeMap<_, _>>()),
+            other => bincode::serialize(other),
+        }
+        .map_err(|e| DiskDBError::Database(format!("Serialization error: {}", e)))?;
+
+        let entry = (&record.key, value.type_name(), canonical, record.deadline, &record.field_deadlines);
+        hasher.update(bincode::serialize(&entry)
+            .map_err(|e| DiskDBError::Database(format!("Serialization error: {}", e)))?);
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
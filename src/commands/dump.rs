@@ -0,0 +1,101 @@
+//! DUMP/RESTORE payloads.
+//!
+//! A payload is `<version byte><body><checksum>` written out as lowercase hex,
+//! since requests and replies carry UTF-8 text. The checksum is the first 8
+//! bytes of the SHA1 of the version and body, so a payload damaged or edited
+//! in transit is refused rather than restored as garbage.
+
+use crate::commands::CommandExecutor;
+use crate::data_types::DataType;
+use crate::error::Result;
+use crate::protocol::Response;
+use sha1::{Digest, Sha1};
+
+/// Version written by DUMP
+pub const DUMP_VERSION: u8 = 1;
+/// Bytes of checksum at the end of a payload
+pub const CHECKSUM_LEN: usize = 8;
+/// Reply to RESTORE for a payload that is damaged or from a newer server
+pub const BAD_PAYLOAD: &str = "ERR DUMP payload version or checksum are wrong";
+
+/// Checksum over a payload's version byte and body
+pub fn checksum(bytes: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let digest = Sha1::digest(bytes);
+    let mut sum = [0; CHECKSUM_LEN];
+    sum.copy_from_slice(&digest[..CHECKSUM_LEN]);
+    sum
+}
+
+/// Serialize `value` into a DUMP payload
+pub fn encode(value: &DataType) -> Result<String> {
+    let body = bincode::serialize(value)
+        .map_err(|e| crate::error::DiskDBError::Database(format!("Serialization error: {}", e)))?;
+    let mut payload = Vec::with_capacity(1 + body.len() + CHECKSUM_LEN);
+    payload.push(DUMP_VERSION);
+    payload.extend_from_slice(&body);
+    payload.extend_from_slice(&checksum(&payload));
+    Ok(payload.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// The value in a DUMP payload, or None if it fails its checksum, comes from
+/// an unknown version or does not decode
+pub fn decode(payload: &str) -> Option<DataType> {
+    let bytes = from_hex(payload)?;
+    if bytes.len() < 1 + CHECKSUM_LEN {
+        return None;
+    }
+    let (signed, sum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    if checksum(signed) != sum {
+        return None;
+    }
+    let (version, body) = (signed[0], &signed[1..]);
+    match version {
+        // Every version so far shares the storage encoding, which only ever
+        // gains variants; a payload from a later server may use ones this
+        // server does not know
+        1 => bincode::deserialize(body).ok(),
+        _ => None,
+    }
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
+impl CommandExecutor {
+    pub(super) async fn dump(&self, key: &str) -> Result<Response> {
+        match self.storage.get(key).await? {
+            Some(value) => Ok(Response::String(Some(encode(&value)?))),
+            None => Ok(Response::Null),
+        }
+    }
+
+    /// RESTORE: `ttl` is in milliseconds, relative unless `absttl`; 0 means no expiry
+    pub(super) async fn restore(&self, key: &str, ttl: i64, payload: &str, replace: bool, absttl: bool) -> Result<Response> {
+        let Some(value) = decode(payload) else {
+            return Ok(Response::Error(BAD_PAYLOAD.to_string()));
+        };
+        if !replace && self.storage.exists(key).await? {
+            return Ok(Response::Error("BUSYKEY Target key name already exists.".to_string()));
+        }
+        let deadline = match ttl {
+            0 => None,
+            ttl if absttl => Some(ttl as u64),
+            ttl => Some(self.storage.now_millis().saturating_add(ttl as u64)),
+        };
+
+        // A replaced key must not keep the old value's expiry or field deadlines
+        self.storage.delete(key).await?;
+        self.storage.set(key, value).await?;
+        if let Some(deadline) = deadline {
+            self.storage.expire_at(key, deadline).await?;
+        }
+        Ok(Response::Ok)
+    }
+}
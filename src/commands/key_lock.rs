@@ -0,0 +1,44 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use tokio::sync::{Mutex, MutexGuard};
+
+/// Number of lock shards; keys hashing to the same shard share a lock
+const SHARDS: usize = 1024;
+
+/// Sharded locks serializing writes to the same key.
+///
+/// Commands read a value, change it and write it back, so two writes to one key
+/// must not interleave. A write locks the shards of all its keys in ascending
+/// order, which keeps multi-key writes from deadlocking each other; writes to
+/// keys on other shards, and all reads, go ahead in parallel.
+#[derive(Clone)]
+pub struct KeyLocks {
+    shards: Arc<Vec<Mutex<()>>>,
+}
+
+impl Default for KeyLocks {
+    fn default() -> Self {
+        Self { shards: Arc::new((0..SHARDS).map(|_| Mutex::new(())).collect()) }
+    }
+}
+
+impl KeyLocks {
+    /// Lock every shard holding one of `keys`, released when the guards drop
+    pub async fn lock(&self, keys: &[&str]) -> Vec<MutexGuard<'_, ()>> {
+        let mut shards: Vec<usize> = keys.iter().map(|key| shard(key)).collect();
+        shards.sort_unstable();
+        shards.dedup();
+        let mut guards = Vec::with_capacity(shards.len());
+        for index in shards {
+            guards.push(self.shards[index].lock().await);
+        }
+        guards
+    }
+}
+
+fn shard(key: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish() as usize % SHARDS
+}
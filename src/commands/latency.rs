@@ -0,0 +1,117 @@
+use crate::commands::CommandExecutor;
+use crate::protocol::Response;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Event recorded for a command that ran past the threshold
+pub const COMMAND_EVENT: &str = "command";
+/// Event recorded for an active expiry cycle that ran past the threshold
+pub const EXPIRE_CYCLE_EVENT: &str = "expire-cycle";
+
+/// Samples kept per event; older ones are dropped first
+const MAX_SAMPLES: usize = 160;
+
+/// Latency spikes per event, as reported by LATENCY LATEST and HISTORY.
+///
+/// Only durations at or above the threshold are recorded, and spikes within
+/// the same second are folded into one sample holding the largest. With a
+/// threshold of 0 nothing is recorded.
+#[derive(Clone, Default)]
+pub struct LatencyMonitor {
+    events: Arc<Mutex<HashMap<&'static str, EventHistory>>>,
+    threshold_ms: u64,
+}
+
+#[derive(Default)]
+struct EventHistory {
+    /// (Unix seconds, milliseconds), oldest first
+    samples: VecDeque<(u64, u64)>,
+    /// Largest spike since the event was last reset
+    max_ms: u64,
+}
+
+impl LatencyMonitor {
+    pub fn new(threshold_ms: u64) -> Self {
+        Self { events: Arc::default(), threshold_ms }
+    }
+
+    /// Record `elapsed` for `event` at `now_millis` if it reaches the threshold
+    pub fn record(&self, event: &'static str, now_millis: u64, elapsed: Duration) {
+        let ms = elapsed.as_millis() as u64;
+        if self.threshold_ms == 0 || ms < self.threshold_ms {
+            return;
+        }
+        let second = now_millis / 1000;
+        let mut events = self.events.lock().unwrap();
+        let history = events.entry(event).or_default();
+        history.max_ms = history.max_ms.max(ms);
+        match history.samples.back_mut() {
+            Some((at, latest)) if *at == second => *latest = (*latest).max(ms),
+            _ => {
+                if history.samples.len() == MAX_SAMPLES {
+                    history.samples.pop_front();
+                }
+                history.samples.push_back((second, ms));
+            }
+        }
+    }
+
+    /// Per event: name, time of the latest spike, its milliseconds and the largest seen
+    pub fn latest(&self) -> Vec<(&'static str, u64, u64, u64)> {
+        let events = self.events.lock().unwrap();
+        let mut latest: Vec<_> = events.iter()
+            .filter_map(|(name, history)| {
+                history.samples.back().map(|&(at, ms)| (*name, at, ms, history.max_ms))
+            })
+            .collect();
+        latest.sort_unstable();
+        latest
+    }
+
+    /// Samples recorded for `event`, oldest first
+    pub fn history(&self, event: &str) -> Vec<(u64, u64)> {
+        let events = self.events.lock().unwrap();
+        events.get(event).map(|history| history.samples.iter().copied().collect()).unwrap_or_default()
+    }
+
+    /// Forget the named events, or all of them when `names` is empty, returning how many were dropped
+    pub fn reset(&self, names: &[String]) -> usize {
+        let mut events = self.events.lock().unwrap();
+        if names.is_empty() {
+            let count = events.len();
+            events.clear();
+            return count;
+        }
+        names.iter().filter(|name| events.remove(name.as_str()).is_some()).count()
+    }
+}
+
+impl CommandExecutor {
+    /// LATENCY LATEST, HISTORY and RESET
+    pub(super) fn latency(&self, subcommand: &str, args: &[String]) -> Response {
+        match (subcommand, args) {
+            ("LATEST", []) => Response::Array(
+                self.latency.latest().into_iter()
+                    .map(|(name, at, ms, max_ms)| Response::Array(vec![
+                        Response::String(Some(name.to_string())),
+                        Response::Integer(at as i64),
+                        Response::Integer(ms as i64),
+                        Response::Integer(max_ms as i64),
+                    ]))
+                    .collect(),
+            ),
+            ("HISTORY", [event]) => Response::Array(
+                self.latency.history(event).into_iter()
+                    .map(|(at, ms)| Response::Array(vec![Response::Integer(at as i64), Response::Integer(ms as i64)]))
+                    .collect(),
+            ),
+            ("RESET", names) => Response::Integer(self.latency.reset(names) as i64),
+            ("LATEST" | "HISTORY", _) => Response::Error(format!(
+                "ERR wrong number of arguments for 'latency|{}' command",
+                subcommand.to_lowercase(),
+            )),
+            _ => Response::Error(format!("ERR unknown subcommand '{}'. Try LATENCY HELP.", subcommand)),
+        }
+    }
+}
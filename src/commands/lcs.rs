@@ -0,0 +1,172 @@
+use crate::commands::CommandExecutor;
+use crate::data_types::DataType;
+use crate::error::Result;
+use crate::protocol::Response;
+
+/// Largest table LCS IDX may allocate, the default proto-max-bulk-len as in Redis
+const MAX_TABLE_BYTES: usize = 512 * 1024 * 1024;
+
+impl CommandExecutor {
+    /// LCS: the longest common subsequence of two strings, compared byte by byte.
+    /// A missing key counts as an empty string.
+    ///
+    /// The subsequence and its length are found keeping two rows of the
+    /// dynamic-programming table at a time; only IDX, which walks the table back
+    /// to find the ranges, builds it whole.
+    pub(super) async fn lcs(
+        &self,
+        key1: &str,
+        key2: &str,
+        len_only: bool,
+        idx: bool,
+        minmatchlen: Option<usize>,
+        withmatchlen: bool,
+    ) -> Result<Response> {
+        let (Some(a), Some(b)) = (self.lcs_input(key1).await?, self.lcs_input(key2).await?) else {
+            return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()));
+        };
+        let (a, b) = (a.as_bytes(), b.as_bytes());
+
+        if idx {
+            if (a.len() + 1).saturating_mul(b.len() + 1).saturating_mul(std::mem::size_of::<u32>()) > MAX_TABLE_BYTES {
+                return Ok(Response::Error(
+                    "ERR Insufficient memory, transient memory for LCS exceeds proto-max-bulk-len".to_string(),
+                ));
+            }
+            return Ok(lcs_ranges(a, b, minmatchlen.unwrap_or(0), withmatchlen));
+        }
+        if len_only {
+            return Ok(Response::Integer(last_row(a, b)[b.len()] as i64));
+        }
+        let mut common = Vec::new();
+        lcs_bytes(a, b, &mut common);
+        // A subsequence of bytes may split a multi-byte character
+        Ok(Response::String(Some(String::from_utf8_lossy(&common).into_owned())))
+    }
+
+    /// The string under `key`, empty when missing; None when it holds another type
+    async fn lcs_input(&self, key: &str) -> Result<Option<String>> {
+        Ok(match self.storage.get(key).await? {
+            Some(DataType::String(value)) => Some(value),
+            Some(_) => None,
+            None => Some(String::new()),
+        })
+    }
+}
+
+/// LCS lengths of `a` against every prefix of `b`: the last row of the table,
+/// computed keeping only two rows
+fn last_row(a: &[u8], b: &[u8]) -> Vec<u32> {
+    let mut previous = vec![0u32; b.len() + 1];
+    let mut current = vec![0u32; b.len() + 1];
+    for &x in a {
+        for (j, &y) in b.iter().enumerate() {
+            current[j + 1] = if x == y {
+                previous[j] + 1
+            } else {
+                current[j].max(previous[j + 1])
+            };
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous
+}
+
+/// Append a longest common subsequence of `a` and `b` to `out` in linear space
+/// (Hirschberg): split `a` in half, find where the halves' subsequences meet in
+/// `b` from a forward and a backward pass, and recurse on both sides. When
+/// several subsequences are equally long, the one returned may differ from
+/// Redis's choice.
+fn lcs_bytes(a: &[u8], b: &[u8], out: &mut Vec<u8>) {
+    if a.is_empty() || b.is_empty() {
+        return;
+    }
+    if a.len() == 1 {
+        if b.contains(&a[0]) {
+            out.push(a[0]);
+        }
+        return;
+    }
+
+    let (top, bottom) = a.split_at(a.len() / 2);
+    let forward = last_row(top, b);
+    let reversed = |s: &[u8]| s.iter().rev().copied().collect::<Vec<u8>>();
+    let backward = last_row(&reversed(bottom), &reversed(b));
+    let split = (0..=b.len())
+        .max_by_key(|&k| forward[k] + backward[b.len() - k])
+        .unwrap_or(0);
+    lcs_bytes(top, &b[..split], out);
+    lcs_bytes(bottom, &b[split..], out);
+}
+
+/// The IDX reply: the matching ranges of `a` and `b`, last to first, skipping
+/// those shorter than `minmatchlen`, followed by the subsequence's length. The
+/// table is walked back the same way Redis does, so the ranges match its own.
+fn lcs_ranges(a: &[u8], b: &[u8], minmatchlen: usize, withmatchlen: bool) -> Response {
+    let width = b.len() + 1;
+    let mut table = vec![0u32; (a.len() + 1) * width];
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            table[i * width + j] = if a[i - 1] == b[j - 1] {
+                table[(i - 1) * width + j - 1] + 1
+            } else {
+                table[(i - 1) * width + j].max(table[i * width + j - 1])
+            };
+        }
+    }
+    let lcs = |i: usize, j: usize| table[i * width + j];
+
+    let range = |start: usize, end: usize| Response::Array(vec![Response::Integer(start as i64), Response::Integer(end as i64)]);
+    let mut matches = Vec::new();
+    // The range being extended backwards as (a start, a end, b start, b end)
+    let mut current: Option<(usize, usize, usize, usize)> = None;
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 && j > 0 {
+        let mut emit = false;
+        if a[i - 1] == b[j - 1] {
+            current = match current {
+                Some((a_start, a_end, b_start, b_end)) if a_start == i && b_start == j => {
+                    Some((a_start - 1, a_end, b_start - 1, b_end))
+                }
+                Some(range) => {
+                    emit = true;
+                    Some(range)
+                }
+                None => Some((i - 1, i - 1, j - 1, j - 1)),
+            };
+            // A range touching the start of either string cannot grow further
+            if current.is_some_and(|(a_start, _, b_start, _)| a_start == 0 || b_start == 0) {
+                emit = true;
+            }
+            i -= 1;
+            j -= 1;
+        } else {
+            if lcs(i - 1, j) > lcs(i, j - 1) {
+                i -= 1;
+            } else {
+                j -= 1;
+            }
+            emit = current.is_some();
+        }
+
+        if emit {
+            if let Some((a_start, a_end, b_start, b_end)) = current.take() {
+                let len = a_end - a_start + 1;
+                if len >= minmatchlen {
+                    let mut entry = vec![range(a_start, a_end), range(b_start, b_end)];
+                    if withmatchlen {
+                        entry.push(Response::Integer(len as i64));
+                    }
+                    matches.push(Response::Array(entry));
+                }
+            }
+        }
+    }
+
+    Response::Array(vec![
+        Response::String(Some("matches".to_string())),
+        Response::Array(matches),
+        Response::String(Some("len".to_string())),
+        Response::Integer(lcs(a.len(), b.len()) as i64),
+    ])
+}
@@ -0,0 +1,144 @@
+use crate::commands::CommandExecutor;
+use crate::data_types::DataType;
+use crate::error::Result;
+use crate::protocol::Response;
+
+/// Bytes charged to every key for its RocksDB entry and value header
+const KEY_OVERHEAD: usize = 48;
+/// Bytes charged to every element of a collection beyond its own data, for its
+/// length prefix and the container's bookkeeping
+const ELEMENT_OVERHEAD: usize = 16;
+/// Elements sized per collection by MEMORY USAGE when SAMPLES is not given
+pub const DEFAULT_SAMPLES: usize = 5;
+
+impl CommandExecutor {
+    /// MEMORY USAGE, STATS, DOCTOR and POOL
+    pub(super) async fn memory(&self, subcommand: &str, args: &[String]) -> Result<Response> {
+        match (subcommand, args) {
+            ("USAGE", [key, options @ ..]) => {
+                let samples = match options {
+                    [] => DEFAULT_SAMPLES,
+                    [option, count] if option.eq_ignore_ascii_case("SAMPLES") => match count.parse::<usize>() {
+                        Ok(count) => count,
+                        Err(_) => return Ok(Response::Error("ERR value is not an integer or out of range".to_string())),
+                    },
+                    _ => return Ok(Response::Error("ERR syntax error".to_string())),
+                };
+                Ok(match self.storage.get(key).await? {
+                    Some(value) => Response::Integer(estimate(key, &value, samples) as i64),
+                    None => Response::Null,
+                })
+            }
+            ("STATS", []) => {
+                let aggregates = self.storage.aggregates().await?;
+                let mut stats = vec![
+                    Response::String(Some("keys.count".to_string())),
+                    Response::Integer(aggregates.keys as i64),
+                    Response::String(Some("dataset.bytes".to_string())),
+                    Response::Integer(aggregates.bytes as i64),
+                    Response::String(Some("keys.bytes-per-key".to_string())),
+                    Response::Integer(aggregates.bytes.checked_div(aggregates.keys).unwrap_or(0) as i64),
+                ];
+                for (type_name, count) in aggregates.keys_by_type {
+                    stats.push(Response::String(Some(format!("keys.{}", type_name))));
+                    stats.push(Response::Integer(count as i64));
+                }
+                Ok(Response::Array(stats))
+            }
+            ("DOCTOR", []) => {
+                let aggregates = self.storage.aggregates().await?;
+                let report = match aggregates.keys_by_type.iter().max_by_key(|(_, count)| **count) {
+                    None => "Empty dataset, nothing to report.".to_string(),
+                    Some((type_name, count)) => format!(
+                        "{} keys taking about {} bytes, {} bytes per key on average. Most keys are {} ({} of them); \
+                         use MEMORY USAGE to size individual keys.",
+                        aggregates.keys,
+                        aggregates.bytes,
+                        aggregates.bytes.checked_div(aggregates.keys).unwrap_or(0),
+                        type_name,
+                        count,
+                    ),
+                };
+                Ok(Response::String(Some(report)))
+            }
+            ("POOL", []) => Ok(pool_stats()),
+            ("USAGE" | "STATS" | "DOCTOR" | "POOL", _) => Ok(Response::Error(format!(
+                "ERR wrong number of arguments for 'memory|{}' command",
+                subcommand.to_lowercase(),
+            ))),
+            _ => Ok(Response::Error(format!("ERR unknown subcommand '{}'. Try MEMORY HELP.", subcommand))),
+        }
+    }
+}
+
+/// Counters of the native allocation pool since it was set up. The hit rate is
+/// the percentage of pool allocations served from a free list.
+#[cfg(feature = "memory_pool")]
+fn pool_stats() -> Response {
+    let stats = crate::ffi::memory::get_memory_stats();
+    let lookups = stats.pool_hits + stats.pool_misses;
+    let hit_rate = match lookups {
+        0 => 0.0,
+        lookups => stats.pool_hits as f64 * 100.0 / lookups as f64,
+    };
+    let name = |name: &str| Response::String(Some(name.to_string()));
+    Response::Array(vec![
+        name("pool.enabled"),
+        Response::Integer(1),
+        name("pool.allocations"),
+        Response::Integer(stats.allocations as i64),
+        name("pool.deallocations"),
+        Response::Integer(stats.deallocations as i64),
+        name("pool.hit-rate"),
+        Response::String(Some(format!("{:.2}", hit_rate))),
+        name("pool.active-objects"),
+        Response::Integer(stats.active_objects as i64),
+        name("pool.bytes-allocated"),
+        Response::Integer(stats.bytes_allocated as i64),
+        name("pool.bytes-freed"),
+        Response::Integer(stats.bytes_freed as i64),
+    ])
+}
+
+/// Without the `memory_pool` feature there is no pool to report on
+#[cfg(not(feature = "memory_pool"))]
+fn pool_stats() -> Response {
+    Response::Array(vec![Response::String(Some("pool.enabled".to_string())), Response::Integer(0)])
+}
+
+/// Estimated bytes held by `key`. Collections are sized from their first
+/// `samples` elements scaled up to their length; 0 sizes every element.
+fn estimate(key: &str, value: &DataType, samples: usize) -> usize {
+    let base = KEY_OVERHEAD + key.len();
+    match value {
+        DataType::String(s) => base + s.len(),
+        DataType::List(items) => base + scaled(items.iter().map(String::len), items.len(), samples),
+        DataType::ListPack(pack) => base + pack.byte_len(),
+        DataType::Set(members) => base + scaled(members.iter().map(String::len), members.len(), samples),
+        DataType::Hash(fields) => {
+            base + scaled(fields.iter().map(|(field, value)| field.len() + value.len()), fields.len(), samples)
+        }
+        DataType::SortedSet(members) => {
+            base + scaled(members.keys().map(|member| member.len() + std::mem::size_of::<f64>()), members.len(), samples)
+        }
+        DataType::Json(json) => base + json.to_string().len(),
+        DataType::Stream(entries) => {
+            let entry_size = |entry: &crate::data_types::StreamEntry| {
+                entry.id.len()
+                    + std::mem::size_of::<u64>()
+                    + entry.fields.iter().map(|(field, value)| field.len() + value.len() + ELEMENT_OVERHEAD).sum::<usize>()
+            };
+            base + scaled(entries.iter().map(entry_size), entries.len(), samples)
+        }
+    }
+}
+
+/// Size of `len` elements extrapolated from the first `samples` of `sizes`
+fn scaled(sizes: impl Iterator<Item = usize>, len: usize, samples: usize) -> usize {
+    let take = if samples == 0 { len } else { samples.min(len) };
+    if take == 0 {
+        return 0;
+    }
+    let sampled: usize = sizes.take(take).map(|size| size + ELEMENT_OVERHEAD).sum();
+    sampled * len / take
+}
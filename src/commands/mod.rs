@@ -1,40 +1,318 @@
-use crate::data_types::DataType;
+use crate::data_types::{DataType, ZAddReply};
 use crate::error::Result;
-use crate::protocol::{Request, Response};
+use crate::glob::glob_match;
+use crate::protocol::{Aggregate, BitOp, ExpireFlags, GetExOption, Request, Response, SetExpiry, SetOptions};
 use crate::storage::Storage;
 use async_trait::async_trait;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
 
+/// Page size of SSCAN/HSCAN/ZSCAN when no COUNT is given
+const DEFAULT_SCAN_COUNT: usize = 10;
+
+/// Number of logical databases; everything lives in database 0
+pub const DATABASES: i64 = 1;
+
+/// Reply for writes whose value is larger than the configured limit
+pub const VALUE_TOO_LARGE: &str = "ERR value exceeds max-value-size";
+
+pub mod blocking;
+pub mod cluster;
+pub mod debug;
+pub mod dump;
 pub mod get;
+pub mod key_lock;
+pub mod latency;
+pub mod lcs;
+pub mod memory;
+pub mod monitor;
+pub mod script;
 pub mod set;
+pub mod table;
+pub mod tracking;
+
+use blocking::{BlockedClients, Pop};
+use key_lock::KeyLocks;
+use latency::{LatencyMonitor, COMMAND_EVENT};
+use monitor::Monitor;
+use script::{FunctionRegistry, ScriptCache, NO_SCRIPT};
+use table::CommandSpec;
+use tracking::Tracking;
 
 #[async_trait]
 pub trait Command: Send + Sync {
     async fn execute(&self, storage: Arc<dyn Storage>) -> Result<Response>;
 }
 
+#[derive(Clone)]
 pub struct CommandExecutor {
     storage: Arc<dyn Storage>,
+    /// Largest string value or list element accepted by writes; 0 means unlimited
+    max_value_size: usize,
+    scripts: ScriptCache,
+    functions: FunctionRegistry,
+    /// Commands share this lock; a running script holds it exclusively
+    script_lock: Arc<RwLock<()>>,
+    /// Whether DEBUG subcommands are accepted
+    debug_enabled: bool,
+    blocked: BlockedClients,
+    tracking: Tracking,
+    /// Held by writes for their keys, so read-modify-write commands on one key run one at a time
+    key_locks: KeyLocks,
+    latency: LatencyMonitor,
+    monitor: Monitor,
+    /// How long a script or function may run before it is stopped; zero means no limit
+    lua_time_limit: Duration,
 }
 
 impl CommandExecutor {
     pub fn new(storage: Arc<dyn Storage>) -> Self {
-        Self { storage }
+        Self {
+            storage,
+            max_value_size: 0,
+            scripts: ScriptCache::default(),
+            functions: FunctionRegistry::default(),
+            script_lock: Arc::new(RwLock::new(())),
+            debug_enabled: false,
+            blocked: BlockedClients::default(),
+            tracking: Tracking::default(),
+            key_locks: KeyLocks::default(),
+            latency: LatencyMonitor::default(),
+            monitor: Monitor::default(),
+            lua_time_limit: Duration::ZERO,
+        }
+    }
+    
+    /// Reject SET, APPEND and list pushes whose value would exceed `bytes`
+    pub fn with_max_value_size(mut self, bytes: usize) -> Self {
+        self.max_value_size = bytes;
+        self
+    }
+    
+    /// Accept DEBUG subcommands, which can rewrite the whole keyspace
+    pub fn with_debug_command(mut self, enabled: bool) -> Self {
+        self.debug_enabled = enabled;
+        self
+    }
+    
+    /// Track at most `keys` keys per CLIENT TRACKING client before sending it every invalidation
+    pub fn with_tracking_limit(mut self, keys: usize) -> Self {
+        self.tracking = Tracking::new(keys);
+        self
+    }
+    
+    /// Keys read by tracking clients; every write through this executor invalidates them
+    pub fn tracking(&self) -> &Tracking {
+        &self.tracking
+    }
+    
+    /// Record commands taking at least `ms` milliseconds for LATENCY; 0 records nothing
+    pub fn with_latency_threshold(mut self, ms: u64) -> Self {
+        self.latency = LatencyMonitor::new(ms);
+        self
+    }
+    
+    /// Stop scripts and functions that run longer than `ms` milliseconds; 0 lets them run forever
+    pub fn with_lua_time_limit(mut self, ms: u64) -> Self {
+        self.lua_time_limit = Duration::from_millis(ms);
+        self
+    }
+    
+    /// Latency spikes reported by LATENCY; other background work records into it too
+    pub fn latency_monitor(&self) -> &LatencyMonitor {
+        &self.latency
+    }
+    
+    /// Feed of commands for MONITOR; connections send what they process, scripts their calls
+    pub fn monitor(&self) -> &Monitor {
+        &self.monitor
+    }
+    
+    fn value_too_large(&self, len: usize) -> bool {
+        self.max_value_size != 0 && len > self.max_value_size
+    }
+
+    /// Run `requests` in order and collect one result per request.
+    ///
+    /// A failing request does not abort the batch: its error is returned in its slot
+    /// and the following requests still run. Requests are applied one after another
+    /// against live storage, so the batch is an ordering guarantee, not an atomic
+    /// transaction.
+    pub async fn execute_batch(&self, requests: Vec<Request>) -> Vec<Result<Response>> {
+        let mut responses = Vec::with_capacity(requests.len());
+        for request in requests {
+            responses.push(self.execute(request).await);
+        }
+        responses
     }
 
     pub async fn execute(&self, request: Request) -> Result<Response> {
+        self.execute_until(request, std::future::pending()).await
+    }
+    
+    /// Execute `request`, except that a blocking pop stops waiting once `hangup`
+    /// resolves, as it does when the client disconnects, so nothing is popped for
+    /// a client that is gone
+    pub async fn execute_until(&self, request: Request, hangup: impl Future<Output = ()>) -> Result<Response> {
+        // Blocking pops wait for other clients by design, so their wait is not a spike
+        let timed = !request.is_blocking();
+        let started = Instant::now();
+        let response = match request {
+            Request::BLPop { keys, timeout_ms } => self.blocking_pop(keys, Pop::Left, timeout_ms, hangup).await,
+            Request::BRPop { keys, timeout_ms } => self.blocking_pop(keys, Pop::Right, timeout_ms, hangup).await,
+            Request::BZPopMin { keys, timeout_ms } => self.blocking_pop(keys, Pop::Min, timeout_ms, hangup).await,
+            Request::BZPopMax { keys, timeout_ms } => self.blocking_pop(keys, Pop::Max, timeout_ms, hangup).await,
+            request => self.dispatch(request).await,
+        };
+        if timed {
+            self.latency.record(COMMAND_EVENT, self.storage.now_millis(), started.elapsed());
+        }
+        response
+    }
+    
+    async fn dispatch(&self, request: Request) -> Result<Response> {
+        match request {
+            Request::Eval { script, keys, args } => {
+                let _exclusive = self.script_lock.write().await;
+                self.scripts.load(script.clone());
+                self.run_script(script, keys, args).await
+            }
+            Request::EvalSha { sha, keys, args } => {
+                let _exclusive = self.script_lock.write().await;
+                match self.scripts.get(&sha) {
+                    Some(script) => self.run_script(script, keys, args).await,
+                    None => Ok(Response::Error(NO_SCRIPT.to_string())),
+                }
+            }
+            Request::ScriptLoad { script } => {
+                Ok(Response::String(Some(self.scripts.load(script))))
+            }
+            Request::FunctionLoad { code, replace } => {
+                let _exclusive = self.script_lock.write().await;
+                self.load_function_library(code, replace).await
+            }
+            Request::FCall { name, keys, args } => {
+                let _exclusive = self.script_lock.write().await;
+                self.call_function(name, keys, args).await
+            }
+            Request::FunctionList => {
+                Ok(self.list_functions())
+            }
+            Request::DebugReload => {
+                let _exclusive = self.script_lock.write().await;
+                self.debug_reload().await
+            }
+            Request::FlushDb { .. } | Request::FlushAll { .. } => {
+                // No other command may see the keyspace half cleared
+                let _exclusive = self.script_lock.write().await;
+                self.execute_command(request).await
+            }
+            Request::DebugListpackLimits { entries, bytes } => {
+                if !self.debug_enabled {
+                    return Ok(Response::Error(debug::DEBUG_DISABLED.to_string()));
+                }
+                let mut limits = self.storage.encoding_limits();
+                limits.list_max_listpack_entries = entries;
+                limits.list_max_listpack_bytes = bytes;
+                self.storage.set_encoding_limits(limits);
+                Ok(Response::Ok)
+            }
+            Request::DebugStringMatch { pattern, text } => {
+                if !self.debug_enabled {
+                    return Ok(Response::Error(debug::DEBUG_DISABLED.to_string()));
+                }
+                Ok(Response::Integer(glob_match(pattern.as_bytes(), text.as_bytes()) as i64))
+            }
+            Request::DebugSetTime { millis } => {
+                if !self.debug_enabled {
+                    return Ok(Response::Error(debug::DEBUG_DISABLED.to_string()));
+                }
+                Ok(match self.storage.clock().as_mock() {
+                    Some(clock) => {
+                        clock.set(millis);
+                        Response::Ok
+                    }
+                    None => Response::Error(debug::NO_MOCK_CLOCK.to_string()),
+                })
+            }
+            Request::DebugAdvanceTime { millis } => {
+                if !self.debug_enabled {
+                    return Ok(Response::Error(debug::DEBUG_DISABLED.to_string()));
+                }
+                Ok(match self.storage.clock().as_mock() {
+                    Some(clock) => Response::Integer(clock.advance(millis) as i64),
+                    None => Response::Error(debug::NO_MOCK_CLOCK.to_string()),
+                })
+            }
+            Request::DebugSleep { millis } => {
+                if !self.debug_enabled {
+                    return Ok(Response::Error(debug::DEBUG_DISABLED.to_string()));
+                }
+                tokio::time::sleep(Duration::from_millis(millis)).await;
+                Ok(Response::Ok)
+            }
+            Request::DebugObjectCheck { key } => {
+                if !self.debug_enabled {
+                    return Ok(Response::Error(debug::DEBUG_DISABLED.to_string()));
+                }
+                Ok(match self.storage.get(&key).await? {
+                    Some(data) => match data.verify_invariants() {
+                        Ok(()) => Response::Ok,
+                        Err(e) => Response::Error(format!("ERR {}", e)),
+                    },
+                    None => Response::Error("ERR no such key".to_string()),
+                })
+            }
+            Request::DebugPopulate { count, prefix, size } => {
+                let _exclusive = self.script_lock.write().await;
+                self.debug_populate(count, &prefix, size).await
+            }
+            request => {
+                let _shared = self.script_lock.read().await;
+                let _keys = if request.is_write() {
+                    self.key_locks.lock(&request.keys()).await
+                } else {
+                    Vec::new()
+                };
+                self.execute_unlocked(request).await
+            }
+        }
+    }
+    
+    /// Execute without taking the script or key locks; scripts, which hold the
+    /// script lock exclusively, call back in through here
+    async fn execute_unlocked(&self, request: Request) -> Result<Response> {
+        let written: Vec<String> = if request.is_write() {
+            request.keys().into_iter().map(str::to_string).collect()
+        } else {
+            Vec::new()
+        };
+        let response = self.execute_command(request).await;
+        self.tracking.invalidate(&written.iter().map(String::as_str).collect::<Vec<_>>());
+        response
+    }
+    
+    async fn execute_command(&self, request: Request) -> Result<Response> {
         match request {
             // String operations
             Request::Get { key } => {
-                match self.storage.get(&key).await? {
+                match self.storage.get_now(&key)? {
                     Some(DataType::String(value)) => Ok(Response::String(Some(value))),
                     Some(_) => Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
                     None => Ok(Response::Null),
                 }
             }
-            Request::Set { key, value } => {
-                self.storage.set(&key, DataType::String(value)).await?;
-                Ok(Response::Ok)
+            Request::GetEx { key, expiry } => {
+                self.execute_getex(&key, expiry).await
+            }
+            Request::Set { key, value, options } => {
+                if self.value_too_large(value.len()) {
+                    return Ok(Response::Error(VALUE_TOO_LARGE.to_string()));
+                }
+                self.execute_set(&key, value, options).await
             }
             Request::Incr { key } => {
                 self.execute_incr(&key, 1).await
@@ -46,87 +324,147 @@ impl CommandExecutor {
                 self.execute_incr(&key, delta).await
             }
             Request::DecrBy { key, delta } => {
-                self.execute_incr(&key, -delta).await
+                let delta = delta.checked_neg()
+                    .ok_or_else(|| crate::error::DiskDBError::Database("Increment or decrement would overflow".to_string()))?;
+                self.execute_incr(&key, delta).await
+            }
+            Request::IncrByFloat { key, delta } => {
+                match self.storage.incr_by_float(&key, delta).await? {
+                    Ok(value) => Ok(Response::String(Some(value))),
+                    Err(e) => Ok(Response::Error(e)),
+                }
             }
             Request::Append { key, value } => {
-                let result = match self.storage.get(&key).await? {
-                    Some(DataType::String(mut s)) => {
-                        s.push_str(&value);
-                        let len = s.len();
-                        self.storage.set(&key, DataType::String(s)).await?;
-                        len
-                    }
-                    None => {
-                        let len = value.len();
-                        self.storage.set(&key, DataType::String(value)).await?;
-                        len
-                    }
+                // The reply is the byte length of the string after the append
+                let current = match self.storage.collection_len(&key).await? {
+                    Some(("string", len)) => len,
                     Some(_) => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                    None => 0,
                 };
-                Ok(Response::Integer(result as i64))
+                if self.value_too_large(current + value.len()) {
+                    return Ok(Response::Error(VALUE_TOO_LARGE.to_string()));
+                }
+                let len = self.storage.append(&key, &value).await?;
+                Ok(Response::Integer(len as i64))
+            }
+            Request::GetRange { key, start, end } => {
+                match self.storage.get(&key).await? {
+                    Some(DataType::String(s)) => Ok(Response::String(Some(byte_range(&s, start, end)))),
+                    None => Ok(Response::String(Some(String::new()))),
+                    Some(_) => Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                }
+            }
+            Request::StrLen { key } => {
+                self.execute_len(&key, "string").await
+            }
+            Request::Lcs { key1, key2, len_only, idx, minmatchlen, withmatchlen } => {
+                self.lcs(&key1, &key2, len_only, idx, minmatchlen, withmatchlen).await
+            }
+            Request::BitPos { key, bit, start, end } => {
+                match self.storage.get(&key).await? {
+                    Some(DataType::String(s)) => Ok(Response::Integer(bit_position(s.as_bytes(), bit, start, end))),
+                    // A missing key is an endless run of zero bits
+                    None => Ok(Response::Integer(if bit == 0 { 0 } else { -1 })),
+                    Some(_) => Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                }
+            }
+            Request::BitOp { op, dest, keys } => {
+                self.execute_bitop(op, &dest, &keys).await
             }
             
             // List operations
             Request::LPush { key, values } => {
+                if values.iter().any(|v| self.value_too_large(v.len())) {
+                    return Ok(Response::Error(VALUE_TOO_LARGE.to_string()));
+                }
                 let mut data = self.storage.get_or_create_list(&key).await?;
                 let count = data.lpush(values).map_err(crate::error::DiskDBError::Database)?;
                 self.storage.set(&key, data).await?;
+                self.serve_blocked(&key).await?;
                 Ok(Response::Integer(count as i64))
             }
             Request::RPush { key, values } => {
+                if values.iter().any(|v| self.value_too_large(v.len())) {
+                    return Ok(Response::Error(VALUE_TOO_LARGE.to_string()));
+                }
                 let mut data = self.storage.get_or_create_list(&key).await?;
                 let count = data.rpush(values).map_err(crate::error::DiskDBError::Database)?;
                 self.storage.set(&key, data).await?;
+                self.serve_blocked(&key).await?;
                 Ok(Response::Integer(count as i64))
             }
-            Request::LPop { key } => {
+            Request::LPop { key, count } => self.execute_pop(&key, true, count).await,
+            Request::RPop { key, count } => self.execute_pop(&key, false, count).await,
+            Request::LMPop { keys, from_left, count } => {
+                self.execute_mpop(&keys, "list", |data| {
+                    let values = data.lpop_count(from_left, count)?;
+                    Ok(values.into_iter().map(|v| Response::String(Some(v))).collect())
+                }).await
+            }
+            // Scripts cannot block, so these only pop what is already there
+            Request::BLPop { keys, .. } => {
+                Ok(self.pop_first(&keys, Pop::Left).await?.unwrap_or(Response::Null))
+            }
+            Request::BRPop { keys, .. } => {
+                Ok(self.pop_first(&keys, Pop::Right).await?.unwrap_or(Response::Null))
+            }
+            Request::LRange { key, start, stop } => {
                 match self.storage.get(&key).await? {
-                    Some(mut data) => match data.lpop() {
-                        Ok(Some(value)) => {
-                            if data.as_list().map(|l| l.is_empty()).unwrap_or(false) {
-                                self.storage.delete(&key).await?;
-                            } else {
-                                self.storage.set(&key, data).await?;
-                            }
-                            Ok(Response::String(Some(value)))
-                        }
-                        Ok(None) => Ok(Response::Null),
+                    Some(data) => match data.lrange(start, stop) {
+                        Ok(values) => Ok(Response::Array(values.into_iter().map(|v| Response::String(Some(v))).collect())),
                         Err(e) => Ok(Response::Error(e)),
                     },
-                    None => Ok(Response::Null),
+                    None => Ok(Response::Array(vec![])),
                 }
             }
-            Request::RPop { key } => {
+            Request::LLen { key } => {
+                self.execute_len(&key, "list").await
+            }
+            Request::LSet { key, index, value } => {
+                if self.value_too_large(value.len()) {
+                    return Ok(Response::Error(VALUE_TOO_LARGE.to_string()));
+                }
                 match self.storage.get(&key).await? {
-                    Some(mut data) => match data.rpop() {
-                        Ok(Some(value)) => {
-                            if data.as_list().map(|l| l.is_empty()).unwrap_or(false) {
-                                self.storage.delete(&key).await?;
-                            } else {
-                                self.storage.set(&key, data).await?;
-                            }
-                            Ok(Response::String(Some(value)))
+                    Some(mut data) => match data.lset(index, value) {
+                        Ok(()) => {
+                            self.storage.set(&key, data).await?;
+                            Ok(Response::Ok)
                         }
-                        Ok(None) => Ok(Response::Null),
                         Err(e) => Ok(Response::Error(e)),
                     },
-                    None => Ok(Response::Null),
+                    None => Ok(Response::Error("ERR no such key".to_string())),
                 }
             }
-            Request::LRange { key, start, stop } => {
+            Request::LInsert { key, before, pivot, value } => {
+                if self.value_too_large(value.len()) {
+                    return Ok(Response::Error(VALUE_TOO_LARGE.to_string()));
+                }
                 match self.storage.get(&key).await? {
-                    Some(data) => match data.lrange(start, stop) {
-                        Ok(values) => Ok(Response::Array(values.into_iter().map(|v| Response::String(Some(v))).collect())),
+                    Some(mut data) => match data.linsert(before, &pivot, value) {
+                        Ok(Some(len)) => {
+                            self.storage.set(&key, data).await?;
+                            Ok(Response::Integer(len as i64))
+                        }
+                        Ok(None) => Ok(Response::Integer(-1)),
                         Err(e) => Ok(Response::Error(e)),
                     },
-                    None => Ok(Response::Array(vec![])),
+                    None => Ok(Response::Integer(0)),
                 }
             }
-            Request::LLen { key } => {
+            Request::LTrim { key, start, stop } => {
                 match self.storage.get(&key).await? {
-                    Some(DataType::List(list)) => Ok(Response::Integer(list.len() as i64)),
-                    Some(_) => Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
-                    None => Ok(Response::Integer(0)),
+                    Some(mut data) => match data.ltrim(start, stop) {
+                        Ok(()) => {
+                            if data.as_list().is_some_and(|l| l.is_empty()) {
+                                self.storage.delete(&key).await?;
+                            } else {
+                                self.storage.set(&key, data).await?;
+                            }
+                            Ok(Response::Ok)
+                        }
+                        Err(e) => Ok(Response::Error(e)),
+                    },
+                    None => Ok(Response::Ok),
                 }
             }
             
@@ -170,23 +508,76 @@ impl CommandExecutor {
                     None => Ok(Response::Integer(0)),
                 }
             }
+            Request::SMIsMember { key, members } => {
+                let found = match self.storage.get(&key).await? {
+                    Some(data) => match data.smismember(&members) {
+                        Ok(found) => found,
+                        Err(_) => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                    },
+                    None => vec![false; members.len()],
+                };
+                Ok(Response::Array(found.into_iter().map(|f| Response::Integer(f as i64)).collect()))
+            }
             Request::SCard { key } => {
-                match self.storage.get(&key).await? {
-                    Some(DataType::Set(set)) => Ok(Response::Integer(set.len() as i64)),
-                    Some(_) => Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
-                    None => Ok(Response::Integer(0)),
-                }
+                self.execute_len(&key, "set").await
+            }
+            Request::SScan { key, cursor, match_pattern, count } => {
+                let data = self.storage.get(&key).await?;
+                self.execute_collection_scan(data, "set", cursor, match_pattern.as_deref(), count)
             }
             
             // Hash operations
             Request::HSet { key, field, value } => {
-                let mut data = self.storage.get_or_create_hash(&key).await?;
-                let is_new = data.hset(field, value).map_err(crate::error::DiskDBError::Database)?;
+                let (data, mut deadlines) = self.get_hash(&key).await?;
+                let mut data = match data {
+                    Some(data @ DataType::Hash(_)) => data,
+                    None => DataType::Hash(HashMap::new()),
+                    Some(_) => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                };
+                let is_new = data.hset(field.clone(), value).map_err(crate::error::DiskDBError::Database)?;
                 self.storage.set(&key, data).await?;
+                // Overwriting a field clears its TTL
+                if deadlines.remove(&field).is_some() {
+                    self.storage.set_field_deadlines(&key, &deadlines).await?;
+                }
                 Ok(Response::Integer(if is_new { 1 } else { 0 }))
             }
+            Request::HMSet { key, pairs } => {
+                let (data, mut deadlines) = self.get_hash(&key).await?;
+                let mut data = match data {
+                    Some(data @ DataType::Hash(_)) => data,
+                    None => DataType::Hash(HashMap::new()),
+                    Some(_) => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                };
+                let cleared = pairs.iter().filter(|(field, _)| deadlines.remove(field).is_some()).count();
+                data.hset_multiple(pairs).map_err(crate::error::DiskDBError::Database)?;
+                self.storage.set(&key, data).await?;
+                if cleared > 0 {
+                    self.storage.set_field_deadlines(&key, &deadlines).await?;
+                }
+                Ok(Response::Ok)
+            }
+            Request::HStrLen { key, field } => {
+                match self.get_hash(&key).await?.0 {
+                    Some(data @ DataType::Hash(_)) => Ok(Response::Integer(data.hstrlen(&field).map_err(crate::error::DiskDBError::Database)? as i64)),
+                    Some(_) => Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                    None => Ok(Response::Integer(0)),
+                }
+            }
+            Request::HSetNx { key, field, value } => {
+                let mut data = match self.get_hash(&key).await?.0 {
+                    Some(data @ DataType::Hash(_)) => data,
+                    None => DataType::Hash(HashMap::new()),
+                    Some(_) => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                };
+                if !data.hsetnx(field, value).map_err(crate::error::DiskDBError::Database)? {
+                    return Ok(Response::Integer(0));
+                }
+                self.storage.set(&key, data).await?;
+                Ok(Response::Integer(1))
+            }
             Request::HGet { key, field } => {
-                match self.storage.get(&key).await? {
+                match self.get_hash(&key).await?.0 {
                     Some(data) => match data.hget(&field) {
                         Ok(Some(value)) => Ok(Response::String(Some(value))),
                         Ok(None) => Ok(Response::Null),
@@ -196,21 +587,25 @@ impl CommandExecutor {
                 }
             }
             Request::HDel { key, fields } => {
-                match self.storage.get(&key).await? {
-                    Some(mut data) => {
+                match self.get_hash(&key).await? {
+                    (Some(mut data), mut deadlines) => {
+                        let had_deadline = fields.iter().any(|f| deadlines.remove(f).is_some());
                         let deleted = data.hdel(fields).map_err(crate::error::DiskDBError::Database)?;
                         if data.as_hash().map(|h| h.is_empty()).unwrap_or(false) {
                             self.storage.delete(&key).await?;
                         } else {
                             self.storage.set(&key, data).await?;
+                            if had_deadline {
+                                self.storage.set_field_deadlines(&key, &deadlines).await?;
+                            }
                         }
                         Ok(Response::Integer(deleted as i64))
                     }
-                    None => Ok(Response::Integer(0)),
+                    (None, _) => Ok(Response::Integer(0)),
                 }
             }
             Request::HGetAll { key } => {
-                match self.storage.get(&key).await? {
+                match self.get_hash(&key).await?.0 {
                     Some(DataType::Hash(hash)) => {
                         let mut result = Vec::new();
                         for (field, value) in hash {
@@ -223,8 +618,13 @@ impl CommandExecutor {
                     None => Ok(Response::Array(vec![])),
                 }
             }
+            Request::HLen { key } => {
+                // Expired fields must be dropped before the header count is trusted
+                self.get_hash(&key).await?;
+                self.execute_len(&key, "hash").await
+            }
             Request::HExists { key, field } => {
-                match self.storage.get(&key).await? {
+                match self.get_hash(&key).await?.0 {
                     Some(DataType::Hash(hash)) => {
                         Ok(Response::Integer(if hash.contains_key(&field) { 1 } else { 0 }))
                     }
@@ -233,12 +633,66 @@ impl CommandExecutor {
                 }
             }
             
+            Request::HScan { key, cursor, match_pattern, count } => {
+                let data = self.get_hash(&key).await?.0;
+                self.execute_collection_scan(data, "hash", cursor, match_pattern.as_deref(), count)
+            }
+            Request::HExpire { key, seconds, fields } => {
+                self.execute_hexpire(&key, seconds, &fields).await
+            }
+            Request::HTtl { key, fields } => {
+                match self.get_hash(&key).await? {
+                    (Some(DataType::Hash(hash)), deadlines) => {
+                        let now = self.storage.now_millis();
+                        Ok(Response::Array(fields.iter().map(|field| {
+                            Response::Integer(match (hash.contains_key(field), deadlines.get(field)) {
+                                (false, _) => -2,
+                                (true, None) => -1,
+                                (true, Some(deadline)) => ((deadline.saturating_sub(now) + 500) / 1000) as i64,
+                            })
+                        }).collect()))
+                    }
+                    (Some(_), _) => Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                    (None, _) => Ok(Response::Array(fields.iter().map(|_| Response::Integer(-2)).collect())),
+                }
+            }
+            Request::HPersist { key, fields } => {
+                match self.get_hash(&key).await? {
+                    (Some(DataType::Hash(hash)), mut deadlines) => {
+                        let statuses: Vec<Response> = fields.iter().map(|field| {
+                            Response::Integer(if !hash.contains_key(field) {
+                                -2
+                            } else if deadlines.remove(field).is_some() {
+                                1
+                            } else {
+                                -1
+                            })
+                        }).collect();
+                        self.storage.set_field_deadlines(&key, &deadlines).await?;
+                        Ok(Response::Array(statuses))
+                    }
+                    (Some(_), _) => Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                    (None, _) => Ok(Response::Array(fields.iter().map(|_| Response::Integer(-2)).collect())),
+                }
+            }
+            
             // Sorted Set operations
-            Request::ZAdd { key, members } => {
+            Request::ZAdd { key, members, flags } => {
                 let mut data = self.storage.get_or_create_sorted_set(&key).await?;
-                let added = data.zadd(members).map_err(crate::error::DiskDBError::Database)?;
-                self.storage.set(&key, data).await?;
-                Ok(Response::Integer(added as i64))
+                let reply = match data.zadd(members, flags) {
+                    Ok(reply) => reply,
+                    Err(e) => return Ok(Response::Error(e)),
+                };
+                // XX or INCR under a failed condition can leave a new key empty
+                if data.as_sorted_set().map(|z| !z.is_empty()).unwrap_or(false) {
+                    self.storage.set(&key, data).await?;
+                    self.serve_blocked(&key).await?;
+                }
+                match reply {
+                    ZAddReply::Count(count) => Ok(Response::Integer(count as i64)),
+                    ZAddReply::Score(Some(score)) => Ok(Response::String(Some(score.to_string()))),
+                    ZAddReply::Score(None) => Ok(Response::Null),
+                }
             }
             Request::ZRem { key, members } => {
                 match self.storage.get(&key).await? {
@@ -254,6 +708,23 @@ impl CommandExecutor {
                     None => Ok(Response::Integer(0)),
                 }
             }
+            Request::BZPopMin { keys, .. } => {
+                Ok(self.pop_first(&keys, Pop::Min).await?.unwrap_or(Response::Null))
+            }
+            Request::BZPopMax { keys, .. } => {
+                Ok(self.pop_first(&keys, Pop::Max).await?.unwrap_or(Response::Null))
+            }
+            Request::ZMPop { keys, min, count } => {
+                self.execute_mpop(&keys, "zset", |data| {
+                    let members = data.zpop(min, count)?;
+                    Ok(members.into_iter()
+                        .map(|(member, score)| Response::Array(vec![
+                            Response::String(Some(member)),
+                            Response::String(Some(score.to_string())),
+                        ]))
+                        .collect())
+                }).await
+            }
             Request::ZRange { key, start, stop, with_scores } => {
                 match self.storage.get(&key).await? {
                     Some(data) => match data.zrange(start, stop, with_scores) {
@@ -284,13 +755,68 @@ impl CommandExecutor {
                     None => Ok(Response::Null),
                 }
             }
-            Request::ZCard { key } => {
+            Request::ZRangeByLex { key, min, max, limit } => {
                 match self.storage.get(&key).await? {
-                    Some(DataType::SortedSet(zset)) => Ok(Response::Integer(zset.len() as i64)),
-                    Some(_) => Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                    Some(data) => match data.zrangebylex(&min, &max, limit) {
+                        Ok(members) => Ok(Response::Array(
+                            members.into_iter().map(|member| Response::String(Some(member))).collect(),
+                        )),
+                        Err(e) => Ok(Response::Error(e)),
+                    },
+                    None => Ok(Response::Array(vec![])),
+                }
+            }
+            Request::ZLexCount { key, min, max } => {
+                match self.storage.get(&key).await? {
+                    Some(data) => match data.zlexcount(&min, &max) {
+                        Ok(count) => Ok(Response::Integer(count as i64)),
+                        Err(e) => Ok(Response::Error(e)),
+                    },
                     None => Ok(Response::Integer(0)),
                 }
             }
+            Request::ZUnionStore { dest, keys, weights, aggregate } => {
+                self.execute_zstore(&dest, &keys, &weights, aggregate, false).await
+            }
+            Request::ZInterStore { dest, keys, weights, aggregate } => {
+                self.execute_zstore(&dest, &keys, &weights, aggregate, true).await
+            }
+            Request::ZDiff { keys, with_scores } => {
+                let diff = match self.zdiff(&keys).await? {
+                    Some(diff) => diff,
+                    None => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                };
+                let mut members: Vec<(String, f64)> = diff.into_iter().collect();
+                members.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+                let result = members.into_iter()
+                    .flat_map(|(member, score)| {
+                        let score = with_scores.then(|| Response::String(Some(score.to_string())));
+                        std::iter::once(Response::String(Some(member))).chain(score)
+                    })
+                    .collect();
+                Ok(Response::Array(result))
+            }
+            Request::ZDiffStore { dest, keys } => {
+                let diff = match self.zdiff(&keys).await? {
+                    Some(diff) => diff,
+                    None => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                };
+                let len = diff.len();
+                if len == 0 {
+                    self.storage.delete(&dest).await?;
+                } else {
+                    self.storage.set(&dest, DataType::SortedSet(diff)).await?;
+                    self.serve_blocked(&dest).await?;
+                }
+                Ok(Response::Integer(len as i64))
+            }
+            Request::ZCard { key } => {
+                self.execute_len(&key, "zset").await
+            }
+            Request::ZScan { key, cursor, match_pattern, count } => {
+                let data = self.storage.get(&key).await?;
+                self.execute_collection_scan(data, "zset", cursor, match_pattern.as_deref(), count)
+            }
             
             // JSON operations
             Request::JsonSet { key, path, value } => {
@@ -333,7 +859,7 @@ impl CommandExecutor {
                 let mut data = self.storage.get_or_create_stream(&key).await?;
                 let id_option = if id == "*" { None } else { Some(id) };
                 let fields_map: std::collections::HashMap<String, String> = fields.into_iter().collect();
-                match data.xadd(id_option, fields_map) {
+                match data.xadd(id_option, fields_map, self.storage.now_millis()) {
                     Ok(entry_id) => {
                         self.storage.set(&key, data).await?;
                         Ok(Response::String(Some(entry_id)))
@@ -361,57 +887,866 @@ impl CommandExecutor {
                 }
             }
             Request::XLen { key } => {
-                match self.storage.get(&key).await? {
-                    Some(data) => match data.xlen() {
-                        Ok(len) => Ok(Response::Integer(len as i64)),
-                        Err(e) => Ok(Response::Error(e)),
-                    },
-                    None => Ok(Response::Integer(0)),
-                }
+                self.execute_len(&key, "stream").await
+            }
+            
+            // Expiry operations
+            Request::Expire { key, seconds, flags } => {
+                let deadline = (self.storage.now_millis() as i64).saturating_add(seconds.saturating_mul(1000));
+                self.execute_expire_at(&key, deadline, flags).await
+            }
+            Request::ExpireAt { key, timestamp, flags } => {
+                self.execute_expire_at(&key, timestamp.saturating_mul(1000), flags).await
+            }
+            Request::PExpire { key, millis, flags } => {
+                let deadline = (self.storage.now_millis() as i64).saturating_add(millis);
+                self.execute_expire_at(&key, deadline, flags).await
+            }
+            Request::PExpireAt { key, ms_timestamp, flags } => {
+                self.execute_expire_at(&key, ms_timestamp, flags).await
+            }
+            Request::Ttl { key } => {
+                self.execute_ttl(&key, false).await
+            }
+            Request::PTtl { key } => {
+                self.execute_ttl(&key, true).await
+            }
+            
+            // Sort operations
+            Request::Sort { key, alpha, desc, limit, by, get } => {
+                self.execute_sort(&key, alpha, desc, limit, by.as_deref(), &get).await
             }
             
             // Utility operations
+            Request::ObjectFreq { key } => {
+                if !self.storage.tracks_access_frequency() {
+                    return Ok(Response::Error("ERR An LFU maxmemory policy is not selected, access frequency not tracked".to_string()));
+                }
+                match self.storage.access_frequency(&key).await? {
+                    Some(freq) => Ok(Response::Integer(freq as i64)),
+                    None => Ok(Response::Null),
+                }
+            }
+            Request::ObjectEncoding { key } => {
+                match self.storage.get(&key).await? {
+                    Some(data) => Ok(Response::String(Some(data.encoding(&self.storage.encoding_limits()).to_string()))),
+                    None => Ok(Response::Null),
+                }
+            }
             Request::Type { key } => {
                 match self.storage.get_type(&key).await? {
                     Some(type_name) => Ok(Response::String(Some(type_name))),
                     None => Ok(Response::String(Some("none".to_string()))),
                 }
             }
+            Request::Dump { key } => self.dump(&key).await,
+            Request::Restore { key, ttl, payload, replace, absttl } => {
+                self.restore(&key, ttl, &payload, replace, absttl).await
+            }
+            Request::Copy { source, destination, db, replace } => {
+                if db.is_some_and(|db| !(0..DATABASES).contains(&db)) {
+                    return Ok(Response::Error("ERR DB index is out of range".to_string()));
+                }
+                // Every valid index is the current database
+                if source == destination {
+                    return Ok(Response::Error("ERR source and destination objects are the same".to_string()));
+                }
+                if !self.storage.copy(&source, &destination, replace).await? {
+                    return Ok(Response::Integer(0));
+                }
+                self.serve_blocked(&destination).await?;
+                Ok(Response::Integer(1))
+            }
             Request::Del { keys } => {
                 let deleted = self.storage.delete_multiple(&keys).await?;
                 Ok(Response::Integer(deleted as i64))
             }
+            Request::Unlink { keys } => {
+                let unlinked = self.storage.unlink(&keys).await?;
+                Ok(Response::Integer(unlinked as i64))
+            }
+            Request::DelPrefix { prefix } => {
+                let deleted = match crate::storage::prefix_range(&prefix) {
+                    Some((start, end)) => self.storage.delete_range(&start, &end).await?,
+                    None => 0,
+                };
+                Ok(Response::Integer(deleted as i64))
+            }
             Request::Exists { keys } => {
                 let count = self.storage.exists_multiple(&keys).await?;
                 Ok(Response::Integer(count as i64))
             }
             Request::Ping => Ok(Response::String(Some("PONG".to_string()))),
             Request::Echo { message } => Ok(Response::String(Some(message))),
-            Request::FlushDb => {
-                // For now, return error as this is dangerous
-                Ok(Response::Error("FLUSHDB not implemented for safety".to_string()))
+            Request::FlushDb { lazy } => {
+                // Everything lives in database 0
+                self.storage.flush_db(0, lazy).await?;
+                self.tracking.invalidate_all();
+                Ok(Response::Ok)
+            }
+            Request::FlushAll { lazy } => {
+                self.storage.flush_all(lazy).await?;
+                self.tracking.invalidate_all();
+                Ok(Response::Ok)
+            }
+            Request::Scan { cursor, match_pattern, count, type_filter } => {
+                let cursor = usize::try_from(cursor).unwrap_or(usize::MAX);
+                let (next, keys) = self.storage
+                    .scan(cursor, count.unwrap_or(DEFAULT_SCAN_COUNT), type_filter.as_deref())
+                    .await?;
+                let keys = keys.into_iter()
+                    .filter(|key| match &match_pattern {
+                        Some(pattern) => glob_match(pattern.as_bytes(), key.as_bytes()),
+                        None => true,
+                    })
+                    .map(|key| Response::String(Some(key)))
+                    .collect();
+                Ok(Response::Array(vec![Response::String(Some(next.to_string())), Response::Array(keys)]))
+            }
+            Request::DbSize => {
+                let size = self.storage.dbsize().await?;
+                Ok(Response::Integer(size as i64))
+            }
+            Request::RandomKey => {
+                Ok(self.storage.random_key().await?.map_or(Response::Null, |key| Response::String(Some(key))))
             }
             Request::Info => {
                 // Return basic server info
-                let info = "# Server\nversion:0.1.0\n# Storage\nengine:rocksdb".to_string();
+                let compression = self.storage.compression_stats().await?;
+                let info = format!(
+                    "# Server\nversion:{}\ngit_commit:{}\n# Storage\nengine:rocksdb\ncompression:{}\nraw_data_bytes:{}\nstored_data_bytes:{}\ncompression_ratio:{:.2}",
+                    env!("CARGO_PKG_VERSION"),
+                    env!("DISKDB_GIT_COMMIT"),
+                    compression.codec,
+                    compression.raw_bytes,
+                    compression.stored_bytes,
+                    compression.ratio(),
+                );
                 Ok(Response::String(Some(info)))
             }
+            Request::Version => {
+                Ok(Self::version())
+            }
+            Request::Wait { numreplicas, .. } => Ok(Self::wait(numreplicas)),
+            Request::WaitAof { numlocal, .. } => Ok(Self::wait_aof(numlocal)),
+            Request::GetWriteOffset => {
+                Ok(Response::Integer(self.storage.write_offset().await? as i64))
+            }
+            Request::MinOffset { offset, request } => {
+                // Standalone, nothing else will bring the offset forward, so an
+                // offset from the future is refused rather than waited for
+                let current = self.storage.write_offset().await?;
+                if current < offset {
+                    return Ok(Response::Error(format!(
+                        "LOADING write offset {} has not reached {} yet", current, offset,
+                    )));
+                }
+                Box::pin(self.execute_command(*request)).await
+            }
+            Request::Command { subcommand, args } => {
+                Ok(Self::command(subcommand.as_deref(), &args))
+            }
+            Request::Memory { subcommand, args } => {
+                self.memory(&subcommand, &args).await
+            }
+            Request::Cluster { subcommand, args } => {
+                Ok(Self::cluster(&subcommand, &args))
+            }
+            Request::Latency { subcommand, args } => {
+                Ok(self.latency(&subcommand, &args))
+            }
+            Request::Help { command } => {
+                let lines = table::help(command).map(|(_, lines)| lines).unwrap_or_default();
+                Ok(Response::Array(lines.iter().map(|line| Response::String(Some(line.to_string()))).collect()))
+            }
+            Request::Eval { .. } |
+            Request::EvalSha { .. } |
+            Request::ScriptLoad { .. } |
+            Request::FunctionLoad { .. } |
+            Request::FCall { .. } |
+            Request::FunctionList |
+            Request::DebugReload |
+            Request::DebugListpackLimits { .. } |
+            Request::DebugStringMatch { .. } |
+            Request::DebugSetTime { .. } |
+            Request::DebugAdvanceTime { .. } |
+            Request::DebugSleep { .. } |
+            Request::DebugObjectCheck { .. } |
+            Request::DebugPopulate { .. } => {
+                Ok(Response::Error("ERR This command is not allowed from scripts".to_string()))
+            }
+            
+            // Connection operations are answered by the connection that owns the client state
+            Request::Client { subcommand, .. } => {
+                Ok(Response::Error(format!("CLIENT {} is not supported on this connection", subcommand)))
+            }
+            Request::PoolStats => {
+                Ok(Response::Error("POOL STATS is not supported on this connection".to_string()))
+            }
+            Request::Reset => {
+                Ok(Response::Error("RESET is not supported on this connection".to_string()))
+            }
+            // The connection closes itself after sending this
+            Request::Quit => Ok(Response::Ok),
+            Request::Hello { .. } => {
+                Ok(Response::Error("HELLO is not supported on this connection".to_string()))
+            }
+            Request::Monitor => {
+                Ok(Response::Error("MONITOR is not supported on this connection".to_string()))
+            }
+            Request::Subscribe { .. } |
+            Request::Unsubscribe { .. } |
+            Request::PSubscribe { .. } |
+            Request::PUnsubscribe { .. } |
+            Request::Publish { .. } |
+            Request::PubSub { .. } => {
+                Ok(Response::Error("Pub/Sub is not supported on this connection".to_string()))
+            }
         }
     }
     
-    async fn execute_incr(&self, key: &str, delta: i64) -> Result<Response> {
-        let result = match self.storage.get(key).await? {
-            Some(mut data) => {
-                let new_val = data.incr(delta).map_err(crate::error::DiskDBError::Database)?;
+    /// Build metadata: crate version, git commit, rocksdb crate version and the
+    /// compile-time features of this binary
+    fn version() -> Response {
+        let text = |s: &str| Response::String(Some(s.to_string()));
+        let features = [
+            ("c_parser", cfg!(feature = "c_parser")),
+            ("memory_pool", cfg!(feature = "memory_pool")),
+            ("io_uring", cfg!(feature = "io_uring")),
+            ("tracing", cfg!(feature = "tracing")),
+            // native-tls is always linked; whether it is used is a runtime setting
+            ("tls", true),
+        ];
+        
+        Response::Array(vec![
+            text("version"), text(env!("CARGO_PKG_VERSION")),
+            text("git_commit"), text(env!("DISKDB_GIT_COMMIT")),
+            text("rocksdb"), text(env!("DISKDB_ROCKSDB_VERSION")),
+            text("features"),
+            Response::Array(features.iter()
+                .map(|(name, enabled)| text(&format!("{}={}", name, if *enabled { "on" } else { "off" })))
+                .collect()),
+        ])
+    }
+    
+    /// COMMAND introspection over the static command table
+    fn command(subcommand: Option<&str>, names: &[String]) -> Response {
+        let text = |s: &str| Response::String(Some(s.to_string()));
+        let flags = |spec: &CommandSpec| Response::Array(spec.flags.iter().map(|f| text(f)).collect());
+        let entry = |spec: &CommandSpec| Response::Array(vec![
+            text(spec.name), Response::Integer(spec.arity as i64), flags(spec),
+            Response::Integer(spec.first_key as i64), Response::Integer(spec.last_key as i64), Response::Integer(spec.step as i64),
+        ]);
+        // Named commands in request order, or the whole table; unknown names are skipped
+        let selected = || -> Vec<&'static CommandSpec> {
+            if names.is_empty() {
+                table::COMMAND_TABLE.iter().collect()
+            } else {
+                names.iter().filter_map(|name| table::lookup(name)).collect()
+            }
+        };
+        
+        match subcommand {
+            None | Some("INFO") => Response::Array(selected().into_iter().map(entry).collect()),
+            Some("COUNT") => Response::Integer(table::COMMAND_TABLE.len() as i64),
+            Some("DOCS") => Response::Array(selected().into_iter()
+                .flat_map(|spec| [
+                    text(spec.name),
+                    Response::Array(vec![
+                        text("arity"), Response::Integer(spec.arity as i64),
+                        text("flags"), flags(spec),
+                    ]),
+                ])
+                .collect()),
+            Some("GETKEYS") => Self::command_getkeys(names),
+            Some(other) => Response::Error(format!("ERR unknown subcommand '{}'. Try COMMAND HELP.", other)),
+        }
+    }
+    
+    /// COMMAND GETKEYS: the key arguments of a full command, read from the table's
+    /// key positions. Commands whose keys move with a `numkeys` argument are parsed
+    /// to find them.
+    fn command_getkeys(args: &[String]) -> Response {
+        let Some(name) = args.first() else {
+            return Response::Error("ERR wrong number of arguments for 'command|getkeys' command".to_string());
+        };
+        let Some(spec) = table::lookup(name) else {
+            return Response::Error("ERR Invalid command specified".to_string());
+        };
+        let count = args.len() as i32;
+        if (spec.arity >= 0 && count != spec.arity) || count < -spec.arity {
+            return Response::Error("ERR Invalid number of arguments specified for command".to_string());
+        }
+        
+        let keys = if spec.has_movable_keys() {
+            let parts: Vec<&str> = args.iter().map(String::as_str).collect();
+            match Request::parse_parts(&parts) {
+                Ok(request) => request.keys().into_iter().map(str::to_string).collect(),
+                Err(_) => return Response::Error("ERR Invalid arguments specified for command".to_string()),
+            }
+        } else {
+            spec.key_args(args).into_iter().map(str::to_string).collect::<Vec<_>>()
+        };
+        if keys.is_empty() {
+            return Response::Error("ERR The command has no key arguments".to_string());
+        }
+        Response::Array(keys.into_iter().map(|key| Response::String(Some(key))).collect())
+    }
+    
+    /// WAIT: there is no replication, so no replica can acknowledge a write.
+    /// Asking for none replies 0 at once; asking for any is refused rather than
+    /// answered with a count that could never grow.
+    fn wait(numreplicas: usize) -> Response {
+        if numreplicas > 0 {
+            return Response::Error("ERR WAIT is not supported, this server has no replicas".to_string());
+        }
+        Response::Integer(0)
+    }
+    
+    /// WAITAOF: there is no append-only file to fsync, since writes go straight
+    /// to RocksDB, and no replicas. Asking for local acks is refused as Redis does
+    /// with appendonly off; otherwise replies at once with `[local_acks, replica_acks]`,
+    /// both 0.
+    fn wait_aof(numlocal: usize) -> Response {
+        if numlocal > 0 {
+            return Response::Error("ERR WAITAOF cannot be used when numlocal is set but appendonly is disabled.".to_string());
+        }
+        Response::Array(vec![Response::Integer(0), Response::Integer(0)])
+    }
+    
+    /// Cardinality of a collection, read from its header rather than decoding every element
+    async fn execute_len(&self, key: &str, expected: &str) -> Result<Response> {
+        match self.storage.collection_len(key).await? {
+            Some((type_name, len)) if type_name == expected => Ok(Response::Integer(len as i64)),
+            Some(_) => Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+            None => Ok(Response::Integer(0)),
+        }
+    }
+    
+    /// BITOP: combine source strings byte by byte, treating missing keys and short inputs as zero bytes
+    async fn execute_bitop(&self, op: BitOp, dest: &str, keys: &[String]) -> Result<Response> {
+        let mut sources = Vec::with_capacity(keys.len());
+        for key in keys {
+            match self.storage.get(key).await? {
+                Some(DataType::String(s)) => sources.push(s.into_bytes()),
+                None => sources.push(Vec::new()),
+                Some(_) => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+            }
+        }
+        
+        let len = sources.iter().map(|s| s.len()).max().unwrap_or(0);
+        if len == 0 {
+            self.storage.delete(dest).await?;
+            return Ok(Response::Integer(0));
+        }
+        
+        let byte = |source: &Vec<u8>, i: usize| source.get(i).copied().unwrap_or(0);
+        let result: Vec<u8> = (0..len)
+            .map(|i| match op {
+                BitOp::Not => !byte(&sources[0], i),
+                BitOp::And => sources.iter().fold(0xff, |acc, s| acc & byte(s, i)),
+                BitOp::Or => sources.iter().fold(0, |acc, s| acc | byte(s, i)),
+                BitOp::Xor => sources.iter().fold(0, |acc, s| acc ^ byte(s, i)),
+            })
+            .collect();
+        
+        // String values are stored as UTF-8, so results outside it cannot be kept yet
+        match String::from_utf8(result) {
+            Ok(value) => {
+                self.storage.set(dest, DataType::String(value)).await?;
+                Ok(Response::Integer(len as i64))
+            }
+            Err(_) => Ok(Response::Error("ERR BITOP result is not valid UTF-8 and cannot be stored as a string".to_string())),
+        }
+    }
+    
+    /// ZUNIONSTORE and ZINTERSTORE: scale each input's scores by its weight, then
+    /// merge them, combining the scores of a member found in several inputs with
+    /// `aggregate`. Plain sets count as sorted sets whose members all score 1, and
+    /// missing keys as empty inputs. An empty result deletes `dest`.
+    async fn execute_zstore(&self, dest: &str, keys: &[String], weights: &[f64], aggregate: Aggregate, intersect: bool) -> Result<Response> {
+        let mut inputs = Vec::with_capacity(keys.len());
+        for (key, &weight) in keys.iter().zip(weights) {
+            let Some(members) = self.zset_input(key).await? else {
+                return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()));
+            };
+            // 0 * inf is NaN; Redis scores it 0
+            let weighted: BTreeMap<String, f64> = members.into_iter()
+                .map(|(member, score)| {
+                    let score = score * weight;
+                    (member, if score.is_nan() { 0.0 } else { score })
+                })
+                .collect();
+            inputs.push(weighted);
+        }
+        
+        let mut inputs = inputs.into_iter();
+        let mut result = inputs.next().unwrap_or_default();
+        for input in inputs {
+            if intersect {
+                result = result.into_iter()
+                    .filter_map(|(member, score)| {
+                        input.get(&member).map(|&other| (member, aggregate.combine(score, other)))
+                    })
+                    .collect();
+            } else {
+                for (member, score) in input {
+                    result.entry(member)
+                        .and_modify(|current| *current = aggregate.combine(*current, score))
+                        .or_insert(score);
+                }
+            }
+        }
+        
+        let len = result.len();
+        if len == 0 {
+            self.storage.delete(dest).await?;
+        } else {
+            self.storage.set(dest, DataType::SortedSet(result)).await?;
+            self.serve_blocked(dest).await?;
+        }
+        Ok(Response::Integer(len as i64))
+    }
+    
+    /// ZDIFF and ZDIFFSTORE: the members of the first input found in none of the
+    /// others, with their scores in the first. Inputs are read as in
+    /// `execute_zstore`; `None` when one holds another type.
+    async fn zdiff(&self, keys: &[String]) -> Result<Option<BTreeMap<String, f64>>> {
+        let mut inputs = Vec::with_capacity(keys.len());
+        for key in keys {
+            let Some(members) = self.zset_input(key).await? else {
+                return Ok(None);
+            };
+            inputs.push(members);
+        }
+        
+        let mut inputs = inputs.into_iter();
+        let mut result: BTreeMap<String, f64> = inputs.next().unwrap_or_default().into_iter().collect();
+        for input in inputs {
+            for (member, _) in input {
+                result.remove(&member);
+            }
+            if result.is_empty() {
+                break;
+            }
+        }
+        Ok(Some(result))
+    }
+    
+    /// The members and scores of a sorted set input, members of a plain set scoring
+    /// 1 and a missing key being empty; `None` when the key holds another type
+    async fn zset_input(&self, key: &str) -> Result<Option<Vec<(String, f64)>>> {
+        Ok(match self.storage.get(key).await? {
+            Some(DataType::SortedSet(z)) => Some(z.into_iter().collect()),
+            Some(DataType::Set(s)) => Some(s.into_iter().map(|member| (member, 1.0)).collect()),
+            None => Some(Vec::new()),
+            Some(_) => None,
+        })
+    }
+    
+    /// Fetch a key, first dropping hash fields whose HEXPIRE deadline has passed.
+    /// Also returns the deadlines of the fields that remain.
+    async fn get_hash(&self, key: &str) -> Result<(Option<DataType>, HashMap<String, u64>)> {
+        let mut deadlines = self.storage.field_deadlines(key).await?;
+        let data = self.storage.get(key).await?;
+        if deadlines.is_empty() {
+            return Ok((data, deadlines));
+        }
+        
+        let Some(mut data) = data else {
+            return Ok((None, HashMap::new()));
+        };
+        if data.purge_expired_fields(&mut deadlines, self.storage.now_millis()) {
+            if data.as_hash().map(|h| h.is_empty()).unwrap_or(false) {
+                self.storage.delete(key).await?;
+                return Ok((None, HashMap::new()));
+            }
+            self.storage.set(key, data.clone()).await?;
+            self.storage.set_field_deadlines(key, &deadlines).await?;
+        }
+        Ok((Some(data), deadlines))
+    }
+    
+    /// The value under `key` with expired hash fields left out, as `get_hash` reads
+    /// it but without purging them, for commands that must not write
+    async fn peek_hash(&self, key: &str) -> Result<Option<DataType>> {
+        let mut deadlines = self.storage.field_deadlines(key).await?;
+        let mut data = self.storage.get(key).await?;
+        if let Some(data) = data.as_mut() {
+            data.purge_expired_fields(&mut deadlines, self.storage.now_millis());
+        }
+        Ok(data)
+    }
+    
+    /// HEXPIRE: per field, 1 if the deadline was set, 0 if the field is missing,
+    /// or 2 for every field when the key does not exist
+    async fn execute_hexpire(&self, key: &str, seconds: i64, fields: &[String]) -> Result<Response> {
+        let (data, mut deadlines) = match self.get_hash(key).await? {
+            (Some(data @ DataType::Hash(_)), deadlines) => (data, deadlines),
+            (Some(_), _) => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+            (None, _) => return Ok(Response::Array(fields.iter().map(|_| Response::Integer(2)).collect())),
+        };
+        
+        let deadline = (self.storage.now_millis() as i64).saturating_add(seconds.saturating_mul(1000)).max(0) as u64;
+        let statuses = fields.iter().map(|field| {
+            if data.as_hash().map(|h| h.contains_key(field)).unwrap_or(false) {
+                deadlines.insert(field.clone(), deadline);
+                Response::Integer(1)
+            } else {
+                Response::Integer(0)
+            }
+        }).collect();
+        self.storage.set_field_deadlines(key, &deadlines).await?;
+        
+        // A deadline that has already passed removes the field right away
+        if seconds <= 0 {
+            self.get_hash(key).await?;
+        }
+        Ok(Response::Array(statuses))
+    }
+    
+    /// LPOP and RPOP. Without a count one element is popped and replied alone;
+    /// with one, up to that many come off in a single drain and are replied as an
+    /// array. The list is deleted once emptied.
+    async fn execute_pop(&self, key: &str, from_left: bool, count: Option<usize>) -> Result<Response> {
+        let Some(mut data) = self.storage.get(key).await? else {
+            // With a count the reply is an array, so a missing list is a null array
+            return Ok(if count.is_some() { Response::NullArray } else { Response::Null });
+        };
+        let popped = match data.lpop_count(from_left, count.unwrap_or(1)) {
+            Ok(popped) => popped,
+            Err(e) => return Ok(Response::Error(e)),
+        };
+        if !popped.is_empty() {
+            if data.as_list().is_some_and(|l| l.is_empty()) {
+                self.storage.delete(key).await?;
+            } else {
                 self.storage.set(key, data).await?;
-                new_val
             }
-            None => {
-                let data = DataType::String(delta.to_string());
+        }
+        
+        let mut popped = popped.into_iter().map(|value| Response::String(Some(value)));
+        Ok(match count {
+            None => popped.next().unwrap_or(Response::Null),
+            Some(_) => Response::Array(popped.collect()),
+        })
+    }
+    
+    /// LMPOP/ZMPOP: pop from the first non-empty key, in argument order, and
+    /// reply `[key, [popped...]]`, or a null array when every key is empty
+    async fn execute_mpop<F>(&self, keys: &[String], expected: &str, pop: F) -> Result<Response>
+    where
+        F: Fn(&mut DataType) -> std::result::Result<Vec<Response>, String>,
+    {
+        for key in keys {
+            let mut data = match self.storage.get(key).await? {
+                Some(data) if data.type_name() == expected => data,
+                Some(_) => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+                None => continue,
+            };
+            
+            let popped = pop(&mut data).map_err(crate::error::DiskDBError::Database)?;
+            if popped.is_empty() {
+                continue;
+            }
+            let emptied = match &data {
+                DataType::List(l) => l.is_empty(),
+                DataType::ListPack(p) => p.is_empty(),
+                DataType::SortedSet(z) => z.is_empty(),
+                _ => false,
+            };
+            if emptied {
+                self.storage.delete(key).await?;
+            } else {
                 self.storage.set(key, data).await?;
-                delta
             }
+            return Ok(Response::Array(vec![Response::String(Some(key.clone())), Response::Array(popped)]));
+        }
+        Ok(Response::NullArray)
+    }
+    
+    /// SSCAN/HSCAN/ZSCAN: the cursor is an offset into the collection's sorted
+    /// elements. MATCH filters the page after it is taken, as Redis does, so a
+    /// page may hold fewer than COUNT elements while the cursor is not yet 0.
+    fn execute_collection_scan(
+        &self,
+        data: Option<DataType>,
+        expected: &str,
+        cursor: u64,
+        match_pattern: Option<&str>,
+        count: Option<usize>,
+    ) -> Result<Response> {
+        let data = match data {
+            Some(data) if data.type_name() == expected => data,
+            Some(_) => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+            None => return Ok(Response::Array(vec![Response::String(Some("0".to_string())), Response::Array(vec![])])),
+        };
+        
+        let cursor = usize::try_from(cursor).unwrap_or(usize::MAX);
+        let (next, entries) = data.scan_page(cursor, count.unwrap_or(DEFAULT_SCAN_COUNT))
+            .map_err(crate::error::DiskDBError::Database)?;
+        
+        let mut elements = Vec::with_capacity(entries.len() * 2);
+        for (element, value) in entries {
+            if let Some(pattern) = match_pattern {
+                if !glob_match(pattern.as_bytes(), element.as_bytes()) {
+                    continue;
+                }
+            }
+            elements.push(Response::String(Some(element)));
+            if let Some(value) = value {
+                elements.push(Response::String(Some(value)));
+            }
+        }
+        
+        Ok(Response::Array(vec![Response::String(Some(next.to_string())), Response::Array(elements)]))
+    }
+    
+    /// GETEX: read a string and adjust its expiry metadata, leaving the value untouched
+    async fn execute_getex(&self, key: &str, expiry: GetExOption) -> Result<Response> {
+        let value = match self.storage.get(key).await? {
+            Some(DataType::String(value)) => value,
+            Some(_) => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+            None => return Ok(Response::Null),
+        };
+        
+        let now = self.storage.now_millis() as i64;
+        let deadline = match expiry {
+            GetExOption::None => None,
+            GetExOption::Ex(seconds) => Some(now.saturating_add(seconds.saturating_mul(1000))),
+            GetExOption::Px(millis) => Some(now.saturating_add(millis)),
+            GetExOption::ExAt(timestamp) => Some(timestamp.saturating_mul(1000)),
+            GetExOption::PxAt(ms_timestamp) => Some(ms_timestamp),
+            GetExOption::Persist => {
+                self.storage.persist(key).await?;
+                None
+            }
+        };
+        if let Some(deadline) = deadline {
+            self.storage.expire_at(key, deadline.max(0) as u64).await?;
+        }
+        
+        Ok(Response::String(Some(value)))
+    }
+    
+    /// SET with its conditions, GET reply and expiry; with GET, a non-string value
+    /// fails with WRONGTYPE before anything is written
+    async fn execute_set(&self, key: &str, value: String, options: SetOptions) -> Result<Response> {
+        if options == SetOptions::default() {
+            self.storage.set_now(key, &DataType::String(value))?;
+            return Ok(Response::Ok);
+        }
+        
+        let (exists, previous) = if options.get || options.nx || options.xx {
+            match self.storage.get(key).await? {
+                Some(DataType::String(previous)) => (true, Some(previous)),
+                Some(_) if options.get => {
+                    return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()));
+                }
+                Some(_) => (true, None),
+                None => (false, None),
+            }
+        } else {
+            (false, None)
+        };
+        let reply = if options.get {
+            previous.map_or(Response::Null, |value| Response::String(Some(value)))
+        } else {
+            Response::Ok
         };
+        if (options.nx && exists) || (options.xx && !exists) {
+            return Ok(if options.get { reply } else { Response::Null });
+        }
+        
+        self.storage.set(key, DataType::String(value)).await?;
+        let now = self.storage.now_millis() as i64;
+        let deadline = match options.expiry {
+            SetExpiry::Clear => {
+                self.storage.persist(key).await?;
+                None
+            }
+            SetExpiry::KeepTtl => None,
+            SetExpiry::Ex(seconds) => Some(now.saturating_add(seconds.saturating_mul(1000))),
+            SetExpiry::Px(millis) => Some(now.saturating_add(millis)),
+            SetExpiry::ExAt(timestamp) => Some(timestamp.saturating_mul(1000)),
+            SetExpiry::PxAt(ms_timestamp) => Some(ms_timestamp),
+        };
+        if let Some(deadline) = deadline {
+            self.storage.expire_at(key, deadline.max(0) as u64).await?;
+        }
+        Ok(reply)
+    }
+    
+    /// Set a key's deadline unless `flags` rule it out against the current one
+    async fn execute_expire_at(&self, key: &str, deadline_ms: i64, flags: ExpireFlags) -> Result<Response> {
+        if flags != ExpireFlags::default() {
+            if !self.storage.exists(key).await? {
+                return Ok(Response::Integer(0));
+            }
+            if !flags.allows(self.storage.expiry(key).await?, deadline_ms) {
+                return Ok(Response::Integer(0));
+            }
+        }
+        let applied = self.storage.expire_at(key, deadline_ms.max(0) as u64).await?;
+        Ok(Response::Integer(if applied { 1 } else { 0 }))
+    }
+    
+    /// TTL/PTTL: -2 if the key is missing, -1 if it has no deadline
+    async fn execute_ttl(&self, key: &str, millis: bool) -> Result<Response> {
+        if !self.storage.exists(key).await? {
+            return Ok(Response::Integer(-2));
+        }
+        match self.storage.expiry(key).await? {
+            Some(deadline) => {
+                let remaining = deadline.saturating_sub(self.storage.now_millis());
+                let ttl = if millis { remaining } else { (remaining + 500) / 1000 };
+                Ok(Response::Integer(ttl as i64))
+            }
+            None => Ok(Response::Integer(-1)),
+        }
+    }
+    
+    async fn execute_sort(
+        &self,
+        key: &str,
+        alpha: bool,
+        desc: bool,
+        limit: Option<(i64, i64)>,
+        by: Option<&str>,
+        get: &[String],
+    ) -> Result<Response> {
+        let mut elements: Vec<String> = match self.storage.get(key).await? {
+            Some(DataType::List(list)) => list.into(),
+            Some(DataType::ListPack(pack)) => pack.unpack(),
+            Some(DataType::Set(set)) => set.into_iter().collect(),
+            Some(_) => return Ok(Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
+            None => Vec::new(),
+        };
+        
+        // A BY pattern without a wildcard means "don't sort"
+        let sort = by.map(|pattern| pattern.contains('*')).unwrap_or(true);
+        
+        let mut elements = if sort {
+            let mut weights = Vec::with_capacity(elements.len());
+            for element in &elements {
+                weights.push(match by {
+                    Some(pattern) => self.lookup_sort_pattern(pattern, element).await?,
+                    None => Some(element.clone()),
+                });
+            }
+            
+            let mut order: Vec<usize> = (0..elements.len()).collect();
+            if alpha {
+                order.sort_by(|&a, &b| weights[a].cmp(&weights[b]).then_with(|| elements[a].cmp(&elements[b])));
+            } else {
+                let mut scores = Vec::with_capacity(weights.len());
+                for weight in &weights {
+                    match weight.as_deref().map(|w| w.trim().parse::<f64>()) {
+                        Some(Ok(score)) => scores.push(score),
+                        Some(Err(_)) => return Ok(Response::Error("ERR One or more scores can't be converted into double".to_string())),
+                        None => scores.push(0.0),
+                    }
+                }
+                order.sort_by(|&a, &b| {
+                    scores[a].partial_cmp(&scores[b])
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                        .then_with(|| elements[a].cmp(&elements[b]))
+                });
+            }
+            
+            if desc {
+                order.reverse();
+            }
+            order.into_iter().map(|i| std::mem::take(&mut elements[i])).collect()
+        } else {
+            elements
+        };
+        
+        if let Some((offset, count)) = limit {
+            let offset = offset.max(0) as usize;
+            let count = if count < 0 { elements.len() } else { count as usize };
+            elements = elements.into_iter().skip(offset).take(count).collect();
+        }
+        
+        if get.is_empty() {
+            return Ok(Response::Array(elements.into_iter().map(|e| Response::String(Some(e))).collect()));
+        }
+        
+        let mut result = Vec::with_capacity(elements.len() * get.len());
+        for element in &elements {
+            for pattern in get {
+                match self.lookup_sort_pattern(pattern, element).await? {
+                    Some(value) => result.push(Response::String(Some(value))),
+                    None => result.push(Response::Null),
+                }
+            }
+        }
+        Ok(Response::Array(result))
+    }
+    
+    /// Resolve a SORT BY/GET pattern for one element.
+    ///
+    /// `#` yields the element itself, `*` is replaced by the element to form a
+    /// key, and a trailing `->field` reads that field from a hash.
+    async fn lookup_sort_pattern(&self, pattern: &str, element: &str) -> Result<Option<String>> {
+        if pattern == "#" {
+            return Ok(Some(element.to_string()));
+        }
+        
+        let (key_pattern, field) = match pattern.rsplit_once("->") {
+            Some((key_pattern, field)) if !field.is_empty() => (key_pattern, Some(field)),
+            _ => (pattern, None),
+        };
+        let key = key_pattern.replacen('*', element, 1);
+        
+        match (self.peek_hash(&key).await?, field) {
+            (Some(DataType::String(value)), None) => Ok(Some(value)),
+            (Some(DataType::Hash(hash)), Some(field)) => Ok(hash.get(field).cloned()),
+            _ => Ok(None),
+        }
+    }
+    
+    async fn execute_incr(&self, key: &str, delta: i64) -> Result<Response> {
+        let result = self.storage.incr_by(key, delta).await?;
         Ok(Response::Integer(result))
     }
-}
\ No newline at end of file
+}
+/// Bytes `start..=end` of `s` with Redis-style negative indices, clamped to the string
+fn byte_range(s: &str, start: i64, end: i64) -> String {
+    let len = s.len() as i64;
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let end = if end < 0 { len + end } else { end.min(len - 1) };
+    if start > end {
+        return String::new();
+    }
+    String::from_utf8_lossy(&s.as_bytes()[start as usize..=end as usize]).into_owned()
+}
+
+/// Position of the first bit equal to `bit` within the inclusive byte range, or -1.
+///
+/// Looking for a clear bit without an explicit end treats the string as padded
+/// with zero bytes, so a fully set range reports the first bit past its end.
+fn bit_position(bytes: &[u8], bit: u8, start: Option<i64>, end: Option<i64>) -> i64 {
+    let len = bytes.len() as i64;
+    let normalize = |index: i64| if index < 0 { (len + index).max(0) } else { index };
+    let first = normalize(start.unwrap_or(0));
+    let last = end.map(normalize).unwrap_or(len - 1).min(len - 1);
+    if len == 0 || first > last {
+        return -1;
+    }
+    
+    let skip = if bit == 1 { 0x00 } else { 0xff };
+    for i in first..=last {
+        let byte = bytes[i as usize];
+        if byte != skip {
+            let offset = if bit == 1 { byte.leading_zeros() } else { (!byte).leading_zeros() };
+            return i * 8 + offset as i64;
+        }
+    }
+    
+    if bit == 0 && end.is_none() {
+        (last + 1) * 8
+    } else {
+        -1
+    }
+}
@@ -0,0 +1,53 @@
+use crate::protocol::quote_arg;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// Lines a monitoring connection may fall behind by before it skips ahead
+const MONITOR_BACKLOG: usize = 1024;
+
+/// Feed of processed commands for MONITOR.
+///
+/// While at least one connection monitors, every command costs a formatted
+/// line and a send to the shared channel, which slows a busy server down
+/// noticeably; with no monitors nothing is formatted. The feed is best-effort:
+/// a monitor more than `MONITOR_BACKLOG` lines behind loses the oldest ones
+/// rather than holding up the commands it watches.
+#[derive(Clone)]
+pub struct Monitor {
+    feed: broadcast::Sender<String>,
+}
+
+impl Default for Monitor {
+    fn default() -> Self {
+        let (feed, _) = broadcast::channel(MONITOR_BACKLOG);
+        Self { feed }
+    }
+}
+
+impl Monitor {
+    /// Start receiving every line fed from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.feed.subscribe()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.feed.receiver_count() > 0
+    }
+
+    /// Send `args` as run by `client`, an address or `lua` for script calls, to
+    /// every monitor as `<unix time> [0 <client>] "CMD" "arg" ...`
+    pub fn feed<S: AsRef<str>>(&self, client: &str, args: &[S]) {
+        if !self.is_active() {
+            return;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        // Everything lives in database 0
+        let mut line = format!("{}.{:06} [0 {}]", now.as_secs(), now.subsec_micros(), client);
+        for arg in args {
+            line.push(' ');
+            line.push_str(&quote_arg(arg.as_ref()));
+        }
+        // Fails only when the last monitor left in the meantime
+        let _ = self.feed.send(line);
+    }
+}
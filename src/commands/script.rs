@@ -0,0 +1,370 @@
+use crate::commands::CommandExecutor;
+use crate::error::{DiskDBError, Result};
+use crate::protocol::{Request, Response};
+use mlua::{Function, HookTriggers, Lua, LuaOptions, MultiValue, StdLib, Table, Value};
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+
+/// Reply for EVALSHA with a digest that was never loaded
+pub const NO_SCRIPT: &str = "NOSCRIPT No matching script. Please use EVAL.";
+
+/// Scripts seen by EVAL or SCRIPT LOAD, keyed by the hex SHA1 of their source
+#[derive(Clone, Default)]
+pub struct ScriptCache {
+    scripts: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl ScriptCache {
+    /// Cache `script` and return its SHA1
+    pub fn load(&self, script: String) -> String {
+        let sha = sha1_hex(&script);
+        self.scripts.lock().unwrap().insert(sha.clone(), script);
+        sha
+    }
+
+    pub fn get(&self, sha: &str) -> Option<String> {
+        self.scripts.lock().unwrap().get(&sha.to_lowercase()).cloned()
+    }
+}
+
+pub fn sha1_hex(script: &str) -> String {
+    format!("{:x}", Sha1::digest(script.as_bytes()))
+}
+
+/// An error reply passed through to the client as is, such as one from a command run by `redis.call`
+#[derive(Debug)]
+struct CallError(String);
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CallError {}
+
+/// Lua instructions run between checks of the script time limit
+const TIME_CHECK_INSTRUCTIONS: u32 = 10_000;
+
+/// Installs `redis.register_function` and returns the table it fills, name to callback
+const REGISTER_PRELUDE: &str = r#"
+local functions = {}
+redis.register_function = function(name, callback)
+    if type(name) == 'table' then
+        name, callback = name.function_name, name.callback
+    end
+    if type(name) ~= 'string' or type(callback) ~= 'function' then
+        error('register_function requires a function name and a callback')
+    end
+    if functions[name] then
+        error('function ' .. name .. ' is registered twice')
+    end
+    functions[name] = callback
+end
+return functions
+"#;
+
+/// Function libraries registered by FUNCTION LOAD, keyed by library name
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    libraries: Arc<Mutex<BTreeMap<String, Library>>>,
+}
+
+struct Library {
+    code: String,
+    functions: Vec<String>,
+}
+
+impl FunctionRegistry {
+    /// Whether a library may be registered: it must not exist unless `replace` is set,
+    /// and none of its functions may belong to another library
+    fn check(&self, name: &str, functions: &[String], replace: bool) -> std::result::Result<(), String> {
+        let libraries = self.libraries.lock().unwrap();
+        if !replace && libraries.contains_key(name) {
+            return Err(format!("ERR Library '{}' already exists", name));
+        }
+        let taken = libraries.iter()
+            .filter(|(library, _)| *library != name)
+            .flat_map(|(_, library)| &library.functions)
+            .find(|function| functions.contains(function));
+        match taken {
+            Some(function) => Err(format!("ERR Function {} already exists", function)),
+            None => Ok(()),
+        }
+    }
+    
+    fn insert(&self, name: String, code: String, functions: Vec<String>) {
+        self.libraries.lock().unwrap().insert(name, Library { code, functions });
+    }
+    
+    /// Source of the library defining `function`
+    fn code_for(&self, function: &str) -> Option<String> {
+        self.libraries.lock().unwrap().values()
+            .find(|library| library.functions.iter().any(|f| f == function))
+            .map(|library| library.code.clone())
+    }
+    
+    /// Libraries in name order with their function names
+    fn list(&self) -> Vec<(String, Vec<String>)> {
+        self.libraries.lock().unwrap().iter()
+            .map(|(name, library)| (name.clone(), library.functions.clone()))
+            .collect()
+    }
+}
+
+/// Split FUNCTION LOAD code into the library name from its `#!lua name=<library>`
+/// first line and the Lua body that follows
+fn parse_library(code: &str) -> std::result::Result<(String, &str), String> {
+    let (header, body) = code.split_once('\n').unwrap_or((code, ""));
+    let mut fields = header.split_whitespace();
+    if fields.next() != Some("#!lua") {
+        return Err("ERR Missing library metadata".to_string());
+    }
+    fields.find_map(|field| field.strip_prefix("name="))
+        .filter(|name| !name.is_empty())
+        .map(|name| (name.to_string(), body))
+        .ok_or_else(|| "ERR Library name was not given".to_string())
+}
+
+/// Run a library body and return the table of functions it registered
+fn register_library<'lua>(lua: &'lua Lua, body: &str) -> mlua::Result<Table<'lua>> {
+    let functions: Table = lua.load(REGISTER_PRELUDE).set_name("register").eval()?;
+    lua.load(body).set_name("library").exec()?;
+    Ok(functions)
+}
+
+/// Check that a library compiles and return its name and function names.
+/// Only `redis.register_function` is available while it loads.
+fn compile_library(code: &str, time_limit: Duration) -> std::result::Result<(String, Vec<String>), String> {
+    let (name, body) = parse_library(code)?;
+    let lua = sandbox(time_limit).map_err(|e| format!("ERR Error compiling function: {}", e))?;
+    let functions = lua.create_table()
+        .and_then(|redis| lua.globals().set("redis", redis))
+        .and_then(|_| register_library(&lua, body))
+        .and_then(|table| table.pairs::<String, Function>().map(|pair| pair.map(|(name, _)| name)).collect::<mlua::Result<Vec<_>>>())
+        .map_err(|e| format!("ERR Error compiling function: {}", e))?;
+    if functions.is_empty() {
+        return Err("ERR No functions registered".to_string());
+    }
+    Ok((name, functions))
+}
+
+/// A VM with only the table, string and math libraries, which raises an error
+/// once it has run for longer than `time_limit`, unless that is zero
+fn sandbox(time_limit: Duration) -> mlua::Result<Lua> {
+    let lua = Lua::new_with(StdLib::TABLE | StdLib::STRING | StdLib::MATH, LuaOptions::default())?;
+    if !time_limit.is_zero() {
+        let started = Instant::now();
+        lua.set_hook(HookTriggers::new().every_nth_instruction(TIME_CHECK_INSTRUCTIONS), move |_, _| {
+            if started.elapsed() < time_limit {
+                return Ok(());
+            }
+            Err(mlua::Error::external(CallError(format!(
+                "ERR Script stopped after running for more than {} ms", time_limit.as_millis(),
+            ))))
+        });
+    }
+    Ok(lua)
+}
+
+impl CommandExecutor {
+    /// Run a Lua script with no other command interleaving.
+    ///
+    /// The caller holds the script lock exclusively. The interpreter is
+    /// synchronous, so the script runs on a blocking thread and each
+    /// `redis.call` blocks on the executor from there.
+    pub(super) async fn run_script(&self, script: String, keys: Vec<String>, args: Vec<String>) -> Result<Response> {
+        self.run_lua(move |lua| {
+            let globals = lua.globals();
+            globals.set("KEYS", keys)?;
+            globals.set("ARGV", args)?;
+            let value: Value = lua.load(script.as_str()).set_name("script").eval()?;
+            lua_to_response(&value)
+        }).await
+    }
+    
+    /// FUNCTION LOAD: compile the library, persist it, then register its functions.
+    /// The caller holds the script lock exclusively, so the check cannot go stale.
+    pub(super) async fn load_function_library(&self, code: String, replace: bool) -> Result<Response> {
+        let checked = compile_library(&code, self.lua_time_limit)
+            .and_then(|(name, functions)| self.functions.check(&name, &functions, replace).map(|_| (name, functions)));
+        let (name, functions) = match checked {
+            Ok(library) => library,
+            Err(e) => return Ok(Response::Error(e)),
+        };
+        
+        self.storage.save_function_library(&name, &code).await?;
+        self.functions.insert(name.clone(), code, functions);
+        Ok(Response::String(Some(name)))
+    }
+    
+    /// FCALL: run a registered function with no other command interleaving
+    pub(super) async fn call_function(&self, name: String, keys: Vec<String>, args: Vec<String>) -> Result<Response> {
+        let code = match self.functions.code_for(&name) {
+            Some(code) => code,
+            None => return Ok(Response::Error("ERR Function not found".to_string())),
+        };
+        self.run_lua(move |lua| {
+            let (_, body) = parse_library(&code).map_err(mlua::Error::RuntimeError)?;
+            let function: Function = register_library(lua, body)?.get(name.as_str())?;
+            let value: Value = function.call((keys, args))?;
+            lua_to_response(&value)
+        }).await
+    }
+    
+    /// FUNCTION LIST: each library's name, engine and function names
+    pub(super) fn list_functions(&self) -> Response {
+        let text = |s: &str| Response::String(Some(s.to_string()));
+        Response::Array(self.functions.list().into_iter()
+            .map(|(library, functions)| Response::Array(vec![
+                text("library_name"), text(&library),
+                text("engine"), text("LUA"),
+                text("functions"), Response::Array(functions.iter().map(|f| text(f)).collect()),
+            ]))
+            .collect())
+    }
+    
+    /// Re-register the function libraries persisted in storage, e.g. after a restart.
+    /// Returns the number of libraries restored.
+    pub async fn restore_functions(&self) -> Result<usize> {
+        let libraries = self.storage.function_libraries().await?;
+        let count = libraries.len();
+        for (name, code) in libraries {
+            let (_, functions) = compile_library(&code, self.lua_time_limit)
+                .map_err(|e| DiskDBError::Database(format!("Function library '{}' failed to load: {}", name, e)))?;
+            self.functions.insert(name, code, functions);
+        }
+        Ok(count)
+    }
+    
+    /// Run `body` in a fresh sandboxed VM with `redis.call` and `redis.pcall` installed
+    async fn run_lua<F>(&self, body: F) -> Result<Response>
+    where
+        F: FnOnce(&Lua) -> mlua::Result<Response> + Send + 'static,
+    {
+        let executor = self.clone();
+        let handle = Handle::current();
+        let limit = self.lua_time_limit;
+        tokio::task::spawn_blocking(move || {
+            let lua = sandbox(limit)
+                .map_err(|e| DiskDBError::Database(format!("failed to start Lua: {}", e)))?;
+            let reply = Self::install_redis_api(&lua, executor, handle)
+                .and_then(|_| body(&lua))
+                .unwrap_or_else(|e| Response::Error(script_error(&e)));
+            Ok(reply)
+        })
+        .await
+        .map_err(|e| DiskDBError::Database(format!("script task failed: {}", e)))?
+    }
+    
+    fn install_redis_api(lua: &Lua, executor: CommandExecutor, handle: Handle) -> mlua::Result<()> {
+        let redis = lua.create_table()?;
+        let (call_executor, call_handle) = (executor.clone(), handle.clone());
+        redis.set("call", lua.create_function(move |lua, args: MultiValue| {
+            match Self::script_call(&call_executor, &call_handle, args)? {
+                Response::Error(e) => Err(mlua::Error::external(CallError(e))),
+                response => response_to_lua(lua, response),
+            }
+        })?)?;
+        // Like call, but error replies come back as an {err = ...} table instead of raising
+        redis.set("pcall", lua.create_function(move |lua, args: MultiValue| {
+            response_to_lua(lua, Self::script_call(&executor, &handle, args)?)
+        })?)?;
+        lua.globals().set("redis", redis)
+    }
+    
+    /// Run one `redis.call` from inside a script
+    fn script_call(executor: &CommandExecutor, handle: &Handle, args: MultiValue) -> mlua::Result<Response> {
+        let mut parts = Vec::with_capacity(args.len());
+        for arg in args {
+            parts.push(match arg {
+                Value::String(s) => s.to_str()?.to_string(),
+                Value::Integer(i) => i.to_string(),
+                Value::Number(n) => n.to_string(),
+                _ => return Err(mlua::Error::external(CallError(
+                    "ERR Lua redis lib command arguments must be strings or integers".to_string(),
+                ))),
+            });
+        }
+        if parts.is_empty() {
+            return Err(mlua::Error::external(CallError(
+                "ERR Please specify at least one argument for this redis lib call".to_string(),
+            )));
+        }
+
+        executor.monitor.feed("lua", &parts);
+        let parts: Vec<&str> = parts.iter().map(String::as_str).collect();
+        let request = match Request::parse_parts(&parts) {
+            Ok(request) => request,
+            Err(e) => return Ok(Response::Error(format!("ERR {}", e))),
+        };
+        handle.block_on(executor.execute_unlocked(request))
+            .or_else(|e| Ok(Response::Error(e.to_string())))
+    }
+}
+
+/// Convert a command reply to the Lua value a script sees
+fn response_to_lua(lua: &Lua, response: Response) -> mlua::Result<Value<'_>> {
+    Ok(match response {
+        Response::Ok => {
+            let status = lua.create_table()?;
+            status.set("ok", "OK")?;
+            Value::Table(status)
+        }
+        Response::String(Some(s)) => Value::String(lua.create_string(&s)?),
+        Response::String(None) | Response::Null | Response::NullArray => Value::Boolean(false),
+        Response::Integer(i) => Value::Integer(i),
+        Response::Array(items) => {
+            let table = lua.create_table()?;
+            for item in items {
+                table.push(response_to_lua(lua, item)?)?;
+            }
+            Value::Table(table)
+        }
+        Response::Error(e) => {
+            let error = lua.create_table()?;
+            error.set("err", e)?;
+            Value::Table(error)
+        }
+    })
+}
+
+/// Convert a script's return value to a reply, following the Redis conversion rules
+fn lua_to_response(value: &Value) -> mlua::Result<Response> {
+    Ok(match value {
+        Value::Nil | Value::Boolean(false) => Response::Null,
+        Value::Boolean(true) => Response::Integer(1),
+        Value::Integer(i) => Response::Integer(*i),
+        Value::Number(n) => Response::Integer(*n as i64),
+        Value::String(s) => Response::String(Some(s.to_str()?.to_string())),
+        Value::Table(table) => {
+            if let Some(error) = table.get::<_, Option<String>>("err")? {
+                Response::Error(error)
+            } else if let Some(status) = table.get::<_, Option<String>>("ok")? {
+                if status == "OK" { Response::Ok } else { Response::String(Some(status)) }
+            } else {
+                let items = table.clone().sequence_values::<Value>()
+                    .map(|item| lua_to_response(&item?))
+                    .collect::<mlua::Result<_>>()?;
+                Response::Array(items)
+            }
+        }
+        _ => Response::Null,
+    })
+}
+
+/// Reply text for a failed script: errors raised by `redis.call` pass through unchanged
+fn script_error(error: &mlua::Error) -> String {
+    match error {
+        mlua::Error::CallbackError { cause, .. } => script_error(cause),
+        mlua::Error::ExternalError(e) => match e.downcast_ref::<CallError>() {
+            Some(CallError(message)) => message.clone(),
+            None => format!("ERR Error running script: {}", e),
+        },
+        other => format!("ERR Error running script: {}", other),
+    }
+}
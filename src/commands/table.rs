@@ -0,0 +1,305 @@
+//! Static metadata for every command the parser accepts. COMMAND replies and
+//! `Request::is_write` both read it, so a command's arity, flags and key
+//! positions are declared once.
+
+/// Arity, flags and key positions of one command, in the form COMMAND reports them
+#[derive(Debug)]
+pub struct CommandSpec {
+    /// Lowercase command name
+    pub name: &'static str,
+    /// Argument count including the command name; negative means at least that many
+    pub arity: i32,
+    pub flags: &'static [&'static str],
+    /// Index of the first key argument, the command name being 0; 0 when there are none
+    pub first_key: i32,
+    /// Index of the last key argument; negative counts back from the end, -1 being the last
+    pub last_key: i32,
+    /// Distance between key arguments, 2 when each key is followed by a value
+    pub step: i32,
+}
+
+impl CommandSpec {
+    pub fn is_write(&self) -> bool {
+        self.flags.contains(&WRITE)
+    }
+    
+    pub fn is_readonly(&self) -> bool {
+        self.flags.contains(&READONLY)
+    }
+    
+    pub fn is_blocking(&self) -> bool {
+        self.flags.contains(&BLOCKING)
+    }
+    
+    /// Whether the keys depend on other arguments, such as a `numkeys` count, so
+    /// the positions cover only the fixed ones
+    pub fn has_movable_keys(&self) -> bool {
+        self.flags.contains(&MOVABLEKEYS)
+    }
+    
+    /// The arguments at this command's key positions, `args[0]` being the command name
+    pub fn key_args<'a>(&self, args: &'a [String]) -> Vec<&'a str> {
+        if self.first_key <= 0 || self.step <= 0 {
+            return Vec::new();
+        }
+        let last = if self.last_key < 0 { args.len() as i32 + self.last_key } else { self.last_key };
+        (self.first_key..=last)
+            .step_by(self.step as usize)
+            .filter_map(|index| args.get(index as usize).map(String::as_str))
+            .collect()
+    }
+    
+    const fn keys(self, first_key: i32, last_key: i32, step: i32) -> Self {
+        Self { first_key, last_key, step, ..self }
+    }
+}
+
+const WRITE: &str = "write";
+const READONLY: &str = "readonly";
+const FAST: &str = "fast";
+const ADMIN: &str = "admin";
+const PUBSUB: &str = "pubsub";
+const NOSCRIPT: &str = "noscript";
+const BLOCKING: &str = "blocking";
+const MOVABLEKEYS: &str = "movablekeys";
+
+/// A command without key arguments; chain `.keys(first, last, step)` for one with them
+const fn spec(name: &'static str, arity: i32, flags: &'static [&'static str]) -> CommandSpec {
+    CommandSpec { name, arity, flags, first_key: 0, last_key: 0, step: 0 }
+}
+
+pub static COMMAND_TABLE: &[CommandSpec] = &[
+    // String operations
+    spec("get", 2, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("getex", -2, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("set", -3, &[WRITE]).keys(1, 1, 1),
+    spec("incr", 2, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("decr", 2, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("incrby", 3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("decrby", 3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("incrbyfloat", 3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("append", -3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("getrange", 4, &[READONLY]).keys(1, 1, 1),
+    spec("substr", 4, &[READONLY]).keys(1, 1, 1),
+    spec("strlen", 2, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("lcs", -3, &[READONLY]).keys(1, 2, 1),
+    spec("bitpos", -3, &[READONLY]).keys(1, 1, 1),
+    spec("bitop", -4, &[WRITE]).keys(2, -1, 1),
+
+    // List operations
+    spec("lpush", -3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("rpush", -3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("lpop", -2, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("rpop", -2, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("lmpop", -4, &[WRITE, MOVABLEKEYS]),
+    spec("blpop", -3, &[WRITE, BLOCKING]).keys(1, -2, 1),
+    spec("brpop", -3, &[WRITE, BLOCKING]).keys(1, -2, 1),
+    spec("lrange", 4, &[READONLY]).keys(1, 1, 1),
+    spec("llen", 2, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("lset", 4, &[WRITE]).keys(1, 1, 1),
+    spec("linsert", 5, &[WRITE]).keys(1, 1, 1),
+    spec("ltrim", 4, &[WRITE]).keys(1, 1, 1),
+
+    // Set operations
+    spec("sadd", -3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("srem", -3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("smembers", 2, &[READONLY]).keys(1, 1, 1),
+    spec("sismember", 3, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("smismember", -3, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("scard", 2, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("sscan", -3, &[READONLY]).keys(1, 1, 1),
+
+    // Hash operations
+    spec("hset", 4, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("hsetnx", 4, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("hmset", -4, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("hstrlen", 3, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("hget", 3, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("hdel", -3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("hgetall", 2, &[READONLY]).keys(1, 1, 1),
+    spec("hlen", 2, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("hexists", 3, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("hexpire", -6, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("httl", -5, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("hpersist", -5, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("hscan", -3, &[READONLY]).keys(1, 1, 1),
+
+    // Sorted Set operations
+    spec("zadd", -4, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("zrem", -3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("zrange", -4, &[READONLY]).keys(1, 1, 1),
+    spec("zrangebylex", -4, &[READONLY]).keys(1, 1, 1),
+    spec("zlexcount", 4, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("zunionstore", -4, &[WRITE, MOVABLEKEYS]).keys(1, 1, 1),
+    spec("zinterstore", -4, &[WRITE, MOVABLEKEYS]).keys(1, 1, 1),
+    spec("zdiff", -3, &[READONLY, MOVABLEKEYS]),
+    spec("zdiffstore", -4, &[WRITE, MOVABLEKEYS]).keys(1, 1, 1),
+    spec("zmpop", -4, &[WRITE, MOVABLEKEYS]),
+    spec("bzpopmin", -3, &[WRITE, BLOCKING]).keys(1, -2, 1),
+    spec("bzpopmax", -3, &[WRITE, BLOCKING]).keys(1, -2, 1),
+    spec("zscore", 3, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("zcard", 2, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("zscan", -3, &[READONLY]).keys(1, 1, 1),
+
+    // JSON operations
+    spec("json.set", -4, &[WRITE]).keys(1, 1, 1),
+    spec("json.get", 3, &[READONLY]).keys(1, 1, 1),
+    spec("json.del", 3, &[WRITE]).keys(1, 1, 1),
+
+    // Stream operations
+    spec("xadd", -5, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("xrange", -4, &[READONLY]).keys(1, 1, 1),
+    spec("xlen", 2, &[READONLY, FAST]).keys(1, 1, 1),
+
+    // Expiry operations
+    spec("expire", -3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("expireat", -3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("pexpire", -3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("pexpireat", -3, &[WRITE, FAST]).keys(1, 1, 1),
+    spec("ttl", 2, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("pttl", 2, &[READONLY, FAST]).keys(1, 1, 1),
+
+    // Sort operations
+    spec("sort", -2, &[READONLY]).keys(1, 1, 1),
+
+    // Utility operations
+    spec("type", 2, &[READONLY, FAST]).keys(1, 1, 1),
+    spec("dump", 2, &[READONLY]).keys(1, 1, 1),
+    spec("restore", -4, &[WRITE]).keys(1, 1, 1),
+    spec("copy", -3, &[WRITE]).keys(1, 2, 1),
+    spec("object", -2, &[READONLY]).keys(2, 2, 1),
+    spec("del", -2, &[WRITE]).keys(1, -1, 1),
+    spec("unlink", -2, &[WRITE, FAST]).keys(1, -1, 1),
+    spec("delprefix", 2, &[WRITE]),
+    spec("exists", -2, &[READONLY, FAST]).keys(1, -1, 1),
+    spec("ping", -1, &[FAST]),
+    spec("echo", -2, &[FAST]),
+    spec("flushdb", -1, &[WRITE]),
+    spec("flushall", -1, &[WRITE]),
+    spec("dbsize", 1, &[READONLY, FAST]),
+    spec("randomkey", 1, &[READONLY]),
+    spec("scan", -2, &[READONLY]),
+    spec("info", -1, &[]),
+    spec("version", 1, &[FAST]),
+    spec("wait", 3, &[]),
+    spec("waitaof", 4, &[]),
+    spec("command", -1, &[]),
+    spec("memory", -2, &[READONLY, MOVABLEKEYS]),
+    spec("cluster", -2, &[]),
+    spec("latency", -2, &[ADMIN]),
+    spec("pool", 2, &[ADMIN]),
+    spec("debug", -2, &[ADMIN, NOSCRIPT]),
+    spec("getwriteoffset", 1, &[READONLY, FAST]),
+    spec("minoffset", -3, &[READONLY, MOVABLEKEYS]),
+
+    // Scripting operations
+    spec("eval", -3, &[NOSCRIPT, MOVABLEKEYS]),
+    spec("evalsha", -3, &[NOSCRIPT, MOVABLEKEYS]),
+    spec("script", -2, &[NOSCRIPT]),
+    spec("function", -2, &[NOSCRIPT]),
+    spec("fcall", -3, &[NOSCRIPT, MOVABLEKEYS]),
+
+    // Connection operations
+    spec("client", -2, &[ADMIN, NOSCRIPT]),
+    spec("reset", 1, &[FAST, NOSCRIPT]),
+    spec("quit", 1, &[FAST, NOSCRIPT]),
+    spec("hello", -1, &[FAST, NOSCRIPT]),
+    spec("monitor", 1, &[ADMIN, NOSCRIPT]),
+
+    // Pub/Sub operations
+    spec("subscribe", -2, &[PUBSUB, NOSCRIPT]),
+    spec("unsubscribe", -1, &[PUBSUB, NOSCRIPT]),
+    spec("psubscribe", -2, &[PUBSUB, NOSCRIPT]),
+    spec("punsubscribe", -1, &[PUBSUB, NOSCRIPT]),
+    spec("publish", -3, &[PUBSUB, FAST]),
+    spec("pubsub", -2, &[PUBSUB]),
+];
+
+/// Metadata of a command by name, ignoring case
+pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+    COMMAND_TABLE.iter().find(|spec| spec.name.eq_ignore_ascii_case(name))
+}
+
+/// Usage lines returned by `<command> HELP`, one per subcommand the dispatch accepts.
+/// Keep in step with the parser and the handlers when a subcommand is added.
+pub static SUBCOMMAND_HELP: &[(&str, &[&str])] = &[
+    ("object", &[
+        "ENCODING <key> -- Return the kind of internal representation used to store <key>.",
+        "FREQ <key> -- Return the logarithmic access frequency counter of <key>.",
+        "HELP -- Print this help.",
+    ]),
+    ("client", &[
+        "ID -- Return the ID of the current connection.",
+        "GETNAME -- Return the name of the current connection.",
+        "SETNAME <name> -- Assign a name to the current connection.",
+        "LIST -- Return information about client connections.",
+        "KILL <ip:port> | KILL ID <id> | KILL ADDR <ip:port> -- Kill the matching connections.",
+        "TRACKING ON|OFF -- Receive invalidation pushes for keys this connection read (RESP3 only).",
+        "HELP -- Print this help.",
+    ]),
+    ("command", &[
+        "(no subcommand) -- Return details about all commands.",
+        "COUNT -- Return the total number of commands in this server.",
+        "INFO [<command-name> ...] -- Return details about the given commands, or all of them.",
+        "DOCS [<command-name> ...] -- Return documentation for the given commands, or all of them.",
+        "GETKEYS <full-command> -- Return the keys from a full command.",
+        "HELP -- Print this help.",
+    ]),
+    ("script", &[
+        "LOAD <script> -- Cache <script> and return its SHA1 digest for EVALSHA.",
+        "HELP -- Print this help.",
+    ]),
+    ("function", &[
+        "LOAD [REPLACE] <function-code> -- Create a library, replacing one of the same name with REPLACE.",
+        "LIST -- Return the loaded libraries and their functions.",
+        "HELP -- Print this help.",
+    ]),
+    ("pubsub", &[
+        "CHANNELS [<pattern>] -- Return the channels with at least one subscriber, optionally matching <pattern>.",
+        "NUMSUB [<channel> ...] -- Return the number of subscribers of each <channel>.",
+        "NUMPAT -- Return the number of patterns subscribed to.",
+        "HELP -- Print this help.",
+    ]),
+    ("debug", &[
+        "RELOAD -- Save the dataset, clear it and load it back, failing if anything changed.",
+        "LISTPACK-LIMITS <entries> <bytes> -- Store lists within both limits in the compact listpack encoding.",
+        "STRINGMATCH-LEN <pattern> <string> -- Return 1 if the glob <pattern> matches <string>, else 0.",
+        "SET-TIME <ms> -- Set the mock clock, when the server runs on one, to <ms> since the Unix epoch.",
+        "ADVANCE-TIME <ms> -- Move the mock clock forward by <ms>.",
+        "SLEEP <seconds> -- Stall the connection for <seconds>, which may be fractional.",
+        "OBJECT-CHECK <key> -- Verify the internal invariants of <key>'s value, replying OK or the first inconsistency.",
+        "POPULATE <count> [<prefix>] [<size>] -- Create <count> string keys named <prefix>:0 onwards, 'key' by default, skipping existing ones. Values are value:<n>, padded with zero bytes or cut to <size>.",
+        "HELP -- Print this help.",
+    ]),
+    ("cluster", &[
+        "INFO -- Return cluster state; clustering is always disabled.",
+        "SLOTS -- Return the slot ranges served by each node, always empty.",
+        "SHARDS -- Return the shards of the cluster, always empty.",
+        "MYID -- Return the ID of this node.",
+        "HELP -- Print this help.",
+    ]),
+    ("latency", &[
+        "LATEST -- Return the latest and largest spike of each event.",
+        "HISTORY <event> -- Return the time and milliseconds of each recorded spike of <event>.",
+        "RESET [<event> ...] -- Forget the recorded spikes of the given events, or of all events.",
+        "HELP -- Print this help.",
+    ]),
+    ("memory", &[
+        "USAGE <key> [SAMPLES <count>] -- Estimate the bytes held by <key>, sizing <count> elements of a collection (0 for all).",
+        "STATS -- Return key count, dataset size and keys per type.",
+        "DOCTOR -- Return a summary of the dataset's memory use.",
+        "POOL -- Return allocation pool counters, or that pooling is disabled in this build.",
+        "HELP -- Print this help.",
+    ]),
+    ("pool", &[
+        "STATS -- Return connection pool statistics.",
+        "HELP -- Print this help.",
+    ]),
+];
+
+/// Help lines of a command that takes subcommands, with its table name, ignoring case
+pub fn help(command: &str) -> Option<(&'static str, &'static [&'static str])> {
+    SUBCOMMAND_HELP.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(command))
+        .map(|&(name, lines)| (name, lines))
+}
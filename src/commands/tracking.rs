@@ -0,0 +1,173 @@
+use crate::protocol::Response;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+
+/// Where a connection receives out-of-band messages
+pub type Mailbox = mpsc::UnboundedSender<Response>;
+
+/// Default cap on keys remembered per tracking client
+pub const DEFAULT_MAX_TRACKED_KEYS: usize = 10_000;
+
+/// Keys read by clients with CLIENT TRACKING ON, and who to tell when they change.
+///
+/// A key is forgotten once its invalidation is sent, until the client reads it
+/// again. A client that reads more than the per-client cap stops being tracked
+/// key by key and is sent every invalidation instead.
+#[derive(Clone)]
+pub struct Tracking {
+    state: Arc<Mutex<TrackingState>>,
+    /// Number of tracking clients, so writes skip the lock while there are none
+    clients: Arc<AtomicUsize>,
+    max_keys_per_client: usize,
+}
+
+#[derive(Default)]
+struct TrackingState {
+    clients: HashMap<u64, TrackedClient>,
+    /// Key -> ids of clients that read it
+    readers: HashMap<String, HashSet<u64>>,
+}
+
+struct TrackedClient {
+    mailbox: Mailbox,
+    keys: HashSet<String>,
+    /// Over the cap: receives invalidations for every key
+    broadcast: bool,
+}
+
+impl Default for Tracking {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_TRACKED_KEYS)
+    }
+}
+
+impl Tracking {
+    pub fn new(max_keys_per_client: usize) -> Self {
+        Self { state: Arc::default(), clients: Arc::default(), max_keys_per_client }
+    }
+
+    pub fn enable(&self, id: u64, mailbox: Mailbox) {
+        let mut state = self.state.lock().unwrap();
+        if let Entry::Vacant(entry) = state.clients.entry(id) {
+            entry.insert(TrackedClient { mailbox, keys: HashSet::new(), broadcast: false });
+            self.clients.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn disable(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(client) = state.clients.remove(&id) {
+            self.clients.fetch_sub(1, Ordering::Relaxed);
+            for key in client.keys {
+                forget(&mut state.readers, &key, id);
+            }
+        }
+    }
+
+    pub fn is_enabled(&self, id: u64) -> bool {
+        self.state.lock().unwrap().clients.contains_key(&id)
+    }
+
+    /// Whether the client went over the cap and now gets every invalidation
+    pub fn is_broadcast(&self, id: u64) -> bool {
+        self.state.lock().unwrap().clients.get(&id).is_some_and(|client| client.broadcast)
+    }
+
+    /// Note that client `id` read `keys`; a no-op unless it enabled tracking
+    pub fn remember(&self, id: u64, keys: &[&str]) {
+        let mut state = self.state.lock().unwrap();
+        let TrackingState { clients, readers } = &mut *state;
+        let Some(client) = clients.get_mut(&id) else { return };
+        if client.broadcast {
+            return;
+        }
+
+        for key in keys {
+            if client.keys.insert(key.to_string()) {
+                readers.entry(key.to_string()).or_default().insert(id);
+            }
+        }
+        if client.keys.len() > self.max_keys_per_client {
+            client.broadcast = true;
+            for key in std::mem::take(&mut client.keys) {
+                forget(readers, &key, id);
+            }
+        }
+    }
+
+    /// Tell every client that read one of `keys` (and every broadcast client)
+    /// that it changed
+    pub fn invalidate(&self, keys: &[&str]) {
+        if keys.is_empty() || self.clients.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+
+        let mut pending: HashMap<u64, Vec<String>> = HashMap::new();
+        for key in keys {
+            for id in state.readers.remove(*key).unwrap_or_default() {
+                if let Some(client) = state.clients.get_mut(&id) {
+                    client.keys.remove(*key);
+                }
+                pending.entry(id).or_default().push(key.to_string());
+            }
+        }
+        for (id, client) in &state.clients {
+            if client.broadcast {
+                pending.insert(*id, keys.iter().map(|key| key.to_string()).collect());
+            }
+        }
+
+        let mut gone = Vec::new();
+        for (id, keys) in pending {
+            let Some(client) = state.clients.get(&id) else { continue };
+            let message = Response::Array(vec![
+                Response::String(Some("invalidate".to_string())),
+                Response::Array(keys.into_iter().map(|key| Response::String(Some(key))).collect()),
+            ]);
+            if client.mailbox.send(message).is_err() {
+                gone.push(id);
+            }
+        }
+        drop(state);
+        for id in gone {
+            self.disable(id);
+        }
+    }
+
+    /// Tell every tracking client that all keys changed, as after a flush, with a
+    /// null in place of the key list
+    pub fn invalidate_all(&self) {
+        if self.clients.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        let TrackingState { clients, readers } = &mut *state;
+        readers.clear();
+
+        let mut gone = Vec::new();
+        for (id, client) in clients.iter_mut() {
+            client.keys.clear();
+            let message = Response::Array(vec![Response::String(Some("invalidate".to_string())), Response::Null]);
+            if client.mailbox.send(message).is_err() {
+                gone.push(*id);
+            }
+        }
+        drop(state);
+        for id in gone {
+            self.disable(id);
+        }
+    }
+}
+
+fn forget(readers: &mut HashMap<String, HashSet<u64>>, key: &str, id: u64) {
+    if let Some(ids) = readers.get_mut(key) {
+        ids.remove(&id);
+        if ids.is_empty() {
+            readers.remove(key);
+        }
+    }
+}
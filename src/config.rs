@@ -1,4 +1,144 @@
 use std::path::PathBuf;
+use std::str::FromStr;
+
+/// RocksDB tuning preset applied when the storage engine is opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageProfile {
+    #[default]
+    Balanced,
+    WriteHeavy,
+    ReadHeavy,
+    LowMemory,
+}
+
+impl FromStr for StorageProfile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['-', '_'], "").as_str() {
+            "balanced" => Ok(StorageProfile::Balanced),
+            "writeheavy" => Ok(StorageProfile::WriteHeavy),
+            "readheavy" => Ok(StorageProfile::ReadHeavy),
+            "lowmemory" => Ok(StorageProfile::LowMemory),
+            other => Err(format!("Unknown storage profile: {}", other)),
+        }
+    }
+}
+
+/// Block compression codec for stored data.
+///
+/// RocksDB records the codec per SST block, so switching codecs on an existing
+/// database keeps reading older files; they are rewritten with the new codec as
+/// compaction reaches them. Values are compressed a block at a time, which keeps
+/// small values from paying a per-value overhead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    None,
+    Lz4,
+    Zstd,
+    #[default]
+    Snappy,
+}
+
+impl Compression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Lz4 => "lz4",
+            Compression::Zstd => "zstd",
+            Compression::Snappy => "snappy",
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "lz4" => Ok(Compression::Lz4),
+            "zstd" => Ok(Compression::Zstd),
+            "snappy" => Ok(Compression::Snappy),
+            other => Err(format!("Unknown compression: {}", other)),
+        }
+    }
+}
+
+/// Key eviction policy, named as in Redis' `maxmemory-policy`.
+///
+/// The LFU variants also enable per-key access frequency tracking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaxMemoryPolicy {
+    #[default]
+    NoEviction,
+    AllKeysLru,
+    VolatileLru,
+    AllKeysLfu,
+    VolatileLfu,
+    AllKeysRandom,
+    VolatileRandom,
+    VolatileTtl,
+}
+
+impl MaxMemoryPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MaxMemoryPolicy::NoEviction => "noeviction",
+            MaxMemoryPolicy::AllKeysLru => "allkeys-lru",
+            MaxMemoryPolicy::VolatileLru => "volatile-lru",
+            MaxMemoryPolicy::AllKeysLfu => "allkeys-lfu",
+            MaxMemoryPolicy::VolatileLfu => "volatile-lfu",
+            MaxMemoryPolicy::AllKeysRandom => "allkeys-random",
+            MaxMemoryPolicy::VolatileRandom => "volatile-random",
+            MaxMemoryPolicy::VolatileTtl => "volatile-ttl",
+        }
+    }
+    
+    pub fn is_lfu(&self) -> bool {
+        matches!(self, MaxMemoryPolicy::AllKeysLfu | MaxMemoryPolicy::VolatileLfu)
+    }
+}
+
+impl FromStr for MaxMemoryPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "noeviction" => Ok(MaxMemoryPolicy::NoEviction),
+            "allkeys-lru" => Ok(MaxMemoryPolicy::AllKeysLru),
+            "volatile-lru" => Ok(MaxMemoryPolicy::VolatileLru),
+            "allkeys-lfu" => Ok(MaxMemoryPolicy::AllKeysLfu),
+            "volatile-lfu" => Ok(MaxMemoryPolicy::VolatileLfu),
+            "allkeys-random" => Ok(MaxMemoryPolicy::AllKeysRandom),
+            "volatile-random" => Ok(MaxMemoryPolicy::VolatileRandom),
+            "volatile-ttl" => Ok(MaxMemoryPolicy::VolatileTtl),
+            other => Err(format!("Unknown maxmemory policy: {}", other)),
+        }
+    }
+}
+
+/// What happens when a subscriber's queue of undelivered messages is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PubSubOverflow {
+    /// Discard the oldest queued message, counting it in the subscriber's lag
+    #[default]
+    DropOldest,
+    /// Close the subscriber's connection
+    Disconnect,
+}
+
+impl FromStr for PubSubOverflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "drop-oldest" => Ok(PubSubOverflow::DropOldest),
+            "disconnect" => Ok(PubSubOverflow::Disconnect),
+            other => Err(format!("Unknown pub/sub overflow policy: {}", other)),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -8,7 +148,72 @@ pub struct Config {
     pub cert_path: Option<PathBuf>,
     pub key_path: Option<PathBuf>,
     pub max_connections: usize,
+    /// New connections each client IP may open per second; 0 disables the limit
+    pub max_connections_per_sec: u32,
+    /// Connections a client IP may open at once before the per-second limit applies
+    pub connection_burst: u32,
+    /// Connections the kernel queues for the listener before they are accepted
+    pub tcp_backlog: u32,
+    /// Receive buffer of the listener and of each connection, in bytes; 0 keeps the system default
+    pub so_rcvbuf: usize,
+    /// Send buffer of the listener and of each connection, in bytes; 0 keeps the system default
+    pub so_sndbuf: usize,
+    /// Seconds a connection stays idle before keepalive probes are sent; 0 disables keepalive
+    pub tcp_keepalive_secs: u64,
+    /// Disable Nagle's algorithm so small replies are sent at once
+    pub tcp_nodelay: bool,
     pub thread_pool_size: usize,
+    pub active_expire_interval_ms: u64,
+    pub active_expire_sample_size: usize,
+    pub max_pipeline_response_bytes: usize,
+    /// Array replies with at least this many elements are encoded and written in
+    /// pieces of about `max_pipeline_response_bytes`; 0 always encodes them whole
+    pub stream_array_threshold: usize,
+    /// Close connections that send no command for this long; 0 disables the check
+    pub idle_timeout_ms: u64,
+    /// Close clients with more than this many reply bytes not yet taken by the socket; 0 disables the limit
+    pub client_output_buffer_hard_limit: usize,
+    /// Close clients whose unwritten reply bytes stay above this for `client_output_buffer_soft_ms`;
+    /// 0 disables the limit
+    pub client_output_buffer_soft_limit: usize,
+    pub client_output_buffer_soft_ms: u64,
+    /// Log commands slower than this many microseconds; 0 disables the check
+    pub slow_command_log_us: u64,
+    /// Largest string value or list element accepted by writes, in bytes; 0 means unlimited
+    pub max_value_size: usize,
+    /// Largest bulk string a RESP request may declare, in bytes
+    pub proto_max_bulk_len: usize,
+    /// Most arguments accepted in one request, whether a RESP multibulk or a text line
+    pub proto_max_multibulk_len: usize,
+    /// Accept DEBUG subcommands; off by default since they rewrite the whole keyspace
+    pub enable_debug_command: bool,
+    /// Keys remembered per CLIENT TRACKING client before it is sent every invalidation
+    pub tracking_max_keys: usize,
+    /// Published messages held for a subscriber that has not taken them yet; 0 means unlimited
+    pub pubsub_queue_limit: usize,
+    /// What to do with a subscriber whose queue is full
+    pub pubsub_overflow: PubSubOverflow,
+    /// Record LATENCY events that take at least this many milliseconds; 0 disables the monitor
+    pub latency_monitor_threshold_ms: u64,
+    /// Stop a script or function that runs longer than this many milliseconds; 0 disables the limit
+    pub lua_time_limit_ms: u64,
+    /// Lists with at most this many elements are stored as a compact listpack
+    pub list_max_listpack_entries: usize,
+    /// Lists whose elements total at most this many bytes are stored as a compact listpack
+    pub list_max_listpack_bytes: usize,
+    /// Hashes with at most this many fields are stored as a compact listpack
+    pub hash_max_listpack_entries: usize,
+    /// Sets of at most this many members, all integers, are stored as a compact intset
+    pub set_max_intset_entries: usize,
+    /// Sorted sets with at most this many members are stored as a compact listpack
+    pub zset_max_listpack_entries: usize,
+    pub storage_profile: StorageProfile,
+    /// Overrides the profile's block cache size
+    pub block_cache_mb: Option<usize>,
+    /// Overrides the profile's memtable size
+    pub write_buffer_mb: Option<usize>,
+    pub compression: Compression,
+    pub maxmemory_policy: MaxMemoryPolicy,
 }
 
 impl Config {
@@ -47,6 +252,212 @@ impl Config {
             }
         }
         
+        if let Ok(rate) = std::env::var("DISKDB_MAX_CONNECTIONS_PER_SEC") {
+            if let Ok(r) = rate.parse() {
+                config.max_connections_per_sec = r;
+            }
+        }
+        
+        if let Ok(burst) = std::env::var("DISKDB_CONNECTION_BURST") {
+            if let Ok(b) = burst.parse() {
+                config.connection_burst = b;
+            }
+        }
+        
+        if let Ok(backlog) = std::env::var("DISKDB_TCP_BACKLOG") {
+            if let Ok(b) = backlog.parse() {
+                config.tcp_backlog = b;
+            }
+        }
+        
+        if let Ok(rcvbuf) = std::env::var("DISKDB_SO_RCVBUF") {
+            if let Ok(b) = rcvbuf.parse() {
+                config.so_rcvbuf = b;
+            }
+        }
+        
+        if let Ok(sndbuf) = std::env::var("DISKDB_SO_SNDBUF") {
+            if let Ok(b) = sndbuf.parse() {
+                config.so_sndbuf = b;
+            }
+        }
+        
+        if let Ok(keepalive) = std::env::var("DISKDB_TCP_KEEPALIVE") {
+            if let Ok(k) = keepalive.parse() {
+                config.tcp_keepalive_secs = k;
+            }
+        }
+        
+        if let Ok(nodelay) = std::env::var("DISKDB_TCP_NODELAY") {
+            config.tcp_nodelay = nodelay.to_lowercase() == "true" || nodelay == "1";
+        }
+        
+        if let Ok(interval) = std::env::var("DISKDB_ACTIVE_EXPIRE_INTERVAL_MS") {
+            if let Ok(i) = interval.parse() {
+                config.active_expire_interval_ms = i;
+            }
+        }
+        
+        if let Ok(sample) = std::env::var("DISKDB_ACTIVE_EXPIRE_SAMPLE_SIZE") {
+            if let Ok(s) = sample.parse() {
+                config.active_expire_sample_size = s;
+            }
+        }
+        
+        if let Ok(max_bytes) = std::env::var("DISKDB_MAX_PIPELINE_RESPONSE_BYTES") {
+            if let Ok(m) = max_bytes.parse() {
+                config.max_pipeline_response_bytes = m;
+            }
+        }
+        
+        if let Ok(threshold) = std::env::var("DISKDB_STREAM_ARRAY_THRESHOLD") {
+            if let Ok(t) = threshold.parse() {
+                config.stream_array_threshold = t;
+            }
+        }
+        
+        if let Ok(idle) = std::env::var("DISKDB_IDLE_TIMEOUT_MS") {
+            if let Ok(i) = idle.parse() {
+                config.idle_timeout_ms = i;
+            }
+        }
+        
+        if let Ok(hard) = std::env::var("DISKDB_CLIENT_OUTPUT_BUFFER_HARD_LIMIT") {
+            if let Ok(h) = hard.parse() {
+                config.client_output_buffer_hard_limit = h;
+            }
+        }
+        
+        if let Ok(soft) = std::env::var("DISKDB_CLIENT_OUTPUT_BUFFER_SOFT_LIMIT") {
+            if let Ok(s) = soft.parse() {
+                config.client_output_buffer_soft_limit = s;
+            }
+        }
+        
+        if let Ok(soft_ms) = std::env::var("DISKDB_CLIENT_OUTPUT_BUFFER_SOFT_MS") {
+            if let Ok(ms) = soft_ms.parse() {
+                config.client_output_buffer_soft_ms = ms;
+            }
+        }
+        
+        if let Ok(slow) = std::env::var("DISKDB_SLOW_COMMAND_LOG_US") {
+            if let Ok(s) = slow.parse() {
+                config.slow_command_log_us = s;
+            }
+        }
+        
+        if let Ok(max_value) = std::env::var("DISKDB_MAX_VALUE_SIZE") {
+            if let Ok(m) = max_value.parse() {
+                config.max_value_size = m;
+            }
+        }
+        
+        if let Ok(max_bulk) = std::env::var("DISKDB_PROTO_MAX_BULK_LEN") {
+            if let Ok(m) = max_bulk.parse() {
+                config.proto_max_bulk_len = m;
+            }
+        }
+        
+        if let Ok(max_multibulk) = std::env::var("DISKDB_PROTO_MAX_MULTIBULK_LEN") {
+            if let Ok(m) = max_multibulk.parse() {
+                config.proto_max_multibulk_len = m;
+            }
+        }
+        
+        if let Ok(debug) = std::env::var("DISKDB_ENABLE_DEBUG_COMMAND") {
+            config.enable_debug_command = debug.to_lowercase() == "true" || debug == "1";
+        }
+        
+        if let Ok(max_keys) = std::env::var("DISKDB_TRACKING_MAX_KEYS") {
+            if let Ok(m) = max_keys.parse() {
+                config.tracking_max_keys = m;
+            }
+        }
+        
+        if let Ok(limit) = std::env::var("DISKDB_PUBSUB_QUEUE_LIMIT") {
+            if let Ok(l) = limit.parse() {
+                config.pubsub_queue_limit = l;
+            }
+        }
+        
+        if let Ok(policy) = std::env::var("DISKDB_PUBSUB_OVERFLOW") {
+            if let Ok(p) = policy.parse() {
+                config.pubsub_overflow = p;
+            }
+        }
+        
+        if let Ok(threshold) = std::env::var("DISKDB_LATENCY_MONITOR_THRESHOLD_MS") {
+            if let Ok(t) = threshold.parse() {
+                config.latency_monitor_threshold_ms = t;
+            }
+        }
+        
+        if let Ok(limit) = std::env::var("DISKDB_LUA_TIME_LIMIT_MS") {
+            if let Ok(l) = limit.parse() {
+                config.lua_time_limit_ms = l;
+            }
+        }
+        
+        if let Ok(entries) = std::env::var("DISKDB_LIST_MAX_LISTPACK_ENTRIES") {
+            if let Ok(e) = entries.parse() {
+                config.list_max_listpack_entries = e;
+            }
+        }
+        
+        if let Ok(bytes) = std::env::var("DISKDB_LIST_MAX_LISTPACK_BYTES") {
+            if let Ok(b) = bytes.parse() {
+                config.list_max_listpack_bytes = b;
+            }
+        }
+        
+        if let Ok(entries) = std::env::var("DISKDB_HASH_MAX_LISTPACK_ENTRIES") {
+            if let Ok(e) = entries.parse() {
+                config.hash_max_listpack_entries = e;
+            }
+        }
+        
+        if let Ok(entries) = std::env::var("DISKDB_SET_MAX_INTSET_ENTRIES") {
+            if let Ok(e) = entries.parse() {
+                config.set_max_intset_entries = e;
+            }
+        }
+        
+        if let Ok(entries) = std::env::var("DISKDB_ZSET_MAX_LISTPACK_ENTRIES") {
+            if let Ok(e) = entries.parse() {
+                config.zset_max_listpack_entries = e;
+            }
+        }
+        
+        if let Ok(profile) = std::env::var("DISKDB_STORAGE_PROFILE") {
+            if let Ok(p) = profile.parse() {
+                config.storage_profile = p;
+            }
+        }
+        
+        if let Ok(cache) = std::env::var("DISKDB_BLOCK_CACHE_MB") {
+            if let Ok(c) = cache.parse() {
+                config.block_cache_mb = Some(c);
+            }
+        }
+        
+        if let Ok(buffer) = std::env::var("DISKDB_WRITE_BUFFER_MB") {
+            if let Ok(b) = buffer.parse() {
+                config.write_buffer_mb = Some(b);
+            }
+        }
+        
+        if let Ok(compression) = std::env::var("DISKDB_COMPRESSION") {
+            if let Ok(c) = compression.parse() {
+                config.compression = c;
+            }
+        }
+        
+        if let Ok(policy) = std::env::var("DISKDB_MAXMEMORY_POLICY") {
+            if let Ok(p) = policy.parse() {
+                config.maxmemory_policy = p;
+            }
+        }
+        
         config
     }
 }
@@ -60,7 +471,42 @@ impl Default for Config {
             cert_path: None,
             key_path: None,
             max_connections: 1000,
+            max_connections_per_sec: 0,
+            connection_burst: 20,
+            tcp_backlog: 1024,
+            so_rcvbuf: 256 * 1024,
+            so_sndbuf: 256 * 1024,
+            tcp_keepalive_secs: 300,
+            tcp_nodelay: true,
             thread_pool_size: num_cpus::get(),
+            active_expire_interval_ms: 100,
+            active_expire_sample_size: 20,
+            max_pipeline_response_bytes: 1024 * 1024,
+            stream_array_threshold: 1024,
+            idle_timeout_ms: 0,
+            client_output_buffer_hard_limit: 0,
+            client_output_buffer_soft_limit: 0,
+            client_output_buffer_soft_ms: 60_000,
+            slow_command_log_us: 0,
+            max_value_size: 512 * 1024 * 1024,
+            proto_max_bulk_len: 512 * 1024 * 1024,
+            proto_max_multibulk_len: 1024 * 1024,
+            enable_debug_command: false,
+            tracking_max_keys: 10_000,
+            pubsub_queue_limit: 1024,
+            pubsub_overflow: PubSubOverflow::DropOldest,
+            latency_monitor_threshold_ms: 0,
+            lua_time_limit_ms: 5000,
+            list_max_listpack_entries: crate::data_types::DEFAULT_LIST_MAX_LISTPACK_ENTRIES,
+            list_max_listpack_bytes: crate::data_types::DEFAULT_LIST_MAX_LISTPACK_BYTES,
+            hash_max_listpack_entries: crate::data_types::DEFAULT_HASH_MAX_LISTPACK_ENTRIES,
+            set_max_intset_entries: crate::data_types::DEFAULT_SET_MAX_INTSET_ENTRIES,
+            zset_max_listpack_entries: crate::data_types::DEFAULT_ZSET_MAX_LISTPACK_ENTRIES,
+            storage_profile: StorageProfile::Balanced,
+            block_cache_mb: None,
+            write_buffer_mb: None,
+            compression: Compression::Snappy,
+            maxmemory_policy: MaxMemoryPolicy::NoEviction,
         }
     }
 }
\ No newline at end of file
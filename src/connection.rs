@@ -31,7 +31,9 @@ impl Connection {
                                 continue;
                             }
 
-                            let response = match Request::parse(&line) {
+                            let request = Request::parse(&line);
+                            let quit = matches!(request, Ok(Request::Quit));
+                            let response = match request {
                                 Ok(request) => {
                                     match executor.execute(request).await {
                                         Ok(resp) => resp,
@@ -45,6 +47,9 @@ impl Connection {
                                 error!("Failed to write response: {}", e);
                                 break;
                             }
+                            if quit {
+                                break;
+                            }
                         }
                         Err(e) => {
                             error!("Failed to read from stream: {}", e);
@@ -67,7 +72,9 @@ impl Connection {
                                 continue;
                             }
 
-                            let response = match Request::parse(&line) {
+                            let request = Request::parse(&line);
+                            let quit = matches!(request, Ok(Request::Quit));
+                            let response = match request {
                                 Ok(request) => {
                                     match executor.execute(request).await {
                                         Ok(resp) => resp,
@@ -81,6 +88,9 @@ impl Connection {
                                 error!("Failed to write response: {}", e);
                                 break;
                             }
+                            if quit {
+                                break;
+                            }
                         }
                         Err(e) => {
                             error!("Failed to read from stream: {}", e);
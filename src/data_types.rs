@@ -1,43 +1,169 @@
 use serde::{Deserialize, Serialize, Deserializer, Serializer};
-use std::collections::{HashMap, HashSet, BTreeMap};
+use std::collections::{HashMap, HashSet, BTreeMap, VecDeque};
+use std::fmt::Write;
 use std::time::SystemTime;
 
+/// Most elements a list may hold and still be stored as a listpack
+pub const DEFAULT_LIST_MAX_LISTPACK_ENTRIES: usize = 128;
+/// Most element bytes a list may hold and still be stored as a listpack
+pub const DEFAULT_LIST_MAX_LISTPACK_BYTES: usize = 8192;
+
+/// Most fields a hash may hold and still be stored as a listpack
+pub const DEFAULT_HASH_MAX_LISTPACK_ENTRIES: usize = 128;
+/// Most members a set of integers may hold and still be stored as an intset
+pub const DEFAULT_SET_MAX_INTSET_ENTRIES: usize = 512;
+/// Most members a sorted set may hold and still be stored as a listpack
+pub const DEFAULT_ZSET_MAX_LISTPACK_ENTRIES: usize = 128;
+
+/// Sizes under which values are stored in a compact encoding. Each storage keeps
+/// its own, so values written through one are not affected by another's config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodingLimits {
+    /// Most elements a list may hold and still be stored as a listpack; 0 turns
+    /// the compact encoding off
+    pub list_max_listpack_entries: usize,
+    /// Most element bytes a list may hold and still be stored as a listpack
+    pub list_max_listpack_bytes: usize,
+    /// Most fields a hash may hold and still be stored as a listpack; 0 turns
+    /// the compact encoding off, as it does for sets and sorted sets
+    pub hash_max_listpack_entries: usize,
+    /// Most members a set of integers may hold and still be stored as an intset
+    pub set_max_intset_entries: usize,
+    /// Most members a sorted set may hold and still be stored as a listpack
+    pub zset_max_listpack_entries: usize,
+}
+
+impl Default for EncodingLimits {
+    fn default() -> Self {
+        Self {
+            list_max_listpack_entries: DEFAULT_LIST_MAX_LISTPACK_ENTRIES,
+            list_max_listpack_bytes: DEFAULT_LIST_MAX_LISTPACK_BYTES,
+            hash_max_listpack_entries: DEFAULT_HASH_MAX_LISTPACK_ENTRIES,
+            set_max_intset_entries: DEFAULT_SET_MAX_INTSET_ENTRIES,
+            zset_max_listpack_entries: DEFAULT_ZSET_MAX_LISTPACK_ENTRIES,
+        }
+    }
+}
+
+impl EncodingLimits {
+    fn fits_listpack(&self, list: &VecDeque<String>) -> bool {
+        list.len() <= self.list_max_listpack_entries
+            && list.iter().map(String::len).sum::<usize>() <= self.list_max_listpack_bytes
+    }
+
+    fn fits_hash_listpack(&self, hash: &HashMap<String, String>) -> bool {
+        hash.len() <= self.hash_max_listpack_entries
+    }
+
+    fn fits_intset(&self, set: &HashSet<String>) -> bool {
+        set.len() <= self.set_max_intset_entries && set.iter().all(|member| canonical_int(member).is_some())
+    }
+
+    fn fits_zset_listpack(&self, zset: &BTreeMap<String, f64>) -> bool {
+        zset.len() <= self.zset_max_listpack_entries
+    }
+}
+
+/// bincode variant index of `DataTypeRepr::Int`
+const INT_VARIANT: u32 = 8;
+
+/// The integer a string holds, if it is written exactly as that integer
+/// prints: no sign other than '-', no leading zeros, no surrounding spaces.
+/// Only such strings are stored int-encoded, so they read back unchanged.
+pub fn canonical_int(s: &str) -> Option<i64> {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    let canonical = !digits.is_empty()
+        && digits.len() <= 19
+        && digits.bytes().all(|b| b.is_ascii_digit())
+        && (!digits.starts_with('0') || s == "0");
+    canonical.then(|| s.parse().ok()).flatten()
+}
+
 #[derive(Debug, Clone)]
 pub enum DataType {
     String(String),
-    List(Vec<String>),
+    /// A deque, so pushes and pops at either end are O(1)
+    List(VecDeque<String>),
     Set(HashSet<String>),
     Hash(HashMap<String, String>),
     SortedSet(BTreeMap<String, f64>), // member -> score
     Json(serde_json::Value),
     Stream(Vec<StreamEntry>),
+    /// A small list as read back from its compact encoding. Writes unpack it
+    /// into a `List` first.
+    ListPack(ListPack),
 }
 
-// Custom serialization to handle JSON values
+/// A value serialized with the compact encodings its storage's limits allow
+pub struct Encoded<'a> {
+    value: &'a DataType,
+    limits: &'a EncodingLimits,
+}
+
+// Serialized on its own, a value uses the default limits
 impl Serialize for DataType {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        self.encoded(&EncodingLimits::default()).serialize(serializer)
+    }
+}
+
+// Custom serialization to handle JSON values
+impl Serialize for Encoded<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let limits = self.limits;
         #[derive(Serialize)]
         enum DataTypeRepr {
             String(String),
-            List(Vec<String>),
+            List(VecDeque<String>), // a sequence, as it was when lists were a Vec
             Set(HashSet<String>),
             Hash(HashMap<String, String>),
             SortedSet(BTreeMap<String, f64>),
             Json(String), // Store JSON as string
             Stream(Vec<StreamEntry>),
+            ListPack(u64, String), // element count, then the packed elements
+            Int(i64), // a string holding a canonical integer
+            HashPack(u64, String), // field count, then fields and values packed alternately
+            IntSet(Vec<i64>), // a set of canonical integers, ascending
+            ZSetPack(u64, String), // member count, then members and scores packed in score order
         }
         
-        let repr = match self {
-            DataType::String(s) => DataTypeRepr::String(s.clone()),
+        let repr = match self.value {
+            DataType::String(s) => match canonical_int(s) {
+                Some(n) => DataTypeRepr::Int(n),
+                None => DataTypeRepr::String(s.clone()),
+            },
+            DataType::List(l) if limits.fits_listpack(l) => {
+                let pack = ListPack::pack_all(l);
+                DataTypeRepr::ListPack(pack.len as u64, pack.buf)
+            }
             DataType::List(l) => DataTypeRepr::List(l.clone()),
+            DataType::Set(s) if limits.fits_intset(s) => {
+                let mut members: Vec<i64> = s.iter().filter_map(|member| canonical_int(member)).collect();
+                members.sort_unstable();
+                DataTypeRepr::IntSet(members)
+            }
             DataType::Set(s) => DataTypeRepr::Set(s.clone()),
+            DataType::Hash(h) if limits.fits_hash_listpack(h) => {
+                let pack = ListPack::pack_all(h.iter().flat_map(|(field, value)| [field, value]));
+                DataTypeRepr::HashPack(h.len() as u64, pack.buf)
+            }
             DataType::Hash(h) => DataTypeRepr::Hash(h.clone()),
+            DataType::SortedSet(z) if limits.fits_zset_listpack(z) => {
+                let mut members: Vec<(&String, f64)> = z.iter().map(|(member, score)| (member, *score)).collect();
+                members.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(b.0)));
+                let pack = ListPack::pack_all(members.into_iter().flat_map(|(member, score)| [member.clone(), score.to_string()]));
+                DataTypeRepr::ZSetPack(z.len() as u64, pack.buf)
+            }
             DataType::SortedSet(z) => DataTypeRepr::SortedSet(z.clone()),
             DataType::Json(j) => DataTypeRepr::Json(j.to_string()),
             DataType::Stream(s) => DataTypeRepr::Stream(s.clone()),
+            DataType::ListPack(p) => DataTypeRepr::ListPack(p.len as u64, p.buf.clone()),
         };
         
         repr.serialize(serializer)
@@ -52,12 +178,17 @@ impl<'de> Deserialize<'de> for DataType {
         #[derive(Deserialize)]
         enum DataTypeRepr {
             String(String),
-            List(Vec<String>),
+            List(VecDeque<String>), // a sequence, as it was when lists were a Vec
             Set(HashSet<String>),
             Hash(HashMap<String, String>),
             SortedSet(BTreeMap<String, f64>),
             Json(String), // JSON stored as string
             Stream(Vec<StreamEntry>),
+            ListPack(u64, String),
+            Int(i64),
+            HashPack(u64, String),
+            IntSet(Vec<i64>),
+            ZSetPack(u64, String),
         }
         
         let repr = DataTypeRepr::deserialize(deserializer)?;
@@ -74,6 +205,39 @@ impl<'de> Deserialize<'de> for DataType {
                 DataType::Json(value)
             },
             DataTypeRepr::Stream(s) => DataType::Stream(s),
+            DataTypeRepr::ListPack(len, buf) => {
+                let pack = ListPack::from_parts(len as usize, buf)
+                    .ok_or_else(|| serde::de::Error::custom("malformed listpack"))?;
+                DataType::ListPack(pack)
+            }
+            DataTypeRepr::Int(n) => DataType::String(n.to_string()),
+            DataTypeRepr::HashPack(len, buf) => {
+                let entries = len.checked_mul(2)
+                    .ok_or_else(|| serde::de::Error::custom("malformed hash listpack length"))?;
+                let pack = ListPack::from_parts(entries as usize, buf)
+                    .ok_or_else(|| serde::de::Error::custom("malformed hash listpack"))?;
+                let mut entries = pack.iter();
+                let mut hash = HashMap::with_capacity(pack.len() / 2);
+                while let (Some(field), Some(value)) = (entries.next(), entries.next()) {
+                    hash.insert(field.to_string(), value.to_string());
+                }
+                DataType::Hash(hash)
+            }
+            DataTypeRepr::IntSet(members) => DataType::Set(members.iter().map(i64::to_string).collect()),
+            DataTypeRepr::ZSetPack(len, buf) => {
+                let entries = len.checked_mul(2)
+                    .ok_or_else(|| serde::de::Error::custom("malformed sorted set listpack length"))?;
+                let pack = ListPack::from_parts(entries as usize, buf)
+                    .ok_or_else(|| serde::de::Error::custom("malformed sorted set listpack"))?;
+                let mut entries = pack.iter();
+                let mut zset = BTreeMap::new();
+                while let (Some(member), Some(score)) = (entries.next(), entries.next()) {
+                    let score = score.parse::<f64>()
+                        .map_err(|_| serde::de::Error::custom("malformed sorted set listpack score"))?;
+                    zset.insert(member.to_string(), score);
+                }
+                DataType::SortedSet(zset)
+            }
         })
     }
 }
@@ -85,7 +249,139 @@ pub struct StreamEntry {
     pub fields: HashMap<String, String>,
 }
 
+/// The elements of a small list packed into one buffer as `<byte length>:<element>`
+/// entries, so the list decodes with one allocation rather than one per element
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListPack {
+    len: usize,
+    buf: String,
+}
+
+impl ListPack {
+    pub fn pack(items: &[String]) -> Self {
+        let mut buf = String::with_capacity(items.iter().map(|item| item.len() + 4).sum());
+        for item in items {
+            let _ = write!(buf, "{}:", item.len());
+            buf.push_str(item);
+        }
+        Self { len: items.len(), buf }
+    }
+
+    /// Pack entries from any source, such as a hash's fields and values in turn
+    fn pack_all<S: AsRef<str>>(items: impl IntoIterator<Item = S>) -> Self {
+        let mut pack = Self::default();
+        for item in items {
+            let item = item.as_ref();
+            let _ = write!(pack.buf, "{}:", item.len());
+            pack.buf.push_str(item);
+            pack.len += 1;
+        }
+        pack
+    }
+
+    /// A pack read from disk, if `buf` holds exactly `len` well-formed entries
+    fn from_parts(len: usize, buf: String) -> Option<Self> {
+        let mut rest = buf.as_str();
+        let mut count = 0;
+        while !rest.is_empty() {
+            rest = next_entry(rest)?.1;
+            count += 1;
+        }
+        (count == len).then_some(Self { len, buf })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Size of the packed buffer, entry headers included
+    pub fn byte_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        let mut rest = self.buf.as_str();
+        std::iter::from_fn(move || {
+            let (item, next) = next_entry(rest)?;
+            rest = next;
+            Some(item)
+        })
+    }
+
+    pub fn unpack(&self) -> Vec<String> {
+        self.iter().map(str::to_string).collect()
+    }
+}
+
+/// Split the first entry off a packed buffer
+fn next_entry(buf: &str) -> Option<(&str, &str)> {
+    let (len, rest) = buf.split_once(':')?;
+    let len: usize = len.parse().ok()?;
+    Some((rest.get(..len)?, rest.get(len..)?))
+}
+
 impl DataType {
+    /// Read the type and length prefix of an encoded value without decoding its elements.
+    ///
+    /// The length is the element count for collections and the byte length for
+    /// strings (for JSON, of its text form). bincode writes the `DataTypeRepr`
+    /// variant index as a u32 followed by the u64 length prefix of its payload;
+    /// an int-encoded string has the integer there instead.
+    pub fn peek_len(bytes: &[u8]) -> Option<(&'static str, usize)> {
+        if let Some(n) = Self::peek_int(bytes) {
+            let sign = usize::from(n < 0);
+            return Some(("string", sign + n.unsigned_abs().checked_ilog10().map_or(1, |digits| digits as usize + 1)));
+        }
+        let variant = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?);
+        let len = u64::from_le_bytes(bytes.get(4..12)?.try_into().ok()?);
+        Some((Self::variant_name(variant)?, len as usize))
+    }
+    
+    /// Read an int-encoded string without decoding it to text
+    pub fn peek_int(bytes: &[u8]) -> Option<i64> {
+        let variant = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?);
+        if variant != INT_VARIANT || bytes.len() != 12 {
+            return None;
+        }
+        Some(i64::from_le_bytes(bytes[4..12].try_into().ok()?))
+    }
+    
+    /// Encoded form of a string holding `n`, as `serialize` writes it
+    pub fn encode_int(n: i64) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(12);
+        bytes.extend_from_slice(&INT_VARIANT.to_le_bytes());
+        bytes.extend_from_slice(&n.to_le_bytes());
+        bytes
+    }
+    
+    /// Read only the type of an encoded value from its bincode variant index
+    pub fn peek_type(bytes: &[u8]) -> Option<&'static str> {
+        let variant = u32::from_le_bytes(bytes.get(0..4)?.try_into().ok()?);
+        Self::variant_name(variant)
+    }
+    
+    fn variant_name(variant: u32) -> Option<&'static str> {
+        match variant {
+            0 => Some("string"),
+            1 => Some("list"),
+            2 => Some("set"),
+            3 => Some("hash"),
+            4 => Some("zset"),
+            5 => Some("json"),
+            6 => Some("stream"),
+            7 => Some("list"),
+            8 => Some("string"),
+            9 => Some("hash"),
+            10 => Some("set"),
+            11 => Some("zset"),
+            _ => None,
+        }
+    }
+    
     pub fn type_name(&self) -> &'static str {
         match self {
             DataType::String(_) => "string",
@@ -95,6 +391,38 @@ impl DataType {
             DataType::SortedSet(_) => "zset",
             DataType::Json(_) => "json",
             DataType::Stream(_) => "stream",
+            DataType::ListPack(_) => "list",
+        }
+    }
+
+    /// This value as serialized under `limits`
+    pub fn encoded<'a>(&'a self, limits: &'a EncodingLimits) -> Encoded<'a> {
+        Encoded { value: self, limits }
+    }
+
+    /// Internal encoding reported by OBJECT ENCODING for a value stored under `limits`
+    pub fn encoding(&self, limits: &EncodingLimits) -> &'static str {
+        match self {
+            DataType::String(s) if canonical_int(s).is_some() => "int",
+            DataType::String(s) if s.len() <= 44 => "embstr",
+            DataType::String(_) => "raw",
+            DataType::List(l) if limits.fits_listpack(l) => "listpack",
+            DataType::List(_) => "quicklist",
+            DataType::ListPack(_) => "listpack",
+            DataType::Set(s) if limits.fits_intset(s) => "intset",
+            DataType::Hash(h) if limits.fits_hash_listpack(h) => "listpack",
+            DataType::Set(_) | DataType::Hash(_) => "hashtable",
+            DataType::SortedSet(z) if limits.fits_zset_listpack(z) => "listpack",
+            DataType::SortedSet(_) => "skiplist",
+            DataType::Json(_) => "raw",
+            DataType::Stream(_) => "stream",
+        }
+    }
+
+    /// Turn a listpack into a full list so it can be modified in place
+    fn unpack_list(&mut self) {
+        if let DataType::ListPack(pack) = self {
+            *self = DataType::List(pack.unpack().into());
         }
     }
 }
@@ -126,18 +454,38 @@ impl DataType {
             _ => Err("Operation not supported on this type".to_string()),
         }
     }
+
+    /// Add `delta` to a float string, storing the shortest decimal form that
+    /// round-trips (no exponent, no trailing zeros, e.g. "10.6" or "3")
+    pub fn incrbyfloat(&mut self, delta: f64) -> Result<f64, String> {
+        match self {
+            DataType::String(s) => {
+                let val: f64 = s.parse()
+                    .ok()
+                    .filter(|v: &f64| v.is_finite())
+                    .ok_or("ERR value is not a valid float")?;
+                let new_val = val + delta;
+                if !new_val.is_finite() {
+                    return Err("ERR increment would produce NaN or Infinity".to_string());
+                }
+                *s = new_val.to_string();
+                Ok(new_val)
+            }
+            _ => Err("WRONGTYPE Operation against a key holding the wrong kind of value".to_string()),
+        }
+    }
 }
 
 // List operations
 impl DataType {
-    pub fn as_list(&self) -> Option<&Vec<String>> {
+    pub fn as_list(&self) -> Option<&VecDeque<String>> {
         match self {
             DataType::List(l) => Some(l),
             _ => None,
         }
     }
 
-    pub fn as_list_mut(&mut self) -> Option<&mut Vec<String>> {
+    pub fn as_list_mut(&mut self) -> Option<&mut VecDeque<String>> {
         match self {
             DataType::List(l) => Some(l),
             _ => None,
@@ -145,11 +493,12 @@ impl DataType {
     }
 
     pub fn lpush(&mut self, values: Vec<String>) -> Result<usize, String> {
+        self.unpack_list();
         match self {
             DataType::List(l) => {
                 // Push values in the order they appear
                 for v in values.into_iter() {
-                    l.insert(0, v);
+                    l.push_front(v);
                 }
                 Ok(l.len())
             }
@@ -158,6 +507,7 @@ impl DataType {
     }
 
     pub fn rpush(&mut self, values: Vec<String>) -> Result<usize, String> {
+        self.unpack_list();
         match self {
             DataType::List(l) => {
                 l.extend(values);
@@ -168,32 +518,95 @@ impl DataType {
     }
 
     pub fn lpop(&mut self) -> Result<Option<String>, String> {
+        self.unpack_list();
         match self {
-            DataType::List(l) => Ok(if l.is_empty() { None } else { Some(l.remove(0)) }),
+            DataType::List(l) => Ok(l.pop_front()),
             _ => Err("Operation not supported on this type".to_string()),
         }
     }
 
     pub fn rpop(&mut self) -> Result<Option<String>, String> {
+        self.unpack_list();
         match self {
-            DataType::List(l) => Ok(l.pop()),
+            DataType::List(l) => Ok(l.pop_back()),
             _ => Err("Operation not supported on this type".to_string()),
         }
     }
 
-    pub fn lrange(&self, start: i64, stop: i64) -> Result<Vec<String>, String> {
+    /// Pop up to `count` elements from the head (or tail) of a list
+    pub fn lpop_count(&mut self, from_left: bool, count: usize) -> Result<Vec<String>, String> {
+        self.unpack_list();
         match self {
             DataType::List(l) => {
-                let len = l.len() as i64;
-                let start = if start < 0 { (len + start).max(0) } else { start } as usize;
-                let stop = if stop < 0 { (len + stop + 1).max(0) } else { stop + 1 } as usize;
-                let stop = stop.min(l.len());
-                
-                if start >= l.len() {
-                    Ok(vec![])
+                let count = count.min(l.len());
+                if from_left {
+                    Ok(l.drain(..count).collect())
                 } else {
-                    Ok(l[start..stop].to_vec())
+                    let split = l.len() - count;
+                    Ok(l.drain(split..).rev().collect())
+                }
+            }
+            _ => Err("Operation not supported on this type".to_string()),
+        }
+    }
+
+    /// Replace the element at `index`, counting from the tail when negative
+    pub fn lset(&mut self, index: i64, value: String) -> Result<(), String> {
+        self.unpack_list();
+        match self {
+            DataType::List(l) => {
+                let index = if index < 0 { l.len() as i64 + index } else { index };
+                match usize::try_from(index).ok().and_then(|i| l.get_mut(i)) {
+                    Some(slot) => {
+                        *slot = value;
+                        Ok(())
+                    }
+                    None => Err("ERR index out of range".to_string()),
+                }
+            }
+            _ => Err("Operation not supported on this type".to_string()),
+        }
+    }
+
+    /// Insert `value` next to the first occurrence of `pivot`, returning the new
+    /// length, or None when the pivot is not in the list
+    pub fn linsert(&mut self, before: bool, pivot: &str, value: String) -> Result<Option<usize>, String> {
+        self.unpack_list();
+        match self {
+            DataType::List(l) => match l.iter().position(|item| item == pivot) {
+                Some(at) => {
+                    l.insert(if before { at } else { at + 1 }, value);
+                    Ok(Some(l.len()))
                 }
+                None => Ok(None),
+            },
+            _ => Err("Operation not supported on this type".to_string()),
+        }
+    }
+
+    /// Keep only the elements between `start` and `stop`, inclusive, with the
+    /// same index rules as LRANGE
+    pub fn ltrim(&mut self, start: i64, stop: i64) -> Result<(), String> {
+        let kept = self.lrange(start, stop)?;
+        *self = DataType::List(kept.into());
+        Ok(())
+    }
+
+    pub fn lrange(&self, start: i64, stop: i64) -> Result<Vec<String>, String> {
+        let bounds = |len: usize| {
+            let len = len as i64;
+            let start = if start < 0 { (len + start).max(0) } else { start } as usize;
+            let stop = if stop < 0 { (len + stop + 1).max(0) } else { stop + 1 } as usize;
+            (start, stop.min(len as usize))
+        };
+        match self {
+            DataType::List(l) => {
+                let (start, stop) = bounds(l.len());
+                Ok(if start < stop { l.range(start..stop).cloned().collect() } else { Vec::new() })
+            }
+            DataType::ListPack(p) => {
+                let (start, stop) = bounds(p.len());
+                Ok(p.iter().skip(start).take(stop.saturating_sub(start)).map(str::to_string).collect())
             }
             _ => Err("Operation not supported on this type".to_string()),
         }
@@ -252,6 +665,14 @@ impl DataType {
             _ => Err("Operation not supported on this type".to_string()),
         }
     }
+
+    /// Membership of each member, in the order given
+    pub fn smismember(&self, members: &[String]) -> Result<Vec<bool>, String> {
+        match self {
+            DataType::Set(s) => Ok(members.iter().map(|m| s.contains(m)).collect()),
+            _ => Err("Operation not supported on this type".to_string()),
+        }
+    }
 }
 
 // Hash operations
@@ -281,6 +702,44 @@ impl DataType {
         }
     }
 
+    /// Set several fields in one pass, returning how many were new
+    pub fn hset_multiple(&mut self, pairs: Vec<(String, String)>) -> Result<usize, String> {
+        match self {
+            DataType::Hash(h) => {
+                let mut added = 0;
+                for (field, value) in pairs {
+                    if h.insert(field, value).is_none() {
+                        added += 1;
+                    }
+                }
+                Ok(added)
+            }
+            _ => Err("Operation not supported on this type".to_string()),
+        }
+    }
+
+    /// Byte length of a field's value, 0 if the field is missing
+    pub fn hstrlen(&self, field: &str) -> Result<usize, String> {
+        match self {
+            DataType::Hash(h) => Ok(h.get(field).map_or(0, String::len)),
+            _ => Err("Operation not supported on this type".to_string()),
+        }
+    }
+
+    /// Set `field` only if the hash does not have it yet; true if it was added
+    pub fn hsetnx(&mut self, field: String, value: String) -> Result<bool, String> {
+        match self {
+            DataType::Hash(h) => match h.entry(field) {
+                std::collections::hash_map::Entry::Occupied(_) => Ok(false),
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    entry.insert(value);
+                    Ok(true)
+                }
+            },
+            _ => Err("Operation not supported on this type".to_string()),
+        }
+    }
+
     pub fn hget(&self, field: &str) -> Result<Option<String>, String> {
         match self {
             DataType::Hash(h) => Ok(h.get(field).cloned()),
@@ -302,6 +761,127 @@ impl DataType {
             _ => Err("Operation not supported on this type".to_string()),
         }
     }
+
+    /// Remove fields whose deadline is at or before `now`, along with their
+    /// deadlines and any deadlines left for fields that no longer exist.
+    /// Returns true if the hash or its deadlines changed.
+    pub fn purge_expired_fields(&mut self, deadlines: &mut HashMap<String, u64>, now: u64) -> bool {
+        let DataType::Hash(h) = self else {
+            return false;
+        };
+        let before = (h.len(), deadlines.len());
+        deadlines.retain(|field, deadline| {
+            if *deadline <= now {
+                h.remove(field);
+                false
+            } else {
+                h.contains_key(field)
+            }
+        });
+        before != (h.len(), deadlines.len())
+    }
+}
+
+/// Conditions and reply mode for ZADD
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ZAddFlags {
+    /// Only add new members
+    pub nx: bool,
+    /// Only update existing members
+    pub xx: bool,
+    /// Only update when the new score is greater
+    pub gt: bool,
+    /// Only update when the new score is less
+    pub lt: bool,
+    /// Count changed members as well as added ones
+    pub ch: bool,
+    /// Increment the score of a single member instead of setting it
+    pub incr: bool,
+}
+
+impl ZAddFlags {
+    /// Reject flag combinations that contradict each other
+    pub fn validate(&self, pairs: usize) -> Result<(), String> {
+        if self.nx && self.xx {
+            return Err("ERR XX and NX options at the same time are not compatible".to_string());
+        }
+        if [self.nx, self.gt, self.lt].iter().filter(|set| **set).count() > 1 {
+            return Err("ERR GT, LT, and/or NX options at the same time are not compatible".to_string());
+        }
+        if self.incr && pairs != 1 {
+            return Err("ERR INCR option supports a single increment-element pair".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// One end of a ZRANGEBYLEX or ZLEXCOUNT range
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexBound {
+    /// `-`, before every member
+    Min,
+    /// `+`, after every member
+    Max,
+    /// `[member`
+    Inclusive(String),
+    /// `(member`
+    Exclusive(String),
+}
+
+impl LexBound {
+    /// Parse `-`, `+`, `[member` or `(member`
+    pub fn parse(arg: &str) -> Option<Self> {
+        match arg {
+            "-" => Some(LexBound::Min),
+            "+" => Some(LexBound::Max),
+            _ => {
+                if let Some(member) = arg.strip_prefix('[') {
+                    Some(LexBound::Inclusive(member.to_string()))
+                } else {
+                    arg.strip_prefix('(').map(|member| LexBound::Exclusive(member.to_string()))
+                }
+            }
+        }
+    }
+
+    /// Whether `member` is at or past this bound used as the start of a range
+    fn admits_from(&self, member: &str) -> bool {
+        match self {
+            LexBound::Min => true,
+            LexBound::Max => false,
+            LexBound::Inclusive(bound) => member >= bound.as_str(),
+            LexBound::Exclusive(bound) => member > bound.as_str(),
+        }
+    }
+
+    /// Whether `member` is at or before this bound used as the end of a range
+    fn admits_to(&self, member: &str) -> bool {
+        match self {
+            LexBound::Min => false,
+            LexBound::Max => true,
+            LexBound::Inclusive(bound) => member <= bound.as_str(),
+            LexBound::Exclusive(bound) => member < bound.as_str(),
+        }
+    }
+}
+
+impl std::fmt::Display for LexBound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LexBound::Min => write!(f, "-"),
+            LexBound::Max => write!(f, "+"),
+            LexBound::Inclusive(member) => write!(f, "[{}", member),
+            LexBound::Exclusive(member) => write!(f, "({}", member),
+        }
+    }
+}
+
+/// Result of ZADD: a member count, or the new score in INCR mode
+/// (`None` when a condition prevented the update)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ZAddReply {
+    Count(usize),
+    Score(Option<f64>),
 }
 
 // Sorted Set operations
@@ -320,17 +900,52 @@ impl DataType {
         }
     }
 
-    pub fn zadd(&mut self, members: Vec<(f64, String)>) -> Result<usize, String> {
+    pub fn zadd(&mut self, members: Vec<(f64, String)>, flags: ZAddFlags) -> Result<ZAddReply, String> {
+        flags.validate(members.len())?;
         match self {
             DataType::SortedSet(z) => {
                 let mut added = 0;
+                let mut changed = 0;
+                let mut last_score = None;
                 for (score, member) in members {
-                    if !z.contains_key(&member) {
-                        added += 1;
+                    let current = z.get(&member).copied();
+                    let score = match (flags.incr, current) {
+                        (true, Some(current)) => current + score,
+                        _ => score,
+                    };
+                    if score.is_nan() {
+                        return Err("ERR resulting score is not a number (NaN)".to_string());
+                    }
+                    
+                    let allowed = match current {
+                        None => !flags.xx,
+                        Some(current) => {
+                            !flags.nx
+                                && (!flags.gt || score > current)
+                                && (!flags.lt || score < current)
+                        }
+                    };
+                    if !allowed {
+                        last_score = None;
+                        continue;
+                    }
+                    
+                    match current {
+                        None => added += 1,
+                        Some(current) if current != score => changed += 1,
+                        Some(_) => {}
                     }
                     z.insert(member, score);
+                    last_score = Some(score);
+                }
+                
+                if flags.incr {
+                    Ok(ZAddReply::Score(last_score))
+                } else if flags.ch {
+                    Ok(ZAddReply::Count(added + changed))
+                } else {
+                    Ok(ZAddReply::Count(added))
                 }
-                Ok(added)
             }
             _ => Err("Operation not supported on this type".to_string()),
         }
@@ -351,6 +966,26 @@ impl DataType {
         }
     }
 
+    /// Remove up to `count` members with the lowest (or highest) scores,
+    /// returned in pop order. Ties are broken by member, as ZRANGE orders them.
+    pub fn zpop(&mut self, min: bool, count: usize) -> Result<Vec<(String, f64)>, String> {
+        match self {
+            DataType::SortedSet(z) => {
+                let mut sorted: Vec<(String, f64)> = z.iter().map(|(m, s)| (m.clone(), *s)).collect();
+                sorted.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+                if !min {
+                    sorted.reverse();
+                }
+                sorted.truncate(count);
+                for (member, _) in &sorted {
+                    z.remove(member);
+                }
+                Ok(sorted)
+            }
+            _ => Err("Operation not supported on this type".to_string()),
+        }
+    }
+
     pub fn zscore(&self, member: &str) -> Result<Option<f64>, String> {
         match self {
             DataType::SortedSet(z) => Ok(z.get(member).copied()),
@@ -383,6 +1018,42 @@ impl DataType {
             _ => Err("Operation not supported on this type".to_string()),
         }
     }
+
+    /// Members between `min` and `max` in byte order, skipping `offset` of them and
+    /// returning at most `count` (all when negative).
+    ///
+    /// Like Redis, this assumes every member has the same score, so that score
+    /// order is member order; with mixed scores the members are still filtered
+    /// by name alone and the result is not a slice of ZRANGE.
+    pub fn zrangebylex(&self, min: &LexBound, max: &LexBound, limit: Option<(i64, i64)>) -> Result<Vec<String>, String> {
+        match self {
+            DataType::SortedSet(z) => {
+                let (offset, count) = limit.unwrap_or((0, -1));
+                if offset < 0 {
+                    return Ok(vec![]);
+                }
+                let count = if count < 0 { usize::MAX } else { count as usize };
+                Ok(z.keys()
+                    .filter(|member| min.admits_from(member) && max.admits_to(member))
+                    .skip(offset as usize)
+                    .take(count)
+                    .cloned()
+                    .collect())
+            }
+            _ => Err("Operation not supported on this type".to_string()),
+        }
+    }
+
+    /// Number of members between `min` and `max`, under the same equal-score
+    /// assumption as `zrangebylex`
+    pub fn zlexcount(&self, min: &LexBound, max: &LexBound) -> Result<usize, String> {
+        match self {
+            DataType::SortedSet(z) => {
+                Ok(z.keys().filter(|member| min.admits_from(member) && max.admits_to(member)).count())
+            }
+            _ => Err("Operation not supported on this type".to_string()),
+        }
+    }
 }
 
 // JSON operations
@@ -447,20 +1118,15 @@ impl DataType {
         }
     }
 
-    pub fn xadd(&mut self, id: Option<String>, fields: HashMap<String, String>) -> Result<String, String> {
+    /// Append an entry, generating its ID from `now_ms` when none is given
+    pub fn xadd(&mut self, id: Option<String>, fields: HashMap<String, String>, now_ms: u64) -> Result<String, String> {
         match self {
             DataType::Stream(s) => {
-                let id = id.unwrap_or_else(|| {
-                    let timestamp = SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis();
-                    format!("{}-0", timestamp)
-                });
+                let id = id.unwrap_or_else(|| format!("{}-0", now_ms));
                 
                 let entry = StreamEntry {
                     id: id.clone(),
-                    timestamp: SystemTime::now(),
+                    timestamp: SystemTime::UNIX_EPOCH + std::time::Duration::from_millis(now_ms),
                     fields,
                 };
                 
@@ -495,4 +1161,97 @@ impl DataType {
             _ => Err("Operation not supported on this type".to_string()),
         }
     }
-}
\ No newline at end of file
+}
+
+// Cursor iteration
+impl DataType {
+    /// One page of a hash, set or sorted set in a stable order (sorted by field or
+    /// member), starting at offset `cursor`. Hash entries carry their value and
+    /// sorted-set entries their score. Returns the next cursor, or 0 after the last page.
+    pub fn scan_page(&self, cursor: usize, count: usize) -> Result<(usize, Vec<(String, Option<String>)>), String> {
+        match self {
+            DataType::Hash(h) => {
+                let mut fields: Vec<(&String, &String)> = h.iter().collect();
+                fields.sort_unstable();
+                Ok(scan_slice(&fields, cursor, count, |(field, value)| ((*field).clone(), Some((*value).clone()))))
+            }
+            DataType::Set(s) => {
+                let mut members: Vec<&String> = s.iter().collect();
+                members.sort_unstable();
+                Ok(scan_slice(&members, cursor, count, |member| ((*member).clone(), None)))
+            }
+            DataType::SortedSet(z) => {
+                let members: Vec<(&String, &f64)> = z.iter().collect();
+                Ok(scan_slice(&members, cursor, count, |(member, score)| ((*member).clone(), Some(score.to_string()))))
+            }
+            _ => Err("Operation not supported on this type".to_string()),
+        }
+    }
+}
+
+fn scan_slice<T, F>(items: &[T], cursor: usize, count: usize, entry: F) -> (usize, Vec<(String, Option<String>)>)
+where
+    F: Fn(&T) -> (String, Option<String>),
+{
+    let start = cursor.min(items.len());
+    let end = start.saturating_add(count.max(1)).min(items.len());
+    let next = if end >= items.len() { 0 } else { end };
+    (next, items[start..end].iter().map(entry).collect())
+}
+
+// Consistency checks
+impl DataType {
+    /// Walk the value's internal structure and report the first broken invariant.
+    ///
+    /// Stream IDs must be well-formed and strictly increasing, sorted-set scores
+    /// must be numbers, and a listpack must hold as many entries as it records.
+    /// Hash fields and set members are unique by construction, so there is
+    /// nothing further to check for them.
+    pub fn verify_invariants(&self) -> Result<(), String> {
+        match self {
+            DataType::Stream(entries) => {
+                let mut previous: Option<((u64, u64), &str)> = None;
+                for (index, entry) in entries.iter().enumerate() {
+                    let id = parse_entry_id(&entry.id)
+                        .ok_or_else(|| format!("stream entry {} has malformed ID '{}'", index, entry.id))?;
+                    if let Some((last, last_id)) = previous {
+                        if id <= last {
+                            return Err(format!(
+                                "stream entry {} has ID {}, not greater than the previous ID {}",
+                                index, entry.id, last_id
+                            ));
+                        }
+                    }
+                    previous = Some((id, &entry.id));
+                }
+                Ok(())
+            }
+            DataType::SortedSet(z) => match z.iter().find(|(_, score)| score.is_nan()) {
+                Some((member, _)) => Err(format!("sorted set member '{}' has a NaN score", member)),
+                None => Ok(()),
+            },
+            DataType::ListPack(pack) => {
+                let mut rest = pack.buf.as_str();
+                let mut count = 0;
+                while !rest.is_empty() {
+                    rest = next_entry(rest)
+                        .ok_or_else(|| format!("listpack entry {} is malformed", count))?
+                        .1;
+                    count += 1;
+                }
+                if count == pack.len {
+                    Ok(())
+                } else {
+                    Err(format!("listpack holds {} entries but records {}", count, pack.len))
+                }
+            }
+            DataType::String(_) | DataType::List(_) | DataType::Set(_) | DataType::Hash(_) | DataType::Json(_) => Ok(()),
+        }
+    }
+}
+
+/// Split a stream ID into its milliseconds and sequence number
+fn parse_entry_id(id: &str) -> Option<(u64, u64)> {
+    let (ms, seq) = id.split_once('-')?;
+    Some((ms.parse().ok()?, seq.parse().ok()?))
+}
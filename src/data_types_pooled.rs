@@ -1,6 +1,6 @@
 use crate::data_types::{DataType, StreamEntry};
 use crate::error::Result;
-use std::collections::{HashMap, HashSet, BTreeMap};
+use std::collections::{HashMap, HashSet, BTreeMap, VecDeque};
 
 #[cfg(feature = "memory_pool")]
 use crate::ffi::memory::{PooledString, PooledVec, PooledBox, init_memory_pool};
@@ -54,6 +54,7 @@ impl PooledDataType {
                 }
                 Ok(PooledDataType::List(pooled_list))
             }
+            DataType::ListPack(pack) => Self::from_data_type(DataType::List(pack.unpack().into())),
             DataType::Set(set) => {
                 let mut pooled_set = HashSet::new();
                 for item in set {
@@ -111,7 +112,7 @@ impl PooledDataType {
                 for item in list.as_slice() {
                     regular_list.push(item.to_string());
                 }
-                DataType::List(regular_list)
+                DataType::List(regular_list.into())
             }
             PooledDataType::Set(set) => {
                 let mut regular_set = HashSet::new();
@@ -176,11 +177,11 @@ impl PooledStorageOps {
     #[cfg(feature = "memory_pool")]
     pub fn create_list(capacity: usize) -> Result<DataType> {
         let _ = PooledVec::<PooledString>::with_capacity(capacity)?;
-        Ok(DataType::List(Vec::with_capacity(capacity)))
+        Ok(DataType::List(VecDeque::with_capacity(capacity)))
     }
     
     #[cfg(not(feature = "memory_pool"))]
     pub fn create_list(capacity: usize) -> Result<DataType> {
-        Ok(DataType::List(Vec::with_capacity(capacity)))
+        Ok(DataType::List(VecDeque::with_capacity(capacity)))
     }
 }
\ No newline at end of file
@@ -7,6 +7,11 @@ use tokio::time::sleep;
 use log::error;
 use std::fs;
 use std::path::Path;
+use crate::commands::key_lock::KeyLocks;
+use crate::data_types::{DataType, ZAddFlags, ZAddReply};
+use crate::error::{Context, DiskDBError, Result};
+use crate::storage::rocksdb_storage::RocksDBStorage;
+use crate::storage::Storage;
 
 // Type alias for the cache to reduce complexity
 type CacheMap = HashMap<String, (String, Option<Instant>)>;
@@ -24,7 +29,9 @@ pub struct DiskDB {
 
 impl DiskDB {
     /// Creates a new instance of the database, initializing RocksDB with optimizations.
-    pub fn new(path: &str) -> Self {
+    /// Fails if the path cannot be cleared or RocksDB cannot open it, e.g. when
+    /// another process holds its lock.
+    pub fn new(path: &str) -> Result<Self> {
         let mut opts = Options::default();
         opts.create_if_missing(true);
         opts.set_wal_ttl_seconds(60); // Enable WAL (Write-Ahead Logging) with a 60-second TTL.
@@ -39,18 +46,18 @@ impl DiskDB {
         let path = Path::new(path);
         if path.exists() {
             if path.is_dir() {
-                fs::remove_dir_all(path).expect("Failed to remove existing RocksDB directory");
+                fs::remove_dir_all(path)?;
             } else {
-                fs::remove_file(path).expect("Failed to remove existing RocksDB file");
+                fs::remove_file(path)?;
             }
         }
         
-        let db = DB::open(&opts, path).expect("Failed to open DB");
-        Self {
+        let db = DB::open(&opts, path).context(|| format!("opening database at {}", path.display()))?;
+        Ok(Self {
             db: Arc::new(RwLock::new(db)),
             cache: Arc::new(RwLock::new(HashMap::new())),
             memtable: Arc::new(RwLock::new(VecDeque::new())),
-        }
+        })
     }
 
     /// Periodically flushes the memtable to disk in batch mode.
@@ -108,4 +115,215 @@ impl DiskDB {
             }
         }
     }
-}
\ No newline at end of file
+}
+/// Typed, in-process access to a [`Storage`] for embedding DiskDB in another
+/// program, without building `Request`s or formatting `Response`s.
+///
+/// Each method reads and writes values the way the matching command does,
+/// using the same [`DataType`] operations, so data written here reads back
+/// identically over the network and the other way round. Writes to one key are
+/// serialized like the executor's, but only among calls on this handle and its
+/// clones. Type mismatches fail with a `WRONGTYPE` protocol error.
+#[derive(Clone)]
+pub struct EmbeddedDB {
+    storage: Arc<dyn Storage>,
+    key_locks: KeyLocks,
+}
+
+impl EmbeddedDB {
+    /// Open, or create, a database at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::from_storage(Arc::new(RocksDBStorage::new(path)?)))
+    }
+    
+    /// Wrap storage that is already open
+    pub fn from_storage(storage: Arc<dyn Storage>) -> Self {
+        Self { storage, key_locks: KeyLocks::default() }
+    }
+    
+    /// The underlying storage, e.g. to hand to a `CommandExecutor`
+    pub fn storage(&self) -> &Arc<dyn Storage> {
+        &self.storage
+    }
+    
+    /// GET
+    pub async fn get_string(&self, key: &str) -> Result<Option<String>> {
+        self.storage.get_string(key).await
+    }
+    
+    /// SET, which also clears any expiry on the key
+    pub async fn set_string(&self, key: &str, value: &str) -> Result<()> {
+        let _lock = self.key_locks.lock(&[key]).await;
+        self.storage.set(key, DataType::String(value.to_string())).await?;
+        self.storage.persist(key).await?;
+        Ok(())
+    }
+    
+    /// DEL: the number of keys that existed
+    pub async fn delete(&self, keys: &[String]) -> Result<usize> {
+        let _lock = self.key_locks.lock(&keys.iter().map(String::as_str).collect::<Vec<_>>()).await;
+        self.storage.delete_multiple(keys).await
+    }
+    
+    /// EXISTS for a single key
+    pub async fn exists(&self, key: &str) -> Result<bool> {
+        self.storage.exists(key).await
+    }
+    
+    /// LPUSH: the length of the list afterwards
+    pub async fn lpush(&self, key: &str, values: Vec<String>) -> Result<usize> {
+        let _lock = self.key_locks.lock(&[key]).await;
+        let mut data = self.storage.get_or_create_list(key).await?;
+        let len = data.lpush(values).map_err(DiskDBError::Database)?;
+        self.storage.set(key, data).await?;
+        Ok(len)
+    }
+    
+    /// RPUSH: the length of the list afterwards
+    pub async fn rpush(&self, key: &str, values: Vec<String>) -> Result<usize> {
+        let _lock = self.key_locks.lock(&[key]).await;
+        let mut data = self.storage.get_or_create_list(key).await?;
+        let len = data.rpush(values).map_err(DiskDBError::Database)?;
+        self.storage.set(key, data).await?;
+        Ok(len)
+    }
+    
+    /// LPOP, deleting the list once it is empty
+    pub async fn lpop(&self, key: &str) -> Result<Option<String>> {
+        self.pop(key, true).await
+    }
+    
+    /// RPOP, deleting the list once it is empty
+    pub async fn rpop(&self, key: &str) -> Result<Option<String>> {
+        self.pop(key, false).await
+    }
+    
+    async fn pop(&self, key: &str, from_left: bool) -> Result<Option<String>> {
+        let _lock = self.key_locks.lock(&[key]).await;
+        let Some(mut data) = self.storage.get(key).await? else {
+            return Ok(None);
+        };
+        let value = if from_left { data.lpop() } else { data.rpop() }.map_err(wrong_type)?;
+        if value.is_some() {
+            let emptied = match &data {
+                DataType::List(l) => l.is_empty(),
+                DataType::ListPack(p) => p.is_empty(),
+                _ => false,
+            };
+            if emptied {
+                self.storage.delete(key).await?;
+            } else {
+                self.storage.set(key, data).await?;
+            }
+        }
+        Ok(value)
+    }
+    
+    /// LRANGE, with negative indexes counting from the end
+    pub async fn lrange(&self, key: &str, start: i64, stop: i64) -> Result<Vec<String>> {
+        match self.storage.get(key).await? {
+            Some(data) => data.lrange(start, stop).map_err(wrong_type),
+            None => Ok(Vec::new()),
+        }
+    }
+    
+    /// The whole list, empty if the key does not exist
+    pub async fn get_list(&self, key: &str) -> Result<Vec<String>> {
+        self.lrange(key, 0, -1).await
+    }
+    
+    /// SADD: the number of members that were not already present
+    pub async fn sadd(&self, key: &str, members: Vec<String>) -> Result<usize> {
+        let _lock = self.key_locks.lock(&[key]).await;
+        let mut data = self.storage.get_or_create_set(key).await?;
+        let added = data.sadd(members).map_err(DiskDBError::Database)?;
+        self.storage.set(key, data).await?;
+        Ok(added)
+    }
+    
+    /// SMEMBERS, in no particular order
+    pub async fn smembers(&self, key: &str) -> Result<Vec<String>> {
+        match self.storage.get(key).await? {
+            Some(DataType::Set(set)) => Ok(set.into_iter().collect()),
+            Some(_) => Err(wrong_type(String::new())),
+            None => Ok(Vec::new()),
+        }
+    }
+    
+    /// SISMEMBER
+    pub async fn sismember(&self, key: &str, member: &str) -> Result<bool> {
+        match self.storage.get(key).await? {
+            Some(data) => data.sismember(member).map_err(wrong_type),
+            None => Ok(false),
+        }
+    }
+    
+    /// HSET of one field: true if the field is new. Overwriting a field clears its TTL.
+    pub async fn hset(&self, key: &str, field: &str, value: &str) -> Result<bool> {
+        let _lock = self.key_locks.lock(&[key]).await;
+        let mut data = self.storage.get_or_create_hash(key).await?;
+        let mut deadlines = self.storage.field_deadlines(key).await?;
+        let is_new = data.hset(field.to_string(), value.to_string()).map_err(DiskDBError::Database)?;
+        self.storage.set(key, data).await?;
+        if deadlines.remove(field).is_some() {
+            self.storage.set_field_deadlines(key, &deadlines).await?;
+        }
+        Ok(is_new)
+    }
+    
+    /// HGET
+    pub async fn hget(&self, key: &str, field: &str) -> Result<Option<String>> {
+        match self.hash(key).await? {
+            Some(data) => data.hget(field).map_err(wrong_type),
+            None => Ok(None),
+        }
+    }
+    
+    /// HGETALL as a map
+    pub async fn hgetall(&self, key: &str) -> Result<HashMap<String, String>> {
+        match self.hash(key).await? {
+            Some(DataType::Hash(hash)) => Ok(hash),
+            Some(_) => Err(wrong_type(String::new())),
+            None => Ok(HashMap::new()),
+        }
+    }
+    
+    /// The value at `key` with any hash fields past their TTL left out. Unlike
+    /// the executor, expired fields are not removed from storage here.
+    async fn hash(&self, key: &str) -> Result<Option<DataType>> {
+        let Some(mut data) = self.storage.get(key).await? else {
+            return Ok(None);
+        };
+        let mut deadlines = self.storage.field_deadlines(key).await?;
+        if !deadlines.is_empty() {
+            data.purge_expired_fields(&mut deadlines, self.storage.now_millis());
+        }
+        Ok(Some(data))
+    }
+    
+    /// ZADD without flags: the number of members added
+    pub async fn zadd(&self, key: &str, members: Vec<(f64, String)>) -> Result<usize> {
+        let _lock = self.key_locks.lock(&[key]).await;
+        let mut data = self.storage.get_or_create_sorted_set(key).await?;
+        let added = match data.zadd(members, ZAddFlags::default()).map_err(DiskDBError::Protocol)? {
+            ZAddReply::Count(count) => count,
+            ZAddReply::Score(_) => 0,
+        };
+        self.storage.set(key, data).await?;
+        Ok(added)
+    }
+    
+    /// ZSCORE
+    pub async fn zscore(&self, key: &str, member: &str) -> Result<Option<f64>> {
+        match self.storage.get(key).await? {
+            Some(data) => data.zscore(member).map_err(wrong_type),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The error for an operation on a key of the wrong type; `DataType` reports
+/// these with a generic message
+fn wrong_type(_: String) -> DiskDBError {
+    DiskDBError::Protocol("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+}
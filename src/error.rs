@@ -48,5 +48,17 @@ impl From<rocksdb::Error> for DiskDBError {
     }
 }
 
+/// Attach what was being done to a RocksDB error, so it reads as
+/// "reading key 'foo': <rocksdb message>" instead of the bare message
+pub trait Context<T> {
+    fn context<F: FnOnce() -> String>(self, what: F) -> Result<T>;
+}
+
+impl<T> Context<T> for std::result::Result<T, rocksdb::Error> {
+    fn context<F: FnOnce() -> String>(self, what: F) -> Result<T> {
+        self.map_err(|err| DiskDBError::Database(format!("{}: {}", what(), err)))
+    }
+}
+
 
 pub type Result<T> = std::result::Result<T, DiskDBError>;
\ No newline at end of file
@@ -0,0 +1,98 @@
+use crate::commands::latency::{LatencyMonitor, EXPIRE_CYCLE_EVENT};
+use crate::config::Config;
+use crate::error::Result;
+use crate::storage::Storage;
+use log::{debug, error};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Keep sampling within a cycle while more than this fraction of the sample was expired
+const ACCEPTABLE_STALE_FRACTION: f64 = 0.25;
+
+/// Background task that reclaims expired keys nobody reads.
+///
+/// Each cycle walks a slice of the expiry metadata from a rolling cursor and
+/// deletes keys past their deadline. Like Redis, a cycle keeps going while the
+/// sample is mostly stale, bounded to a quarter of the interval so the command
+/// path is never starved.
+pub struct ActiveExpirer {
+    storage: Arc<dyn Storage>,
+    interval: Duration,
+    sample_size: usize,
+    cursor: Option<String>,
+    latency: LatencyMonitor,
+}
+
+impl ActiveExpirer {
+    pub fn new(storage: Arc<dyn Storage>, config: &Config) -> Self {
+        Self {
+            storage,
+            interval: Duration::from_millis(config.active_expire_interval_ms.max(1)),
+            sample_size: config.active_expire_sample_size.max(1),
+            cursor: None,
+            latency: LatencyMonitor::default(),
+        }
+    }
+
+    /// Record cycles that run past the monitor's threshold as `expire-cycle` spikes
+    pub fn with_latency_monitor(mut self, latency: LatencyMonitor) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Run expiration cycles forever
+    pub async fn run(mut self) {
+        loop {
+            tokio::time::sleep(self.interval).await;
+            match self.run_cycle().await {
+                Ok(0) => {}
+                Ok(reclaimed) => debug!("Active expiry reclaimed {} keys", reclaimed),
+                Err(e) => error!("Active expiry cycle failed: {}", e),
+            }
+        }
+    }
+
+    /// Run one adaptive cycle, returning the number of keys reclaimed
+    pub async fn run_cycle(&mut self) -> Result<usize> {
+        let budget = self.interval / 4;
+        let started = Instant::now();
+        let mut reclaimed = 0;
+
+        loop {
+            let sample = self.storage
+                .scan_expiring(self.cursor.as_deref(), self.sample_size)
+                .await?;
+
+            // Wrap around once the end of the keyspace is reached
+            self.cursor = if sample.len() < self.sample_size {
+                None
+            } else {
+                sample.last().map(|(key, _)| key.clone())
+            };
+
+            let now = self.storage.now_millis();
+            let mut expired = 0;
+            for (key, deadline) in &sample {
+                if *deadline <= now {
+                    // The lazy check in the storage layer removes the key and its metadata
+                    self.storage.exists(key).await?;
+                    expired += 1;
+                }
+            }
+            reclaimed += expired;
+
+            let stale_fraction = if sample.is_empty() {
+                0.0
+            } else {
+                expired as f64 / sample.len() as f64
+            };
+            if stale_fraction <= ACCEPTABLE_STALE_FRACTION || started.elapsed() >= budget {
+                break;
+            }
+            tokio::task::yield_now().await;
+        }
+
+        self.latency.record(EXPIRE_CYCLE_EVENT, self.storage.now_millis(), started.elapsed());
+        Ok(reclaimed)
+    }
+}
@@ -22,6 +22,7 @@ pub enum CommandType {
     Incr,
     Decr,
     IncrBy,
+    DecrBy,
     Append,
     // List operations
     LPush,
@@ -143,6 +144,13 @@ impl SafeParser {
             
             let parsed_ref = &*parsed;
             
+            // Commands the C parser doesn't know yet go through the Rust parser, as
+            // does FLUSHDB with its ASYNC|SYNC modifier
+            if parsed_ref.cmd_type == CommandType::Unknown
+                || (parsed_ref.cmd_type == CommandType::FlushDb && parsed_ref.arg_count > 0) {
+                return Request::parse_rust(input);
+            }
+            
             // Check for parse errors
             if !parsed_ref.error.is_null() {
                 let error_str = CStr::from_ptr(parsed_ref.error).to_string_lossy();
@@ -187,9 +195,12 @@ impl SafeParser {
             CommandType::Get => Request::Get { 
                 key: get_arg(0) 
             },
-            CommandType::Set => Request::Set { 
-                key: get_arg(0), 
-                value: get_arg(1) 
+            CommandType::Set => {
+                // Options, and values split on spaces, are interpreted by the Rust side
+                let args: Vec<String> = (1..parsed.arg_count as usize)
+                    .map(|i| get_arg(i))
+                    .collect();
+                Request::parse_set(&get_arg(0), &args)?
             },
             CommandType::Incr => Request::Incr { 
                 key: get_arg(0) 
@@ -201,9 +212,17 @@ impl SafeParser {
                 key: get_arg(0), 
                 delta: unsafe { parsed.numeric.integer_arg } 
             },
+            CommandType::DecrBy => Request::DecrBy { 
+                key: get_arg(0), 
+                delta: unsafe { parsed.numeric.integer_arg } 
+            },
             CommandType::Append => Request::Append { 
                 key: get_arg(0), 
-                value: get_arg(1) 
+                // Match the text parser, which keeps everything after the key
+                value: (1..parsed.arg_count as usize)
+                    .map(|i| get_arg(i))
+                    .collect::<Vec<_>>()
+                    .join(" ")
             },
             CommandType::LPush => {
                 let key = get_arg(0);
@@ -220,10 +239,12 @@ impl SafeParser {
                 Request::RPush { key, values }
             },
             CommandType::LPop => Request::LPop { 
-                key: get_arg(0) 
+                key: get_arg(0),
+                count: (parsed.arg_count > 1).then(|| Request::parse_pop_count(&get_arg(1))).transpose()?,
             },
             CommandType::RPop => Request::RPop { 
-                key: get_arg(0) 
+                key: get_arg(0),
+                count: (parsed.arg_count > 1).then(|| Request::parse_pop_count(&get_arg(1))).transpose()?,
             },
             CommandType::LRange => Request::LRange { 
                 key: get_arg(0),
@@ -281,20 +302,11 @@ impl SafeParser {
                 field: get_arg(1),
             },
             CommandType::ZAdd => {
-                let key = get_arg(0);
-                let mut members = Vec::new();
-                
-                // Parse score-member pairs
-                let mut i = 1;
-                while i + 1 < parsed.arg_count as usize {
-                    let score = get_arg(i).parse::<f64>()
-                        .map_err(|_| DiskDBError::Protocol("Invalid score in ZADD".into()))?;
-                    let member = get_arg(i + 1);
-                    members.push((member, score));
-                    i += 2;
-                }
-                
-                Request::ZAdd { key, members: members.into_iter().map(|(m, s)| (s, m)).collect() }
+                // Flags and score/member pairs are interpreted by the Rust side
+                let args: Vec<String> = (1..parsed.arg_count as usize)
+                    .map(|i| get_arg(i))
+                    .collect();
+                Request::parse_zadd(&get_arg(0), &args)?
             },
             CommandType::ZRem => {
                 let key = get_arg(0);
@@ -378,7 +390,7 @@ impl SafeParser {
             CommandType::Echo => Request::Echo { 
                 message: get_arg(0) 
             },
-            CommandType::FlushDb => Request::FlushDb,
+            CommandType::FlushDb => Request::FlushDb { lazy: false },
             CommandType::Info => Request::Info,
             CommandType::Unknown => {
                 return Err(DiskDBError::Protocol("Unknown command".into()));
@@ -18,7 +18,7 @@ struct DiskDB {
 
 impl DiskDB {
     /// Creates a new instance of the database, initializing RocksDB with optimizations.
-    fn new(path: &str) -> Self {
+    fn new(path: &str) -> Result<Self, rocksdb::Error> {
         let mut opts = Options::default();
         opts.create_if_missing(true);
         opts.set_wal_ttl_seconds(60); // Enable WAL (Write-Ahead Logging) with a 60-second TTL.
@@ -28,12 +28,12 @@ impl DiskDB {
         opts.set_max_write_buffer_number(4); // Allow up to 4 write buffers before flushing.
         opts.set_target_file_size_base(256 * 1024 * 1024); // Set target SST file size to 256MB.
         opts.set_level_compaction_dynamic_level_bytes(true); // Enable dynamic compaction.
-        let db = DB::open(&opts, path).expect("Failed to open DB");
-        Self {
+        let db = DB::open(&opts, path)?;
+        Ok(Self {
             db: Arc::new(RwLock::new(db)),
             cache: Arc::new(RwLock::new(HashMap::new())),
             memtable: Arc::new(RwLock::new(VecDeque::new())),
-        }
+        })
     }
 
     /// Periodically flushes the memtable to disk in batch mode.
@@ -99,7 +99,13 @@ async fn main() {
     println!("Database is starting...");
 
     env_logger::init();
-    let db = Arc::new(DiskDB::new("diskdb"));
+    let db = match DiskDB::new("diskdb") {
+        Ok(db) => Arc::new(db),
+        Err(e) => {
+            error!("Failed to open database at diskdb: {}", e);
+            std::process::exit(1);
+        }
+    };
     let db_clone = db.clone();
     tokio::spawn(async move {
         db_clone.flush_memtable().await;
@@ -1,3 +1,11 @@
+//! DiskDB: a Redis-compatible key-value server persisted in RocksDB.
+//!
+//! To embed it in another program, use [`EmbeddedDB`]. Its typed methods
+//! (`get_string`, `lpush`, `hgetall`, ...) go straight to [`Storage`] and
+//! return plain Rust values, skipping the `Request`/`Response` protocol layer
+//! that [`CommandExecutor`] and the servers are built on.
+
+pub mod clock;
 pub mod commands;
 pub mod config;
 pub mod connection;
@@ -5,7 +13,12 @@ pub mod data_types;
 pub mod data_types_pooled;
 pub mod db;
 pub mod error;
+pub mod expiration;
+pub mod glob;
+pub mod listener;
 pub mod protocol;
+pub mod rate_limit;
+pub mod resp;
 pub mod server;
 pub mod storage;
 pub mod tls;
@@ -16,10 +29,12 @@ pub mod client;
 #[cfg(feature = "c_parser")]
 pub mod ffi;
 
-pub use config::Config;
-pub use db::DiskDB;
+pub use commands::CommandExecutor;
+pub use config::{Compression, Config, MaxMemoryPolicy, PubSubOverflow, StorageProfile};
+pub use db::{DiskDB, EmbeddedDB};
 pub use error::{DiskDBError, Result};
 pub use server::Server;
 pub use optimized_server::OptimizedServer;
+pub use protocol::{Request, Response};
 pub use storage::Storage;
 pub use client::OptimizedClient;
\ No newline at end of file
@@ -0,0 +1,83 @@
+//! TCP options of the listening socket and of accepted connections, shared by
+//! `Server` and `OptimizedServer`.
+
+use crate::config::Config;
+use crate::error::{DiskDBError, Result};
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+
+/// Socket settings taken from `Config`
+#[derive(Debug, Clone, Copy)]
+pub struct SocketOptions {
+    /// Pending connections the kernel queues before `accept`
+    pub backlog: u32,
+    /// SO_RCVBUF in bytes; 0 keeps the system default
+    pub recv_buffer: usize,
+    /// SO_SNDBUF in bytes; 0 keeps the system default
+    pub send_buffer: usize,
+    /// Idle time before keepalive probes are sent; None disables keepalive
+    pub keepalive: Option<Duration>,
+    pub nodelay: bool,
+}
+
+impl SocketOptions {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            backlog: config.tcp_backlog,
+            recv_buffer: config.so_rcvbuf,
+            send_buffer: config.so_sndbuf,
+            keepalive: match config.tcp_keepalive_secs {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+            nodelay: config.tcp_nodelay,
+        }
+    }
+}
+
+/// Bind a listener on `addr` with the configured backlog and buffer sizes, which
+/// accepted connections start out with. `reuse_port` lets several listeners
+/// share the port.
+pub fn bind(addr: &str, options: &SocketOptions, reuse_port: bool) -> Result<TcpListener> {
+    let addr: SocketAddr = addr.parse()
+        .map_err(|e| DiskDBError::Config(format!("Invalid address: {}", e)))?;
+
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    socket.set_reuse_port(reuse_port)?;
+    #[cfg(not(unix))]
+    let _ = reuse_port;
+
+    // The kernel may clamp or double buffer sizes; a refused size keeps the default
+    if options.recv_buffer > 0 {
+        let _ = socket.set_recv_buffer_size(options.recv_buffer);
+    }
+    if options.send_buffer > 0 {
+        let _ = socket.set_send_buffer_size(options.send_buffer);
+    }
+
+    socket.bind(&addr.into())?;
+    socket.listen(options.backlog.min(i32::MAX as u32) as i32)?;
+    socket.set_nonblocking(true)?;
+    Ok(TcpListener::from_std(socket.into())?)
+}
+
+/// Apply the per-connection options to an accepted stream
+pub fn configure(stream: &TcpStream, options: &SocketOptions) -> Result<()> {
+    let socket = SockRef::from(stream);
+    socket.set_nodelay(options.nodelay)?;
+    if options.recv_buffer > 0 {
+        let _ = socket.set_recv_buffer_size(options.recv_buffer);
+    }
+    if options.send_buffer > 0 {
+        let _ = socket.set_send_buffer_size(options.send_buffer);
+    }
+    match options.keepalive {
+        Some(idle) => socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?,
+        None => socket.set_keepalive(false)?,
+    }
+    Ok(())
+}
@@ -1,10 +1,15 @@
+mod clock;
 mod commands;
 mod config;
 mod connection;
 mod data_types;
 mod db;
 mod error;
+mod expiration;
+mod glob;
+mod listener;
 mod protocol;
+mod rate_limit;
 mod server;
 mod storage;
 mod tls;
@@ -17,12 +22,20 @@ use std::sync::Arc;
 use storage::rocksdb_storage::RocksDBStorage;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
     env_logger::init();
     info!("Starting DiskDB...");
 
-    let config = Config::from_env();
-    let storage = Arc::new(RocksDBStorage::new(&config.database_path)?);
+    // Report startup failures such as a database locked by another process as
+    // one line and a non-zero exit code rather than a panic or a Debug dump
+    if let Err(e) = run(Config::from_env()).await {
+        eprintln!("diskdb: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(config: Config) -> Result<()> {
+    let storage = Arc::new(RocksDBStorage::with_config(&config.database_path, &config)?);
     let server = Server::new(config, storage)?;
     
     server.start().await
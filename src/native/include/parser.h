@@ -27,6 +27,7 @@ typedef enum {
     CMD_INCR,
     CMD_DECR,
     CMD_INCRBY,
+    CMD_DECRBY,
     CMD_APPEND,
     // List operations
     CMD_LPUSH,
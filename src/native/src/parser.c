@@ -16,16 +16,17 @@ typedef struct {
 static const CommandInfo commands[] = {
     // String operations
     {"GET", CMD_GET, 1, 1},
-    {"SET", CMD_SET, 2, 2},
+    {"SET", CMD_SET, 2, MAX_ARGS},
     {"INCR", CMD_INCR, 1, 1},
     {"DECR", CMD_DECR, 1, 1},
     {"INCRBY", CMD_INCRBY, 2, 2},
-    {"APPEND", CMD_APPEND, 2, 2},
+    {"DECRBY", CMD_DECRBY, 2, 2},
+    {"APPEND", CMD_APPEND, 2, MAX_ARGS},
     // List operations
     {"LPUSH", CMD_LPUSH, 2, MAX_ARGS},
     {"RPUSH", CMD_RPUSH, 2, MAX_ARGS},
-    {"LPOP", CMD_LPOP, 1, 1},
-    {"RPOP", CMD_RPOP, 1, 1},
+    {"LPOP", CMD_LPOP, 1, 2},
+    {"RPOP", CMD_RPOP, 1, 2},
     {"LRANGE", CMD_LRANGE, 3, 3},
     {"LLEN", CMD_LLEN, 1, 1},
     // Set operations
@@ -210,6 +211,7 @@ ParsedRequest* parse_request(const char* input, size_t len, Arena* arena) {
     // Pre-parse numeric arguments for specific commands
     switch (req->type) {
         case CMD_INCRBY:
+        case CMD_DECRBY:
             if (req->arg_count >= 2) {
                 char* endptr;
                 req->numeric.integer_arg = strtoll(req->args[1].data, &endptr, 10);
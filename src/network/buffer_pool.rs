@@ -1,5 +1,6 @@
 use bytes::{Bytes, BytesMut};
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 use tokio::sync::Semaphore;
 
@@ -42,6 +43,10 @@ pub struct BufferPool {
     small_sem: Arc<Semaphore>,
     medium_sem: Arc<Semaphore>,
     large_sem: Arc<Semaphore>,
+    
+    // Reuse counters
+    hits: AtomicU64,
+    misses: AtomicU64,
 }
 
 impl BufferPool {
@@ -62,6 +67,8 @@ impl BufferPool {
             small_sem: Arc::new(Semaphore::new(max_small)),
             medium_sem: Arc::new(Semaphore::new(max_medium)),
             large_sem: Arc::new(Semaphore::new(max_large)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
         }
     }
     
@@ -78,6 +85,7 @@ impl BufferPool {
         // Try to get from pool first
         if let Ok(mut guard) = pool.try_lock() {
             if let Some(mut buffer) = guard.pop_front() {
+                self.hits.fetch_add(1, Ordering::Relaxed);
                 buffer.clear();
                 return PooledBuffer {
                     buffer,
@@ -88,6 +96,7 @@ impl BufferPool {
         }
         
         // Allocate new buffer if pool is empty
+        self.misses.fetch_add(1, Ordering::Relaxed);
         let buffer = BytesMut::with_capacity(size.as_usize());
         PooledBuffer {
             buffer,
@@ -117,6 +126,22 @@ impl BufferPool {
         }
     }
     
+    /// Number of requests served from a pooled buffer
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+    
+    /// Number of requests that had to allocate a new buffer
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+    
+    /// Number of idle buffers currently held across all size classes
+    pub fn pooled_buffers(&self) -> usize {
+        let stats = self.stats();
+        stats.small_buffers + stats.medium_buffers + stats.large_buffers
+    }
+    
     /// Get current pool statistics
     pub fn stats(&self) -> BufferPoolStats {
         let small_count = self.small_pool.lock().ok().map(|p| p.len()).unwrap_or(0);
@@ -130,6 +155,8 @@ impl BufferPool {
             small_capacity: self.max_small,
             medium_capacity: self.max_medium,
             large_capacity: self.max_large,
+            hits: self.hits(),
+            misses: self.misses(),
         }
     }
 }
@@ -200,6 +227,8 @@ pub struct BufferPoolStats {
     pub small_capacity: usize,
     pub medium_capacity: usize,
     pub large_capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
 }
 
 // Global buffer pool
@@ -0,0 +1,240 @@
+use crate::network::pubsub::MessageQueue;
+use crate::protocol::Response;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::oneshot;
+
+/// Book-keeping for one connected client
+struct ClientEntry {
+    addr: String,
+    name: Option<String>,
+    connected_at: Instant,
+    last_active: Instant,
+    last_command: String,
+    kill: Option<oneshot::Sender<()>>,
+    /// Published messages waiting for this client
+    messages: Option<Arc<MessageQueue>>,
+}
+
+/// Registry of live connections backing the CLIENT command family
+pub struct ClientRegistry {
+    clients: Arc<Mutex<HashMap<u64, ClientEntry>>>,
+    next_id: AtomicU64,
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        Self {
+            clients: Arc::new(Mutex::new(HashMap::new())),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Register a new connection. The receiver fires when the client is killed.
+    pub fn register(self: &Arc<Self>, addr: &str) -> (ClientHandle, oneshot::Receiver<()>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (kill_tx, kill_rx) = oneshot::channel();
+        let now = Instant::now();
+
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.insert(id, ClientEntry {
+                addr: addr.to_string(),
+                name: None,
+                connected_at: now,
+                last_active: now,
+                last_command: "NULL".to_string(),
+                kill: Some(kill_tx),
+                messages: None,
+            });
+        }
+
+        (ClientHandle { id, registry: self.clone() }, kill_rx)
+    }
+
+    /// Number of registered connections
+    pub fn len(&self) -> usize {
+        self.clients.lock().map(|c| c.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn record_command(&self, id: u64, command: &str) {
+        if let Ok(mut clients) = self.clients.lock() {
+            if let Some(entry) = clients.get_mut(&id) {
+                entry.last_active = Instant::now();
+                entry.last_command = command.to_lowercase();
+            }
+        }
+    }
+
+    fn set_message_queue(&self, id: u64, queue: Arc<MessageQueue>) {
+        if let Ok(mut clients) = self.clients.lock() {
+            if let Some(entry) = clients.get_mut(&id) {
+                entry.messages = Some(queue);
+            }
+        }
+    }
+
+    fn clear_name(&self, id: u64) {
+        if let Ok(mut clients) = self.clients.lock() {
+            if let Some(entry) = clients.get_mut(&id) {
+                entry.name = None;
+            }
+        }
+    }
+
+    fn unregister(&self, id: u64) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.remove(&id);
+        }
+    }
+
+    /// Signal every client matching the filter to disconnect, returning how many were killed
+    fn kill_where<F: Fn(u64, &ClientEntry) -> bool>(&self, filter: F) -> usize {
+        let mut killed = 0;
+        if let Ok(mut clients) = self.clients.lock() {
+            for (id, entry) in clients.iter_mut() {
+                if filter(*id, entry) {
+                    if let Some(kill) = entry.kill.take() {
+                        let _ = kill.send(());
+                        killed += 1;
+                    }
+                }
+            }
+        }
+        killed
+    }
+
+    /// Execute a CLIENT subcommand on behalf of connection `id`
+    pub fn execute(&self, id: u64, subcommand: &str, args: &[String]) -> Response {
+        match (subcommand, args.len()) {
+            ("ID", 0) => Response::Integer(id as i64),
+            ("GETNAME", 0) => {
+                let name = self.clients.lock().ok()
+                    .and_then(|clients| clients.get(&id).and_then(|e| e.name.clone()));
+                match name {
+                    Some(name) => Response::String(Some(name)),
+                    None => Response::Null,
+                }
+            }
+            ("SETNAME", 1) => {
+                let name = &args[0];
+                if name.chars().any(|c| c.is_whitespace()) {
+                    return Response::Error("Client names cannot contain spaces".to_string());
+                }
+                if let Ok(mut clients) = self.clients.lock() {
+                    if let Some(entry) = clients.get_mut(&id) {
+                        entry.name = if name.is_empty() { None } else { Some(name.clone()) };
+                    }
+                }
+                Response::Ok
+            }
+            ("LIST", 0) => Response::String(Some(self.list())),
+            // Old form: CLIENT KILL addr
+            ("KILL", 1) => {
+                let addr = &args[0];
+                if self.kill_where(|_, entry| &entry.addr == addr) > 0 {
+                    Response::Ok
+                } else {
+                    Response::Error("No such client".to_string())
+                }
+            }
+            // New form: CLIENT KILL ID id | CLIENT KILL ADDR addr
+            ("KILL", 2) => match args[0].to_uppercase().as_str() {
+                "ID" => match args[1].parse::<u64>() {
+                    Ok(target) => Response::Integer(self.kill_where(|id, _| id == target) as i64),
+                    Err(_) => Response::Error("Invalid client ID".to_string()),
+                },
+                "ADDR" => {
+                    let addr = &args[1];
+                    Response::Integer(self.kill_where(|_, entry| &entry.addr == addr) as i64)
+                }
+                other => Response::Error(format!("Unsupported CLIENT KILL filter: {}", other)),
+            },
+            (sub, _) => Response::Error(format!("Unknown subcommand or wrong number of arguments for CLIENT {}. Try CLIENT HELP.", sub)),
+        }
+    }
+
+    /// One line per client, in connection order
+    fn list(&self) -> String {
+        let clients = match self.clients.lock() {
+            Ok(clients) => clients,
+            Err(_) => return String::new(),
+        };
+
+        let mut ids: Vec<&u64> = clients.keys().collect();
+        ids.sort();
+        ids.into_iter()
+            .map(|id| {
+                let entry = &clients[id];
+                let (queued, dropped) = entry.messages.as_ref()
+                    .map_or((0, 0), |queue| (queue.queued(), queue.dropped()));
+                format!(
+                    "id={} addr={} name={} age={} idle={} cmd={} pubsub-queued={} pubsub-dropped={}",
+                    id,
+                    entry.addr,
+                    entry.name.as_deref().unwrap_or(""),
+                    entry.connected_at.elapsed().as_secs(),
+                    entry.last_active.elapsed().as_secs(),
+                    entry.last_command,
+                    queued,
+                    dropped,
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Default for ClientRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A connection's registration, removed from the registry when dropped
+pub struct ClientHandle {
+    id: u64,
+    registry: Arc<ClientRegistry>,
+}
+
+impl ClientHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Note the command this client just issued
+    pub fn record_command(&self, command: &str) {
+        self.registry.record_command(self.id, command);
+    }
+
+    /// Number of clients connected to the same server
+    pub fn connected_clients(&self) -> usize {
+        self.registry.len()
+    }
+
+    /// Report the size and lag of this client's queue of published messages in CLIENT LIST
+    pub fn set_message_queue(&self, queue: Arc<MessageQueue>) {
+        self.registry.set_message_queue(self.id, queue);
+    }
+
+    /// Forget the name set by CLIENT SETNAME
+    pub fn clear_name(&self) {
+        self.registry.clear_name(self.id);
+    }
+
+    /// Execute a CLIENT subcommand for this connection
+    pub fn execute(&self, subcommand: &str, args: &[String]) -> Response {
+        self.registry.execute(self.id, subcommand, args)
+    }
+}
+
+impl Drop for ClientHandle {
+    fn drop(&mut self) {
+        self.registry.unregister(self.id);
+    }
+}
@@ -3,9 +3,10 @@ use crate::error::{Result, DiskDBError};
 use crate::network::buffer_pool::GLOBAL_BUFFER_POOL;
 use crate::protocol::{Request, Response};
 use bytes::BytesMut;
-use log::{error, info, trace};
-use std::collections::HashMap;
+use log::{error, info, trace, warn};
+use std::cell::Cell;
 use std::net::SocketAddr;
+use std::rc::Rc;
 use std::sync::Arc;
 use tokio_uring::net::{TcpListener, TcpStream};
 use tokio_uring::buf::BoundedBuf;
@@ -18,6 +19,7 @@ const BUFFER_SIZE: usize = 4096;
 pub struct IoUringServer {
     addr: SocketAddr,
     executor: Arc<CommandExecutor>,
+    max_connections: usize,
 }
 
 #[derive(Debug)]
@@ -25,10 +27,23 @@ struct Connection {
     stream: TcpStream,
     addr: SocketAddr,
     read_buf: Vec<u8>,
+    // Bytes received but not yet terminated by a newline
+    pending: BytesMut,
     write_buf: BytesMut,
     pending_requests: Vec<String>,
 }
 
+/// Releases a connection slot when the handler exits
+struct ConnectionSlot {
+    active: Rc<Cell<usize>>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.active.set(self.active.get() - 1);
+    }
+}
+
 impl IoUringServer {
     pub fn new(addr: &str, executor: Arc<CommandExecutor>) -> Result<Self> {
         let addr = addr.parse()
@@ -37,67 +52,81 @@ impl IoUringServer {
         Ok(Self {
             addr,
             executor,
+            max_connections: MAX_CONNECTIONS,
         })
     }
     
+    /// Override the maximum number of concurrent connections
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections;
+        self
+    }
+    
     /// Start the io_uring-based server
     pub async fn start(self) -> Result<()> {
-        // Initialize tokio_uring runtime
+        self.serve()
+    }
+    
+    /// Run the server on a tokio_uring runtime, blocking the current thread
+    pub fn serve(self) -> Result<()> {
         tokio_uring::start(async move {
-            if let Err(e) = self.run_server().await {
+            let result = self.run_server().await;
+            if let Err(e) = &result {
                 error!("io_uring server error: {}", e);
             }
-        });
-        
-        Ok(())
+            result
+        })
     }
     
     async fn run_server(self) -> Result<()> {
         let listener = TcpListener::bind(self.addr)?;
         info!("io_uring server listening on {}", self.addr);
         
-        let mut connections: HashMap<u64, Connection> = HashMap::new();
+        // tokio_uring runs every task on this thread, so a plain counter suffices
+        let active = Rc::new(Cell::new(0usize));
         let mut next_id = 0u64;
         
         loop {
-            // Accept new connections
-            match listener.accept().await {
-                Ok((stream, addr)) => {
-                    let id = next_id;
-                    next_id += 1;
-                    
-                    info!("New io_uring connection from: {} (id: {})", addr, id);
-                    
-                    // Set TCP_NODELAY
-                    let _ = stream.set_nodelay(true);
-                    
-                    let conn = Connection {
-                        stream,
-                        addr,
-                        read_buf: vec![0u8; BUFFER_SIZE],
-                        write_buf: BytesMut::with_capacity(BUFFER_SIZE),
-                        pending_requests: Vec::new(),
-                    };
-                    
-                    connections.insert(id, conn);
-                    
-                    // Start handling the connection
-                    tokio_uring::spawn(Self::handle_connection(
-                        id,
-                        connections.remove(&id).unwrap(),
-                        self.executor.clone(),
-                    ));
-                }
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
                 Err(e) => {
                     error!("Accept error: {}", e);
                     continue;
                 }
-            }
+            };
             
-            // Limit connections
-            if connections.len() >= MAX_CONNECTIONS {
-                tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+            if active.get() >= self.max_connections {
+                warn!("Rejecting io_uring connection from {}: connection limit reached", addr);
+                let reply = Response::Error("max number of clients reached".to_string());
+                let (_, _) = stream.write_all(reply.to_string().into_bytes()).await;
+                continue;
             }
+            
+            let id = next_id;
+            next_id += 1;
+            active.set(active.get() + 1);
+            
+            info!("New io_uring connection from: {} (id: {})", addr, id);
+            
+            // Set TCP_NODELAY
+            let _ = stream.set_nodelay(true);
+            
+            let conn = Connection {
+                stream,
+                addr,
+                read_buf: Vec::with_capacity(BUFFER_SIZE),
+                pending: BytesMut::with_capacity(BUFFER_SIZE),
+                write_buf: BytesMut::with_capacity(BUFFER_SIZE),
+                pending_requests: Vec::new(),
+            };
+            let slot = ConnectionSlot { active: active.clone() };
+            
+            tokio_uring::spawn(Self::handle_connection(
+                id,
+                conn,
+                self.executor.clone(),
+                slot,
+            ));
         }
     }
     
@@ -105,41 +134,33 @@ impl IoUringServer {
         id: u64,
         mut conn: Connection,
         executor: Arc<CommandExecutor>,
+        _slot: ConnectionSlot,
     ) {
         trace!("Starting io_uring handler for connection {}", id);
         
         loop {
-            // Read data using io_uring
-            let (res, buf) = conn.stream.read(conn.read_buf).await;
+            // Read data using io_uring; the kernel fills the buffer's spare capacity
+            conn.read_buf.clear();
+            let (res, buf) = conn.stream.read(std::mem::take(&mut conn.read_buf)).await;
             conn.read_buf = buf;
             
             match res {
                 Ok(0) => {
-                    info!("Connection {} closed", id);
+                    info!("Connection {} from {} closed", id, conn.addr);
                     break;
                 }
                 Ok(n) => {
                     trace!("Read {} bytes from connection {}", n, id);
                     
-                    // Process the data
-                    let data = &conn.read_buf[..n];
-                    if let Ok(str_data) = std::str::from_utf8(data) {
-                        // Parse lines
-                        for line in str_data.lines() {
-                            if line.is_empty() {
-                                continue;
-                            }
-                            
-                            conn.pending_requests.push(line.to_string());
-                        }
-                        
-                        // Process requests if we have any complete ones
-                        if !conn.pending_requests.is_empty() {
-                            Self::process_requests(
-                                &mut conn,
-                                &executor,
-                            ).await;
-                        }
+                    conn.pending.extend_from_slice(&conn.read_buf[..n]);
+                    Self::extract_requests(&mut conn);
+                    
+                    // Process requests if we have any complete ones
+                    if !conn.pending_requests.is_empty() {
+                        Self::process_requests(
+                            &mut conn,
+                            &executor,
+                        ).await;
                     }
                 }
                 Err(e) => {
@@ -152,6 +173,22 @@ impl IoUringServer {
         trace!("io_uring handler for connection {} finished", id);
     }
     
+    /// Move every newline-terminated line out of the accumulator, keeping any partial tail
+    fn extract_requests(conn: &mut Connection) {
+        while let Some(pos) = conn.pending.iter().position(|&b| b == b'\n') {
+            let line = conn.pending.split_to(pos + 1);
+            match std::str::from_utf8(&line) {
+                Ok(line) => {
+                    let line = line.trim_end_matches(['\r', '\n']);
+                    if !line.is_empty() {
+                        conn.pending_requests.push(line.to_string());
+                    }
+                }
+                Err(_) => error!("Discarding non UTF-8 request from {}", conn.addr),
+            }
+        }
+    }
+    
     async fn process_requests(
         conn: &mut Connection,
         executor: &Arc<CommandExecutor>,
@@ -1,8 +1,12 @@
 pub mod buffer_pool;
+pub mod client_registry;
 pub mod optimized_connection;
+pub mod pubsub;
 
 #[cfg(all(target_os = "linux", feature = "io_uring"))]
 pub mod io_uring_server;
 
 pub use buffer_pool::{BufferPool, PooledBuffer};
-pub use optimized_connection::OptimizedConnection;
\ No newline at end of file
+pub use client_registry::{ClientHandle, ClientRegistry};
+pub use optimized_connection::{ConnectionContext, OptimizedConnection};
+pub use pubsub::PubSub;
\ No newline at end of file
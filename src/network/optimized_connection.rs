@@ -1,21 +1,329 @@
-use crate::commands::CommandExecutor;
+use crate::commands::{CommandExecutor, VALUE_TOO_LARGE};
 use crate::error::{Result, DiskDBError};
-use crate::network::buffer_pool::{BufferPool, GLOBAL_BUFFER_POOL};
-use crate::protocol::{Request, Response};
+use crate::listener::{self, SocketOptions};
+use crate::network::buffer_pool::BufferPool;
+use crate::network::client_registry::{ClientHandle, ClientRegistry};
+use crate::network::pubsub::{PubSub, Subscriber};
+use crate::protocol::{join_args, split_args, Request, Response};
+use crate::resp::{self, Decoded, Frame, RespVersion};
 use bytes::{BufMut, BytesMut};
-use log::{error, info, trace};
-use socket2::{Domain, Protocol, Socket, Type};
+use log::{error, info, trace, warn};
+use std::future::Future;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
-use tokio::time::timeout;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::oneshot;
+use tokio::time::{sleep_until, timeout, timeout_at, Instant};
 use tokio_native_tls::TlsStream;
 
+/// Hard limit on reading the rest of a request once it starts arriving: a client
+/// that stops mid-request is dropped after this long. Time between requests is
+/// left to the configurable idle timeout alone.
 const READ_TIMEOUT: Duration = Duration::from_secs(30);
 const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
 const MAX_PIPELINE_DEPTH: usize = 100;
+/// Room allowed on a request line beyond the value itself, for the command and key
+const MAX_LINE_OVERHEAD: usize = 64 * 1024;
+
+/// Server-wide state handed to every connection
+#[derive(Clone)]
+pub struct ConnectionContext {
+    pub executor: Arc<CommandExecutor>,
+    pub buffer_pool: Arc<BufferPool>,
+    pub clients: Arc<ClientRegistry>,
+    pub pubsub: Arc<PubSub>,
+    pub max_response_bytes: usize,
+    /// Array replies with at least this many elements are written in pieces; 0 disables
+    pub stream_array_threshold: usize,
+    /// Close connections idle for longer than this since their last command
+    pub idle_timeout: Option<Duration>,
+    /// Log commands that take at least this long to execute
+    pub slow_command_threshold: Option<Duration>,
+    /// Largest accepted value in bytes, 0 for unlimited; bounds request line length
+    pub max_value_size: usize,
+    /// Largest bulk string a multibulk request may declare
+    pub proto_max_bulk_len: usize,
+    /// Most arguments in one request; a multibulk declaring more closes the connection
+    pub proto_max_multibulk_len: usize,
+    /// When to give up on a client that is not reading its replies
+    pub output_buffer_limit: OutputBufferLimit,
+    /// TCP options applied to each accepted connection
+    pub socket_options: SocketOptions,
+}
+
+/// Limits on reply bytes a client has not yet taken off the socket, like Redis's
+/// `client-output-buffer-limit`. Bytes count as pending from when a flush starts
+/// until the socket accepts them, so a batch of pipelined replies counts whole.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OutputBufferLimit {
+    /// Close the connection as soon as more than this many bytes are pending; 0 disables
+    pub hard_bytes: usize,
+    /// Close the connection once more than this many bytes stay pending for
+    /// `soft_window`; 0 disables
+    pub soft_bytes: usize,
+    pub soft_window: Duration,
+}
+
+/// Applies an `OutputBufferLimit` across the flushes of one connection
+#[derive(Debug, Default)]
+pub struct OutputGuard {
+    limit: OutputBufferLimit,
+    /// Since when pending output has been above the soft limit
+    over_soft_since: Option<Instant>,
+}
+
+impl OutputGuard {
+    pub fn new(limit: OutputBufferLimit) -> Self {
+        Self { limit, over_soft_since: None }
+    }
+    
+    /// Check `pending` unwritten bytes against the limits. While they are over the
+    /// soft limit, returns when the connection is to be closed if they stay there.
+    fn check(&mut self, pending: usize) -> Result<Option<Instant>> {
+        let limit = self.limit;
+        if limit.hard_bytes > 0 && pending > limit.hard_bytes {
+            return Err(Self::breach(format!(
+                "{} reply bytes pending, over the hard limit of {}", pending, limit.hard_bytes
+            )));
+        }
+        if limit.soft_bytes == 0 || pending <= limit.soft_bytes {
+            self.over_soft_since = None;
+            return Ok(None);
+        }
+        let since = *self.over_soft_since.get_or_insert_with(Instant::now);
+        if since.elapsed() >= limit.soft_window {
+            return Err(Self::breach(format!(
+                "{} reply bytes pending, over the soft limit of {} for {:?}", pending, limit.soft_bytes, limit.soft_window
+            )));
+        }
+        Ok(Some(since + limit.soft_window))
+    }
+    
+    fn breach(reason: String) -> DiskDBError {
+        warn!("Closing slow client: {}", reason);
+        DiskDBError::Io(std::io::Error::other(reason))
+    }
+}
+
+/// Per-connection state threaded through the read loop and dispatch
+struct Session {
+    context: ConnectionContext,
+    client: ClientHandle,
+    addr: String,
+    subscriber: Subscriber,
+    /// Reply encoding negotiated with HELLO; `None` is the line protocol
+    resp: Option<RespVersion>,
+    /// CLIENT TRACKING is on: keys this connection reads are remembered
+    tracking: bool,
+    /// Set by MONITOR: the feed of commands processed by every connection
+    monitor: Option<broadcast::Receiver<String>>,
+    output: OutputGuard,
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        if self.tracking {
+            self.context.executor.tracking().disable(self.client.id());
+        }
+    }
+}
+
+impl Session {
+    /// Append the reply of `command` in this connection's encoding
+    fn put_reply(&self, command: &str, response: Response, buffer: &mut BytesMut) {
+        match self.resp {
+            Some(version) => {
+                let mut encoded = Vec::new();
+                resp::encode_reply(command, response, version, &mut encoded);
+                buffer.put(encoded.as_slice());
+            }
+            None => buffer.put(response.to_string().as_bytes()),
+        }
+    }
+    
+    /// Append a published message; RESP3 sends it as a push
+    fn put_message(&self, message: Response, buffer: &mut BytesMut) {
+        match self.resp {
+            Some(version) => buffer.put(Frame::push(message).to_bytes(version).as_slice()),
+            None => buffer.put(message.to_string().as_bytes()),
+        }
+    }
+    
+    /// Append a MONITOR line; RESP sends it as a status reply, as Redis does
+    fn put_monitor_line(&self, line: String, buffer: &mut BytesMut) {
+        match self.resp {
+            Some(version) => buffer.put(Frame::Simple(line).to_bytes(version).as_slice()),
+            None => buffer.put(Response::String(Some(line)).to_string().as_bytes()),
+        }
+    }
+    
+    /// Subscribers, monitors and tracking clients wait on messages rather than their own requests
+    fn is_listening(&self) -> bool {
+        self.subscriber.is_active() || self.monitor.is_some() || self.tracking
+    }
+}
+
+/// The client end of a connection: its requests, and the signal that it was killed
+struct Peer<R> {
+    reader: BufReader<R>,
+    kill: oneshot::Receiver<()>,
+    /// Disconnected or killed while a blocking command waited for it
+    gone: bool,
+}
+
+impl<R: AsyncRead + Unpin> Peer<R> {
+    /// Resolves, marking the client gone, once it disconnects or is killed. Only
+    /// polled while a blocking command waits; requests pipelined behind that
+    /// command stay buffered, and the socket is not watched past them.
+    async fn hangup(&mut self) {
+        let closed = async {
+            match self.reader.fill_buf().await {
+                Ok([]) | Err(_) => {}
+                Ok(_) => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = closed => {}
+            _ = &mut self.kill => {}
+        }
+        self.gone = true;
+    }
+}
+
+/// The next line of a MONITOR feed; never resolves when the connection is not monitoring
+async fn next_monitor_line(feed: Option<&mut broadcast::Receiver<String>>) -> std::result::Result<String, RecvError> {
+    match feed {
+        Some(feed) => feed.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Outcome of reading one request
+enum Line {
+    /// A line without its terminator
+    Complete(String),
+    /// A RESP multibulk request, already split into arguments
+    Multibulk(Vec<String>),
+    /// The line exceeded the length limit and was discarded unread
+    TooLong,
+    /// A malformed or oversized RESP frame; the stream cannot be resynchronised
+    Invalid(String),
+}
+
+/// Reads newline-terminated lines, or RESP multibulk frames when a request
+/// starts with `*`, without holding more than `max_len` bytes.
+///
+/// Bytes are moved into `partial` as they arrive, so a read can lose a
+/// `select!` race and be resumed later without dropping data.
+struct LineReader {
+    partial: Vec<u8>,
+    max_len: usize,
+    /// Largest bulk string a multibulk frame may declare
+    max_bulk_len: usize,
+    /// Most arguments a multibulk frame may declare
+    max_multibulk_len: usize,
+    /// Skipping the remainder of an over-long line
+    discarding: bool,
+}
+
+impl LineReader {
+    fn new(max_len: usize, max_bulk_len: usize, max_multibulk_len: usize) -> Self {
+        Self { partial: Vec::new(), max_len, max_bulk_len, max_multibulk_len, discarding: false }
+    }
+    
+    /// Whether `buffered` already holds the whole next request, so reading it
+    /// cannot wait on the client
+    fn has_request(&self, buffered: &[u8]) -> bool {
+        if !self.partial.is_empty() || self.discarding {
+            return false;
+        }
+        match buffered.first() {
+            None => false,
+            Some(b'*') => !matches!(resp::decode_command(buffered, self.max_bulk_len, self.max_multibulk_len), Ok(Decoded::Incomplete)),
+            Some(_) => buffered.contains(&b'\n'),
+        }
+    }
+    
+    /// Whether part of the next request has arrived, so reading it may be left waiting on the client
+    fn mid_request(&self, buffered: &[u8]) -> bool {
+        !self.partial.is_empty() || self.discarding || !buffered.is_empty()
+    }
+    
+    /// Read the next request, or `None` at end of stream
+    async fn read<R>(&mut self, reader: &mut R) -> std::io::Result<Option<Line>>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        loop {
+            let available = reader.fill_buf().await?;
+            if available.is_empty() {
+                // A multibulk frame cut off by the close is dropped
+                if self.partial.is_empty() || self.discarding || self.partial[0] == b'*' {
+                    return Ok(None);
+                }
+                break;
+            }
+            
+            if !self.discarding && self.partial.first().unwrap_or(&available[0]) == &b'*' {
+                // Consume only the frame's own bytes; the next request stays buffered
+                let before = self.partial.len();
+                let arrived = available.len();
+                self.partial.extend_from_slice(available);
+                let decoded = resp::decode_command(&self.partial, self.max_bulk_len, self.max_multibulk_len);
+                let (consumed, line) = match decoded {
+                    Ok(Decoded::Incomplete) if self.partial.len() > self.max_len => {
+                        (arrived, Some(Line::Invalid("Protocol error: request too large".to_string())))
+                    }
+                    Ok(Decoded::Incomplete) => (arrived, None),
+                    Ok(Decoded::Command(args, used)) => (used - before, Some(Line::Multibulk(args))),
+                    Err(e) => (arrived, Some(Line::Invalid(e.to_string()))),
+                };
+                reader.consume(consumed);
+                match line {
+                    Some(line) => {
+                        self.partial.clear();
+                        return Ok(Some(line));
+                    }
+                    None => continue,
+                }
+            }
+            
+            let newline = available.iter().position(|&b| b == b'\n');
+            let take = newline.map_or(available.len(), |pos| pos + 1);
+            
+            if !self.discarding {
+                self.partial.extend_from_slice(&available[..take]);
+                if self.partial.len() > self.max_len {
+                    self.partial = Vec::new();
+                    self.discarding = true;
+                }
+            }
+            reader.consume(take);
+            
+            if newline.is_some() {
+                if std::mem::take(&mut self.discarding) {
+                    return Ok(Some(Line::TooLong));
+                }
+                break;
+            }
+        }
+        
+        // Accept both \n and \r\n; a final line at end of stream may have neither
+        let mut line = std::mem::take(&mut self.partial);
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        String::from_utf8(line)
+            .map(|line| Some(Line::Complete(line)))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
 
 pub enum OptimizedConnection {
     Plain(TcpStream),
@@ -23,20 +331,9 @@ pub enum OptimizedConnection {
 }
 
 impl OptimizedConnection {
-    /// Create an optimized TCP connection with custom socket options
-    pub async fn accept(stream: TcpStream, _addr: SocketAddr) -> Result<Self> {
-        // Set TCP options for better performance
-        let sock_ref = socket2::SockRef::from(&stream);
-        
-        // Enable TCP_NODELAY for low latency
-        sock_ref.set_nodelay(true)?;
-        
-        // Set socket buffer sizes for better throughput
-        let _ = sock_ref.set_recv_buffer_size(256 * 1024);
-        let _ = sock_ref.set_send_buffer_size(256 * 1024);
-        
-        // Enable TCP keepalive
-        sock_ref.set_keepalive(true)?;
+    /// Create an optimized TCP connection with the configured socket options
+    pub async fn accept(stream: TcpStream, _addr: SocketAddr, options: &SocketOptions) -> Result<Self> {
+        listener::configure(&stream, options)?;
         
         #[cfg(target_os = "linux")]
         {
@@ -61,124 +358,222 @@ impl OptimizedConnection {
     }
     
     /// Handle the connection with optimizations
-    pub async fn handle(
-        self,
-        executor: Arc<CommandExecutor>,
-        addr: String,
-        buffer_pool: Option<Arc<BufferPool>>,
-    ) -> Result<()> {
+    pub async fn handle(self, addr: String, context: ConnectionContext) -> Result<()> {
         info!("Optimized connection from: {}", addr);
         
-        let pool = buffer_pool.unwrap_or_else(|| GLOBAL_BUFFER_POOL.clone());
+        let (client, kill) = context.clients.register(&addr);
+        let subscriber = context.pubsub.subscriber();
+        client.set_message_queue(subscriber.queue());
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!("connection", addr = %addr, id = client.id());
+        let session = Session {
+            subscriber,
+            output: OutputGuard::new(context.output_buffer_limit),
+            context,
+            client,
+            addr: addr.clone(),
+            resp: None,
+            tracking: false,
+            monitor: None,
+        };
         
-        match self {
-            OptimizedConnection::Plain(stream) => {
-                Self::handle_plain(stream, executor, addr, pool).await
-            }
-            OptimizedConnection::Tls(stream) => {
-                Self::handle_tls(stream, executor, addr, pool).await
+        let serving = async move {
+            match self {
+                OptimizedConnection::Plain(stream) => {
+                    let (reader, writer) = stream.into_split();
+                    Self::serve(reader, writer, &addr, session, kill).await?;
+                    info!("Optimized connection closed: {}", addr);
+                }
+                OptimizedConnection::Tls(stream) => {
+                    let (reader, writer) = tokio::io::split(stream);
+                    Self::serve(reader, writer, &addr, session, kill).await?;
+                    info!("TLS connection closed: {}", addr);
+                }
             }
-        }
+            Ok(())
+        };
+        #[cfg(feature = "tracing")]
+        let serving = tracing::Instrument::instrument(serving, span);
+        serving.await
     }
     
-    async fn handle_plain(
-        stream: TcpStream,
-        executor: Arc<CommandExecutor>,
-        addr: String,
-        buffer_pool: Arc<BufferPool>,
-    ) -> Result<()> {
-        let (reader, mut writer) = stream.into_split();
-        let mut reader = BufReader::with_capacity(64 * 1024, reader);
+    async fn serve<R, W>(
+        reader: R,
+        mut writer: W,
+        addr: &str,
+        mut session: Session,
+        kill: oneshot::Receiver<()>,
+    ) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut peer = Peer {
+            reader: BufReader::with_capacity(64 * 1024, reader),
+            kill,
+            gone: false,
+        };
+        let max_line_len = match session.context.max_value_size {
+            0 => usize::MAX,
+            max => max.saturating_add(MAX_LINE_OVERHEAD),
+        };
+        let max_bulk_len = match session.context.max_value_size {
+            0 => session.context.proto_max_bulk_len,
+            max => max.min(session.context.proto_max_bulk_len),
+        };
+        let max_args = session.context.proto_max_multibulk_len;
+        let mut lines = LineReader::new(max_line_len, max_bulk_len, max_args);
         
         // Pipeline support - collect multiple requests before responding
         let mut pipeline_buffer = Vec::with_capacity(MAX_PIPELINE_DEPTH);
-        let mut response_buffer = buffer_pool.get(4096).await;
+        let mut response_buffer = session.context.buffer_pool.get(4096).await;
+        let mut last_command = Instant::now();
         
         loop {
-            // Read with timeout
-            let mut line = String::new();
-            match timeout(READ_TIMEOUT, reader.read_line(&mut line)).await {
-                Ok(Ok(0)) => break, // Connection closed
-                Ok(Ok(_)) => {
-                    if line.trim().is_empty() {
-                        continue;
+            // The client left or was killed while a blocking command waited for it
+            if peer.gone {
+                break;
+            }
+            // Listening clients may sit idle indefinitely while waiting for messages
+            let idle_deadline = session.context.idle_timeout
+                .filter(|_| !session.is_listening())
+                .map(|limit| last_command + limit);
+            let reader = &mut peer.reader;
+            let line_read = async {
+                // Only the idle timeout applies until the next request starts arriving
+                if !lines.mid_request(reader.buffer()) {
+                    if let Err(e) = reader.fill_buf().await {
+                        return Ok(Err(e));
                     }
-                    
-                    // Parse request
-                    let request_result = Request::parse(&line);
-                    pipeline_buffer.push((line.clone(), request_result));
-                    
-                    // Check if we should process the pipeline
-                    if pipeline_buffer.len() >= MAX_PIPELINE_DEPTH || 
-                       Self::should_flush_pipeline(&pipeline_buffer) {
-                        Self::process_pipeline(
-                            &mut pipeline_buffer,
-                            &executor,
-                            response_buffer.as_mut(),
-                            &mut writer,
-                            &buffer_pool,
-                        ).await?;
+                }
+                timeout(READ_TIMEOUT, lines.read(reader)).await
+            };
+            
+            // Wait for a request, a published message, a monitored command, or the client being killed
+            let read = tokio::select! {
+                read = line_read => read,
+                message = session.subscriber.recv() => {
+                    let Some(message) = message else {
+                        warn!("Closing subscriber {}: its message queue overflowed", addr);
+                        pipeline_buffer.clear();
+                        break;
+                    };
+                    session.put_message(message, response_buffer.as_mut());
+                    Self::write_responses(&mut writer, response_buffer.as_mut(), &mut session.output).await?;
+                    continue;
+                }
+                line = next_monitor_line(session.monitor.as_mut()) => {
+                    match line {
+                        Ok(line) => {
+                            session.put_monitor_line(line, response_buffer.as_mut());
+                            Self::write_responses(&mut writer, response_buffer.as_mut(), &mut session.output).await?;
+                        }
+                        Err(RecvError::Lagged(skipped)) => trace!("Monitor {} fell behind, skipped {} commands", addr, skipped),
+                        Err(RecvError::Closed) => session.monitor = None,
                     }
+                    continue;
                 }
-                Ok(Err(e)) => {
-                    error!("Read error from {}: {}", addr, e);
+                _ = &mut peer.kill => {
+                    info!("Client {} killed", addr);
+                    pipeline_buffer.clear();
                     break;
                 }
-                Err(_) => {
-                    error!("Read timeout from {}", addr);
+                _ = async {
+                    match idle_deadline {
+                        Some(deadline) => sleep_until(deadline).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    info!("Closing idle connection {}", addr);
+                    // Answer anything still buffered before saying goodbye
+                    Self::process_pipeline(
+                        &mut pipeline_buffer,
+                        &mut session,
+                        response_buffer.as_mut(),
+                        &mut writer,
+                        &mut peer,
+                    ).await?;
+                    let goodbye = Response::Error("ERR idle timeout exceeded, closing connection".to_string());
+                    session.put_reply("", goodbye, response_buffer.as_mut());
+                    Self::write_responses(&mut writer, response_buffer.as_mut(), &mut session.output).await?;
                     break;
                 }
-            }
-        }
-        
-        // Process any remaining requests
-        if !pipeline_buffer.is_empty() {
-            Self::process_pipeline(
-                &mut pipeline_buffer,
-                &executor,
-                response_buffer.as_mut(),
-                &mut writer,
-                &buffer_pool,
-            ).await?;
-        }
-        
-        info!("Optimized connection closed: {}", addr);
-        Ok(())
-    }
-    
-    async fn handle_tls(
-        stream: TlsStream<TcpStream>,
-        executor: Arc<CommandExecutor>,
-        addr: String,
-        buffer_pool: Arc<BufferPool>,
-    ) -> Result<()> {
-        // Similar to plain but with TLS stream
-        let (reader, mut writer) = tokio::io::split(stream);
-        let mut reader = BufReader::with_capacity(64 * 1024, reader);
-        
-        let mut pipeline_buffer = Vec::with_capacity(MAX_PIPELINE_DEPTH);
-        let mut response_buffer = buffer_pool.get(4096).await;
-        
-        loop {
-            let mut line = String::new();
-            match timeout(READ_TIMEOUT, reader.read_line(&mut line)).await {
-                Ok(Ok(0)) => break,
-                Ok(Ok(_)) => {
+            };
+            match read {
+                Ok(Ok(None)) => break, // Connection closed
+                Ok(Ok(Some(Line::TooLong))) => {
+                    // Answer earlier requests first so replies stay in order
+                    Self::process_pipeline(
+                        &mut pipeline_buffer,
+                        &mut session,
+                        response_buffer.as_mut(),
+                        &mut writer,
+                        &mut peer,
+                    ).await?;
+                    last_command = Instant::now();
+                    let rejection = Response::Error(VALUE_TOO_LARGE.to_string());
+                    session.put_reply("", rejection, response_buffer.as_mut());
+                    Self::write_responses(&mut writer, response_buffer.as_mut(), &mut session.output).await?;
+                }
+                Ok(Ok(Some(Line::Invalid(reason)))) => {
+                    Self::process_pipeline(
+                        &mut pipeline_buffer,
+                        &mut session,
+                        response_buffer.as_mut(),
+                        &mut writer,
+                        &mut peer,
+                    ).await?;
+                    warn!("Closing connection {}: {}", addr, reason);
+                    session.put_reply("", Response::Error(format!("ERR {}", reason)), response_buffer.as_mut());
+                    Self::write_responses(&mut writer, response_buffer.as_mut(), &mut session.output).await?;
+                    break;
+                }
+                Ok(Ok(Some(request @ (Line::Complete(_) | Line::Multibulk(_))))) => {
+                    let (line, request_result) = match request {
+                        Line::Complete(line) => {
+                            let request_result = Request::parse_limited(&line, max_args);
+                            (line, request_result)
+                        }
+                        Line::Multibulk(args) => {
+                            let request_result = Request::from_args(&args).map_err(DiskDBError::from);
+                            (join_args(&args), request_result)
+                        }
+                        _ => unreachable!(),
+                    };
                     if line.trim().is_empty() {
                         continue;
                     }
+                    last_command = Instant::now();
+                    let quit = matches!(request_result, Ok(Request::Quit));
+                    pipeline_buffer.push((line, request_result));
                     
-                    let request_result = Request::parse(&line);
-                    pipeline_buffer.push((line.clone(), request_result));
+                    // Answer everything up to and including QUIT, then close; requests
+                    // pipelined after it are dropped
+                    if quit {
+                        Self::process_pipeline(
+                            &mut pipeline_buffer,
+                            &mut session,
+                            response_buffer.as_mut(),
+                            &mut writer,
+                            &mut peer,
+                        ).await?;
+                        writer.shutdown().await.ok();
+                        break;
+                    }
                     
+                    // Check if we should process the pipeline; listening clients get immediate replies,
+                    // and nothing waits once the next request is not fully buffered, since a
+                    // client may hold the rest of it until it has its replies
                     if pipeline_buffer.len() >= MAX_PIPELINE_DEPTH || 
+                       session.is_listening() ||
+                       !lines.has_request(peer.reader.buffer()) ||
                        Self::should_flush_pipeline(&pipeline_buffer) {
-                        // For TLS, we can't use vectored I/O efficiently
-                        Self::process_pipeline_tls(
+                        Self::process_pipeline(
                             &mut pipeline_buffer,
-                            &executor,
+                            &mut session,
                             response_buffer.as_mut(),
                             &mut writer,
+                            &mut peer,
                         ).await?;
                     }
                 }
@@ -193,16 +588,17 @@ impl OptimizedConnection {
             }
         }
         
+        // Process any remaining requests
         if !pipeline_buffer.is_empty() {
-            Self::process_pipeline_tls(
+            Self::process_pipeline(
                 &mut pipeline_buffer,
-                &executor,
+                &mut session,
                 response_buffer.as_mut(),
                 &mut writer,
+                &mut peer,
             ).await?;
         }
         
-        info!("TLS connection closed: {}", addr);
         Ok(())
     }
     
@@ -212,131 +608,359 @@ impl OptimizedConnection {
             match result {
                 Err(_) => true,
                 Ok(req) => matches!(req, 
-                    Request::FlushDb | 
+                    Request::FlushDb { .. } | 
+                    Request::FlushAll { .. } |
                     Request::Info | 
-                    Request::Ping
+                    Request::Ping |
+                    Request::PoolStats |
+                    Request::Client { .. } |
+                    Request::Reset |
+                    Request::Hello { .. } |
+                    Request::Monitor |
+                    Request::Subscribe { .. } |
+                    Request::Unsubscribe { .. } |
+                    Request::PSubscribe { .. } |
+                    Request::PUnsubscribe { .. }
                 ),
             }
         })
     }
     
-    async fn process_pipeline(
-        pipeline: &mut Vec<(String, Result<Request>)>,
-        executor: &Arc<CommandExecutor>,
-        response_buffer: &mut BytesMut,
-        writer: &mut tokio::net::tcp::OwnedWriteHalf,
-        _buffer_pool: &Arc<BufferPool>,
-    ) -> Result<()> {
-        response_buffer.clear();
+    /// Commands a connection in subscriber mode may still issue
+    fn allowed_while_subscribed(request: &Request) -> bool {
+        matches!(request,
+            Request::Subscribe { .. } |
+            Request::Unsubscribe { .. } |
+            Request::PSubscribe { .. } |
+            Request::PUnsubscribe { .. } |
+            Request::Ping |
+            Request::Reset |
+            Request::Quit
+        )
+    }
+    
+    /// Run `dispatch` for one request and time it. Requests that take at least
+    /// the slow command threshold are logged as a warning, and with the `tracing`
+    /// feature each one runs in a `command` span that records its latency.
+    async fn timed_dispatch(
+        session: &mut Session,
+        line: &str,
+        request_result: &Result<Request>,
+        hangup: impl Future<Output = ()>,
+    ) -> Response {
+        let command = line.split_whitespace().next().unwrap_or_default();
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!("command", name = %command.to_uppercase());
         
-        // Process all requests and build responses
-        for (_, request_result) in pipeline.iter() {
-            let response = match request_result {
-                Ok(request) => {
-                    match executor.execute(request.clone()).await {
-                        Ok(resp) => resp,
-                        Err(e) => Response::Error(e.to_string()),
-                    }
-                }
-                Err(e) => Response::Error(e.to_string()),
-            };
+        let started = Instant::now();
+        let dispatch = Self::dispatch(session, line, request_result, hangup);
+        #[cfg(feature = "tracing")]
+        let dispatch = tracing::Instrument::instrument(dispatch, span.clone());
+        let response = dispatch.await;
+        let elapsed = started.elapsed();
+        
+        #[cfg(feature = "tracing")]
+        span.in_scope(|| tracing::debug!(latency_us = elapsed.as_micros() as u64, "command finished"));
+        if session.context.slow_command_threshold.is_some_and(|threshold| elapsed >= threshold) {
+            warn!("Slow command from client {}: {} took {:?}", session.client.id(), command.to_uppercase(), elapsed);
+        }
+        response
+    }
+    
+    /// Execute one request, answering connection-scoped commands locally. A
+    /// blocking command stops waiting once `hangup` resolves.
+    async fn dispatch(
+        session: &mut Session,
+        line: &str,
+        request_result: &Result<Request>,
+        hangup: impl Future<Output = ()>,
+    ) -> Response {
+        if let Some(command) = line.split_whitespace().next() {
+            session.client.record_command(command);
             
-            // Write response to buffer
-            response_buffer.put(response.to_string().as_bytes());
+            if let Ok(request) = request_result {
+                if session.subscriber.is_active() && !Self::allowed_while_subscribed(request) {
+                    return Response::Error(format!(
+                        "ERR Can't execute '{}': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context",
+                        command.to_lowercase()
+                    ));
+                }
+                if session.monitor.is_some() && !matches!(request, Request::Quit | Request::Reset) {
+                    return Response::Error(format!(
+                        "ERR Can't execute '{}': only QUIT / RESET are allowed while monitoring",
+                        command.to_lowercase()
+                    ));
+                }
+                
+                // Monitors see every command from other connections but MONITOR itself
+                let monitor = session.context.executor.monitor();
+                if monitor.is_active() && session.monitor.is_none() && !matches!(request, Request::Monitor) {
+                    let args = split_args(line).unwrap_or_else(|_| line.split_whitespace().map(str::to_string).collect());
+                    monitor.feed(&session.addr, &args);
+                }
+            }
         }
         
-        // Write all responses at once with timeout
-        match timeout(WRITE_TIMEOUT, writer.write_all(response_buffer)).await {
-            Ok(Ok(_)) => {
-                trace!("Sent {} responses in batch", pipeline.len());
-                pipeline.clear();
-                Ok(())
+        match request_result {
+            Ok(Request::Client { subcommand, args }) if subcommand == "TRACKING" => Self::client_tracking(session, args),
+            Ok(Request::Client { subcommand, args }) => session.client.execute(subcommand, args),
+            Ok(Request::PoolStats) => Self::pool_stats(&session.context.buffer_pool, &session.client),
+            Ok(Request::Reset) => Self::reset(session),
+            Ok(Request::Quit) => Response::Ok,
+            Ok(Request::Monitor) => {
+                if session.monitor.is_none() {
+                    session.monitor = Some(session.context.executor.monitor().subscribe());
+                }
+                Response::Ok
             }
-            Ok(Err(e)) => {
-                error!("Write error: {}", e);
-                Err(e.into())
+            Ok(Request::Hello { protover, auth }) => Self::hello(session, *protover, auth),
+            Ok(Request::Subscribe { channels }) => session.subscriber.subscribe(channels),
+            Ok(Request::Unsubscribe { channels }) => session.subscriber.unsubscribe(channels),
+            Ok(Request::PSubscribe { patterns }) => session.subscriber.psubscribe(patterns),
+            Ok(Request::PUnsubscribe { patterns }) => session.subscriber.punsubscribe(patterns),
+            Ok(Request::Publish { channel, message }) => {
+                Response::Integer(session.context.pubsub.publish(channel, message) as i64)
             }
-            Err(_) => {
-                error!("Write timeout");
-                Err(DiskDBError::Io(std::io::Error::new(
-                    std::io::ErrorKind::TimedOut,
-                    "Write timeout",
-                )))
+            Ok(Request::PubSub { subcommand, args }) => session.context.pubsub.introspect(subcommand, args),
+            Ok(request) => {
+                let response = match session.context.executor.execute_until(request.clone(), hangup).await {
+                    Ok(resp) => resp,
+                    Err(e) => Response::Error(e.to_string()),
+                };
+                if session.tracking && !request.is_write() && !matches!(response, Response::Error(_)) {
+                    session.context.executor.tracking().remember(session.client.id(), &request.keys());
+                }
+                response
             }
+            Err(e) => Response::Error(e.to_string()),
         }
     }
     
-    async fn process_pipeline_tls<W>(
+    /// RESET: return the connection to the state it had right after connecting
+    fn reset(session: &mut Session) -> Response {
+        session.subscriber.reset();
+        session.monitor = None;
+        session.client.clear_name();
+        session.resp = None;
+        if std::mem::take(&mut session.tracking) {
+            session.context.executor.tracking().disable(session.client.id());
+        }
+        Response::String(Some("RESET".to_string()))
+    }
+    
+    /// CLIENT TRACKING ON|OFF. Invalidations are sent as pushes, so tracking
+    /// can only be turned on after HELLO 3.
+    fn client_tracking(session: &mut Session, args: &[String]) -> Response {
+        let tracking = session.context.executor.tracking();
+        let id = session.client.id();
+        match args.first().map(|arg| arg.to_uppercase()).as_deref() {
+            Some("ON") if args.len() == 1 => {
+                if session.resp != Some(RespVersion::Resp3) {
+                    return Response::Error("ERR CLIENT TRACKING requires RESP3, switch with HELLO 3 first".to_string());
+                }
+                tracking.enable(id, session.subscriber.mailbox());
+                session.tracking = true;
+                Response::Ok
+            }
+            Some("OFF") if args.len() == 1 => {
+                tracking.disable(id);
+                session.tracking = false;
+                Response::Ok
+            }
+            _ => Response::Error("ERR syntax error, expected CLIENT TRACKING ON|OFF".to_string()),
+        }
+    }
+    
+    /// HELLO: the connection handshake, answered with server metadata as key/value pairs.
+    /// Version 2 is the line protocol and version 3 switches replies to RESP3; without a
+    /// version the connection keeps what it has. No password can be configured, so AUTH
+    /// is always refused.
+    fn hello(session: &mut Session, protover: Option<u32>, auth: &Option<(String, String)>) -> Response {
+        let resp = match protover {
+            None => session.resp,
+            Some(2) => None,
+            Some(3) => Some(RespVersion::Resp3),
+            Some(other) => return Response::Error(format!("NOPROTO unsupported protocol version {}", other)),
+        };
+        if auth.is_some() {
+            return Response::Error("ERR AUTH called without any password configured for the default user".to_string());
+        }
+        session.resp = resp;
+        let proto = session.resp.map_or(2, |version| version.number());
+        
+        let text = |s: &str| Response::String(Some(s.to_string()));
+        Response::Array(vec![
+            text("server"), text("diskdb"),
+            text("version"), text(env!("CARGO_PKG_VERSION")),
+            text("proto"), Response::Integer(proto),
+            text("id"), Response::Integer(session.client.id() as i64),
+            text("mode"), text("standalone"),
+            text("role"), text("master"),
+        ])
+    }
+    
+    /// Labeled buffer-pool and connection counters for POOL STATS
+    fn pool_stats(buffer_pool: &BufferPool, client: &ClientHandle) -> Response {
+        let stats = buffer_pool.stats();
+        let counters = [
+            ("buffer_pool_hits", stats.hits as i64),
+            ("buffer_pool_misses", stats.misses as i64),
+            ("buffer_pool_pooled", buffer_pool.pooled_buffers() as i64),
+            ("buffer_pool_small", stats.small_buffers as i64),
+            ("buffer_pool_medium", stats.medium_buffers as i64),
+            ("buffer_pool_large", stats.large_buffers as i64),
+            ("connected_clients", client.connected_clients() as i64),
+        ];
+        
+        Response::Array(counters.iter()
+            .flat_map(|(label, value)| [Response::String(Some(label.to_string())), Response::Integer(*value)])
+            .collect())
+    }
+    
+    async fn process_pipeline<R, W>(
         pipeline: &mut Vec<(String, Result<Request>)>,
-        executor: &Arc<CommandExecutor>,
+        session: &mut Session,
         response_buffer: &mut BytesMut,
         writer: &mut W,
+        peer: &mut Peer<R>,
     ) -> Result<()>
     where
-        W: AsyncWriteExt + Unpin,
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
     {
         response_buffer.clear();
         
-        for (_, request_result) in pipeline.iter() {
-            let response = match request_result {
-                Ok(request) => {
-                    match executor.execute(request.clone()).await {
-                        Ok(resp) => resp,
-                        Err(e) => Response::Error(e.to_string()),
-                    }
+        // Process all requests and build responses
+        for (line, request_result) in pipeline.iter() {
+            let response = Self::timed_dispatch(session, line, request_result, peer.hangup()).await;
+            if peer.gone {
+                // Nobody is left to answer
+                pipeline.clear();
+                return Ok(());
+            }
+            
+            // Write response to buffer
+            let command = request_result.as_ref().map(|request| request.command_name()).unwrap_or_default();
+            let threshold = session.context.stream_array_threshold;
+            match response {
+                Response::Array(items) if threshold > 0 && items.len() >= threshold => {
+                    let chunk_bytes = session.context.max_response_bytes;
+                    write_array_reply(writer, session.resp, command, items, response_buffer, chunk_bytes, &mut session.output).await?;
                 }
-                Err(e) => Response::Error(e.to_string()),
-            };
+                response => session.put_reply(command, response, response_buffer),
+            }
             
-            response_buffer.put(response.to_string().as_bytes());
+            // Bound memory held for a large pipeline by flushing early
+            if response_buffer.len() >= session.context.max_response_bytes {
+                Self::write_responses(writer, response_buffer, &mut session.output).await?;
+            }
         }
         
-        match timeout(WRITE_TIMEOUT, writer.write_all(response_buffer)).await {
-            Ok(Ok(_)) => {
-                trace!("Sent {} TLS responses", pipeline.len());
-                pipeline.clear();
-                Ok(())
+        // Write the remaining responses at once
+        Self::write_responses(writer, response_buffer, &mut session.output).await?;
+        trace!("Sent {} responses in batch", pipeline.len());
+        pipeline.clear();
+        Ok(())
+    }
+    
+    /// Write and drain the response buffer, retrying partial writes from a slow
+    /// consumer. The timeout bounds how long one flush may wait on it, and `output`
+    /// gives up sooner once the bytes still waiting breach its limits.
+    async fn write_responses<W>(writer: &mut W, response_buffer: &mut BytesMut, output: &mut OutputGuard) -> Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        if response_buffer.is_empty() {
+            return Ok(());
+        }
+        
+        let write_deadline = Instant::now() + WRITE_TIMEOUT;
+        let mut written = 0;
+        while written < response_buffer.len() {
+            let soft_deadline = output.check(response_buffer.len() - written)?;
+            let deadline = soft_deadline.map_or(write_deadline, |soft| soft.min(write_deadline));
+            match timeout_at(deadline, writer.write(&response_buffer[written..])).await {
+                Ok(Ok(0)) => {
+                    return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+                }
+                Ok(Ok(n)) => written += n,
+                Ok(Err(e)) => {
+                    error!("Write error: {}", e);
+                    return Err(e.into());
+                }
+                // The soft window ran out first; the next check closes the connection
+                Err(_) if Instant::now() < write_deadline => {}
+                Err(_) => {
+                    error!("Write timeout");
+                    return Err(DiskDBError::Io(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "Write timeout",
+                    )));
+                }
             }
-            Ok(Err(e)) => {
-                error!("TLS write error: {}", e);
-                Err(e.into())
+        }
+        output.check(0)?;
+        response_buffer.clear();
+        Ok(())
+    }
+}
+
+/// Write an array reply in pieces of about `chunk_bytes`, consuming the items as
+/// they are encoded, so a long reply is never held encoded in full. Produces the
+/// same bytes as encoding it whole; replies RESP sends as several pushes are
+/// encoded whole. The reply goes after whatever `buffer` already holds, and each
+/// piece written counts against `output`'s limits.
+pub async fn write_array_reply<W>(
+    writer: &mut W,
+    resp: Option<RespVersion>,
+    command: &str,
+    items: Vec<Response>,
+    buffer: &mut BytesMut,
+    chunk_bytes: usize,
+    output: &mut OutputGuard,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut encoded = Vec::new();
+    if let Some(version) = resp {
+        if !resp::encode_array_header(command, items.len(), version, &mut encoded) {
+            resp::encode_reply(command, Response::Array(items), version, &mut encoded);
+            buffer.put(encoded.as_slice());
+            return Ok(());
+        }
+        buffer.put(encoded.as_slice());
+    }
+    
+    let text_lines = resp.is_none() && !items.is_empty();
+    for (i, item) in items.into_iter().enumerate() {
+        match resp {
+            Some(version) => {
+                encoded.clear();
+                Frame::from(item).encode(version, &mut encoded);
+                buffer.put(encoded.as_slice());
             }
-            Err(_) => {
-                error!("TLS write timeout");
-                Err(DiskDBError::Io(std::io::Error::new(
-                    std::io::ErrorKind::TimedOut,
-                    "Write timeout",
-                )))
+            // The line protocol separates array items with a blank line
+            None => {
+                if i > 0 {
+                    buffer.put_u8(b'\n');
+                }
+                buffer.put(item.to_string().as_bytes());
             }
         }
+        if buffer.len() >= chunk_bytes {
+            OptimizedConnection::write_responses(writer, buffer, output).await?;
+        }
+    }
+    match (resp, text_lines) {
+        (None, true) => buffer.put_u8(b'\n'),
+        (None, false) => buffer.put(Response::Array(Vec::new()).to_string().as_bytes()),
+        _ => {}
     }
+    Ok(())
 }
 
-/// Create an optimized TCP listener
-pub async fn create_optimized_listener(addr: &str) -> Result<tokio::net::TcpListener> {
-    let addr: SocketAddr = addr.parse()
-        .map_err(|e| DiskDBError::Config(format!("Invalid address: {}", e)))?;
-    
-    // Create socket with custom options
-    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
-    
-    // Set socket options before binding
-    socket.set_reuse_address(true)?;
-    
-    #[cfg(unix)]
-    socket.set_reuse_port(true)?;
-    
-    // Set socket buffer sizes
-    let _ = socket.set_recv_buffer_size(256 * 1024);
-    let _ = socket.set_send_buffer_size(256 * 1024);
-    
-    // Bind and listen
-    socket.bind(&addr.into())?;
-    socket.listen(1024)?;
-    
-    // Convert to tokio TcpListener
-    socket.set_nonblocking(true)?;
-    let listener = tokio::net::TcpListener::from_std(socket.into())?;
-    
-    Ok(listener)
+/// Create an optimized TCP listener, sharing its port with other listeners
+pub async fn create_optimized_listener(addr: &str, options: &SocketOptions) -> Result<tokio::net::TcpListener> {
+    listener::bind(addr, options, true)
 }
\ No newline at end of file
@@ -0,0 +1,386 @@
+use crate::config::PubSubOverflow;
+use crate::glob::glob_match;
+use crate::protocol::Response;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::sync::{mpsc, Notify};
+
+type Subscribers = RwLock<HashMap<String, HashMap<u64, Arc<MessageQueue>>>>;
+
+/// Default cap on messages waiting for one subscriber
+pub const DEFAULT_QUEUE_LIMIT: usize = 1024;
+
+/// Message broker backing SUBSCRIBE / PSUBSCRIBE / PUBLISH
+pub struct PubSub {
+    channels: Subscribers,
+    patterns: Subscribers,
+    next_id: AtomicU64,
+    queue_limit: usize,
+    overflow: PubSubOverflow,
+}
+
+impl PubSub {
+    pub fn new() -> Self {
+        Self {
+            channels: RwLock::new(HashMap::new()),
+            patterns: RwLock::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            queue_limit: DEFAULT_QUEUE_LIMIT,
+            overflow: PubSubOverflow::default(),
+        }
+    }
+
+    /// Hold at most `limit` undelivered messages per subscriber, 0 for no limit,
+    /// and handle a full queue per `overflow`
+    pub fn with_queue_limit(mut self, limit: usize, overflow: PubSubOverflow) -> Self {
+        self.queue_limit = limit;
+        self.overflow = overflow;
+        self
+    }
+
+    /// Create the subscription state for a new connection
+    pub fn subscriber(self: &Arc<Self>) -> Subscriber {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Subscriber {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            broker: self.clone(),
+            queue: Arc::new(MessageQueue::new(self.queue_limit, self.overflow)),
+            tx,
+            rx,
+            channels: BTreeSet::new(),
+            patterns: BTreeSet::new(),
+        }
+    }
+
+    /// Deliver a message, returning the number of subscriptions that received it.
+    /// Never waits on a subscriber: one whose queue is full loses its oldest
+    /// message or is disconnected, per the overflow policy.
+    pub fn publish(&self, channel: &str, message: &str) -> usize {
+        let mut delivered = 0;
+
+        if let Ok(channels) = self.channels.read() {
+            if let Some(subscribers) = channels.get(channel) {
+                for queue in subscribers.values() {
+                    let push = Response::Array(vec![
+                        Response::String(Some("message".to_string())),
+                        Response::String(Some(channel.to_string())),
+                        Response::String(Some(message.to_string())),
+                    ]);
+                    if queue.push(push) {
+                        delivered += 1;
+                    }
+                }
+            }
+        }
+
+        if let Ok(patterns) = self.patterns.read() {
+            for (pattern, subscribers) in patterns.iter() {
+                if !glob_match(pattern.as_bytes(), channel.as_bytes()) {
+                    continue;
+                }
+                for queue in subscribers.values() {
+                    let push = Response::Array(vec![
+                        Response::String(Some("pmessage".to_string())),
+                        Response::String(Some(pattern.clone())),
+                        Response::String(Some(channel.to_string())),
+                        Response::String(Some(message.to_string())),
+                    ]);
+                    if queue.push(push) {
+                        delivered += 1;
+                    }
+                }
+            }
+        }
+
+        delivered
+    }
+
+    /// PUBSUB CHANNELS, NUMSUB and NUMPAT. Only read locks are taken, so
+    /// introspection runs alongside publishers.
+    pub fn introspect(&self, subcommand: &str, args: &[String]) -> Response {
+        let name = |name: &str| Response::String(Some(name.to_string()));
+        match (subcommand, args) {
+            ("CHANNELS", [] | [_]) => {
+                let pattern = args.first();
+                let mut channels: Vec<String> = match self.channels.read() {
+                    Ok(channels) => channels.keys()
+                        .filter(|channel| pattern.is_none_or(|pattern| glob_match(pattern.as_bytes(), channel.as_bytes())))
+                        .cloned()
+                        .collect(),
+                    Err(_) => Vec::new(),
+                };
+                channels.sort();
+                Response::Array(channels.iter().map(|channel| name(channel)).collect())
+            }
+            ("NUMSUB", channels) => {
+                let Ok(subscribers) = self.channels.read() else {
+                    return Response::Array(Vec::new());
+                };
+                let mut counts = Vec::with_capacity(channels.len() * 2);
+                for channel in channels {
+                    counts.push(name(channel));
+                    counts.push(Response::Integer(subscribers.get(channel).map_or(0, HashMap::len) as i64));
+                }
+                Response::Array(counts)
+            }
+            ("NUMPAT", []) => Response::Integer(self.patterns.read().map_or(0, |patterns| patterns.len()) as i64),
+            ("CHANNELS" | "NUMPAT", _) => Response::Error(format!(
+                "ERR wrong number of arguments for 'pubsub|{}' command",
+                subcommand.to_lowercase(),
+            )),
+            _ => Response::Error(format!("ERR unknown subcommand '{}'. Try PUBSUB HELP.", subcommand)),
+        }
+    }
+
+    fn add(map: &Subscribers, name: &str, id: u64, queue: Arc<MessageQueue>) {
+        if let Ok(mut map) = map.write() {
+            map.entry(name.to_string()).or_default().insert(id, queue);
+        }
+    }
+
+    fn remove(map: &Subscribers, name: &str, id: u64) {
+        if let Ok(mut map) = map.write() {
+            if let Some(subscribers) = map.get_mut(name) {
+                subscribers.remove(&id);
+                if subscribers.is_empty() {
+                    map.remove(name);
+                }
+            }
+        }
+    }
+}
+
+impl Default for PubSub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Published messages waiting to be written to one subscriber.
+///
+/// Publishers only ever append, so a subscriber that stops reading cannot hold
+/// them up; the cap keeps it from making the server buffer without limit.
+pub struct MessageQueue {
+    state: Mutex<QueueState>,
+    ready: Notify,
+    /// Most messages held; 0 for no limit
+    limit: usize,
+    overflow: PubSubOverflow,
+}
+
+#[derive(Default)]
+struct QueueState {
+    messages: VecDeque<Response>,
+    /// Messages discarded to make room, reported as the subscriber's lag
+    dropped: u64,
+    /// Overflowed under `PubSubOverflow::Disconnect`; takes no more messages
+    closed: bool,
+}
+
+impl MessageQueue {
+    fn new(limit: usize, overflow: PubSubOverflow) -> Self {
+        Self { state: Mutex::default(), ready: Notify::new(), limit, overflow }
+    }
+
+    /// Messages waiting to be written
+    pub fn queued(&self) -> usize {
+        self.state.lock().unwrap().messages.len()
+    }
+
+    /// Messages dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.state.lock().unwrap().dropped
+    }
+
+    /// Queue `message`, returning false when it was refused because the
+    /// subscriber is being disconnected
+    fn push(&self, message: Response) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return false;
+        }
+        if self.limit > 0 && state.messages.len() >= self.limit {
+            match self.overflow {
+                PubSubOverflow::DropOldest => {
+                    state.messages.pop_front();
+                    state.dropped += 1;
+                }
+                PubSubOverflow::Disconnect => {
+                    state.closed = true;
+                    state.messages.clear();
+                    drop(state);
+                    self.ready.notify_one();
+                    return false;
+                }
+            }
+        }
+        state.messages.push_back(message);
+        drop(state);
+        self.ready.notify_one();
+        true
+    }
+
+    /// The oldest message, or None once the queue overflowed and was closed
+    async fn pop(&self) -> Option<Response> {
+        loop {
+            let ready = self.ready.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.closed {
+                    return None;
+                }
+                if let Some(message) = state.messages.pop_front() {
+                    return Some(message);
+                }
+            }
+            ready.await;
+        }
+    }
+
+    fn clear(&self) {
+        self.state.lock().unwrap().messages.clear();
+    }
+}
+
+/// One connection's channel and pattern subscriptions, removed from the broker when dropped
+pub struct Subscriber {
+    id: u64,
+    broker: Arc<PubSub>,
+    /// Published messages
+    queue: Arc<MessageQueue>,
+    /// Other out-of-band messages, such as tracking invalidations
+    tx: mpsc::UnboundedSender<Response>,
+    rx: mpsc::UnboundedReceiver<Response>,
+    channels: BTreeSet<String>,
+    patterns: BTreeSet<String>,
+}
+
+impl Subscriber {
+    /// Sender for out-of-band messages other than published ones, which bypass
+    /// the bounded queue
+    pub fn mailbox(&self) -> mpsc::UnboundedSender<Response> {
+        self.tx.clone()
+    }
+
+    /// This connection's queue of published messages, for its size and lag
+    pub fn queue(&self) -> Arc<MessageQueue> {
+        self.queue.clone()
+    }
+
+    /// Whether the connection is in subscriber mode
+    pub fn is_active(&self) -> bool {
+        self.count() > 0
+    }
+
+    /// Total number of channel and pattern subscriptions
+    pub fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    /// Wait for the next published or out-of-band message. Returns None when the
+    /// queue overflowed under `PubSubOverflow::Disconnect` and the connection is
+    /// to be closed.
+    pub async fn recv(&mut self) -> Option<Response> {
+        tokio::select! {
+            Some(message) = self.rx.recv() => Some(message),
+            message = self.queue.pop() => message,
+        }
+    }
+
+    pub fn subscribe(&mut self, channels: &[String]) -> Response {
+        let mut replies = Vec::with_capacity(channels.len() * 3);
+        for channel in channels {
+            if self.channels.insert(channel.clone()) {
+                PubSub::add(&self.broker.channels, channel, self.id, self.queue.clone());
+            }
+            self.push_reply(&mut replies, "subscribe", Some(channel));
+        }
+        Response::Array(replies)
+    }
+
+    /// Unsubscribe from the given channels, or from all of them when none are given
+    pub fn unsubscribe(&mut self, channels: &[String]) -> Response {
+        let targets: Vec<String> = if channels.is_empty() {
+            self.channels.iter().cloned().collect()
+        } else {
+            channels.to_vec()
+        };
+
+        let mut replies = Vec::with_capacity(targets.len().max(1) * 3);
+        for channel in &targets {
+            if self.channels.remove(channel) {
+                PubSub::remove(&self.broker.channels, channel, self.id);
+            }
+            self.push_reply(&mut replies, "unsubscribe", Some(channel));
+        }
+        if targets.is_empty() {
+            self.push_reply(&mut replies, "unsubscribe", None);
+        }
+        Response::Array(replies)
+    }
+
+    pub fn psubscribe(&mut self, patterns: &[String]) -> Response {
+        let mut replies = Vec::with_capacity(patterns.len() * 3);
+        for pattern in patterns {
+            if self.patterns.insert(pattern.clone()) {
+                PubSub::add(&self.broker.patterns, pattern, self.id, self.queue.clone());
+            }
+            self.push_reply(&mut replies, "psubscribe", Some(pattern));
+        }
+        Response::Array(replies)
+    }
+
+    /// Unsubscribe from the given patterns, or from all of them when none are given
+    pub fn punsubscribe(&mut self, patterns: &[String]) -> Response {
+        let targets: Vec<String> = if patterns.is_empty() {
+            self.patterns.iter().cloned().collect()
+        } else {
+            patterns.to_vec()
+        };
+
+        let mut replies = Vec::with_capacity(targets.len().max(1) * 3);
+        for pattern in &targets {
+            if self.patterns.remove(pattern) {
+                PubSub::remove(&self.broker.patterns, pattern, self.id);
+            }
+            self.push_reply(&mut replies, "punsubscribe", Some(pattern));
+        }
+        if targets.is_empty() {
+            self.push_reply(&mut replies, "punsubscribe", None);
+        }
+        Response::Array(replies)
+    }
+
+    /// Drop every subscription and any messages still waiting to be delivered
+    pub fn reset(&mut self) {
+        for channel in std::mem::take(&mut self.channels) {
+            PubSub::remove(&self.broker.channels, &channel, self.id);
+        }
+        for pattern in std::mem::take(&mut self.patterns) {
+            PubSub::remove(&self.broker.patterns, &pattern, self.id);
+        }
+        self.queue.clear();
+        while self.rx.try_recv().is_ok() {}
+    }
+
+    fn push_reply(&self, replies: &mut Vec<Response>, kind: &str, name: Option<&String>) {
+        replies.push(Response::String(Some(kind.to_string())));
+        replies.push(match name {
+            Some(name) => Response::String(Some(name.clone())),
+            None => Response::Null,
+        });
+        replies.push(Response::Integer(self.count() as i64));
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        for channel in &self.channels {
+            PubSub::remove(&self.broker.channels, channel, self.id);
+        }
+        for pattern in &self.patterns {
+            PubSub::remove(&self.broker.patterns, pattern, self.id);
+        }
+    }
+}
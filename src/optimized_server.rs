@@ -1,14 +1,20 @@
 use crate::commands::CommandExecutor;
 use crate::config::Config;
 use crate::error::Result;
+use crate::expiration::ActiveExpirer;
+use crate::listener::SocketOptions;
 use crate::network::{
     buffer_pool::GLOBAL_BUFFER_POOL,
-    optimized_connection::{create_optimized_listener, OptimizedConnection},
+    client_registry::ClientRegistry,
+    optimized_connection::{create_optimized_listener, ConnectionContext, OptimizedConnection, OutputBufferLimit},
+    pubsub::PubSub,
 };
+use crate::rate_limit::ConnectionRateLimiter;
 use crate::storage::Storage;
 use crate::tls::create_tls_acceptor;
-use log::{error, info};
+use log::{error, info, warn};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio_native_tls::TlsAcceptor;
 
@@ -17,6 +23,8 @@ pub struct OptimizedServer {
     config: Config,
     storage: Arc<dyn Storage>,
     tls_acceptor: Option<TlsAcceptor>,
+    clients: Arc<ClientRegistry>,
+    pubsub: Arc<PubSub>,
 }
 
 impl OptimizedServer {
@@ -32,28 +40,49 @@ impl OptimizedServer {
             None
         };
 
+        let pubsub = Arc::new(PubSub::new().with_queue_limit(config.pubsub_queue_limit, config.pubsub_overflow));
         Ok(Self {
             config,
             storage,
             tls_acceptor,
+            clients: Arc::new(ClientRegistry::new()),
+            pubsub,
         })
     }
 
     pub async fn start(&self) -> Result<()> {
         let addr = format!("0.0.0.0:{}", self.config.server_port);
         
+        let executor = Arc::new(
+            CommandExecutor::new(self.storage.clone())
+                .with_max_value_size(self.config.max_value_size)
+                .with_debug_command(self.config.enable_debug_command)
+                .with_tracking_limit(self.config.tracking_max_keys)
+                .with_latency_threshold(self.config.latency_monitor_threshold_ms)
+                .with_lua_time_limit(self.config.lua_time_limit_ms),
+        );
+        tokio::spawn(
+            ActiveExpirer::new(self.storage.clone(), &self.config)
+                .with_latency_monitor(executor.latency_monitor().clone())
+                .run(),
+        );
+        let libraries = executor.restore_functions().await?;
+        if libraries > 0 {
+            info!("Restored {} function libraries", libraries);
+        }
+        
         // Use io_uring on Linux if available
         #[cfg(all(target_os = "linux", feature = "io_uring"))]
         {
             if !self.config.use_tls {
                 info!("Starting io_uring optimized server on {}", addr);
-                let executor = Arc::new(CommandExecutor::new(self.storage.clone()));
                 return crate::network::io_uring_server::create_io_uring_server(&addr, executor).await;
             }
         }
         
         // Use optimized TCP listener
-        let listener = create_optimized_listener(&addr).await?;
+        let socket_options = SocketOptions::from_config(&self.config);
+        let listener = create_optimized_listener(&addr, &socket_options).await?;
         info!("Optimized server listening on {}", addr);
         
         if self.config.use_tls {
@@ -64,23 +93,48 @@ impl OptimizedServer {
         info!("Pre-allocating network buffers...");
         GLOBAL_BUFFER_POOL.preallocate(200, 100, 20);
 
-        let executor = Arc::new(CommandExecutor::new(self.storage.clone()));
-        let buffer_pool = GLOBAL_BUFFER_POOL.clone();
+        let context = ConnectionContext {
+            executor,
+            buffer_pool: GLOBAL_BUFFER_POOL.clone(),
+            clients: self.clients.clone(),
+            pubsub: self.pubsub.clone(),
+            max_response_bytes: self.config.max_pipeline_response_bytes,
+            stream_array_threshold: self.config.stream_array_threshold,
+            idle_timeout: match self.config.idle_timeout_ms {
+                0 => None,
+                ms => Some(Duration::from_millis(ms)),
+            },
+            slow_command_threshold: match self.config.slow_command_log_us {
+                0 => None,
+                us => Some(Duration::from_micros(us)),
+            },
+            max_value_size: self.config.max_value_size,
+            proto_max_bulk_len: self.config.proto_max_bulk_len,
+            proto_max_multibulk_len: self.config.proto_max_multibulk_len,
+            output_buffer_limit: OutputBufferLimit {
+                hard_bytes: self.config.client_output_buffer_hard_limit,
+                soft_bytes: self.config.client_output_buffer_soft_limit,
+                soft_window: Duration::from_millis(self.config.client_output_buffer_soft_ms),
+            },
+            socket_options,
+        };
+
+        let rate_limiter = ConnectionRateLimiter::new(self.config.max_connections_per_sec, self.config.connection_burst);
+        if let Some(limiter) = &rate_limiter {
+            tokio::spawn(limiter.clone().run_cleanup());
+        }
 
         loop {
             let (stream, addr) = listener.accept().await?;
-            let executor = executor.clone();
+            if rate_limiter.as_ref().is_some_and(|limiter| !limiter.allow(addr.ip())) {
+                warn!("Refusing connection from {}: connection rate limit exceeded", addr);
+                continue;
+            }
             let tls_acceptor = self.tls_acceptor.clone();
-            let buffer_pool = buffer_pool.clone();
+            let context = context.clone();
             
             tokio::spawn(async move {
-                if let Err(e) = Self::handle_client(
-                    stream, 
-                    addr, 
-                    executor, 
-                    tls_acceptor,
-                    buffer_pool,
-                ).await {
+                if let Err(e) = Self::handle_client(stream, addr, tls_acceptor, context).await {
                     error!("Error handling client {}: {}", addr, e);
                 }
             });
@@ -90,12 +144,11 @@ impl OptimizedServer {
     async fn handle_client(
         stream: TcpStream,
         addr: std::net::SocketAddr,
-        executor: Arc<CommandExecutor>,
         tls_acceptor: Option<TlsAcceptor>,
-        buffer_pool: Arc<crate::network::buffer_pool::BufferPool>,
+        context: ConnectionContext,
     ) -> Result<()> {
         // Create optimized connection
-        let mut connection = OptimizedConnection::accept(stream, addr).await?;
+        let mut connection = OptimizedConnection::accept(stream, addr, &context.socket_options).await?;
         
         // Handle TLS if enabled
         if let Some(acceptor) = tls_acceptor {
@@ -116,7 +169,17 @@ impl OptimizedServer {
         }
 
         // Handle with optimizations
-        connection.handle(executor, addr.to_string(), Some(buffer_pool)).await
+        connection.handle(addr.to_string(), context).await
+    }
+    
+    /// Registry of the clients currently connected
+    pub fn clients(&self) -> Arc<ClientRegistry> {
+        self.clients.clone()
+    }
+    
+    /// Broker shared by the pub/sub commands
+    pub fn pubsub(&self) -> Arc<PubSub> {
+        self.pubsub.clone()
     }
     
     /// Get server statistics
@@ -130,7 +193,7 @@ impl OptimizedServer {
                 memory_pool: cfg!(feature = "memory_pool"),
                 io_uring: cfg!(all(target_os = "linux", feature = "io_uring")),
                 vectored_io: true,
-                tcp_nodelay: true,
+                tcp_nodelay: self.config.tcp_nodelay,
                 buffer_pooling: true,
             },
         }
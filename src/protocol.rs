@@ -1,3 +1,5 @@
+use crate::commands::table;
+use crate::data_types::{LexBound, ZAddFlags};
 use crate::error::{DiskDBError, Result};
 use std::fmt;
 
@@ -5,41 +7,77 @@ use std::fmt;
 pub enum Request {
     // String operations
     Get { key: String },
-    Set { key: String, value: String },
+    GetEx { key: String, expiry: GetExOption },
+    Set { key: String, value: String, options: SetOptions },
     Incr { key: String },
     Decr { key: String },
     IncrBy { key: String, delta: i64 },
     DecrBy { key: String, delta: i64 },
+    IncrByFloat { key: String, delta: f64 },
     Append { key: String, value: String },
+    GetRange { key: String, start: i64, end: i64 },
+    StrLen { key: String },
+    /// `LCS key1 key2 [LEN] [IDX] [MINMATCHLEN len] [WITHMATCHLEN]`: longest common
+    /// subsequence of two strings, or its length, or the ranges it matches
+    Lcs { key1: String, key2: String, len_only: bool, idx: bool, minmatchlen: Option<usize>, withmatchlen: bool },
+    BitPos { key: String, bit: u8, start: Option<i64>, end: Option<i64> },
+    BitOp { op: BitOp, dest: String, keys: Vec<String> },
     
     // List operations
     LPush { key: String, values: Vec<String> },
     RPush { key: String, values: Vec<String> },
-    LPop { key: String },
-    RPop { key: String },
+    /// Without a count, pops one element and replies with it alone
+    LPop { key: String, count: Option<usize> },
+    RPop { key: String, count: Option<usize> },
+    LMPop { keys: Vec<String>, from_left: bool, count: usize },
+    BLPop { keys: Vec<String>, timeout_ms: u64 },
+    BRPop { keys: Vec<String>, timeout_ms: u64 },
     LRange { key: String, start: i64, stop: i64 },
     LLen { key: String },
+    LSet { key: String, index: i64, value: String },
+    LInsert { key: String, before: bool, pivot: String, value: String },
+    LTrim { key: String, start: i64, stop: i64 },
     
     // Set operations
     SAdd { key: String, members: Vec<String> },
     SRem { key: String, members: Vec<String> },
     SMembers { key: String },
     SIsMember { key: String, member: String },
+    SMIsMember { key: String, members: Vec<String> },
     SCard { key: String },
+    SScan { key: String, cursor: u64, match_pattern: Option<String>, count: Option<usize> },
     
     // Hash operations
     HSet { key: String, field: String, value: String },
+    HSetNx { key: String, field: String, value: String },
+    HMSet { key: String, pairs: Vec<(String, String)> },
+    HStrLen { key: String, field: String },
     HGet { key: String, field: String },
     HDel { key: String, fields: Vec<String> },
     HGetAll { key: String },
+    HLen { key: String },
     HExists { key: String, field: String },
+    HExpire { key: String, seconds: i64, fields: Vec<String> },
+    HTtl { key: String, fields: Vec<String> },
+    HPersist { key: String, fields: Vec<String> },
+    HScan { key: String, cursor: u64, match_pattern: Option<String>, count: Option<usize> },
     
     // Sorted Set operations
-    ZAdd { key: String, members: Vec<(f64, String)> },
+    ZAdd { key: String, members: Vec<(f64, String)>, flags: ZAddFlags },
     ZRem { key: String, members: Vec<String> },
     ZRange { key: String, start: i64, stop: i64, with_scores: bool },
+    ZRangeByLex { key: String, min: LexBound, max: LexBound, limit: Option<(i64, i64)> },
+    ZLexCount { key: String, min: LexBound, max: LexBound },
+    ZUnionStore { dest: String, keys: Vec<String>, weights: Vec<f64>, aggregate: Aggregate },
+    ZInterStore { dest: String, keys: Vec<String>, weights: Vec<f64>, aggregate: Aggregate },
+    ZDiff { keys: Vec<String>, with_scores: bool },
+    ZDiffStore { dest: String, keys: Vec<String> },
+    ZMPop { keys: Vec<String>, min: bool, count: usize },
+    BZPopMin { keys: Vec<String>, timeout_ms: u64 },
+    BZPopMax { keys: Vec<String>, timeout_ms: u64 },
     ZScore { key: String, member: String },
     ZCard { key: String },
+    ZScan { key: String, cursor: u64, match_pattern: Option<String>, count: Option<usize> },
     
     // JSON operations
     JsonSet { key: String, path: String, value: String },
@@ -51,23 +89,115 @@ pub enum Request {
     XRange { key: String, start: String, end: String, count: Option<usize> },
     XLen { key: String },
     
+    // Expiry operations
+    Expire { key: String, seconds: i64, flags: ExpireFlags },
+    ExpireAt { key: String, timestamp: i64, flags: ExpireFlags },
+    PExpire { key: String, millis: i64, flags: ExpireFlags },
+    PExpireAt { key: String, ms_timestamp: i64, flags: ExpireFlags },
+    Ttl { key: String },
+    PTtl { key: String },
+    
+    // Sort operations
+    Sort {
+        key: String,
+        alpha: bool,
+        desc: bool,
+        limit: Option<(i64, i64)>,
+        by: Option<String>,
+        get: Vec<String>,
+    },
+    
     // Utility operations
     Type { key: String },
+    Dump { key: String },
+    /// `RESTORE <key> <ttl-ms> <payload> [REPLACE] [ABSTTL]`
+    Restore { key: String, ttl: i64, payload: String, replace: bool, absttl: bool },
+    /// `COPY <source> <destination> [DB <index>] [REPLACE]`
+    Copy { source: String, destination: String, db: Option<i64>, replace: bool },
+    ObjectFreq { key: String },
+    ObjectEncoding { key: String },
     Del { keys: Vec<String> },
+    Unlink { keys: Vec<String> },
+    /// `DELPREFIX <prefix>`: delete every key starting with `prefix` by key range
+    DelPrefix { prefix: String },
     Exists { keys: Vec<String> },
     Ping,
     Echo { message: String },
-    FlushDb,
+    /// `FLUSHDB [ASYNC|SYNC]`: delete every key of the database; with ASYNC the
+    /// space is freed in the background
+    FlushDb { lazy: bool },
+    /// `FLUSHALL [ASYNC|SYNC]`: delete every key of every database
+    FlushAll { lazy: bool },
+    DbSize,
+    RandomKey,
+    Scan { cursor: u64, match_pattern: Option<String>, count: Option<usize>, type_filter: Option<String> },
     Info,
+    Version,
+    Wait { numreplicas: usize, timeout_ms: u64 },
+    WaitAof { numlocal: usize, numreplicas: usize, timeout_ms: u64 },
+    Command { subcommand: Option<String>, args: Vec<String> },
+    Memory { subcommand: String, args: Vec<String> },
+    /// `CLUSTER <subcommand> [args]`, answered as a single node without clustering
+    Cluster { subcommand: String, args: Vec<String> },
+    Latency { subcommand: String, args: Vec<String> },
+    PoolStats,
+    /// `<command> HELP` for any command listed in the subcommand help table
+    Help { command: &'static str },
+    DebugReload,
+    /// `DEBUG LISTPACK-LIMITS <entries> <bytes>`: size under which lists use the compact encoding
+    DebugListpackLimits { entries: usize, bytes: usize },
+    /// `DEBUG STRINGMATCH-LEN <pattern> <string>`: run the glob matcher on its own
+    DebugStringMatch { pattern: String, text: String },
+    /// `DEBUG SET-TIME <ms>`: stop a mock clock at `millis` since the Unix epoch
+    DebugSetTime { millis: u64 },
+    /// `DEBUG ADVANCE-TIME <ms>`: move a mock clock forward
+    DebugAdvanceTime { millis: u64 },
+    DebugSleep { millis: u64 },
+    DebugObjectCheck { key: String },
+    /// Create `count` string keys named `<prefix>:<n>` that do not exist yet
+    DebugPopulate { count: u64, prefix: String, size: Option<usize> },
+    GetWriteOffset,
+    /// `MINOFFSET <offset> <read command...>`: run the read only once the write
+    /// offset has reached `offset`
+    MinOffset { offset: u64, request: Box<Request> },
+    
+    // Scripting operations
+    Eval { script: String, keys: Vec<String>, args: Vec<String> },
+    EvalSha { sha: String, keys: Vec<String>, args: Vec<String> },
+    ScriptLoad { script: String },
+    FunctionLoad { code: String, replace: bool },
+    FCall { name: String, keys: Vec<String>, args: Vec<String> },
+    FunctionList,
+    
+    // Connection operations
+    Client { subcommand: String, args: Vec<String> },
+    Reset,
+    /// Reply OK and close the connection
+    Quit,
+    Hello { protover: Option<u32>, auth: Option<(String, String)> },
+    /// Stream every command the server processes to this connection
+    Monitor,
+    
+    // Pub/Sub operations
+    Subscribe { channels: Vec<String> },
+    Unsubscribe { channels: Vec<String> },
+    PSubscribe { patterns: Vec<String> },
+    PUnsubscribe { patterns: Vec<String> },
+    Publish { channel: String, message: String },
+    /// `PUBSUB CHANNELS|NUMSUB|NUMPAT`; the subcommand is uppercased
+    PubSub { subcommand: String, args: Vec<String> },
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum Response {
     Ok,
     String(Option<String>),
     Integer(i64),
     Array(Vec<Response>),
     Null,
+    /// No array at all, such as a blocking pop that timed out. RESP2 tells it
+    /// apart from both a null string and an empty array.
+    NullArray,
     Error(String),
 }
 
@@ -103,6 +233,360 @@ impl Response {
             Ok(Response::String(Some(trimmed.to_string())))
         }
     }
+    
+    /// Render as JSON for tools and HTTP APIs: OK becomes `"OK"`, a missing
+    /// string null, arrays nest, and an error `{"error": "<message>"}`
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Response::Ok => serde_json::Value::from("OK"),
+            Response::String(Some(s)) => serde_json::Value::from(s.as_str()),
+            Response::String(None) | Response::Null | Response::NullArray => serde_json::Value::Null,
+            Response::Integer(n) => serde_json::Value::from(*n),
+            Response::Array(items) => serde_json::Value::Array(items.iter().map(Response::to_json).collect()),
+            Response::Error(msg) => serde_json::json!({ "error": msg }),
+        }
+    }
+}
+
+/// Expiry change applied by GETEX alongside the read
+#[derive(Debug, Clone, PartialEq)]
+pub enum GetExOption {
+    /// Leave the expiry as it is
+    None,
+    Ex(i64),
+    Px(i64),
+    ExAt(i64),
+    PxAt(i64),
+    /// Remove any existing expiry
+    Persist,
+}
+
+/// Expiry given to a key by SET
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SetExpiry {
+    /// Remove any existing expiry
+    #[default]
+    Clear,
+    Ex(i64),
+    Px(i64),
+    ExAt(i64),
+    PxAt(i64),
+    /// Leave the existing expiry as it is
+    KeepTtl,
+}
+
+/// Conditions, reply mode and expiry for SET; the default always sets and clears the expiry
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SetOptions {
+    /// Only set a key that does not exist
+    pub nx: bool,
+    /// Only set a key that already exists
+    pub xx: bool,
+    /// Reply with the previous value, or nil, instead of OK
+    pub get: bool,
+    pub expiry: SetExpiry,
+}
+
+impl SetOptions {
+    /// Whether `token` starts SET's options rather than continuing its value
+    fn is_option(token: &str) -> bool {
+        ["NX", "XX", "GET", "EX", "PX", "EXAT", "PXAT", "KEEPTTL"].iter().any(|name| token.eq_ignore_ascii_case(name))
+    }
+}
+
+impl fmt::Display for SetOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (set, name) in [(self.nx, "NX"), (self.xx, "XX"), (self.get, "GET")] {
+            if set {
+                write!(f, " {}", name)?;
+            }
+        }
+        match self.expiry {
+            SetExpiry::Clear => Ok(()),
+            SetExpiry::Ex(seconds) => write!(f, " EX {}", seconds),
+            SetExpiry::Px(millis) => write!(f, " PX {}", millis),
+            SetExpiry::ExAt(timestamp) => write!(f, " EXAT {}", timestamp),
+            SetExpiry::PxAt(ms_timestamp) => write!(f, " PXAT {}", ms_timestamp),
+            SetExpiry::KeepTtl => write!(f, " KEEPTTL"),
+        }
+    }
+}
+
+/// Conditions on EXPIRE, PEXPIRE, EXPIREAT and PEXPIREAT; none set means always apply
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExpireFlags {
+    /// Only set an expiry on a key without one
+    pub nx: bool,
+    /// Only change an existing expiry
+    pub xx: bool,
+    /// Only move the expiry later; a key without one never expires, so it never applies there
+    pub gt: bool,
+    /// Only move the expiry earlier; always applies to a key without one
+    pub lt: bool,
+}
+
+impl ExpireFlags {
+    /// Whether a new deadline of `deadline_ms` may replace `current`, the key's deadline if any
+    pub fn allows(&self, current: Option<u64>, deadline_ms: i64) -> bool {
+        match current {
+            None => !self.xx && !self.gt,
+            Some(current) => {
+                !self.nx
+                    && (!self.gt || deadline_ms > current as i64)
+                    && (!self.lt || deadline_ms < current as i64)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ExpireFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (set, name) in [(self.nx, "NX"), (self.xx, "XX"), (self.gt, "GT"), (self.lt, "LT")] {
+            if set {
+                write!(f, " {}", name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bitwise operation applied by BITOP
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+impl BitOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BitOp::And => "AND",
+            BitOp::Or => "OR",
+            BitOp::Xor => "XOR",
+            BitOp::Not => "NOT",
+        }
+    }
+}
+
+/// How ZUNIONSTORE and ZINTERSTORE combine the scores of a member found in several inputs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aggregate::Sum => "SUM",
+            Aggregate::Min => "MIN",
+            Aggregate::Max => "MAX",
+        }
+    }
+    
+    /// Combine two weighted scores; `inf + -inf` sums to 0, as in Redis
+    pub fn combine(&self, a: f64, b: f64) -> f64 {
+        match self {
+            Aggregate::Sum => {
+                let sum = a + b;
+                if sum.is_nan() { 0.0 } else { sum }
+            }
+            Aggregate::Min => a.min(b),
+            Aggregate::Max => a.max(b),
+        }
+    }
+}
+
+/// What went wrong while parsing a command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The line holds no tokens
+    Empty,
+    /// The command name is not recognised
+    UnknownCommand,
+    /// Missing or surplus arguments for the command
+    WrongArity,
+    /// An argument that must be an integer is not one
+    InvalidInteger,
+    /// An argument that must be a number is not one
+    InvalidFloat,
+    /// Any other malformed argument, such as an unknown option
+    Syntax,
+}
+
+/// Split a command line into arguments separated by whitespace, like redis-cli's
+/// inline parsing.
+///
+/// An argument may be quoted to hold whitespace or be empty. Double quotes
+/// understand the escapes `\n`, `\r`, `\t`, `\b`, `\a`, `\\`, `\"` and `\xHH`;
+/// single quotes only `\'`. A closing quote must end the argument. Quotes inside
+/// an unquoted argument are kept as ordinary characters.
+pub fn split_args(input: &str) -> ParseResult<Vec<String>> {
+    split_args_limited(input, usize::MAX)
+}
+
+/// Split a command line like `split_args`, failing as soon as it holds more
+/// than `max_args` arguments
+pub fn split_args_limited(input: &str, max_args: usize) -> ParseResult<Vec<String>> {
+    let unbalanced = |index| ParseError::syntax("Unbalanced quotes in request".to_string()).at(index);
+    let hex_digit = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let mut args = Vec::new();
+    let mut bytes = input.bytes().peekable();
+    loop {
+        while bytes.next_if(|b| b.is_ascii_whitespace()).is_some() {}
+        if args.len() == max_args && bytes.peek().is_some() {
+            return Err(ParseError::syntax(format!("Protocol error: more than {} arguments", max_args)).at(max_args));
+        }
+        let quote = match bytes.peek() {
+            None => return Ok(args),
+            Some(b'"') | Some(b'\'') => bytes.next(),
+            Some(_) => None,
+        };
+        
+        // Collected as bytes so \xHH escapes can spell out multi-byte UTF-8
+        let mut arg = Vec::new();
+        match quote {
+            Some(quote) => {
+                loop {
+                    match bytes.next() {
+                        None => return Err(unbalanced(args.len())),
+                        Some(b) if b == quote => break,
+                        Some(b'\\') if quote == b'"' => match bytes.next() {
+                            Some(b'n') => arg.push(b'\n'),
+                            Some(b'r') => arg.push(b'\r'),
+                            Some(b't') => arg.push(b'\t'),
+                            Some(b'b') => arg.push(0x08),
+                            Some(b'a') => arg.push(0x07),
+                            Some(b'x') => {
+                                let mut lookahead = bytes.clone();
+                                match (lookahead.next().and_then(hex_digit), lookahead.next().and_then(hex_digit)) {
+                                    (Some(hi), Some(lo)) => {
+                                        arg.push((hi << 4) | lo);
+                                        bytes = lookahead;
+                                    }
+                                    // Not a hex escape: keep the x, as redis-cli does
+                                    _ => arg.push(b'x'),
+                                }
+                            }
+                            Some(b) => arg.push(b),
+                            None => return Err(unbalanced(args.len())),
+                        },
+                        Some(b'\\') if bytes.next_if_eq(&b'\'').is_some() => arg.push(b'\''),
+                        Some(b) => arg.push(b),
+                    }
+                }
+                if bytes.peek().is_some_and(|b| !b.is_ascii_whitespace()) {
+                    return Err(ParseError::syntax("Closing quote must be followed by a space".to_string()).at(args.len()));
+                }
+            }
+            None => {
+                while let Some(b) = bytes.next_if(|b| !b.is_ascii_whitespace()) {
+                    arg.push(b);
+                }
+            }
+        }
+        let arg = String::from_utf8(arg)
+            .map_err(|_| ParseError::syntax("Argument is not valid UTF-8".to_string()).at(args.len()))?;
+        args.push(arg);
+    }
+}
+
+/// Quote an argument the way Redis prints one: in double quotes, with `\n`,
+/// `\r`, `\t`, `\b`, `\a`, `\\` and `\"` escaped and any other byte outside
+/// printable ASCII as `\xHH`. `split_args` reads it back unchanged.
+pub fn quote_arg(arg: &str) -> String {
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('"');
+    for b in arg.bytes() {
+        match b {
+            b'\\' => quoted.push_str("\\\\"),
+            b'"' => quoted.push_str("\\\""),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            0x08 => quoted.push_str("\\b"),
+            0x07 => quoted.push_str("\\a"),
+            b' '..=b'~' => quoted.push(b as char),
+            _ => quoted.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Join arguments into a command line that `split_args` splits back into the
+/// same arguments, quoting only those that need it
+pub fn join_args<S: AsRef<str>>(args: &[S]) -> String {
+    let plain = |arg: &str| !arg.is_empty() && arg.bytes().all(|b| b.is_ascii_graphic() && b != b'"' && b != b'\'');
+    args.iter()
+        .map(|arg| match arg.as_ref() {
+            arg if plain(arg) => arg.to_string(),
+            arg => quote_arg(arg),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Structured parse failure returned by `Request::parse_detailed`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Upper-cased command name; empty for an empty line
+    pub command: String,
+    /// Index of the offending token, counting the command name as 0, when known
+    pub arg_index: Option<usize>,
+    pub kind: ParseErrorKind,
+    /// The same text `parse_rust` reports
+    pub message: String,
+}
+
+type ParseResult<T> = std::result::Result<T, ParseError>;
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, arg_index: Option<usize>, message: impl Into<String>) -> Self {
+        Self { command: String::new(), arg_index, kind, message: message.into() }
+    }
+    
+    fn wrong_arity(message: String) -> Self {
+        Self::new(ParseErrorKind::WrongArity, None, message)
+    }
+    
+    fn invalid_integer(index: usize, message: String) -> Self {
+        Self::new(ParseErrorKind::InvalidInteger, Some(index), message)
+    }
+    
+    fn invalid_float(index: usize, message: String) -> Self {
+        Self::new(ParseErrorKind::InvalidFloat, Some(index), message)
+    }
+    
+    fn syntax(message: String) -> Self {
+        Self::new(ParseErrorKind::Syntax, None, message)
+    }
+    
+    /// Point the error at the token with the given index
+    fn at(mut self, index: usize) -> Self {
+        self.arg_index = Some(index);
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for DiskDBError {
+    fn from(e: ParseError) -> Self {
+        match e.kind {
+            ParseErrorKind::UnknownCommand => DiskDBError::InvalidCommand(e.message),
+            _ => DiskDBError::Protocol(e.message),
+        }
+    }
 }
 
 impl Request {
@@ -110,39 +594,129 @@ impl Request {
     pub fn to_string(&self) -> String {
         match self {
             Request::Get { key } => format!("GET {}", key),
-            Request::Set { key, value } => format!("SET {} {}", key, value),
+            Request::GetEx { key, expiry } => match expiry {
+                GetExOption::None => format!("GETEX {}", key),
+                GetExOption::Ex(seconds) => format!("GETEX {} EX {}", key, seconds),
+                GetExOption::Px(millis) => format!("GETEX {} PX {}", key, millis),
+                GetExOption::ExAt(timestamp) => format!("GETEX {} EXAT {}", key, timestamp),
+                GetExOption::PxAt(ms_timestamp) => format!("GETEX {} PXAT {}", key, ms_timestamp),
+                GetExOption::Persist => format!("GETEX {} PERSIST", key),
+            },
+            Request::Set { key, value, options } => format!("SET {} {}{}", key, value, options),
             Request::Del { keys } => format!("DEL {}", keys.join(" ")),
+            Request::Unlink { keys } => format!("UNLINK {}", keys.join(" ")),
+            Request::DelPrefix { prefix } => format!("DELPREFIX {}", prefix),
             Request::Exists { keys } => format!("EXISTS {}", keys.join(" ")),
             Request::Type { key } => format!("TYPE {}", key),
+            Request::Dump { key } => format!("DUMP {}", key),
+            Request::Restore { key, ttl, payload, replace, absttl } => format!(
+                "RESTORE {} {} {}{}{}",
+                key,
+                ttl,
+                payload,
+                if *replace { " REPLACE" } else { "" },
+                if *absttl { " ABSTTL" } else { "" },
+            ),
+            Request::Copy { source, destination, db, replace } => format!(
+                "COPY {} {}{}{}",
+                source,
+                destination,
+                db.map(|db| format!(" DB {}", db)).unwrap_or_default(),
+                if *replace { " REPLACE" } else { "" },
+            ),
+            Request::ObjectFreq { key } => format!("OBJECT FREQ {}", key),
+            Request::ObjectEncoding { key } => format!("OBJECT ENCODING {}", key),
             Request::Incr { key } => format!("INCR {}", key),
             Request::Decr { key } => format!("DECR {}", key),
             Request::IncrBy { key, delta } => format!("INCRBY {} {}", key, delta),
             Request::DecrBy { key, delta } => format!("DECRBY {} {}", key, delta),
+            Request::IncrByFloat { key, delta } => format!("INCRBYFLOAT {} {}", key, delta),
             Request::Append { key, value } => format!("APPEND {} {}", key, value),
+            Request::GetRange { key, start, end } => format!("GETRANGE {} {} {}", key, start, end),
+            Request::StrLen { key } => format!("STRLEN {}", key),
+            Request::Lcs { key1, key2, len_only, idx, minmatchlen, withmatchlen } => format!(
+                "LCS {} {}{}{}{}{}",
+                key1,
+                key2,
+                if *len_only { " LEN" } else { "" },
+                if *idx { " IDX" } else { "" },
+                minmatchlen.map(|len| format!(" MINMATCHLEN {}", len)).unwrap_or_default(),
+                if *withmatchlen { " WITHMATCHLEN" } else { "" },
+            ),
+            Request::BitPos { key, bit, start, end } => match (start, end) {
+                (Some(start), Some(end)) => format!("BITPOS {} {} {} {}", key, bit, start, end),
+                (Some(start), None) => format!("BITPOS {} {} {}", key, bit, start),
+                _ => format!("BITPOS {} {}", key, bit),
+            },
+            Request::BitOp { op, dest, keys } => format!("BITOP {} {} {}", op.name(), dest, keys.join(" ")),
             Request::LPush { key, values } => format!("LPUSH {} {}", key, values.join(" ")),
             Request::RPush { key, values } => format!("RPUSH {} {}", key, values.join(" ")),
-            Request::LPop { key } => format!("LPOP {}", key),
-            Request::RPop { key } => format!("RPOP {}", key),
+            Request::LPop { key, count: None } => format!("LPOP {}", key),
+            Request::LPop { key, count: Some(count) } => format!("LPOP {} {}", key, count),
+            Request::RPop { key, count: None } => format!("RPOP {}", key),
+            Request::RPop { key, count: Some(count) } => format!("RPOP {} {}", key, count),
+            Request::LMPop { keys, from_left, count } => {
+                let side = if *from_left { "LEFT" } else { "RIGHT" };
+                format!("LMPOP {} {} {} COUNT {}", keys.len(), keys.join(" "), side, count)
+            }
+            Request::BLPop { keys, timeout_ms } => format!("BLPOP {} {}", keys.join(" "), *timeout_ms as f64 / 1000.0),
+            Request::BRPop { keys, timeout_ms } => format!("BRPOP {} {}", keys.join(" "), *timeout_ms as f64 / 1000.0),
             Request::LRange { key, start, stop } => format!("LRANGE {} {} {}", key, start, stop),
             Request::LLen { key } => format!("LLEN {}", key),
+            Request::LSet { key, index, value } => format!("LSET {} {} {}", key, index, value),
+            Request::LInsert { key, before, pivot, value } => {
+                format!("LINSERT {} {} {} {}", key, if *before { "BEFORE" } else { "AFTER" }, pivot, value)
+            }
+            Request::LTrim { key, start, stop } => format!("LTRIM {} {} {}", key, start, stop),
             Request::SAdd { key, members } => format!("SADD {} {}", key, members.join(" ")),
             Request::SRem { key, members } => format!("SREM {} {}", key, members.join(" ")),
             Request::SMembers { key } => format!("SMEMBERS {}", key),
             Request::SIsMember { key, member } => format!("SISMEMBER {} {}", key, member),
+            Request::SMIsMember { key, members } => format!("SMISMEMBER {} {}", key, members.join(" ")),
             Request::SCard { key } => format!("SCARD {}", key),
+            Request::SScan { key, cursor, match_pattern, count } => {
+                Self::scan_to_string("SSCAN", key, *cursor, match_pattern, *count)
+            }
             Request::HSet { key, field, value } => format!("HSET {} {} {}", key, field, value),
+            Request::HSetNx { key, field, value } => format!("HSETNX {} {} {}", key, field, value),
+            Request::HMSet { key, pairs } => {
+                let pairs: Vec<String> = pairs.iter().map(|(field, value)| format!("{} {}", field, value)).collect();
+                format!("HMSET {} {}", key, pairs.join(" "))
+            }
+            Request::HStrLen { key, field } => format!("HSTRLEN {} {}", key, field),
             Request::HGet { key, field } => format!("HGET {} {}", key, field),
             Request::HDel { key, fields } => format!("HDEL {} {}", key, fields.join(" ")),
             Request::HGetAll { key } => format!("HGETALL {}", key),
+            Request::HLen { key } => format!("HLEN {}", key),
             Request::HExists { key, field } => format!("HEXISTS {} {}", key, field),
-            Request::ZAdd { key, members } => {
-                let pairs: Vec<String> = members.iter()
-                    .map(|(score, member)| format!("{} {}", score, member))
+            Request::HExpire { key, seconds, fields } => {
+                format!("HEXPIRE {} {} FIELDS {} {}", key, seconds, fields.len(), fields.join(" "))
+            }
+            Request::HTtl { key, fields } => format!("HTTL {} FIELDS {} {}", key, fields.len(), fields.join(" ")),
+            Request::HPersist { key, fields } => format!("HPERSIST {} FIELDS {} {}", key, fields.len(), fields.join(" ")),
+            Request::HScan { key, cursor, match_pattern, count } => {
+                Self::scan_to_string("HSCAN", key, *cursor, match_pattern, *count)
+            }
+            Request::ZAdd { key, members, flags } => {
+                let options = [
+                    (flags.nx, "NX"), (flags.xx, "XX"), (flags.gt, "GT"),
+                    (flags.lt, "LT"), (flags.ch, "CH"), (flags.incr, "INCR"),
+                ];
+                let mut parts: Vec<String> = options.iter()
+                    .filter(|(set, _)| *set)
+                    .map(|(_, name)| name.to_string())
                     .collect();
-                format!("ZADD {} {}", key, pairs.join(" "))
+                parts.extend(members.iter().map(|(score, member)| format!("{} {}", score, member)));
+                format!("ZADD {} {}", key, parts.join(" "))
             }
             Request::ZRem { key, members } => format!("ZREM {} {}", key, members.join(" ")),
             Request::ZScore { key, member } => format!("ZSCORE {} {}", key, member),
+            Request::ZMPop { keys, min, count } => {
+                let side = if *min { "MIN" } else { "MAX" };
+                format!("ZMPOP {} {} {} COUNT {}", keys.len(), keys.join(" "), side, count)
+            }
+            Request::BZPopMin { keys, timeout_ms } => format!("BZPOPMIN {} {}", keys.join(" "), *timeout_ms as f64 / 1000.0),
+            Request::BZPopMax { keys, timeout_ms } => format!("BZPOPMAX {} {}", keys.join(" "), *timeout_ms as f64 / 1000.0),
             Request::ZRange { key, start, stop, with_scores } => {
                 if *with_scores {
                     format!("ZRANGE {} {} {} WITHSCORES", key, start, stop)
@@ -150,7 +724,26 @@ impl Request {
                     format!("ZRANGE {} {} {}", key, start, stop)
                 }
             }
+            Request::ZRangeByLex { key, min, max, limit } => match limit {
+                Some((offset, count)) => format!("ZRANGEBYLEX {} {} {} LIMIT {} {}", key, min, max, offset, count),
+                None => format!("ZRANGEBYLEX {} {} {}", key, min, max),
+            },
+            Request::ZLexCount { key, min, max } => format!("ZLEXCOUNT {} {} {}", key, min, max),
+            Request::ZUnionStore { dest, keys, weights, aggregate } => {
+                Self::zstore_to_string("ZUNIONSTORE", dest, keys, weights, *aggregate)
+            }
+            Request::ZInterStore { dest, keys, weights, aggregate } => {
+                Self::zstore_to_string("ZINTERSTORE", dest, keys, weights, *aggregate)
+            }
+            Request::ZDiff { keys, with_scores } => {
+                let suffix = if *with_scores { " WITHSCORES" } else { "" };
+                format!("ZDIFF {} {}{}", keys.len(), keys.join(" "), suffix)
+            }
+            Request::ZDiffStore { dest, keys } => format!("ZDIFFSTORE {} {} {}", dest, keys.len(), keys.join(" ")),
             Request::ZCard { key } => format!("ZCARD {}", key),
+            Request::ZScan { key, cursor, match_pattern, count } => {
+                Self::scan_to_string("ZSCAN", key, *cursor, match_pattern, *count)
+            }
             Request::JsonSet { key, path, value } => format!("JSON.SET {} {} {}", key, path, value),
             Request::JsonGet { key, path } => format!("JSON.GET {} {}", key, path),
             Request::JsonDel { key, path } => format!("JSON.DEL {} {}", key, path),
@@ -169,19 +762,529 @@ impl Request {
                 }
             }
             Request::XLen { key } => format!("XLEN {}", key),
+            Request::Expire { key, seconds, flags } => format!("EXPIRE {} {}{}", key, seconds, flags),
+            Request::ExpireAt { key, timestamp, flags } => format!("EXPIREAT {} {}{}", key, timestamp, flags),
+            Request::PExpire { key, millis, flags } => format!("PEXPIRE {} {}{}", key, millis, flags),
+            Request::PExpireAt { key, ms_timestamp, flags } => format!("PEXPIREAT {} {}{}", key, ms_timestamp, flags),
+            Request::Ttl { key } => format!("TTL {}", key),
+            Request::PTtl { key } => format!("PTTL {}", key),
+            Request::Sort { key, alpha, desc, limit, by, get } => {
+                let mut cmd = format!("SORT {}", key);
+                if let Some(pattern) = by {
+                    cmd.push_str(&format!(" BY {}", pattern));
+                }
+                if let Some((offset, count)) = limit {
+                    cmd.push_str(&format!(" LIMIT {} {}", offset, count));
+                }
+                for pattern in get {
+                    cmd.push_str(&format!(" GET {}", pattern));
+                }
+                if *desc {
+                    cmd.push_str(" DESC");
+                }
+                if *alpha {
+                    cmd.push_str(" ALPHA");
+                }
+                cmd
+            }
             Request::Ping => "PING".to_string(),
             Request::Echo { message } => format!("ECHO {}", message),
-            Request::FlushDb => "FLUSHDB".to_string(),
+            Request::FlushDb { lazy } => if *lazy { "FLUSHDB ASYNC" } else { "FLUSHDB" }.to_string(),
+            Request::FlushAll { lazy } => if *lazy { "FLUSHALL ASYNC" } else { "FLUSHALL" }.to_string(),
+            Request::DbSize => "DBSIZE".to_string(),
+            Request::RandomKey => "RANDOMKEY".to_string(),
+            Request::Scan { cursor, match_pattern, count, type_filter } => {
+                let mut cmd = format!("SCAN {}", cursor);
+                if let Some(pattern) = match_pattern {
+                    cmd.push_str(&format!(" MATCH {}", pattern));
+                }
+                if let Some(count) = count {
+                    cmd.push_str(&format!(" COUNT {}", count));
+                }
+                if let Some(type_name) = type_filter {
+                    cmd.push_str(&format!(" TYPE {}", type_name));
+                }
+                cmd
+            }
             Request::Info => "INFO".to_string(),
+            Request::Version => "VERSION".to_string(),
+            Request::Wait { numreplicas, timeout_ms } => format!("WAIT {} {}", numreplicas, timeout_ms),
+            Request::WaitAof { numlocal, numreplicas, timeout_ms } => format!("WAITAOF {} {} {}", numlocal, numreplicas, timeout_ms),
+            Request::Command { subcommand, args } => {
+                let mut parts = vec!["COMMAND".to_string()];
+                parts.extend(subcommand.iter().cloned());
+                parts.extend(args.iter().cloned());
+                parts.join(" ")
+            }
+            Request::Memory { subcommand, args } => {
+                if args.is_empty() {
+                    format!("MEMORY {}", subcommand)
+                } else {
+                    format!("MEMORY {} {}", subcommand, args.join(" "))
+                }
+            }
+            Request::Cluster { subcommand, args } => {
+                if args.is_empty() {
+                    format!("CLUSTER {}", subcommand)
+                } else {
+                    format!("CLUSTER {} {}", subcommand, args.join(" "))
+                }
+            }
+            Request::Latency { subcommand, args } => {
+                if args.is_empty() {
+                    format!("LATENCY {}", subcommand)
+                } else {
+                    format!("LATENCY {} {}", subcommand, args.join(" "))
+                }
+            }
+            Request::PoolStats => "POOL STATS".to_string(),
+            Request::Help { command } => format!("{} HELP", command.to_uppercase()),
+            Request::DebugReload => "DEBUG RELOAD".to_string(),
+            Request::DebugListpackLimits { entries, bytes } => format!("DEBUG LISTPACK-LIMITS {} {}", entries, bytes),
+            Request::DebugStringMatch { pattern, text } => format!("DEBUG STRINGMATCH-LEN {} {}", pattern, text),
+            Request::DebugSetTime { millis } => format!("DEBUG SET-TIME {}", millis),
+            Request::DebugAdvanceTime { millis } => format!("DEBUG ADVANCE-TIME {}", millis),
+            Request::DebugSleep { millis } => format!("DEBUG SLEEP {}", *millis as f64 / 1000.0),
+            Request::DebugObjectCheck { key } => format!("DEBUG OBJECT-CHECK {}", key),
+            Request::DebugPopulate { count, prefix, size } => match size {
+                Some(size) => format!("DEBUG POPULATE {} {} {}", count, prefix, size),
+                None => format!("DEBUG POPULATE {} {}", count, prefix),
+            },
+            Request::GetWriteOffset => "GETWRITEOFFSET".to_string(),
+            Request::MinOffset { offset, request } => format!("MINOFFSET {} {}", offset, request.to_string()),
+            Request::Eval { script, keys, args } => Self::eval_to_string("EVAL", script, keys, args),
+            Request::EvalSha { sha, keys, args } => Self::eval_to_string("EVALSHA", sha, keys, args),
+            Request::ScriptLoad { script } => format!("SCRIPT LOAD {}", script),
+            Request::FunctionLoad { code, replace } => {
+                format!("FUNCTION LOAD {}{}", if *replace { "REPLACE " } else { "" }, code)
+            }
+            Request::FCall { name, keys, args } => Self::eval_to_string("FCALL", name, keys, args),
+            Request::FunctionList => "FUNCTION LIST".to_string(),
+            Request::Client { subcommand, args } => {
+                if args.is_empty() {
+                    format!("CLIENT {}", subcommand)
+                } else {
+                    format!("CLIENT {} {}", subcommand, args.join(" "))
+                }
+            }
+            Request::Reset => "RESET".to_string(),
+            Request::Quit => "QUIT".to_string(),
+            Request::Monitor => "MONITOR".to_string(),
+            Request::Hello { protover, auth } => {
+                let mut cmd = "HELLO".to_string();
+                if let Some(protover) = protover {
+                    cmd.push_str(&format!(" {}", protover));
+                }
+                if let Some((username, password)) = auth {
+                    cmd.push_str(&format!(" AUTH {} {}", username, password));
+                }
+                cmd
+            }
+            Request::Subscribe { channels } => format!("SUBSCRIBE {}", channels.join(" ")),
+            Request::Unsubscribe { channels } => format!("UNSUBSCRIBE {}", channels.join(" ")).trim_end().to_string(),
+            Request::PSubscribe { patterns } => format!("PSUBSCRIBE {}", patterns.join(" ")),
+            Request::PUnsubscribe { patterns } => format!("PUNSUBSCRIBE {}", patterns.join(" ")).trim_end().to_string(),
+            Request::Publish { channel, message } => format!("PUBLISH {} {}", channel, message),
+            Request::PubSub { subcommand, args } => format!("PUBSUB {} {}", subcommand, args.join(" ")).trim_end().to_string(),
         }
     }
 }
 
 impl Request {
+    /// Lowercase name of the command, as listed in the command table
+    pub fn command_name(&self) -> &'static str {
+        match self {
+            Request::Get { .. } => "get",
+            Request::GetEx { .. } => "getex",
+            Request::Set { .. } => "set",
+            Request::Incr { .. } => "incr",
+            Request::Decr { .. } => "decr",
+            Request::IncrBy { .. } => "incrby",
+            Request::DecrBy { .. } => "decrby",
+            Request::IncrByFloat { .. } => "incrbyfloat",
+            Request::Append { .. } => "append",
+            Request::GetRange { .. } => "getrange",
+            Request::StrLen { .. } => "strlen",
+            Request::Lcs { .. } => "lcs",
+            Request::BitPos { .. } => "bitpos",
+            Request::BitOp { .. } => "bitop",
+            Request::LPush { .. } => "lpush",
+            Request::RPush { .. } => "rpush",
+            Request::LPop { .. } => "lpop",
+            Request::RPop { .. } => "rpop",
+            Request::LMPop { .. } => "lmpop",
+            Request::BLPop { .. } => "blpop",
+            Request::BRPop { .. } => "brpop",
+            Request::LRange { .. } => "lrange",
+            Request::LLen { .. } => "llen",
+            Request::LSet { .. } => "lset",
+            Request::LInsert { .. } => "linsert",
+            Request::LTrim { .. } => "ltrim",
+            Request::SAdd { .. } => "sadd",
+            Request::SRem { .. } => "srem",
+            Request::SMembers { .. } => "smembers",
+            Request::SIsMember { .. } => "sismember",
+            Request::SMIsMember { .. } => "smismember",
+            Request::SCard { .. } => "scard",
+            Request::SScan { .. } => "sscan",
+            Request::HSet { .. } => "hset",
+            Request::HSetNx { .. } => "hsetnx",
+            Request::HMSet { .. } => "hmset",
+            Request::HStrLen { .. } => "hstrlen",
+            Request::HGet { .. } => "hget",
+            Request::HDel { .. } => "hdel",
+            Request::HGetAll { .. } => "hgetall",
+            Request::HLen { .. } => "hlen",
+            Request::HExists { .. } => "hexists",
+            Request::HExpire { .. } => "hexpire",
+            Request::HTtl { .. } => "httl",
+            Request::HPersist { .. } => "hpersist",
+            Request::HScan { .. } => "hscan",
+            Request::ZAdd { .. } => "zadd",
+            Request::ZRem { .. } => "zrem",
+            Request::ZRange { .. } => "zrange",
+            Request::ZRangeByLex { .. } => "zrangebylex",
+            Request::ZLexCount { .. } => "zlexcount",
+            Request::ZUnionStore { .. } => "zunionstore",
+            Request::ZInterStore { .. } => "zinterstore",
+            Request::ZDiff { .. } => "zdiff",
+            Request::ZDiffStore { .. } => "zdiffstore",
+            Request::ZMPop { .. } => "zmpop",
+            Request::BZPopMin { .. } => "bzpopmin",
+            Request::BZPopMax { .. } => "bzpopmax",
+            Request::ZScore { .. } => "zscore",
+            Request::ZCard { .. } => "zcard",
+            Request::ZScan { .. } => "zscan",
+            Request::JsonSet { .. } => "json.set",
+            Request::JsonGet { .. } => "json.get",
+            Request::JsonDel { .. } => "json.del",
+            Request::XAdd { .. } => "xadd",
+            Request::XRange { .. } => "xrange",
+            Request::XLen { .. } => "xlen",
+            Request::Expire { .. } => "expire",
+            Request::ExpireAt { .. } => "expireat",
+            Request::PExpire { .. } => "pexpire",
+            Request::PExpireAt { .. } => "pexpireat",
+            Request::Ttl { .. } => "ttl",
+            Request::PTtl { .. } => "pttl",
+            Request::Sort { .. } => "sort",
+            Request::Type { .. } => "type",
+            Request::Dump { .. } => "dump",
+            Request::Restore { .. } => "restore",
+            Request::Copy { .. } => "copy",
+            Request::ObjectFreq { .. } | Request::ObjectEncoding { .. } => "object",
+            Request::Del { .. } => "del",
+            Request::Unlink { .. } => "unlink",
+            Request::DelPrefix { .. } => "delprefix",
+            Request::Exists { .. } => "exists",
+            Request::Ping => "ping",
+            Request::Echo { .. } => "echo",
+            Request::FlushDb { .. } => "flushdb",
+            Request::FlushAll { .. } => "flushall",
+            Request::DbSize => "dbsize",
+            Request::RandomKey => "randomkey",
+            Request::Scan { .. } => "scan",
+            Request::Info => "info",
+            Request::Version => "version",
+            Request::Wait { .. } => "wait",
+            Request::WaitAof { .. } => "waitaof",
+            Request::Command { .. } => "command",
+            Request::Memory { .. } => "memory",
+            Request::Cluster { .. } => "cluster",
+            Request::Latency { .. } => "latency",
+            Request::PoolStats => "pool",
+            Request::Help { command } => command,
+            Request::DebugReload |
+            Request::DebugListpackLimits { .. } |
+            Request::DebugStringMatch { .. } |
+            Request::DebugSetTime { .. } |
+            Request::DebugAdvanceTime { .. } |
+            Request::DebugSleep { .. } |
+            Request::DebugObjectCheck { .. } |
+            Request::DebugPopulate { .. } => "debug",
+            Request::GetWriteOffset => "getwriteoffset",
+            Request::MinOffset { .. } => "minoffset",
+            Request::Eval { .. } => "eval",
+            Request::EvalSha { .. } => "evalsha",
+            Request::ScriptLoad { .. } => "script",
+            Request::FunctionLoad { .. } | Request::FunctionList => "function",
+            Request::FCall { .. } => "fcall",
+            Request::Client { .. } => "client",
+            Request::Reset => "reset",
+            Request::Quit => "quit",
+            Request::Hello { .. } => "hello",
+            Request::Monitor => "monitor",
+            Request::Subscribe { .. } => "subscribe",
+            Request::Unsubscribe { .. } => "unsubscribe",
+            Request::PSubscribe { .. } => "psubscribe",
+            Request::PUnsubscribe { .. } => "punsubscribe",
+            Request::Publish { .. } => "publish",
+            Request::PubSub { .. } => "pubsub",
+        }
+    }
+    
+    /// Keys the command reads or writes; scripts report the keys they declare
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            Request::MinOffset { request, .. } => request.keys(),
+            Request::Memory { subcommand, args } if subcommand == "USAGE" => args.iter().take(1).map(String::as_str).collect(),
+            Request::Memory { .. } => Vec::new(),
+            Request::Cluster { .. } => Vec::new(),
+            Request::Latency { .. } => Vec::new(),
+            Request::Get { key } |
+            Request::GetEx { key, .. } |
+            Request::Set { key, .. } |
+            Request::Incr { key } |
+            Request::Decr { key } |
+            Request::IncrBy { key, .. } |
+            Request::DecrBy { key, .. } |
+            Request::IncrByFloat { key, .. } |
+            Request::Append { key, .. } |
+            Request::GetRange { key, .. } |
+            Request::StrLen { key } |
+            Request::BitPos { key, .. } |
+            Request::LPush { key, .. } |
+            Request::RPush { key, .. } |
+            Request::LPop { key, .. } |
+            Request::RPop { key, .. } |
+            Request::LRange { key, .. } |
+            Request::LLen { key } |
+            Request::LSet { key, .. } |
+            Request::LInsert { key, .. } |
+            Request::LTrim { key, .. } |
+            Request::SAdd { key, .. } |
+            Request::SRem { key, .. } |
+            Request::SMembers { key } |
+            Request::SIsMember { key, .. } |
+            Request::SMIsMember { key, .. } |
+            Request::SCard { key } |
+            Request::SScan { key, .. } |
+            Request::HSet { key, .. } |
+            Request::HSetNx { key, .. } |
+            Request::HMSet { key, .. } |
+            Request::HStrLen { key, .. } |
+            Request::HGet { key, .. } |
+            Request::HDel { key, .. } |
+            Request::HGetAll { key } |
+            Request::HLen { key } |
+            Request::HExists { key, .. } |
+            Request::HExpire { key, .. } |
+            Request::HTtl { key, .. } |
+            Request::HPersist { key, .. } |
+            Request::HScan { key, .. } |
+            Request::ZAdd { key, .. } |
+            Request::ZRem { key, .. } |
+            Request::ZRange { key, .. } |
+            Request::ZRangeByLex { key, .. } |
+            Request::ZLexCount { key, .. } |
+            Request::ZScore { key, .. } |
+            Request::ZCard { key } |
+            Request::ZScan { key, .. } |
+            Request::JsonSet { key, .. } |
+            Request::JsonGet { key, .. } |
+            Request::JsonDel { key, .. } |
+            Request::XAdd { key, .. } |
+            Request::XRange { key, .. } |
+            Request::XLen { key } |
+            Request::Expire { key, .. } |
+            Request::ExpireAt { key, .. } |
+            Request::PExpire { key, .. } |
+            Request::PExpireAt { key, .. } |
+            Request::Ttl { key } |
+            Request::PTtl { key } |
+            Request::Sort { key, .. } |
+            Request::Type { key } |
+            Request::Dump { key } |
+            Request::Restore { key, .. } |
+            Request::ObjectFreq { key } | Request::ObjectEncoding { key } |
+            Request::DebugObjectCheck { key } => vec![key.as_str()],
+            Request::Copy { source, destination, .. } => vec![source.as_str(), destination.as_str()],
+            Request::Lcs { key1, key2, .. } => vec![key1.as_str(), key2.as_str()],
+            Request::BitOp { dest, keys, .. } |
+            Request::ZUnionStore { dest, keys, .. } |
+            Request::ZInterStore { dest, keys, .. } |
+            Request::ZDiffStore { dest, keys } => {
+                std::iter::once(dest).chain(keys).map(String::as_str).collect()
+            }
+            Request::LMPop { keys, .. } |
+            Request::BLPop { keys, .. } |
+            Request::BRPop { keys, .. } |
+            Request::ZMPop { keys, .. } |
+            Request::ZDiff { keys, .. } |
+            Request::BZPopMin { keys, .. } |
+            Request::BZPopMax { keys, .. } |
+            Request::Del { keys } |
+            Request::Unlink { keys } |
+            Request::Exists { keys } |
+            Request::Eval { keys, .. } |
+            Request::EvalSha { keys, .. } |
+            Request::FCall { keys, .. } => keys.iter().map(String::as_str).collect(),
+            Request::Ping |
+            Request::Echo { .. } |
+            Request::FlushDb { .. } |
+            Request::FlushAll { .. } |
+            Request::DelPrefix { .. } |
+            Request::DbSize |
+            Request::RandomKey |
+            Request::Scan { .. } |
+            Request::Info |
+            Request::Version |
+            Request::Wait { .. } |
+            Request::WaitAof { .. } |
+            Request::Command { .. } |
+            Request::PoolStats |
+            Request::Help { .. } |
+            Request::DebugReload |
+            Request::DebugListpackLimits { .. } |
+            Request::DebugStringMatch { .. } |
+            Request::DebugSetTime { .. } |
+            Request::DebugAdvanceTime { .. } |
+            Request::DebugSleep { .. } |
+            Request::DebugPopulate { .. } |
+            Request::GetWriteOffset |
+            Request::ScriptLoad { .. } |
+            Request::FunctionLoad { .. } |
+            Request::FunctionList |
+            Request::Client { .. } |
+            Request::Reset |
+            Request::Quit |
+            Request::Hello { .. } |
+            Request::Monitor |
+            Request::Subscribe { .. } |
+            Request::Unsubscribe { .. } |
+            Request::PSubscribe { .. } |
+            Request::PUnsubscribe { .. } |
+            Request::Publish { .. } |
+            Request::PubSub { .. } => Vec::new(),
+        }
+    }
+    
+    /// Whether the command may modify the keyspace, per the command table
+    pub fn is_write(&self) -> bool {
+        table::lookup(self.command_name()).is_some_and(|spec| spec.is_write())
+    }
+    
+    /// Whether the command may wait for another client, per the command table
+    pub fn is_blocking(&self) -> bool {
+        table::lookup(self.command_name()).is_some_and(|spec| spec.is_blocking())
+    }
+    
+    fn scan_to_string(command: &str, key: &str, cursor: u64, match_pattern: &Option<String>, count: Option<usize>) -> String {
+        let mut cmd = format!("{} {} {}", command, key, cursor);
+        if let Some(pattern) = match_pattern {
+            cmd.push_str(&format!(" MATCH {}", pattern));
+        }
+        if let Some(count) = count {
+            cmd.push_str(&format!(" COUNT {}", count));
+        }
+        cmd
+    }
+    
+    fn eval_to_string(command: &str, script: &str, keys: &[String], args: &[String]) -> String {
+        let mut parts = vec![command.to_string(), script.to_string(), keys.len().to_string()];
+        parts.extend(keys.iter().cloned());
+        parts.extend(args.iter().cloned());
+        parts.join(" ")
+    }
+    
+    /// The count of LPOP and RPOP, the token after the key
+    pub(crate) fn parse_pop_count(count: &str) -> ParseResult<usize> {
+        count.parse::<usize>()
+            .map_err(|_| ParseError::invalid_integer(2, "value is out of range, must be positive".to_string()))
+    }
+    
+    /// Parse SET's arguments after the key: the value, then NX, XX, GET and an expiry.
+    /// When the token after the value is not an option, the line protocol's
+    /// unquoted form applies and every remaining token is joined into the value.
+    pub(crate) fn parse_set<S: AsRef<str>>(key: &str, args: &[S]) -> ParseResult<Self> {
+        let key = key.to_string();
+        if !args.get(1).is_some_and(|token| SetOptions::is_option(token.as_ref())) {
+            let value = args.iter().map(AsRef::as_ref).collect::<Vec<_>>().join(" ");
+            return Ok(Request::Set { key, value, options: SetOptions::default() });
+        }
+        
+        let mut options = SetOptions::default();
+        let mut has_expiry = false;
+        // Token index of the first option: command, key, then the value
+        let mut index = 3;
+        while index - 2 < args.len() {
+            let token = args[index - 2].as_ref().to_uppercase();
+            match token.as_str() {
+                "NX" if !options.xx => options.nx = true,
+                "XX" if !options.nx => options.xx = true,
+                "GET" => options.get = true,
+                "KEEPTTL" if !has_expiry => {
+                    options.expiry = SetExpiry::KeepTtl;
+                    has_expiry = true;
+                }
+                "EX" | "PX" | "EXAT" | "PXAT" if !has_expiry => {
+                    let amount = args.get(index - 1)
+                        .ok_or_else(|| ParseError::syntax("syntax error".to_string()).at(index))?
+                        .as_ref()
+                        .parse::<i64>()
+                        .map_err(|_| ParseError::invalid_integer(index + 1, "Invalid expire time".to_string()))?;
+                    if amount <= 0 {
+                        return Err(ParseError::invalid_integer(index + 1, "Invalid expire time in SET".to_string()));
+                    }
+                    options.expiry = match token.as_str() {
+                        "EX" => SetExpiry::Ex(amount),
+                        "PX" => SetExpiry::Px(amount),
+                        "EXAT" => SetExpiry::ExAt(amount),
+                        _ => SetExpiry::PxAt(amount),
+                    };
+                    has_expiry = true;
+                    index += 1;
+                }
+                _ => return Err(ParseError::syntax("syntax error".to_string()).at(index)),
+            }
+            index += 1;
+        }
+        Ok(Request::Set { key, value: args[0].as_ref().to_string(), options })
+    }
+    
+    /// Parse ZADD's arguments after the key: leading flags, then score/member pairs
+    pub(crate) fn parse_zadd<S: AsRef<str>>(key: &str, args: &[S]) -> ParseResult<Self> {
+        let mut flags = ZAddFlags::default();
+        let mut rest = args;
+        while let Some(token) = rest.first() {
+            match token.as_ref().to_uppercase().as_str() {
+                "NX" => flags.nx = true,
+                "XX" => flags.xx = true,
+                "GT" => flags.gt = true,
+                "LT" => flags.lt = true,
+                "CH" => flags.ch = true,
+                "INCR" => flags.incr = true,
+                _ => break,
+            }
+            rest = &rest[1..];
+        }
+        
+        if rest.is_empty() || rest.len() % 2 != 0 {
+            return Err(ParseError::wrong_arity("ZADD requires key and score/member pairs".to_string()));
+        }
+        // Token index of the first score: command, key, then the flags
+        let first_score = 2 + args.len() - rest.len();
+        let mut members = Vec::with_capacity(rest.len() / 2);
+        for (i, pair) in rest.chunks(2).enumerate() {
+            let score = pair[0].as_ref().parse::<f64>()
+                .ok()
+                .filter(|s| !s.is_nan())
+                .ok_or_else(|| ParseError::invalid_float(first_score + 2 * i, "Invalid score".to_string()))?;
+            members.push((score, pair[1].as_ref().to_string()));
+        }
+        flags.validate(members.len()).map_err(ParseError::syntax)?;
+        
+        Ok(Request::ZAdd { key: key.to_string(), members, flags })
+    }
+    
     pub fn parse(input: &str) -> Result<Self> {
         // Use C parser if feature is enabled
         #[cfg(feature = "c_parser")]
         {
+            // The C parser splits on whitespace only; quoted arguments need the Rust tokenizer
+            if input.contains(['"', '\'']) {
+                return Self::parse_rust(input);
+            }
             return crate::ffi::parser::parse_request_fast(input);
         }
         
@@ -192,63 +1295,227 @@ impl Request {
         }
     }
     
+    /// Parse a command line like `parse`, refusing one with more than `max_args`
+    /// arguments before they are all split out
+    pub fn parse_limited(input: &str, max_args: usize) -> Result<Self> {
+        // Each argument takes a byte and a separator, so a short line cannot have too many
+        if input.len() <= max_args.saturating_mul(2) {
+            return Self::parse(input);
+        }
+        let args = split_args_limited(input, max_args).map_err(|mut e| {
+            e.command = input.split_whitespace().next().unwrap_or_default().to_uppercase();
+            e
+        })?;
+        Ok(Self::from_args(&args)?)
+    }
+    
     pub fn parse_rust(input: &str) -> Result<Self> {
-        let parts: Vec<&str> = input.split_whitespace().collect();
+        Ok(Self::parse_detailed(input)?)
+    }
+    
+    /// Parse a command line like `parse_rust`, but report failures as a
+    /// `ParseError` naming the command, the offending token and the failure kind
+    pub fn parse_detailed(input: &str) -> ParseResult<Self> {
+        let args = split_args(input).map_err(|mut e| {
+            e.command = input.split_whitespace().next().unwrap_or_default().to_uppercase();
+            e
+        })?;
+        Self::from_args(&args)
+    }
+    
+    /// Parse a command already split into arguments, such as a RESP multibulk request
+    pub fn from_args(args: &[String]) -> ParseResult<Self> {
+        let parts: Vec<&str> = args.iter().map(String::as_str).collect();
         
         if parts.is_empty() {
-            return Err(DiskDBError::Protocol("Empty command".to_string()));
+            return Err(ParseError::new(ParseErrorKind::Empty, None, "Empty command"));
+        }
+        
+        let command = parts[0].to_uppercase();
+        Self::parse_parts(&parts).map_err(|mut e| {
+            e.command = command;
+            e
+        })
+    }
+    
+    pub(crate) fn parse_parts(parts: &[&str]) -> ParseResult<Self> {
+        if let [command, sub] = parts {
+            if sub.eq_ignore_ascii_case("HELP") {
+                if let Some((command, _)) = table::help(command) {
+                    return Ok(Request::Help { command });
+                }
+            }
         }
         
         match parts[0].to_uppercase().as_str() {
             // String operations
             "GET" => {
                 if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("GET requires exactly one argument".to_string()));
+                    return Err(ParseError::wrong_arity("GET requires exactly one argument".to_string()));
                 }
                 Ok(Request::Get { key: parts[1].to_string() })
             }
+            "GETEX" => {
+                let expiry = match parts.len() {
+                    2 => GetExOption::None,
+                    3 if parts[2].to_uppercase() == "PERSIST" => GetExOption::Persist,
+                    4 => {
+                        let amount = parts[3].parse::<i64>()
+                            .map_err(|_| ParseError::invalid_integer(3, "Invalid expire time".to_string()))?;
+                        if amount <= 0 {
+                            return Err(ParseError::invalid_integer(3, "Invalid expire time in GETEX".to_string()));
+                        }
+                        match parts[2].to_uppercase().as_str() {
+                            "EX" => GetExOption::Ex(amount),
+                            "PX" => GetExOption::Px(amount),
+                            "EXAT" => GetExOption::ExAt(amount),
+                            "PXAT" => GetExOption::PxAt(amount),
+                            other => return Err(ParseError::syntax(format!("Invalid GETEX option: {}", other)).at(2)),
+                        }
+                    }
+                    _ => return Err(ParseError::wrong_arity("GETEX requires a key and at most one expiry option".to_string())),
+                };
+                Ok(Request::GetEx { key: parts[1].to_string(), expiry })
+            }
             "SET" => {
                 if parts.len() < 3 {
-                    return Err(DiskDBError::Protocol("SET requires at least two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("SET requires at least two arguments".to_string()));
                 }
-                let value = parts[2..].join(" ");
-                Ok(Request::Set { 
-                    key: parts[1].to_string(), 
-                    value 
-                })
+                Self::parse_set(parts[1], &parts[2..])
             }
             "INCR" => {
                 if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("INCR requires exactly one argument".to_string()));
+                    return Err(ParseError::wrong_arity("INCR requires exactly one argument".to_string()));
                 }
                 Ok(Request::Incr { key: parts[1].to_string() })
             }
             "DECR" => {
                 if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("DECR requires exactly one argument".to_string()));
+                    return Err(ParseError::wrong_arity("DECR requires exactly one argument".to_string()));
                 }
                 Ok(Request::Decr { key: parts[1].to_string() })
             }
             "INCRBY" => {
                 if parts.len() != 3 {
-                    return Err(DiskDBError::Protocol("INCRBY requires exactly two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("INCRBY requires exactly two arguments".to_string()));
                 }
                 let delta = parts[2].parse::<i64>()
-                    .map_err(|_| DiskDBError::Protocol("Invalid integer".to_string()))?;
+                    .map_err(|_| ParseError::invalid_integer(2, "Invalid integer".to_string()))?;
                 Ok(Request::IncrBy { key: parts[1].to_string(), delta })
             }
+            "DECRBY" => {
+                if parts.len() != 3 {
+                    return Err(ParseError::wrong_arity("DECRBY requires exactly two arguments".to_string()));
+                }
+                let delta = parts[2].parse::<i64>()
+                    .map_err(|_| ParseError::invalid_integer(2, "Invalid integer".to_string()))?;
+                Ok(Request::DecrBy { key: parts[1].to_string(), delta })
+            }
+            "INCRBYFLOAT" => {
+                if parts.len() != 3 {
+                    return Err(ParseError::wrong_arity("INCRBYFLOAT requires exactly two arguments".to_string()));
+                }
+                let delta = parts[2].parse::<f64>()
+                    .ok()
+                    .filter(|d| d.is_finite())
+                    .ok_or_else(|| ParseError::invalid_float(2, "Invalid float".to_string()))?;
+                Ok(Request::IncrByFloat { key: parts[1].to_string(), delta })
+            }
             "APPEND" => {
                 if parts.len() < 3 {
-                    return Err(DiskDBError::Protocol("APPEND requires at least two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("APPEND requires at least two arguments".to_string()));
                 }
                 let value = parts[2..].join(" ");
                 Ok(Request::Append { key: parts[1].to_string(), value })
             }
+            // SUBSTR is the deprecated name Redis keeps for GETRANGE
+            "GETRANGE" | "SUBSTR" => {
+                if parts.len() != 4 {
+                    return Err(ParseError::wrong_arity(format!("{} requires exactly three arguments", parts[0].to_uppercase())));
+                }
+                let start = parts[2].parse::<i64>()
+                    .map_err(|_| ParseError::invalid_integer(2, "Invalid start index".to_string()))?;
+                let end = parts[3].parse::<i64>()
+                    .map_err(|_| ParseError::invalid_integer(3, "Invalid end index".to_string()))?;
+                Ok(Request::GetRange { key: parts[1].to_string(), start, end })
+            }
+            "STRLEN" => {
+                if parts.len() != 2 {
+                    return Err(ParseError::wrong_arity("STRLEN requires exactly one argument".to_string()));
+                }
+                Ok(Request::StrLen { key: parts[1].to_string() })
+            }
+            "LCS" => {
+                if parts.len() < 3 {
+                    return Err(ParseError::wrong_arity("LCS requires two keys".to_string()));
+                }
+                let (mut len_only, mut idx, mut minmatchlen, mut withmatchlen) = (false, false, None, false);
+                let mut i = 3;
+                while i < parts.len() {
+                    match parts[i].to_uppercase().as_str() {
+                        "LEN" => len_only = true,
+                        "IDX" => idx = true,
+                        "WITHMATCHLEN" => withmatchlen = true,
+                        "MINMATCHLEN" if i + 1 < parts.len() => {
+                            i += 1;
+                            // Like Redis, a negative length matches everything
+                            let len = parts[i].parse::<i64>()
+                                .map_err(|_| ParseError::invalid_integer(i, "value is not an integer or out of range".to_string()))?;
+                            minmatchlen = Some(usize::try_from(len).unwrap_or(0));
+                        }
+                        _ => return Err(ParseError::syntax("syntax error".to_string()).at(i)),
+                    }
+                    i += 1;
+                }
+                if len_only && idx {
+                    return Err(ParseError::syntax("If you want both the length and indexes, please just use IDX.".to_string()));
+                }
+                Ok(Request::Lcs { key1: parts[1].to_string(), key2: parts[2].to_string(), len_only, idx, minmatchlen, withmatchlen })
+            }
+            "BITPOS" => {
+                if parts.len() < 3 || parts.len() > 5 {
+                    return Err(ParseError::wrong_arity("BITPOS requires two to four arguments".to_string()));
+                }
+                let bit = match parts[2] {
+                    "0" => 0,
+                    "1" => 1,
+                    _ => return Err(ParseError::syntax("The bit argument must be 1 or 0".to_string()).at(2)),
+                };
+                let start = parts.get(3)
+                    .map(|s| s.parse::<i64>())
+                    .transpose()
+                    .map_err(|_| ParseError::invalid_integer(3, "Invalid start index".to_string()))?;
+                let end = parts.get(4)
+                    .map(|s| s.parse::<i64>())
+                    .transpose()
+                    .map_err(|_| ParseError::invalid_integer(4, "Invalid end index".to_string()))?;
+                Ok(Request::BitPos { key: parts[1].to_string(), bit, start, end })
+            }
+            "BITOP" => {
+                if parts.len() < 4 {
+                    return Err(ParseError::wrong_arity("BITOP requires an operation, a destination and at least one source key".to_string()));
+                }
+                let op = match parts[1].to_uppercase().as_str() {
+                    "AND" => BitOp::And,
+                    "OR" => BitOp::Or,
+                    "XOR" => BitOp::Xor,
+                    "NOT" => BitOp::Not,
+                    other => return Err(ParseError::syntax(format!("Unknown BITOP operation: {}", other)).at(1)),
+                };
+                if op == BitOp::Not && parts.len() != 4 {
+                    return Err(ParseError::wrong_arity("BITOP NOT must be called with a single source key".to_string()));
+                }
+                Ok(Request::BitOp {
+                    op,
+                    dest: parts[2].to_string(),
+                    keys: parts[3..].iter().map(|s| s.to_string()).collect(),
+                })
+            }
             
             // List operations
             "LPUSH" => {
                 if parts.len() < 3 {
-                    return Err(DiskDBError::Protocol("LPUSH requires at least two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("LPUSH requires at least two arguments".to_string()));
                 }
                 Ok(Request::LPush {
                     key: parts[1].to_string(),
@@ -257,7 +1524,7 @@ impl Request {
             }
             "RPUSH" => {
                 if parts.len() < 3 {
-                    return Err(DiskDBError::Protocol("RPUSH requires at least two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("RPUSH requires at least two arguments".to_string()));
                 }
                 Ok(Request::RPush {
                     key: parts[1].to_string(),
@@ -265,25 +1532,39 @@ impl Request {
                 })
             }
             "LPOP" => {
-                if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("LPOP requires exactly one argument".to_string()));
+                if !(2..=3).contains(&parts.len()) {
+                    return Err(ParseError::wrong_arity("LPOP requires a key and an optional count".to_string()));
                 }
-                Ok(Request::LPop { key: parts[1].to_string() })
+                let count = parts.get(2).map(|count| Self::parse_pop_count(count)).transpose()?;
+                Ok(Request::LPop { key: parts[1].to_string(), count })
             }
             "RPOP" => {
-                if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("RPOP requires exactly one argument".to_string()));
+                if !(2..=3).contains(&parts.len()) {
+                    return Err(ParseError::wrong_arity("RPOP requires a key and an optional count".to_string()));
                 }
-                Ok(Request::RPop { key: parts[1].to_string() })
+                let count = parts.get(2).map(|count| Self::parse_pop_count(count)).transpose()?;
+                Ok(Request::RPop { key: parts[1].to_string(), count })
+            }
+            "LMPOP" => {
+                let (keys, from_left, count) = Self::parse_mpop("LMPOP", ("LEFT", "RIGHT"), &parts[1..])?;
+                Ok(Request::LMPop { keys, from_left, count })
+            }
+            "BLPOP" => {
+                let (keys, timeout_ms) = Self::parse_blocking_pop("BLPOP", parts)?;
+                Ok(Request::BLPop { keys, timeout_ms })
+            }
+            "BRPOP" => {
+                let (keys, timeout_ms) = Self::parse_blocking_pop("BRPOP", parts)?;
+                Ok(Request::BRPop { keys, timeout_ms })
             }
             "LRANGE" => {
                 if parts.len() != 4 {
-                    return Err(DiskDBError::Protocol("LRANGE requires exactly three arguments".to_string()));
+                    return Err(ParseError::wrong_arity("LRANGE requires exactly three arguments".to_string()));
                 }
                 let start = parts[2].parse::<i64>()
-                    .map_err(|_| DiskDBError::Protocol("Invalid start index".to_string()))?;
+                    .map_err(|_| ParseError::invalid_integer(2, "Invalid start index".to_string()))?;
                 let stop = parts[3].parse::<i64>()
-                    .map_err(|_| DiskDBError::Protocol("Invalid stop index".to_string()))?;
+                    .map_err(|_| ParseError::invalid_integer(3, "Invalid stop index".to_string()))?;
                 Ok(Request::LRange { 
                     key: parts[1].to_string(), 
                     start, 
@@ -292,15 +1573,49 @@ impl Request {
             }
             "LLEN" => {
                 if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("LLEN requires exactly one argument".to_string()));
+                    return Err(ParseError::wrong_arity("LLEN requires exactly one argument".to_string()));
                 }
                 Ok(Request::LLen { key: parts[1].to_string() })
             }
+            "LSET" => {
+                if parts.len() != 4 {
+                    return Err(ParseError::wrong_arity("LSET requires exactly three arguments".to_string()));
+                }
+                let index = parts[2].parse::<i64>()
+                    .map_err(|_| ParseError::invalid_integer(2, "Invalid index".to_string()))?;
+                Ok(Request::LSet { key: parts[1].to_string(), index, value: parts[3].to_string() })
+            }
+            "LINSERT" => {
+                if parts.len() != 5 {
+                    return Err(ParseError::wrong_arity("LINSERT requires exactly four arguments".to_string()));
+                }
+                let before = match parts[2].to_uppercase().as_str() {
+                    "BEFORE" => true,
+                    "AFTER" => false,
+                    _ => return Err(ParseError::syntax("LINSERT expects BEFORE or AFTER".to_string()).at(2)),
+                };
+                Ok(Request::LInsert {
+                    key: parts[1].to_string(),
+                    before,
+                    pivot: parts[3].to_string(),
+                    value: parts[4].to_string(),
+                })
+            }
+            "LTRIM" => {
+                if parts.len() != 4 {
+                    return Err(ParseError::wrong_arity("LTRIM requires exactly three arguments".to_string()));
+                }
+                let start = parts[2].parse::<i64>()
+                    .map_err(|_| ParseError::invalid_integer(2, "Invalid start index".to_string()))?;
+                let stop = parts[3].parse::<i64>()
+                    .map_err(|_| ParseError::invalid_integer(3, "Invalid stop index".to_string()))?;
+                Ok(Request::LTrim { key: parts[1].to_string(), start, stop })
+            }
             
             // Set operations
             "SADD" => {
                 if parts.len() < 3 {
-                    return Err(DiskDBError::Protocol("SADD requires at least two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("SADD requires at least two arguments".to_string()));
                 }
                 Ok(Request::SAdd {
                     key: parts[1].to_string(),
@@ -309,7 +1624,7 @@ impl Request {
             }
             "SREM" => {
                 if parts.len() < 3 {
-                    return Err(DiskDBError::Protocol("SREM requires at least two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("SREM requires at least two arguments".to_string()));
                 }
                 Ok(Request::SRem {
                     key: parts[1].to_string(),
@@ -318,22 +1633,46 @@ impl Request {
             }
             "SMEMBERS" => {
                 if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("SMEMBERS requires exactly one argument".to_string()));
+                    return Err(ParseError::wrong_arity("SMEMBERS requires exactly one argument".to_string()));
                 }
                 Ok(Request::SMembers { key: parts[1].to_string() })
             }
             "SISMEMBER" => {
                 if parts.len() != 3 {
-                    return Err(DiskDBError::Protocol("SISMEMBER requires exactly two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("SISMEMBER requires exactly two arguments".to_string()));
                 }
                 Ok(Request::SIsMember {
                     key: parts[1].to_string(),
                     member: parts[2].to_string(),
                 })
             }
+            "SMISMEMBER" => {
+                if parts.len() < 3 {
+                    return Err(ParseError::wrong_arity("SMISMEMBER requires a key and at least one member".to_string()));
+                }
+                Ok(Request::SMIsMember {
+                    key: parts[1].to_string(),
+                    members: parts[2..].iter().map(|s| s.to_string()).collect(),
+                })
+            }
+            "SSCAN" | "HSCAN" | "ZSCAN" => {
+                let command = parts[0].to_uppercase();
+                if parts.len() < 3 {
+                    return Err(ParseError::wrong_arity(format!("{} requires a key and a cursor", command)));
+                }
+                let key = parts[1].to_string();
+                let cursor = parts[2].parse::<u64>()
+                    .map_err(|_| ParseError::invalid_integer(2, "Invalid cursor".to_string()))?;
+                let (match_pattern, count) = Self::parse_scan_options(&parts[3..])?;
+                Ok(match command.as_str() {
+                    "SSCAN" => Request::SScan { key, cursor, match_pattern, count },
+                    "HSCAN" => Request::HScan { key, cursor, match_pattern, count },
+                    _ => Request::ZScan { key, cursor, match_pattern, count },
+                })
+            }
             "SCARD" => {
                 if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("SCARD requires exactly one argument".to_string()));
+                    return Err(ParseError::wrong_arity("SCARD requires exactly one argument".to_string()));
                 }
                 Ok(Request::SCard { key: parts[1].to_string() })
             }
@@ -341,7 +1680,7 @@ impl Request {
             // Hash operations
             "HSET" => {
                 if parts.len() != 4 {
-                    return Err(DiskDBError::Protocol("HSET requires exactly three arguments".to_string()));
+                    return Err(ParseError::wrong_arity("HSET requires exactly three arguments".to_string()));
                 }
                 Ok(Request::HSet {
                     key: parts[1].to_string(),
@@ -349,9 +1688,34 @@ impl Request {
                     value: parts[3].to_string(),
                 })
             }
+            "HSETNX" => {
+                if parts.len() != 4 {
+                    return Err(ParseError::wrong_arity("HSETNX requires exactly three arguments".to_string()));
+                }
+                Ok(Request::HSetNx {
+                    key: parts[1].to_string(),
+                    field: parts[2].to_string(),
+                    value: parts[3].to_string(),
+                })
+            }
+            "HMSET" => {
+                if parts.len() < 4 || !parts.len().is_multiple_of(2) {
+                    return Err(ParseError::wrong_arity("HMSET requires a key and field/value pairs".to_string()));
+                }
+                let pairs = parts[2..].chunks(2)
+                    .map(|pair| (pair[0].to_string(), pair[1].to_string()))
+                    .collect();
+                Ok(Request::HMSet { key: parts[1].to_string(), pairs })
+            }
+            "HSTRLEN" => {
+                if parts.len() != 3 {
+                    return Err(ParseError::wrong_arity("HSTRLEN requires exactly two arguments".to_string()));
+                }
+                Ok(Request::HStrLen { key: parts[1].to_string(), field: parts[2].to_string() })
+            }
             "HGET" => {
                 if parts.len() != 3 {
-                    return Err(DiskDBError::Protocol("HGET requires exactly two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("HGET requires exactly two arguments".to_string()));
                 }
                 Ok(Request::HGet {
                     key: parts[1].to_string(),
@@ -360,7 +1724,7 @@ impl Request {
             }
             "HDEL" => {
                 if parts.len() < 3 {
-                    return Err(DiskDBError::Protocol("HDEL requires at least two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("HDEL requires at least two arguments".to_string()));
                 }
                 Ok(Request::HDel {
                     key: parts[1].to_string(),
@@ -369,54 +1733,87 @@ impl Request {
             }
             "HGETALL" => {
                 if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("HGETALL requires exactly one argument".to_string()));
+                    return Err(ParseError::wrong_arity("HGETALL requires exactly one argument".to_string()));
                 }
                 Ok(Request::HGetAll { key: parts[1].to_string() })
             }
+            "HLEN" => {
+                if parts.len() != 2 {
+                    return Err(ParseError::wrong_arity("HLEN requires exactly one argument".to_string()));
+                }
+                Ok(Request::HLen { key: parts[1].to_string() })
+            }
             "HEXISTS" => {
                 if parts.len() != 3 {
-                    return Err(DiskDBError::Protocol("HEXISTS requires exactly two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("HEXISTS requires exactly two arguments".to_string()));
                 }
                 Ok(Request::HExists {
                     key: parts[1].to_string(),
                     field: parts[2].to_string(),
                 })
             }
+            "HEXPIRE" => {
+                if parts.len() < 6 {
+                    return Err(ParseError::wrong_arity("HEXPIRE requires a key, seconds and a FIELDS clause".to_string()));
+                }
+                let seconds = parts[2].parse::<i64>()
+                    .map_err(|_| ParseError::invalid_integer(2, "Invalid seconds value".to_string()))?;
+                Ok(Request::HExpire {
+                    key: parts[1].to_string(),
+                    seconds,
+                    fields: Self::parse_fields("HEXPIRE", &parts[3..])?,
+                })
+            }
+            "HTTL" | "HPERSIST" => {
+                let command = parts[0].to_uppercase();
+                if parts.len() < 5 {
+                    return Err(ParseError::wrong_arity(format!("{} requires a key and a FIELDS clause", command)));
+                }
+                let key = parts[1].to_string();
+                let fields = Self::parse_fields(&command, &parts[2..])?;
+                if command == "HTTL" {
+                    Ok(Request::HTtl { key, fields })
+                } else {
+                    Ok(Request::HPersist { key, fields })
+                }
+            }
             
             // Sorted Set operations
             "ZADD" => {
-                if parts.len() < 4 || (parts.len() - 2) % 2 != 0 {
-                    return Err(DiskDBError::Protocol("ZADD requires key and score/member pairs".to_string()));
-                }
-                let mut members = Vec::new();
-                for i in (2..parts.len()).step_by(2) {
-                    let score = parts[i].parse::<f64>()
-                        .map_err(|_| DiskDBError::Protocol("Invalid score".to_string()))?;
-                    let member = parts[i + 1].to_string();
-                    members.push((score, member));
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("ZADD requires key and score/member pairs".to_string()));
                 }
-                Ok(Request::ZAdd {
-                    key: parts[1].to_string(),
-                    members,
-                })
+                Self::parse_zadd(parts[1], &parts[2..])
             }
             "ZREM" => {
                 if parts.len() < 3 {
-                    return Err(DiskDBError::Protocol("ZREM requires at least two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("ZREM requires at least two arguments".to_string()));
                 }
                 Ok(Request::ZRem {
                     key: parts[1].to_string(),
                     members: parts[2..].iter().map(|s| s.to_string()).collect(),
                 })
             }
+            "ZMPOP" => {
+                let (keys, min, count) = Self::parse_mpop("ZMPOP", ("MIN", "MAX"), &parts[1..])?;
+                Ok(Request::ZMPop { keys, min, count })
+            }
+            "BZPOPMIN" => {
+                let (keys, timeout_ms) = Self::parse_blocking_pop("BZPOPMIN", parts)?;
+                Ok(Request::BZPopMin { keys, timeout_ms })
+            }
+            "BZPOPMAX" => {
+                let (keys, timeout_ms) = Self::parse_blocking_pop("BZPOPMAX", parts)?;
+                Ok(Request::BZPopMax { keys, timeout_ms })
+            }
             "ZRANGE" => {
                 if parts.len() < 4 || parts.len() > 5 {
-                    return Err(DiskDBError::Protocol("ZRANGE requires 3-4 arguments".to_string()));
+                    return Err(ParseError::wrong_arity("ZRANGE requires 3-4 arguments".to_string()));
                 }
                 let start = parts[2].parse::<i64>()
-                    .map_err(|_| DiskDBError::Protocol("Invalid start index".to_string()))?;
+                    .map_err(|_| ParseError::invalid_integer(2, "Invalid start index".to_string()))?;
                 let stop = parts[3].parse::<i64>()
-                    .map_err(|_| DiskDBError::Protocol("Invalid stop index".to_string()))?;
+                    .map_err(|_| ParseError::invalid_integer(3, "Invalid stop index".to_string()))?;
                 let with_scores = parts.len() == 5 && parts[4].to_uppercase() == "WITHSCORES";
                 Ok(Request::ZRange {
                     key: parts[1].to_string(),
@@ -425,9 +1822,59 @@ impl Request {
                     with_scores,
                 })
             }
+            "ZRANGEBYLEX" => {
+                if parts.len() != 4 && parts.len() != 7 {
+                    return Err(ParseError::wrong_arity("ZRANGEBYLEX requires a key, min and max, and optionally LIMIT offset count".to_string()));
+                }
+                let (min, max) = Self::parse_lex_range(parts)?;
+                let limit = if parts.len() == 7 {
+                    if parts[4].to_uppercase() != "LIMIT" {
+                        return Err(ParseError::syntax("syntax error".to_string()).at(4));
+                    }
+                    let offset = parts[5].parse::<i64>()
+                        .map_err(|_| ParseError::invalid_integer(5, "Invalid offset".to_string()))?;
+                    let count = parts[6].parse::<i64>()
+                        .map_err(|_| ParseError::invalid_integer(6, "Invalid count".to_string()))?;
+                    Some((offset, count))
+                } else {
+                    None
+                };
+                Ok(Request::ZRangeByLex { key: parts[1].to_string(), min, max, limit })
+            }
+            "ZLEXCOUNT" => {
+                if parts.len() != 4 {
+                    return Err(ParseError::wrong_arity("ZLEXCOUNT requires a key, min and max".to_string()));
+                }
+                let (min, max) = Self::parse_lex_range(parts)?;
+                Ok(Request::ZLexCount { key: parts[1].to_string(), min, max })
+            }
+            command @ ("ZUNIONSTORE" | "ZINTERSTORE") => {
+                let (dest, keys, weights, aggregate) = Self::parse_zstore(command, parts)?;
+                Ok(if command == "ZUNIONSTORE" {
+                    Request::ZUnionStore { dest, keys, weights, aggregate }
+                } else {
+                    Request::ZInterStore { dest, keys, weights, aggregate }
+                })
+            }
+            "ZDIFF" => {
+                let (keys, rest) = Self::parse_zdiff_keys("ZDIFF", "numkeys key [key ...] [WITHSCORES]", parts, 1)?;
+                let with_scores = match rest {
+                    [] => false,
+                    [option] if option.eq_ignore_ascii_case("WITHSCORES") => true,
+                    _ => return Err(ParseError::syntax("syntax error".to_string()).at(keys.len() + 2)),
+                };
+                Ok(Request::ZDiff { keys, with_scores })
+            }
+            "ZDIFFSTORE" => {
+                let (keys, rest) = Self::parse_zdiff_keys("ZDIFFSTORE", "destination numkeys key [key ...]", parts, 2)?;
+                if !rest.is_empty() {
+                    return Err(ParseError::syntax("syntax error".to_string()).at(keys.len() + 3));
+                }
+                Ok(Request::ZDiffStore { dest: parts[1].to_string(), keys })
+            }
             "ZSCORE" => {
                 if parts.len() != 3 {
-                    return Err(DiskDBError::Protocol("ZSCORE requires exactly two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("ZSCORE requires exactly two arguments".to_string()));
                 }
                 Ok(Request::ZScore {
                     key: parts[1].to_string(),
@@ -436,7 +1883,7 @@ impl Request {
             }
             "ZCARD" => {
                 if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("ZCARD requires exactly one argument".to_string()));
+                    return Err(ParseError::wrong_arity("ZCARD requires exactly one argument".to_string()));
                 }
                 Ok(Request::ZCard { key: parts[1].to_string() })
             }
@@ -444,7 +1891,7 @@ impl Request {
             // JSON operations
             "JSON.SET" => {
                 if parts.len() < 4 {
-                    return Err(DiskDBError::Protocol("JSON.SET requires at least three arguments".to_string()));
+                    return Err(ParseError::wrong_arity("JSON.SET requires at least three arguments".to_string()));
                 }
                 let value = parts[3..].join(" ");
                 Ok(Request::JsonSet {
@@ -455,7 +1902,7 @@ impl Request {
             }
             "JSON.GET" => {
                 if parts.len() != 3 {
-                    return Err(DiskDBError::Protocol("JSON.GET requires exactly two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("JSON.GET requires exactly two arguments".to_string()));
                 }
                 Ok(Request::JsonGet {
                     key: parts[1].to_string(),
@@ -464,7 +1911,7 @@ impl Request {
             }
             "JSON.DEL" => {
                 if parts.len() != 3 {
-                    return Err(DiskDBError::Protocol("JSON.DEL requires exactly two arguments".to_string()));
+                    return Err(ParseError::wrong_arity("JSON.DEL requires exactly two arguments".to_string()));
                 }
                 Ok(Request::JsonDel {
                     key: parts[1].to_string(),
@@ -475,7 +1922,7 @@ impl Request {
             // Stream operations
             "XADD" => {
                 if parts.len() < 5 || (parts.len() - 3) % 2 != 0 {
-                    return Err(DiskDBError::Protocol("XADD requires key, id, and field/value pairs".to_string()));
+                    return Err(ParseError::wrong_arity("XADD requires key, id, and field/value pairs".to_string()));
                 }
                 let id = parts[2].to_string();
                 let mut fields = Vec::new();
@@ -492,11 +1939,11 @@ impl Request {
             }
             "XRANGE" => {
                 if parts.len() < 4 || parts.len() > 6 {
-                    return Err(DiskDBError::Protocol("XRANGE requires 3-5 arguments".to_string()));
+                    return Err(ParseError::wrong_arity("XRANGE requires 3-5 arguments".to_string()));
                 }
                 let count = if parts.len() >= 6 && parts[4].to_uppercase() == "COUNT" {
                     Some(parts[5].parse::<usize>()
-                        .map_err(|_| DiskDBError::Protocol("Invalid count".to_string()))?)
+                        .map_err(|_| ParseError::invalid_integer(5, "Invalid count".to_string()))?)
                 } else {
                     None
                 };
@@ -509,29 +1956,199 @@ impl Request {
             }
             "XLEN" => {
                 if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("XLEN requires exactly one argument".to_string()));
+                    return Err(ParseError::wrong_arity("XLEN requires exactly one argument".to_string()));
                 }
                 Ok(Request::XLen { key: parts[1].to_string() })
             }
             
+            // Expiry operations
+            "EXPIRE" => {
+                let (seconds, flags) = Self::parse_expire("EXPIRE", "Invalid seconds", parts)?;
+                Ok(Request::Expire { key: parts[1].to_string(), seconds, flags })
+            }
+            "EXPIREAT" => {
+                let (timestamp, flags) = Self::parse_expire("EXPIREAT", "Invalid timestamp", parts)?;
+                Ok(Request::ExpireAt { key: parts[1].to_string(), timestamp, flags })
+            }
+            "PEXPIRE" => {
+                let (millis, flags) = Self::parse_expire("PEXPIRE", "Invalid milliseconds", parts)?;
+                Ok(Request::PExpire { key: parts[1].to_string(), millis, flags })
+            }
+            "PEXPIREAT" => {
+                let (ms_timestamp, flags) = Self::parse_expire("PEXPIREAT", "Invalid timestamp", parts)?;
+                Ok(Request::PExpireAt { key: parts[1].to_string(), ms_timestamp, flags })
+            }
+            "TTL" => {
+                if parts.len() != 2 {
+                    return Err(ParseError::wrong_arity("TTL requires exactly one argument".to_string()));
+                }
+                Ok(Request::Ttl { key: parts[1].to_string() })
+            }
+            "PTTL" => {
+                if parts.len() != 2 {
+                    return Err(ParseError::wrong_arity("PTTL requires exactly one argument".to_string()));
+                }
+                Ok(Request::PTtl { key: parts[1].to_string() })
+            }
+            
+            // Sort operations
+            "SORT" => {
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("SORT requires at least one argument".to_string()));
+                }
+                let mut alpha = false;
+                let mut desc = false;
+                let mut limit = None;
+                let mut by = None;
+                let mut get = Vec::new();
+                
+                let mut i = 2;
+                while i < parts.len() {
+                    match parts[i].to_uppercase().as_str() {
+                        "ASC" => desc = false,
+                        "DESC" => desc = true,
+                        "ALPHA" => alpha = true,
+                        "LIMIT" => {
+                            if i + 2 >= parts.len() {
+                                return Err(ParseError::wrong_arity("SORT LIMIT requires offset and count".to_string()));
+                            }
+                            let offset = parts[i + 1].parse::<i64>()
+                                .map_err(|_| ParseError::invalid_integer(i + 1, "Invalid LIMIT offset".to_string()))?;
+                            let count = parts[i + 2].parse::<i64>()
+                                .map_err(|_| ParseError::invalid_integer(i + 2, "Invalid LIMIT count".to_string()))?;
+                            limit = Some((offset, count));
+                            i += 2;
+                        }
+                        "BY" => {
+                            if i + 1 >= parts.len() {
+                                return Err(ParseError::wrong_arity("SORT BY requires a pattern".to_string()));
+                            }
+                            by = Some(parts[i + 1].to_string());
+                            i += 1;
+                        }
+                        "GET" => {
+                            if i + 1 >= parts.len() {
+                                return Err(ParseError::wrong_arity("SORT GET requires a pattern".to_string()));
+                            }
+                            get.push(parts[i + 1].to_string());
+                            i += 1;
+                        }
+                        other => {
+                            return Err(ParseError::syntax(format!("Invalid SORT option: {}", other)).at(i));
+                        }
+                    }
+                    i += 1;
+                }
+                
+                Ok(Request::Sort {
+                    key: parts[1].to_string(),
+                    alpha,
+                    desc,
+                    limit,
+                    by,
+                    get,
+                })
+            }
+            
             // Utility operations
+            "OBJECT" => {
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("OBJECT requires a subcommand".to_string()));
+                }
+                match parts[1].to_uppercase().as_str() {
+                    "FREQ" => {
+                        if parts.len() != 3 {
+                            return Err(ParseError::wrong_arity("OBJECT FREQ requires exactly one key".to_string()));
+                        }
+                        Ok(Request::ObjectFreq { key: parts[2].to_string() })
+                    }
+                    "ENCODING" => {
+                        if parts.len() != 3 {
+                            return Err(ParseError::wrong_arity("OBJECT ENCODING requires exactly one key".to_string()));
+                        }
+                        Ok(Request::ObjectEncoding { key: parts[2].to_string() })
+                    }
+                    other => Err(ParseError::syntax(format!("Unknown OBJECT subcommand: {}", other)).at(1)),
+                }
+            }
             "TYPE" => {
                 if parts.len() != 2 {
-                    return Err(DiskDBError::Protocol("TYPE requires exactly one argument".to_string()));
+                    return Err(ParseError::wrong_arity("TYPE requires exactly one argument".to_string()));
                 }
                 Ok(Request::Type { key: parts[1].to_string() })
             }
+            "DUMP" => {
+                if parts.len() != 2 {
+                    return Err(ParseError::wrong_arity("DUMP requires exactly one argument".to_string()));
+                }
+                Ok(Request::Dump { key: parts[1].to_string() })
+            }
+            "RESTORE" => {
+                if parts.len() < 4 {
+                    return Err(ParseError::wrong_arity("RESTORE requires a key, a TTL and a payload".to_string()));
+                }
+                let ttl = parts[2].parse::<i64>()
+                    .ok()
+                    .filter(|ttl| *ttl >= 0)
+                    .ok_or_else(|| ParseError::invalid_integer(2, "Invalid TTL value, must be >= 0".to_string()))?;
+                let (mut replace, mut absttl) = (false, false);
+                for (i, option) in parts.iter().enumerate().skip(4) {
+                    match option.to_uppercase().as_str() {
+                        "REPLACE" => replace = true,
+                        "ABSTTL" => absttl = true,
+                        _ => return Err(ParseError::syntax(format!("Unknown RESTORE option: {}", option)).at(i)),
+                    }
+                }
+                Ok(Request::Restore { key: parts[1].to_string(), ttl, payload: parts[3].to_string(), replace, absttl })
+            }
+            "COPY" => {
+                if parts.len() < 3 {
+                    return Err(ParseError::wrong_arity("COPY requires a source and a destination".to_string()));
+                }
+                let (mut db, mut replace) = (None, false);
+                let mut i = 3;
+                while i < parts.len() {
+                    match parts[i].to_uppercase().as_str() {
+                        "REPLACE" => replace = true,
+                        "DB" if i + 1 < parts.len() => {
+                            i += 1;
+                            db = Some(parts[i].parse::<i64>()
+                                .map_err(|_| ParseError::invalid_integer(i, "value is not an integer or out of range".to_string()))?);
+                        }
+                        _ => return Err(ParseError::syntax("syntax error".to_string()).at(i)),
+                    }
+                    i += 1;
+                }
+                Ok(Request::Copy { source: parts[1].to_string(), destination: parts[2].to_string(), db, replace })
+            }
             "DEL" => {
                 if parts.len() < 2 {
-                    return Err(DiskDBError::Protocol("DEL requires at least one argument".to_string()));
+                    return Err(ParseError::wrong_arity("DEL requires at least one argument".to_string()));
                 }
                 Ok(Request::Del {
                     keys: parts[1..].iter().map(|s| s.to_string()).collect(),
                 })
             }
+            "UNLINK" => {
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("UNLINK requires at least one argument".to_string()));
+                }
+                Ok(Request::Unlink {
+                    keys: parts[1..].iter().map(|s| s.to_string()).collect(),
+                })
+            }
+            "DELPREFIX" => {
+                if parts.len() != 2 {
+                    return Err(ParseError::wrong_arity("DELPREFIX requires exactly one argument".to_string()));
+                }
+                if parts[1].is_empty() {
+                    return Err(ParseError::syntax("DELPREFIX requires a non-empty prefix".to_string()).at(1));
+                }
+                Ok(Request::DelPrefix { prefix: parts[1].to_string() })
+            }
             "EXISTS" => {
                 if parts.len() < 2 {
-                    return Err(DiskDBError::Protocol("EXISTS requires at least one argument".to_string()));
+                    return Err(ParseError::wrong_arity("EXISTS requires at least one argument".to_string()));
                 }
                 Ok(Request::Exists {
                     keys: parts[1..].iter().map(|s| s.to_string()).collect(),
@@ -540,15 +2157,535 @@ impl Request {
             "PING" => Ok(Request::Ping),
             "ECHO" => {
                 if parts.len() < 2 {
-                    return Err(DiskDBError::Protocol("ECHO requires a message".to_string()));
+                    return Err(ParseError::wrong_arity("ECHO requires a message".to_string()));
                 }
                 Ok(Request::Echo { message: parts[1..].join(" ") })
             }
-            "FLUSHDB" => Ok(Request::FlushDb),
+            "FLUSHDB" => {
+                let lazy = Self::parse_flush_mode("FLUSHDB", parts)?;
+                Ok(Request::FlushDb { lazy })
+            }
+            "FLUSHALL" => {
+                let lazy = Self::parse_flush_mode("FLUSHALL", parts)?;
+                Ok(Request::FlushAll { lazy })
+            }
+            "DBSIZE" => Ok(Request::DbSize),
+            "RANDOMKEY" => {
+                if parts.len() != 1 {
+                    return Err(ParseError::wrong_arity("RANDOMKEY takes no arguments".to_string()));
+                }
+                Ok(Request::RandomKey)
+            }
+            "SCAN" => {
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("SCAN requires a cursor".to_string()));
+                }
+                let cursor = parts[1].parse::<u64>()
+                    .map_err(|_| ParseError::invalid_integer(1, "Invalid cursor".to_string()))?;
+                
+                // TYPE is specific to SCAN; MATCH and COUNT are shared with the collection scans
+                let mut type_filter = None;
+                let mut options = Vec::new();
+                for (i, pair) in parts[2..].chunks(2).enumerate() {
+                    if pair[0].to_uppercase() == "TYPE" && pair.len() == 2 {
+                        let type_name = pair[1].to_lowercase();
+                        if !["string", "list", "set", "hash", "zset", "json", "stream"].contains(&type_name.as_str()) {
+                            return Err(ParseError::syntax(format!("Unknown type name: {}", pair[1])).at(3 + 2 * i));
+                        }
+                        type_filter = Some(type_name);
+                    } else {
+                        options.extend_from_slice(pair);
+                    }
+                }
+                let (match_pattern, count) = Self::parse_scan_options(&options)?;
+                Ok(Request::Scan { cursor, match_pattern, count, type_filter })
+            }
             "INFO" => Ok(Request::Info),
+            "VERSION" => Ok(Request::Version),
+            "WAIT" => {
+                if parts.len() != 3 {
+                    return Err(ParseError::wrong_arity("WAIT requires numreplicas and timeout".to_string()));
+                }
+                let numreplicas = parts[1].parse::<usize>()
+                    .map_err(|_| ParseError::invalid_integer(1, "Invalid number of replicas".to_string()))?;
+                let timeout_ms = parts[2].parse::<u64>()
+                    .map_err(|_| ParseError::invalid_integer(2, "Invalid timeout".to_string()))?;
+                Ok(Request::Wait { numreplicas, timeout_ms })
+            }
+            "WAITAOF" => {
+                if parts.len() != 4 {
+                    return Err(ParseError::wrong_arity("WAITAOF requires numlocal, numreplicas and timeout".to_string()));
+                }
+                let numlocal = parts[1].parse::<usize>()
+                    .map_err(|_| ParseError::invalid_integer(1, "Invalid number of local fsyncs".to_string()))?;
+                let numreplicas = parts[2].parse::<usize>()
+                    .map_err(|_| ParseError::invalid_integer(2, "Invalid number of replicas".to_string()))?;
+                let timeout_ms = parts[3].parse::<u64>()
+                    .map_err(|_| ParseError::invalid_integer(3, "Invalid timeout".to_string()))?;
+                Ok(Request::WaitAof { numlocal, numreplicas, timeout_ms })
+            }
+            "EVAL" => {
+                let (script, keys, args) = Self::parse_eval("EVAL", parts)?;
+                Ok(Request::Eval { script, keys, args })
+            }
+            "EVALSHA" => {
+                let (sha, keys, args) = Self::parse_eval("EVALSHA", parts)?;
+                Ok(Request::EvalSha { sha, keys, args })
+            }
+            "SCRIPT" => {
+                if parts.len() < 2 || parts[1].to_uppercase() != "LOAD" {
+                    return Err(ParseError::syntax("SCRIPT supports only the LOAD subcommand".to_string()).at(1));
+                }
+                if parts.len() != 3 {
+                    return Err(ParseError::wrong_arity("SCRIPT LOAD requires a script".to_string()));
+                }
+                Ok(Request::ScriptLoad { script: parts[2].to_string() })
+            }
+            "FUNCTION" => {
+                match parts.get(1).map(|s| s.to_uppercase()).as_deref() {
+                    Some("LOAD") => match &parts[2..] {
+                        [code] => Ok(Request::FunctionLoad { code: code.to_string(), replace: false }),
+                        [option, code] if option.to_uppercase() == "REPLACE" => {
+                            Ok(Request::FunctionLoad { code: code.to_string(), replace: true })
+                        }
+                        _ => Err(ParseError::wrong_arity("FUNCTION LOAD requires [REPLACE] function-code".to_string())),
+                    },
+                    Some("LIST") if parts.len() == 2 => Ok(Request::FunctionList),
+                    Some("LIST") => Err(ParseError::wrong_arity("FUNCTION LIST takes no arguments".to_string())),
+                    _ => Err(ParseError::syntax("FUNCTION supports only the LOAD and LIST subcommands".to_string()).at(1)),
+                }
+            }
+            "FCALL" => {
+                let (name, keys, args) = Self::parse_eval("FCALL", parts)?;
+                Ok(Request::FCall { name, keys, args })
+            }
+            "COMMAND" => Ok(Request::Command {
+                subcommand: parts.get(1).map(|s| s.to_uppercase()),
+                args: parts.iter().skip(2).map(|s| s.to_string()).collect(),
+            }),
+            "MEMORY" => {
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("MEMORY requires a subcommand".to_string()));
+                }
+                Ok(Request::Memory {
+                    subcommand: parts[1].to_uppercase(),
+                    args: parts[2..].iter().map(|s| s.to_string()).collect(),
+                })
+            }
+            "CLUSTER" => {
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("CLUSTER requires a subcommand".to_string()));
+                }
+                Ok(Request::Cluster {
+                    subcommand: parts[1].to_uppercase(),
+                    args: parts[2..].iter().map(|s| s.to_string()).collect(),
+                })
+            }
+            "LATENCY" => {
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("LATENCY requires a subcommand".to_string()));
+                }
+                Ok(Request::Latency {
+                    subcommand: parts[1].to_uppercase(),
+                    args: parts[2..].iter().map(|s| s.to_string()).collect(),
+                })
+            }
+            "POOL" => {
+                if parts.len() != 2 || parts[1].to_uppercase() != "STATS" {
+                    return Err(ParseError::syntax("POOL supports only the STATS subcommand".to_string()).at(1));
+                }
+                Ok(Request::PoolStats)
+            }
+            "DEBUG" => {
+                match parts.get(1).map(|sub| sub.to_uppercase()).as_deref() {
+                    Some("RELOAD") if parts.len() == 2 => Ok(Request::DebugReload),
+                    Some("LISTPACK-LIMITS") => {
+                        if parts.len() != 4 {
+                            return Err(ParseError::wrong_arity("DEBUG LISTPACK-LIMITS requires an entry count and a byte size".to_string()));
+                        }
+                        let entries = parts[2].parse::<usize>()
+                            .map_err(|_| ParseError::invalid_integer(2, "Invalid entry count".to_string()))?;
+                        let bytes = parts[3].parse::<usize>()
+                            .map_err(|_| ParseError::invalid_integer(3, "Invalid byte size".to_string()))?;
+                        Ok(Request::DebugListpackLimits { entries, bytes })
+                    }
+                    Some("STRINGMATCH-LEN") => {
+                        if parts.len() != 4 {
+                            return Err(ParseError::wrong_arity("DEBUG STRINGMATCH-LEN requires a pattern and a string".to_string()));
+                        }
+                        Ok(Request::DebugStringMatch { pattern: parts[2].to_string(), text: parts[3].to_string() })
+                    }
+                    Some(sub @ ("SET-TIME" | "ADVANCE-TIME")) => {
+                        if parts.len() != 3 {
+                            return Err(ParseError::wrong_arity(format!("DEBUG {} requires a time in milliseconds", sub)));
+                        }
+                        let millis = parts[2].parse::<u64>()
+                            .map_err(|_| ParseError::invalid_integer(2, "Invalid time".to_string()))?;
+                        Ok(if sub == "SET-TIME" {
+                            Request::DebugSetTime { millis }
+                        } else {
+                            Request::DebugAdvanceTime { millis }
+                        })
+                    }
+                    Some("SLEEP") => {
+                        if parts.len() != 3 {
+                            return Err(ParseError::wrong_arity("DEBUG SLEEP requires a time in seconds".to_string()));
+                        }
+                        let seconds = parts[2].parse::<f64>().ok()
+                            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                            .ok_or_else(|| ParseError::invalid_float(2, "Invalid time".to_string()))?;
+                        Ok(Request::DebugSleep { millis: (seconds * 1000.0) as u64 })
+                    }
+                    Some("OBJECT-CHECK") => {
+                        if parts.len() != 3 {
+                            return Err(ParseError::wrong_arity("DEBUG OBJECT-CHECK requires a key".to_string()));
+                        }
+                        Ok(Request::DebugObjectCheck { key: parts[2].to_string() })
+                    }
+                    Some("POPULATE") => {
+                        if !(3..=5).contains(&parts.len()) {
+                            return Err(ParseError::wrong_arity("DEBUG POPULATE requires a count and at most a prefix and a size".to_string()));
+                        }
+                        let count = parts[2].parse::<u64>()
+                            .map_err(|_| ParseError::invalid_integer(2, "Invalid key count".to_string()))?;
+                        let size = match parts.get(4) {
+                            Some(size) => Some(size.parse::<usize>()
+                                .map_err(|_| ParseError::invalid_integer(4, "Invalid value size".to_string()))?),
+                            None => None,
+                        };
+                        Ok(Request::DebugPopulate {
+                            count,
+                            prefix: parts.get(3).unwrap_or(&"key").to_string(),
+                            size,
+                        })
+                    }
+                    _ => Err(ParseError::syntax("DEBUG supports only the RELOAD, LISTPACK-LIMITS, STRINGMATCH-LEN, SET-TIME, ADVANCE-TIME, SLEEP, OBJECT-CHECK and POPULATE subcommands".to_string()).at(1)),
+                }
+            }
+            "GETWRITEOFFSET" => Ok(Request::GetWriteOffset),
+            "MINOFFSET" => {
+                if parts.len() < 3 {
+                    return Err(ParseError::wrong_arity("MINOFFSET requires an offset and a command".to_string()));
+                }
+                let offset = parts[1].parse::<u64>()
+                    .map_err(|_| ParseError::invalid_integer(1, "Invalid offset".to_string()))?;
+                if !table::lookup(parts[2]).is_some_and(|spec| spec.is_readonly()) {
+                    return Err(ParseError::syntax("MINOFFSET only applies to read-only commands".to_string()).at(2));
+                }
+                let request = Self::parse_parts(&parts[2..]).map_err(|mut e| {
+                    e.arg_index = e.arg_index.map(|index| index + 2);
+                    e
+                })?;
+                Ok(Request::MinOffset { offset, request: Box::new(request) })
+            }
             
-            cmd => Err(DiskDBError::InvalidCommand(cmd.to_string())),
+            // Connection operations
+            "CLIENT" => {
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("CLIENT requires a subcommand".to_string()));
+                }
+                Ok(Request::Client {
+                    subcommand: parts[1].to_uppercase(),
+                    args: parts[2..].iter().map(|s| s.to_string()).collect(),
+                })
+            }
+            "RESET" => {
+                if parts.len() != 1 {
+                    return Err(ParseError::wrong_arity("RESET takes no arguments".to_string()));
+                }
+                Ok(Request::Reset)
+            }
+            "QUIT" => {
+                if parts.len() != 1 {
+                    return Err(ParseError::wrong_arity("QUIT takes no arguments".to_string()));
+                }
+                Ok(Request::Quit)
+            }
+            "MONITOR" => {
+                if parts.len() != 1 {
+                    return Err(ParseError::wrong_arity("MONITOR takes no arguments".to_string()));
+                }
+                Ok(Request::Monitor)
+            }
+            "HELLO" => {
+                let protover = match parts.get(1) {
+                    Some(v) => Some(v.parse::<u32>().map_err(|_| {
+                        ParseError::invalid_integer(1, "Protocol version is not an integer or out of range".to_string())
+                    })?),
+                    None => None,
+                };
+                let auth = match &parts[parts.len().min(2)..] {
+                    [] => None,
+                    [option, username, password] if option.to_uppercase() == "AUTH" => {
+                        Some((username.to_string(), password.to_string()))
+                    }
+                    _ => return Err(ParseError::wrong_arity("HELLO accepts only [protover [AUTH username password]]".to_string())),
+                };
+                Ok(Request::Hello { protover, auth })
+            }
+            
+            // Pub/Sub operations
+            "SUBSCRIBE" => {
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("SUBSCRIBE requires at least one channel".to_string()));
+                }
+                Ok(Request::Subscribe { channels: parts[1..].iter().map(|s| s.to_string()).collect() })
+            }
+            "UNSUBSCRIBE" => {
+                Ok(Request::Unsubscribe { channels: parts[1..].iter().map(|s| s.to_string()).collect() })
+            }
+            "PSUBSCRIBE" => {
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("PSUBSCRIBE requires at least one pattern".to_string()));
+                }
+                Ok(Request::PSubscribe { patterns: parts[1..].iter().map(|s| s.to_string()).collect() })
+            }
+            "PUNSUBSCRIBE" => {
+                Ok(Request::PUnsubscribe { patterns: parts[1..].iter().map(|s| s.to_string()).collect() })
+            }
+            "PUBLISH" => {
+                if parts.len() < 3 {
+                    return Err(ParseError::wrong_arity("PUBLISH requires a channel and a message".to_string()));
+                }
+                Ok(Request::Publish { channel: parts[1].to_string(), message: parts[2..].join(" ") })
+            }
+            "PUBSUB" => {
+                if parts.len() < 2 {
+                    return Err(ParseError::wrong_arity("PUBSUB requires a subcommand".to_string()));
+                }
+                Ok(Request::PubSub {
+                    subcommand: parts[1].to_uppercase(),
+                    args: parts[2..].iter().map(|s| s.to_string()).collect(),
+                })
+            }
+            
+            cmd => Err(ParseError::new(ParseErrorKind::UnknownCommand, Some(0), cmd)),
+        }
+    }
+    
+    /// Parse trailing `MATCH pattern` and `COUNT count` options, in any order
+    fn parse_scan_options(parts: &[&str]) -> ParseResult<(Option<String>, Option<usize>)> {
+        let mut match_pattern = None;
+        let mut count = None;
+        let mut options = parts.iter();
+        while let Some(option) = options.next() {
+            let value = options.next()
+                .ok_or_else(|| ParseError::wrong_arity(format!("{} requires a value", option.to_uppercase())))?;
+            match option.to_uppercase().as_str() {
+                "MATCH" => match_pattern = Some(value.to_string()),
+                "COUNT" => {
+                    let c = value.parse::<usize>()
+                        .ok()
+                        .filter(|c| *c > 0)
+                        .ok_or_else(|| ParseError::new(ParseErrorKind::InvalidInteger, None, "COUNT must be a positive integer"))?;
+                    count = Some(c);
+                }
+                other => return Err(ParseError::syntax(format!("Unknown scan option: {}", other))),
+            }
+        }
+        Ok((match_pattern, count))
+    }
+    
+    /// Parse `key [key ...] timeout`, with the timeout in (possibly fractional)
+    /// seconds, into the keys and the timeout in milliseconds; 0 blocks forever
+    fn parse_blocking_pop(command: &str, parts: &[&str]) -> ParseResult<(Vec<String>, u64)> {
+        if parts.len() < 3 {
+            return Err(ParseError::wrong_arity(format!("{} requires key [key ...] timeout", command)));
+        }
+        let last = parts.len() - 1;
+        let seconds = parts[last].parse::<f64>()
+            .ok()
+            .filter(|s| s.is_finite())
+            .ok_or_else(|| ParseError::invalid_float(last, "timeout is not a float or out of range".to_string()))?;
+        if seconds < 0.0 {
+            return Err(ParseError::invalid_float(last, "timeout is negative".to_string()));
+        }
+        let keys = parts[1..last].iter().map(|s| s.to_string()).collect();
+        Ok((keys, (seconds * 1000.0).ceil() as u64))
+    }
+    
+    /// Parse `destination numkeys key [key ...] [WEIGHTS weight ...] [AGGREGATE SUM|MIN|MAX]`,
+    /// defaulting every weight to 1 and the aggregate to SUM
+    fn parse_zstore(command: &str, parts: &[&str]) -> ParseResult<(String, Vec<String>, Vec<f64>, Aggregate)> {
+        if parts.len() < 4 {
+            return Err(ParseError::wrong_arity(format!(
+                "{} requires destination numkeys key [key ...] [WEIGHTS weight ...] [AGGREGATE SUM|MIN|MAX]", command
+            )));
+        }
+        let numkeys = parts[2].parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ParseError::invalid_integer(2, "numkeys should be greater than 0".to_string()))?;
+        if parts.len() < numkeys + 3 {
+            return Err(ParseError::syntax("syntax error".to_string()).at(parts.len()));
+        }
+        let keys: Vec<String> = parts[3..numkeys + 3].iter().map(|s| s.to_string()).collect();
+        
+        let mut weights = vec![1.0; numkeys];
+        let mut aggregate = Aggregate::Sum;
+        let mut i = numkeys + 3;
+        while i < parts.len() {
+            match parts[i].to_uppercase().as_str() {
+                "WEIGHTS" if i + numkeys < parts.len() => {
+                    for (offset, weight) in weights.iter_mut().enumerate() {
+                        let index = i + 1 + offset;
+                        *weight = parts[index].parse::<f64>()
+                            .ok()
+                            .filter(|w| !w.is_nan())
+                            .ok_or_else(|| ParseError::invalid_float(index, "weight value is not a float".to_string()))?;
+                    }
+                    i += numkeys + 1;
+                }
+                "AGGREGATE" if i + 1 < parts.len() => {
+                    aggregate = match parts[i + 1].to_uppercase().as_str() {
+                        "SUM" => Aggregate::Sum,
+                        "MIN" => Aggregate::Min,
+                        "MAX" => Aggregate::Max,
+                        _ => return Err(ParseError::syntax("syntax error".to_string()).at(i + 1)),
+                    };
+                    i += 2;
+                }
+                _ => return Err(ParseError::syntax("syntax error".to_string()).at(i)),
+            }
+        }
+        Ok((parts[1].to_string(), keys, weights, aggregate))
+    }
+    
+    /// Parse the optional ASYNC or SYNC of FLUSHDB/FLUSHALL: whether the space
+    /// is freed in the background. Without either the flush is synchronous.
+    fn parse_flush_mode(command: &str, parts: &[&str]) -> ParseResult<bool> {
+        match &parts[1..] {
+            [] => Ok(false),
+            [mode] if mode.eq_ignore_ascii_case("ASYNC") => Ok(true),
+            [mode] if mode.eq_ignore_ascii_case("SYNC") => Ok(false),
+            [_] => Err(ParseError::syntax(format!("{} accepts only ASYNC or SYNC", command)).at(1)),
+            _ => Err(ParseError::wrong_arity(format!("{} takes at most one argument", command))),
+        }
+    }
+    
+    /// Parse `numkeys key [key ...]` starting at `parts[at]`, returning the keys and
+    /// the arguments after them
+    fn parse_zdiff_keys<'a, 'b>(command: &str, usage: &str, parts: &'a [&'b str], at: usize) -> ParseResult<(Vec<String>, &'a [&'b str])> {
+        if parts.len() <= at + 1 {
+            return Err(ParseError::wrong_arity(format!("{} requires {}", command, usage)));
+        }
+        let numkeys = parts[at].parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ParseError::invalid_integer(at, "numkeys should be greater than 0".to_string()))?;
+        if parts.len() <= at + numkeys {
+            return Err(ParseError::syntax("syntax error".to_string()).at(parts.len()));
+        }
+        let keys = parts[at + 1..=at + numkeys].iter().map(|s| s.to_string()).collect();
+        Ok((keys, &parts[at + numkeys + 1..]))
+    }
+    
+    fn zstore_to_string(command: &str, dest: &str, keys: &[String], weights: &[f64], aggregate: Aggregate) -> String {
+        let weights: Vec<String> = weights.iter().map(|w| w.to_string()).collect();
+        format!(
+            "{} {} {} {} WEIGHTS {} AGGREGATE {}",
+            command, dest, keys.len(), keys.join(" "), weights.join(" "), aggregate.name()
+        )
+    }
+    
+    /// Parse `key time [NX|XX|GT|LT ...]` of the EXPIRE family, returning the time and flags
+    fn parse_expire(command: &str, invalid: &str, parts: &[&str]) -> ParseResult<(i64, ExpireFlags)> {
+        if parts.len() < 3 {
+            return Err(ParseError::wrong_arity(format!("{} requires a key and a time, optionally followed by NX, XX, GT or LT", command)));
+        }
+        let time = parts[2].parse::<i64>()
+            .map_err(|_| ParseError::invalid_integer(2, invalid.to_string()))?;
+        let mut flags = ExpireFlags::default();
+        for (index, option) in parts.iter().enumerate().skip(3) {
+            match option.to_uppercase().as_str() {
+                "NX" => flags.nx = true,
+                "XX" => flags.xx = true,
+                "GT" => flags.gt = true,
+                "LT" => flags.lt = true,
+                other => return Err(ParseError::syntax(format!("Unsupported option {}", other)).at(index)),
+            }
+        }
+        if flags.nx && (flags.xx || flags.gt || flags.lt) {
+            return Err(ParseError::syntax("NX and XX, GT or LT options at the same time are not compatible".to_string()));
+        }
+        if flags.gt && flags.lt {
+            return Err(ParseError::syntax("GT and LT options at the same time are not compatible".to_string()));
+        }
+        Ok((time, flags))
+    }
+    
+    /// Parse the min and max of `ZRANGEBYLEX key min max ...` or `ZLEXCOUNT key min max`
+    fn parse_lex_range(parts: &[&str]) -> ParseResult<(LexBound, LexBound)> {
+        let bound = |index: usize| {
+            LexBound::parse(parts[index])
+                .ok_or_else(|| ParseError::syntax("min or max not valid string range item".to_string()).at(index))
+        };
+        Ok((bound(2)?, bound(3)?))
+    }
+    
+    /// Parse `numkeys key [key ...] <first|second> [COUNT count]`, returning the
+    /// keys, whether `first` was chosen, and the count (default 1)
+    fn parse_mpop(command: &str, sides: (&str, &str), parts: &[&str]) -> ParseResult<(Vec<String>, bool, usize)> {
+        let usage = || ParseError::wrong_arity(format!(
+            "{} requires numkeys key [key ...] {}|{} [COUNT count]", command, sides.0, sides.1
+        ));
+        let numkeys = parts.first()
+            .ok_or_else(usage)?
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ParseError::invalid_integer(1, "numkeys should be greater than 0".to_string()))?;
+        if parts.len() < numkeys + 2 {
+            return Err(usage());
+        }
+        let keys = parts[1..=numkeys].iter().map(|s| s.to_string()).collect();
+        
+        let side = parts[numkeys + 1].to_uppercase();
+        let first = if side == sides.0 {
+            true
+        } else if side == sides.1 {
+            false
+        } else {
+            return Err(usage());
+        };
+        
+        let count = match &parts[numkeys + 2..] {
+            [] => 1,
+            [option, value] if option.to_uppercase() == "COUNT" => value.parse::<usize>()
+                .ok()
+                .filter(|c| *c > 0)
+                .ok_or_else(|| ParseError::new(ParseErrorKind::InvalidInteger, None, "COUNT must be a positive integer"))?,
+            _ => return Err(usage()),
+        };
+        Ok((keys, first, count))
+    }
+    
+    /// Parse `script numkeys [key ...] [arg ...]` after EVAL, EVALSHA or FCALL
+    fn parse_eval(command: &str, parts: &[&str]) -> ParseResult<(String, Vec<String>, Vec<String>)> {
+        if parts.len() < 3 {
+            return Err(ParseError::wrong_arity(format!("{} requires a script and numkeys", command)));
+        }
+        let numkeys = parts[2].parse::<usize>()
+            .map_err(|_| ParseError::invalid_integer(2, "Invalid number of keys".to_string()))?;
+        if parts.len() < numkeys + 3 {
+            return Err(ParseError::wrong_arity(format!("{} numkeys is greater than the number of keys given", command)));
+        }
+        let keys = parts[3..3 + numkeys].iter().map(|s| s.to_string()).collect();
+        let args = parts[3 + numkeys..].iter().map(|s| s.to_string()).collect();
+        Ok((parts[1].to_string(), keys, args))
+    }
+    
+    /// Parse a trailing `FIELDS numfields field [field ...]` clause
+    fn parse_fields(command: &str, parts: &[&str]) -> ParseResult<Vec<String>> {
+        if parts.len() < 3 || parts[0].to_uppercase() != "FIELDS" {
+            return Err(ParseError::wrong_arity(format!("{} requires a FIELDS numfields field ... clause", command)));
+        }
+        let count = parts[1].parse::<usize>()
+            .map_err(|_| ParseError::new(ParseErrorKind::InvalidInteger, None, "Invalid number of fields"))?;
+        if count == 0 || count != parts.len() - 2 {
+            return Err(ParseError::wrong_arity("The numfields argument must match the number of fields".to_string()));
         }
+        Ok(parts[2..].iter().map(|s| s.to_string()).collect())
     }
 }
 
@@ -572,7 +2709,7 @@ impl fmt::Display for Response {
                     writeln!(f)
                 }
             }
-            Response::Null => writeln!(f, "(nil)"),
+            Response::Null | Response::NullArray => writeln!(f, "(nil)"),
             Response::Error(msg) => writeln!(f, "ERROR: {}", msg),
         }
     }
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often buckets that have refilled completely are dropped
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Connection budget of one client IP
+struct TokenBucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    /// Add the tokens earned since the last refill, capped at `burst`
+    fn refill(&mut self, now: Instant, rate: f64, burst: f64) {
+        let earned = now.duration_since(self.refilled_at).as_secs_f64() * rate;
+        self.tokens = (self.tokens + earned).min(burst);
+        self.refilled_at = now;
+    }
+}
+
+/// Per-IP token-bucket limit on new connections.
+///
+/// Every IP may open `burst` connections at once, after which it earns
+/// `per_second` more each second. The accept loop asks [`allow`] before
+/// spawning a handler and drops the connection when it says no.
+///
+/// [`allow`]: ConnectionRateLimiter::allow
+#[derive(Clone)]
+pub struct ConnectionRateLimiter {
+    buckets: Arc<Mutex<HashMap<IpAddr, TokenBucket>>>,
+    per_second: f64,
+    burst: f64,
+}
+
+impl ConnectionRateLimiter {
+    /// None when `per_second` is 0, which disables the limit
+    pub fn new(per_second: u32, burst: u32) -> Option<Self> {
+        (per_second > 0).then(|| Self {
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+            per_second: per_second as f64,
+            burst: burst.max(1) as f64,
+        })
+    }
+
+    /// Take a token for a new connection from `ip`; false if it has none left
+    pub fn allow(&self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let Ok(mut buckets) = self.buckets.lock() else {
+            return true;
+        };
+        let bucket = buckets.entry(ip).or_insert(TokenBucket { tokens: self.burst, refilled_at: now });
+        bucket.refill(now, self.per_second, self.burst);
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Drop the buckets of IPs that are back to a full budget, since a fresh
+    /// bucket would behave the same
+    pub fn cleanup(&self) {
+        let now = Instant::now();
+        if let Ok(mut buckets) = self.buckets.lock() {
+            buckets.retain(|_, bucket| {
+                bucket.refill(now, self.per_second, self.burst);
+                bucket.tokens < self.burst
+            });
+        }
+    }
+
+    /// Run [`cleanup`](Self::cleanup) periodically, forever
+    pub async fn run_cleanup(self) {
+        let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.cleanup();
+        }
+    }
+}
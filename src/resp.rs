@@ -0,0 +1,356 @@
+//! RESP framing: decoding multibulk requests, and encoding replies for
+//! connections that negotiated RESP3 with `HELLO 3`.
+//!
+//! A request starting with `*` is a multibulk frame, any other is a text line.
+//! A reply is first shaped into a [`Frame`] using the command that produced it,
+//! so that commands answering with field/value pairs become RESP3 maps, then
+//! encoded for the negotiated version. RESP2 has no map, set, double, boolean
+//! or push types and gets their flat equivalents instead.
+
+use crate::error::{DiskDBError, Result};
+use crate::protocol::Response;
+
+/// Longest `*<count>` or `$<len>` header line; anything longer is malformed
+const MAX_HEADER_LEN: usize = 32;
+
+/// Outcome of decoding the start of a buffer
+#[derive(Debug, PartialEq)]
+pub enum Decoded {
+    /// The frame is not complete yet
+    Incomplete,
+    /// A whole request and the number of bytes it took
+    Command(Vec<String>, usize),
+}
+
+/// Decode one multibulk request (`*<n>\r\n` followed by n `$<len>\r\n<bytes>\r\n`)
+/// from the start of `buf`. Declared lengths are checked against `max_bulk_len`
+/// and `max_multibulk_len` before any payload is waited for or allocated, so a
+/// bogus length fails at once instead of holding the connection.
+pub fn decode_command(buf: &[u8], max_bulk_len: usize, max_multibulk_len: usize) -> Result<Decoded> {
+    let Some((count, mut pos)) = header(buf, 0, b'*')? else {
+        return Ok(Decoded::Incomplete);
+    };
+    if count <= 0 {
+        return Ok(Decoded::Command(Vec::new(), pos));
+    }
+    if count as u64 > max_multibulk_len as u64 {
+        return Err(protocol_error("invalid multibulk length"));
+    }
+
+    let mut args = Vec::with_capacity((count as usize).min(64));
+    for _ in 0..count {
+        let Some((len, start)) = header(buf, pos, b'$')? else {
+            return Ok(Decoded::Incomplete);
+        };
+        if len < 0 || len as u64 > max_bulk_len as u64 {
+            return Err(protocol_error("invalid bulk length"));
+        }
+        let end = start + len as usize;
+        if buf.len() < end + 2 {
+            return Ok(Decoded::Incomplete);
+        }
+        if &buf[end..end + 2] != b"\r\n" {
+            return Err(protocol_error("expected CRLF after bulk string"));
+        }
+        let arg = std::str::from_utf8(&buf[start..end])
+            .map_err(|_| protocol_error("bulk string is not valid UTF-8"))?;
+        args.push(arg.to_string());
+        pos = end + 2;
+    }
+    Ok(Decoded::Command(args, pos))
+}
+
+/// Decode one RESP2 or RESP3 reply from the start of `buf`, returning it with
+/// the number of bytes it took, or None if it is not complete yet. Aggregates
+/// are one frame however many elements they hold, so replies can be counted
+/// exactly. RESP3 types come back as their RESP2 equivalents: maps and sets as
+/// flat arrays, doubles and big numbers as strings, booleans as 1 or 0.
+pub fn decode_reply(buf: &[u8]) -> Result<Option<(Response, usize)>> {
+    reply_at(buf, 0)
+}
+
+fn reply_at(buf: &[u8], pos: usize) -> Result<Option<(Response, usize)>> {
+    let Some(cr) = buf.get(pos..).and_then(|rest| rest.windows(2).position(|pair| pair == b"\r\n")) else {
+        return Ok(None);
+    };
+    let body = std::str::from_utf8(&buf[pos + 1..pos + cr])
+        .map_err(|_| protocol_error("reply line is not valid UTF-8"))?;
+    let next = pos + cr + 2;
+    let length = || body.parse::<i64>().map_err(|_| protocol_error("invalid length in reply"));
+    let reply = match buf[pos] {
+        b'+' if body == "OK" => Response::Ok,
+        b'+' | b',' | b'(' => Response::String(Some(body.to_string())),
+        b'-' => Response::Error(body.to_string()),
+        b':' => Response::Integer(body.parse().map_err(|_| protocol_error("invalid integer reply"))?),
+        b'#' => Response::Integer((body == "t") as i64),
+        b'_' => Response::Null,
+        b'$' => {
+            let Ok(len) = usize::try_from(length()?) else {
+                return Ok(Some((Response::Null, next)));
+            };
+            if buf.len() < next + len + 2 {
+                return Ok(None);
+            }
+            if &buf[next + len..next + len + 2] != b"\r\n" {
+                return Err(protocol_error("expected CRLF after bulk string"));
+            }
+            let s = std::str::from_utf8(&buf[next..next + len])
+                .map_err(|_| protocol_error("bulk string is not valid UTF-8"))?;
+            return Ok(Some((Response::String(Some(s.to_string())), next + len + 2)));
+        }
+        marker @ (b'*' | b'%' | b'~' | b'>') => {
+            let Ok(count) = usize::try_from(length()?) else {
+                return Ok(Some((Response::NullArray, next)));
+            };
+            let count = if marker == b'%' { count.saturating_mul(2) } else { count };
+            let mut items = Vec::with_capacity(count.min(64));
+            let mut pos = next;
+            for _ in 0..count {
+                let Some((item, after)) = reply_at(buf, pos)? else {
+                    return Ok(None);
+                };
+                items.push(item);
+                pos = after;
+            }
+            return Ok(Some((Response::Array(items), pos)));
+        }
+        other => return Err(protocol_error(&format!("unexpected reply type '{}'", other.escape_ascii()))),
+    };
+    Ok(Some((reply, next)))
+}
+
+/// Parse the `<marker><integer>\r\n` header at `pos`, returning the integer
+/// and the position after it, or None if the line is not complete yet
+fn header(buf: &[u8], pos: usize, marker: u8) -> Result<Option<(i64, usize)>> {
+    let Some(&first) = buf.get(pos) else {
+        return Ok(None);
+    };
+    if first != marker {
+        return Err(protocol_error(&format!("expected '{}', got '{}'", marker as char, first.escape_ascii())));
+    }
+    let window = &buf[pos..buf.len().min(pos + MAX_HEADER_LEN)];
+    let Some(cr) = window.windows(2).position(|pair| pair == b"\r\n") else {
+        if window.len() == MAX_HEADER_LEN {
+            return Err(protocol_error("header line too long"));
+        }
+        return Ok(None);
+    };
+    let value = std::str::from_utf8(&window[1..cr])
+        .ok()
+        .and_then(|digits| digits.parse::<i64>().ok())
+        .ok_or_else(|| protocol_error(&format!("invalid {} length", if marker == b'*' { "multibulk" } else { "bulk" })))?;
+    Ok(Some((value, pos + cr + 2)))
+}
+
+fn protocol_error(msg: &str) -> DiskDBError {
+    DiskDBError::Protocol(msg.to_string())
+}
+
+/// Protocol version of a RESP connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RespVersion {
+    Resp2,
+    Resp3,
+}
+
+impl RespVersion {
+    pub fn number(&self) -> i64 {
+        match self {
+            RespVersion::Resp2 => 2,
+            RespVersion::Resp3 => 3,
+        }
+    }
+}
+
+/// One RESP value, covering the RESP3 types
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(String),
+    Array(Vec<Frame>),
+    Null,
+    /// RESP2's null array; RESP3 has a single null for every type
+    NullArray,
+    Map(Vec<(Frame, Frame)>),
+    Set(Vec<Frame>),
+    Double(f64),
+    BigNumber(String),
+    Boolean(bool),
+    /// Out-of-band data such as pub/sub messages
+    Push(Vec<Frame>),
+}
+
+impl Frame {
+    /// Shape the reply of `command` (a lowercase name from the command table)
+    pub fn reply(command: &str, response: Response) -> Frame {
+        match (command, response) {
+            ("hgetall" | "hello" | "lcs", Response::Array(items)) => Frame::pairs(items),
+            ("smembers", Response::Array(items)) => Frame::Set(items.into_iter().map(Frame::from).collect()),
+            ("zscore", Response::String(Some(score))) => match score.parse::<f64>() {
+                Ok(score) => Frame::Double(score),
+                Err(_) => Frame::Bulk(score),
+            },
+            (_, response) => Frame::from(response),
+        }
+    }
+
+    /// A pub/sub message or (un)subscribe confirmation
+    pub fn push(response: Response) -> Frame {
+        match response {
+            Response::Array(items) => Frame::Push(items.into_iter().map(Frame::from).collect()),
+            other => Frame::from(other),
+        }
+    }
+
+    /// A map from a flat `[field, value, ...]` array; a trailing odd item is dropped
+    fn pairs(items: Vec<Response>) -> Frame {
+        let mut items = items.into_iter().map(Frame::from);
+        let mut pairs = Vec::new();
+        while let (Some(field), Some(value)) = (items.next(), items.next()) {
+            pairs.push((field, value));
+        }
+        Frame::Map(pairs)
+    }
+
+    pub fn encode(&self, version: RespVersion, out: &mut Vec<u8>) {
+        match self {
+            Frame::Simple(s) => line(out, '+', s),
+            Frame::Error(msg) => line(out, '-', &error_line(msg)),
+            Frame::Integer(n) => line(out, ':', &n.to_string()),
+            Frame::Bulk(s) => bulk(out, s),
+            Frame::Array(items) => {
+                line(out, '*', &items.len().to_string());
+                for item in items {
+                    item.encode(version, out);
+                }
+            }
+            Frame::Null => match version {
+                RespVersion::Resp2 => line(out, '$', "-1"),
+                RespVersion::Resp3 => line(out, '_', ""),
+            },
+            Frame::NullArray => match version {
+                RespVersion::Resp2 => line(out, '*', "-1"),
+                RespVersion::Resp3 => line(out, '_', ""),
+            },
+            Frame::Map(pairs) => {
+                match version {
+                    RespVersion::Resp2 => line(out, '*', &(pairs.len() * 2).to_string()),
+                    RespVersion::Resp3 => line(out, '%', &pairs.len().to_string()),
+                }
+                for (field, value) in pairs {
+                    field.encode(version, out);
+                    value.encode(version, out);
+                }
+            }
+            Frame::Set(items) | Frame::Push(items) => {
+                let marker = match (self, version) {
+                    (_, RespVersion::Resp2) => '*',
+                    (Frame::Set(_), RespVersion::Resp3) => '~',
+                    _ => '>',
+                };
+                line(out, marker, &items.len().to_string());
+                for item in items {
+                    item.encode(version, out);
+                }
+            }
+            Frame::Double(d) => {
+                let text = if d.is_infinite() {
+                    if *d > 0.0 { "inf".to_string() } else { "-inf".to_string() }
+                } else {
+                    d.to_string()
+                };
+                match version {
+                    RespVersion::Resp2 => bulk(out, &text),
+                    RespVersion::Resp3 => line(out, ',', &text),
+                }
+            }
+            Frame::BigNumber(digits) => match version {
+                RespVersion::Resp2 => bulk(out, digits),
+                RespVersion::Resp3 => line(out, '(', digits),
+            },
+            Frame::Boolean(b) => match version {
+                RespVersion::Resp2 => line(out, ':', if *b { "1" } else { "0" }),
+                RespVersion::Resp3 => line(out, '#', if *b { "t" } else { "f" }),
+            },
+        }
+    }
+
+    pub fn to_bytes(&self, version: RespVersion) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(version, &mut out);
+        out
+    }
+}
+
+/// Encode the reply of `command` for `version`. (Un)subscribe confirmations come
+/// back as one flat array and go out as one push per channel, as clients expect.
+pub fn encode_reply(command: &str, response: Response, version: RespVersion, out: &mut Vec<u8>) {
+    match (command, response) {
+        ("subscribe" | "unsubscribe" | "psubscribe" | "punsubscribe", Response::Array(items)) => {
+            let mut items = items.into_iter();
+            loop {
+                let confirmation: Vec<Response> = items.by_ref().take(3).collect();
+                if confirmation.is_empty() {
+                    break;
+                }
+                Frame::push(Response::Array(confirmation)).encode(version, out);
+            }
+        }
+        (command, response) => Frame::reply(command, response).encode(version, out),
+    }
+}
+
+/// Header of the array reply of `command` with `len` items, so a long reply can
+/// be written out in pieces: each item follows encoded on its own with
+/// `Frame::from(item).encode(..)`, giving the same bytes as [`encode_reply`].
+/// Returns false, writing nothing, for replies not sent as one aggregate.
+pub fn encode_array_header(command: &str, len: usize, version: RespVersion, out: &mut Vec<u8>) -> bool {
+    let marker = match (command, version) {
+        ("subscribe" | "unsubscribe" | "psubscribe" | "punsubscribe", _) => return false,
+        ("hgetall" | "hello" | "lcs", _) if !len.is_multiple_of(2) => return false,
+        ("hgetall" | "hello" | "lcs", RespVersion::Resp3) => {
+            line(out, '%', &(len / 2).to_string());
+            return true;
+        }
+        ("smembers", RespVersion::Resp3) => '~',
+        _ => '*',
+    };
+    line(out, marker, &len.to_string());
+    true
+}
+
+impl From<Response> for Frame {
+    fn from(response: Response) -> Frame {
+        match response {
+            Response::Ok => Frame::Simple("OK".to_string()),
+            Response::String(Some(s)) => Frame::Bulk(s),
+            Response::String(None) | Response::Null => Frame::Null,
+            Response::NullArray => Frame::NullArray,
+            Response::Integer(n) => Frame::Integer(n),
+            Response::Array(items) => Frame::Array(items.into_iter().map(Frame::from).collect()),
+            Response::Error(msg) => Frame::Error(msg),
+        }
+    }
+}
+
+fn line(out: &mut Vec<u8>, marker: char, body: &str) {
+    out.push(marker as u8);
+    out.extend_from_slice(body.as_bytes());
+    out.extend_from_slice(b"\r\n");
+}
+
+fn bulk(out: &mut Vec<u8>, s: &str) {
+    line(out, '$', &s.len().to_string());
+    out.extend_from_slice(s.as_bytes());
+    out.extend_from_slice(b"\r\n");
+}
+
+/// Errors go on one line and start with an uppercase code, `ERR` unless they carry one
+fn error_line(msg: &str) -> String {
+    let msg = msg.replace(['\r', '\n'], " ");
+    let has_code = msg.split(' ').next()
+        .is_some_and(|code| !code.is_empty() && code.chars().all(|c| c.is_ascii_uppercase()));
+    if has_code { msg } else { format!("ERR {}", msg) }
+}
@@ -2,11 +2,14 @@ use crate::commands::CommandExecutor;
 use crate::config::Config;
 use crate::connection::Connection;
 use crate::error::Result;
+use crate::expiration::ActiveExpirer;
+use crate::listener::{self, SocketOptions};
+use crate::rate_limit::ConnectionRateLimiter;
 use crate::storage::Storage;
 use crate::tls::create_tls_acceptor;
-use log::{error, info};
+use log::{error, info, warn};
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
+use tokio::net::TcpStream;
 use tokio_native_tls::TlsAcceptor;
 
 pub struct Server {
@@ -37,22 +40,48 @@ impl Server {
 
     pub async fn start(&self) -> Result<()> {
         let addr = format!("0.0.0.0:{}", self.config.server_port);
-        let listener = TcpListener::bind(&addr).await?;
+        let socket_options = SocketOptions::from_config(&self.config);
+        let listener = listener::bind(&addr, &socket_options, false)?;
         info!("Server listening on {}", addr);
         
         if self.config.use_tls {
             info!("TLS enabled");
         }
 
-        let executor = Arc::new(CommandExecutor::new(self.storage.clone()));
+        let executor = Arc::new(
+            CommandExecutor::new(self.storage.clone())
+                .with_max_value_size(self.config.max_value_size)
+                .with_debug_command(self.config.enable_debug_command)
+                .with_tracking_limit(self.config.tracking_max_keys)
+                .with_latency_threshold(self.config.latency_monitor_threshold_ms)
+                .with_lua_time_limit(self.config.lua_time_limit_ms),
+        );
+        tokio::spawn(
+            ActiveExpirer::new(self.storage.clone(), &self.config)
+                .with_latency_monitor(executor.latency_monitor().clone())
+                .run(),
+        );
+        let libraries = executor.restore_functions().await?;
+        if libraries > 0 {
+            info!("Restored {} function libraries", libraries);
+        }
+
+        let rate_limiter = ConnectionRateLimiter::new(self.config.max_connections_per_sec, self.config.connection_burst);
+        if let Some(limiter) = &rate_limiter {
+            tokio::spawn(limiter.clone().run_cleanup());
+        }
 
         loop {
             let (stream, addr) = listener.accept().await?;
+            if rate_limiter.as_ref().is_some_and(|limiter| !limiter.allow(addr.ip())) {
+                warn!("Refusing connection from {}: connection rate limit exceeded", addr);
+                continue;
+            }
             let executor = executor.clone();
             let tls_acceptor = self.tls_acceptor.clone();
             
             tokio::spawn(async move {
-                if let Err(e) = Self::handle_client(stream, addr.to_string(), executor, tls_acceptor).await {
+                if let Err(e) = Self::handle_client(stream, addr.to_string(), executor, tls_acceptor, socket_options).await {
                     error!("Error handling client {}: {}", addr, e);
                 }
             });
@@ -64,7 +93,9 @@ impl Server {
         addr: String,
         executor: Arc<CommandExecutor>,
         tls_acceptor: Option<TlsAcceptor>,
+        socket_options: SocketOptions,
     ) -> Result<()> {
+        listener::configure(&stream, &socket_options)?;
         let connection = if let Some(acceptor) = tls_acceptor {
             match acceptor.accept(stream).await {
                 Ok(tls_stream) => Connection::Tls(tls_stream),
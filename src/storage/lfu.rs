@@ -0,0 +1,73 @@
+//! Redis-style LFU access counter: an 8-bit logarithmic (Morris) counter that
+//! grows more slowly the higher it gets, paired with the minute it was last
+//! decremented so idle keys lose frequency over time.
+
+use super::random_u64;
+
+/// Counter given to keys seen for the first time, so new keys are not evicted
+/// before they have had a chance to be accessed
+pub const LFU_INIT_VAL: u8 = 5;
+/// Higher values make the counter saturate more slowly
+const LFU_LOG_FACTOR: f64 = 10.0;
+/// Minutes of inactivity that cost one counter step
+const LFU_DECAY_MINUTES: u16 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LfuCounter {
+    pub counter: u8,
+    /// Minutes since the epoch, truncated to 16 bits, of the last decay
+    pub last_decay: u16,
+}
+
+impl LfuCounter {
+    pub fn new(now_minutes: u16) -> Self {
+        Self { counter: LFU_INIT_VAL, last_decay: now_minutes }
+    }
+    
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [counter, hi, lo] => Some(Self { counter: *counter, last_decay: u16::from_be_bytes([*hi, *lo]) }),
+            _ => None,
+        }
+    }
+    
+    pub fn encode(&self) -> [u8; 3] {
+        let [hi, lo] = self.last_decay.to_be_bytes();
+        [self.counter, hi, lo]
+    }
+    
+    /// The counter after applying decay for the minutes elapsed since `last_decay`
+    pub fn decayed(&self, now_minutes: u16) -> Self {
+        // The 16-bit clock wraps roughly every 45 days
+        let elapsed = now_minutes.wrapping_sub(self.last_decay);
+        let periods = elapsed / LFU_DECAY_MINUTES;
+        if periods == 0 {
+            return *self;
+        }
+        Self {
+            counter: self.counter.saturating_sub(periods.min(u8::MAX as u16) as u8),
+            last_decay: now_minutes,
+        }
+    }
+    
+    /// Record one access: the chance of incrementing falls as the counter grows
+    pub fn incremented(&self) -> Self {
+        if self.counter == u8::MAX {
+            return *self;
+        }
+        let base = self.counter.saturating_sub(LFU_INIT_VAL) as f64;
+        let probability = 1.0 / (base * LFU_LOG_FACTOR + 1.0);
+        let counter = if random_unit() < probability { self.counter + 1 } else { self.counter };
+        Self { counter, ..*self }
+    }
+}
+
+/// Current time in minutes, truncated to the counter's 16-bit clock
+pub fn now_minutes(now_millis: u64) -> u16 {
+    (now_millis / 60_000) as u16
+}
+
+/// Uniform value in [0, 1) from a per-thread xorshift generator
+fn random_unit() -> f64 {
+    (random_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
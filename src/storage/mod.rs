@@ -1,21 +1,204 @@
-use crate::data_types::DataType;
+use crate::clock::Clock;
+use crate::data_types::{DataType, EncodingLimits};
 use crate::error::Result;
 use async_trait::async_trait;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+pub mod lfu;
 pub mod rocksdb_storage;
+pub mod scan_cursor;
+
+/// Current wall-clock time in milliseconds since the Unix epoch
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Uniform random u64 from a per-thread xorshift generator
+pub fn random_u64() -> u64 {
+    thread_local! {
+        static STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+    }
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    })
+}
+
+/// Bounds of the key range holding exactly the keys that start with `prefix`,
+/// end exclusive. Keys are UTF-8, which never contains the byte 0xff, so
+/// bumping the last byte of a non-empty prefix always gives the end.
+pub fn prefix_range(prefix: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+    let start = prefix.as_bytes().to_vec();
+    let mut end = start.clone();
+    let last = end.last_mut()?;
+    *last += 1;
+    Some((start, end))
+}
+
+/// On-disk size of flushed data before and after block compression
+#[derive(Debug, Clone, Default)]
+pub struct CompressionStats {
+    pub codec: &'static str,
+    pub raw_bytes: u64,
+    pub stored_bytes: u64,
+}
+
+impl CompressionStats {
+    /// Raw size divided by stored size, or 1.0 before anything is flushed
+    pub fn ratio(&self) -> f64 {
+        if self.stored_bytes == 0 {
+            1.0
+        } else {
+            self.raw_bytes as f64 / self.stored_bytes as f64
+        }
+    }
+}
+
+/// Totals maintained alongside every write, so reading them needs no scan
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Aggregates {
+    /// Keys stored, including expired keys not yet reclaimed
+    pub keys: u64,
+    /// Approximate logical size: the sum of key and encoded value lengths
+    pub bytes: u64,
+    /// Keys per type name; types without keys are omitted
+    pub keys_by_type: BTreeMap<String, u64>,
+}
 
 #[async_trait]
 pub trait Storage: Send + Sync {
     // Basic operations
+    /// Clock that expiry deadlines are measured against
+    fn clock(&self) -> Arc<dyn Clock>;
+
+    /// Current time on [`clock`](Self::clock), in milliseconds since the Unix epoch
+    fn now_millis(&self) -> u64 {
+        self.clock().now_millis()
+    }
+
     async fn get(&self, key: &str) -> Result<Option<DataType>>;
     async fn set(&self, key: &str, value: DataType) -> Result<()>;
     async fn delete(&self, key: &str) -> Result<bool>;
+    
+    /// `get` without the boxed future, since point reads never wait
+    fn get_now(&self, key: &str) -> Result<Option<DataType>>;
+    /// `set` followed by `persist`, without the boxed futures
+    fn set_now(&self, key: &str, value: &DataType) -> Result<()>;
+    
+    /// Atomically add `delta` to an integer string, creating it at 0 if missing.
+    /// Returns the value observed right after the increment was applied.
+    async fn incr_by(&self, key: &str, delta: i64) -> Result<i64>;
+    /// Atomically add `delta` to a float string, creating it at 0 if missing, and return
+    /// the stored result, or the error reply when the value is not a float, is of
+    /// another type, or the sum would not be finite; the value is then unchanged.
+    async fn incr_by_float(&self, key: &str, delta: f64) -> Result<std::result::Result<String, String>>;
+    /// Append to the string at `key`, creating it if missing, and return the new
+    /// length. The suffix is queued as a merge rather than rewriting the value.
+    async fn append(&self, key: &str, value: &str) -> Result<usize>;
     async fn exists(&self, key: &str) -> Result<bool>;
     async fn get_type(&self, key: &str) -> Result<Option<String>>;
+    /// Type name and length of a value without decoding its elements:
+    /// the element count for collections, the byte length for strings
+    async fn collection_len(&self, key: &str) -> Result<Option<(&'static str, usize)>>;
+    
+    // Expiry operations
+    /// Set an absolute deadline in milliseconds since the Unix epoch.
+    /// A deadline in the past deletes the key immediately. Returns false if the key does not exist.
+    async fn expire_at(&self, key: &str, deadline_ms: u64) -> Result<bool>;
+    /// Get the absolute deadline of a key, if it has one
+    async fn expiry(&self, key: &str) -> Result<Option<u64>>;
+    /// Remove any deadline from a key. Returns true if one was removed.
+    async fn persist(&self, key: &str) -> Result<bool>;
+    /// List up to `count` keys carrying a deadline, in key order, starting after `after`
+    async fn scan_expiring(&self, after: Option<&str>, count: usize) -> Result<Vec<(String, u64)>>;
+    
+    // Hash field expiry
+    /// Absolute per-field deadlines of a hash, in milliseconds since the Unix epoch
+    async fn field_deadlines(&self, key: &str) -> Result<HashMap<String, u64>>;
+    /// Replace the per-field deadlines of a hash; an empty map removes them.
+    /// They are also dropped whenever the key itself is deleted or expires.
+    async fn set_field_deadlines(&self, key: &str, deadlines: &HashMap<String, u64>) -> Result<()>;
+    
+    /// Sizes under which values written from now on are stored in a compact encoding
+    fn encoding_limits(&self) -> EncodingLimits;
+    /// Change those sizes; values already stored keep their encoding until rewritten
+    fn set_encoding_limits(&self, limits: EncodingLimits);
+    
+    /// Whether access frequency is tracked, which an LFU maxmemory policy turns on
+    fn tracks_access_frequency(&self) -> bool;
+    /// LFU access counter of a key after decay, or None if the key does not exist
+    /// or access frequency is not tracked.
+    async fn access_frequency(&self, key: &str) -> Result<Option<u8>>;
+    
+    /// Visit about `count` keys in key order from `cursor`, and return the next
+    /// cursor (0 once the keyspace is exhausted) with the live keys visited.
+    /// With `type_filter`, only keys of that type name are returned; the type is read
+    /// from the encoded value's discriminant without decoding the payload.
+    /// Any cursor is accepted. Every key that exists for the whole iteration is
+    /// returned, exactly once when the cursors are ones recently handed out.
+    async fn scan(&self, cursor: usize, count: usize, type_filter: Option<&str>) -> Result<(usize, Vec<String>)>;
+    /// A random live key, or None when there is none. Not exactly uniform: keys
+    /// that follow a sparse stretch of the keyspace are picked more often.
+    async fn random_key(&self) -> Result<Option<String>>;
+    
+    // Function libraries
+    /// Persist a FUNCTION LOAD library's source under its name, replacing any earlier version
+    async fn save_function_library(&self, name: &str, code: &str) -> Result<()>;
+    /// Every persisted function library as (name, source), in name order
+    async fn function_libraries(&self) -> Result<Vec<(String, String)>>;
+    
+    /// Number of keys stored, including expired keys not yet reclaimed
+    async fn dbsize(&self) -> Result<usize>;
+    
+    /// Position of the latest write. Every write moves it forward, and it
+    /// survives restarts, so clients can hold on to it as a consistency token.
+    async fn write_offset(&self) -> Result<u64>;
+    
+    /// Key count, approximate size and keys per type, without scanning the keyspace
+    async fn aggregates(&self) -> Result<Aggregates>;
+    
+    /// Compression effectiveness over data flushed to disk
+    async fn compression_stats(&self) -> Result<CompressionStats>;
     
     // Batch operations
     async fn delete_multiple(&self, keys: &[String]) -> Result<usize>;
+    /// Delete every key in `[start, end)` with range tombstones rather than one
+    /// delete per key. Returns the number of keys removed, which is approximate:
+    /// it includes expired keys that had not been reclaimed yet.
+    async fn delete_range(&self, start: &[u8], end: &[u8]) -> Result<usize>;
+    /// Delete like `delete_multiple`, but leave reclaiming the values' space to a
+    /// background task so the call returns in bounded time
+    async fn unlink(&self, keys: &[String]) -> Result<usize>;
     async fn exists_multiple(&self, keys: &[String]) -> Result<usize>;
+    /// Store each entry whose key does not exist, in a single write, and return
+    /// how many were stored. Keys past their deadline count as missing.
+    async fn insert_missing(&self, entries: Vec<(String, DataType)>) -> Result<usize>;
+    /// Store a copy of the value under `source` at `destination`, with the source's
+    /// deadline and hash field deadlines, in a single write. Returns false without
+    /// writing when the source is missing or past its deadline, or when the
+    /// destination exists and `replace` is not set.
+    async fn copy(&self, source: &str, destination: &str, replace: bool) -> Result<bool>;
+    /// Delete every key of logical database `index`, keeping function libraries.
+    /// Only database 0 exists, so any other index is an error. The keys are gone
+    /// on return; with `lazy` the space they held is freed in the background.
+    /// Returns the number of keys removed, approximate as with `delete_range`.
+    async fn flush_db(&self, index: usize, lazy: bool) -> Result<usize>;
+    /// Delete every key of every database
+    async fn flush_all(&self, lazy: bool) -> Result<usize> {
+        self.flush_db(0, lazy).await
+    }
     
     // Type-safe get operations
     async fn get_string(&self, key: &str) -> Result<Option<String>> {
@@ -29,10 +212,10 @@ pub trait Storage: Send + Sync {
     async fn get_or_create_list(&self, key: &str) -> Result<DataType> {
         match self.get(key).await? {
             Some(data) => match data {
-                DataType::List(_) => Ok(data),
+                DataType::List(_) | DataType::ListPack(_) => Ok(data),
                 _ => Err(crate::error::DiskDBError::Protocol("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())),
             },
-            None => Ok(DataType::List(Vec::new())),
+            None => Ok(DataType::List(VecDeque::new())),
         }
     }
     
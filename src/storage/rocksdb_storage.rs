@@ -1,19 +1,90 @@
-use crate::data_types::DataType;
-use crate::error::{DiskDBError, Result};
-use crate::storage::Storage;
+use crate::clock::{Clock, SystemClock};
+use crate::config::{Compression, Config, StorageProfile};
+use crate::storage::lfu::{self, LfuCounter};
+use crate::storage::scan_cursor::{self, ScanCursors};
+use crate::data_types::{DataType, EncodingLimits};
+use crate::error::{Context, DiskDBError, Result};
+use crate::storage::{random_u64, Aggregates, CompressionStats, Storage};
 use async_trait::async_trait;
-use rocksdb::{DB, Options, WriteBatch};
-use std::sync::Arc;
+use log::info;
+use rocksdb::{
+    BlockBasedOptions, Cache, ColumnFamily, ColumnFamilyDescriptor, DBCompactionStyle, DBCompressionType, Direction,
+    IteratorMode, DB, MergeOperands, Options, WriteBatch,
+};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
 use std::path::Path;
 
+const INCR_MERGE_OPERATOR: &str = "diskdb.incr";
+
+/// Column family mapping every key to its type name, so the column family
+/// holding its value is found without probing each of them
+const TYPES_CF: &str = "types";
+/// Column family holding the values of each type. Keeping tiny strings apart
+/// from large collections gives compaction and the block cache values of a
+/// similar shape to work with.
+const VALUE_CFS: &[(&str, &str)] = &[
+    ("string", "strings"),
+    ("list", "lists"),
+    ("set", "sets"),
+    ("hash", "hashes"),
+    ("zset", "zsets"),
+    ("json", "json"),
+    ("stream", "streams"),
+];
+/// Keys a SCAN page resumed from a cursor's leading bytes may visit, as a multiple of its COUNT
+const INEXACT_SCAN_PAGES: usize = 10;
+/// Keys moved per write when upgrading a database that kept every value in the
+/// default column family
+const MIGRATION_BATCH: usize = 1000;
+
+/// Column family mapping keys to their absolute expiry deadline (big-endian u64 millis)
+const EXPIRES_CF: &str = "expires";
+/// Column family mapping hash keys to their per-field deadlines (bincode map of field to millis)
+const FIELD_EXPIRES_CF: &str = "field_expires";
+/// Column family mapping keys to their LFU counter (counter byte, big-endian u16 minutes)
+const LFU_CF: &str = "lfu";
+/// Column family mapping function library names to their Lua source
+const FUNCTIONS_CF: &str = "functions";
+/// Column family of aggregates kept in step with every value write: the key count,
+/// the approximate byte size and a count per type, each a little-endian i64
+const STATS_CF: &str = "stats";
+const STATS_MERGE_OPERATOR: &str = "diskdb.sum";
+const STAT_KEYS: &[u8] = b"keys";
+const STAT_BYTES: &[u8] = b"bytes";
+const STAT_TYPE_PREFIX: &str = "type:";
+/// Stripes of the lock that serializes writes to the same key
+const KEY_LOCK_STRIPES: usize = 64;
+/// Live keys past a random seek point that RANDOMKEY picks from
+const RANDOM_KEY_WINDOW: usize = 64;
+
 pub struct RocksDBStorage {
     db: Arc<DB>,
+    compression: Compression,
+    /// Whether reads update the LFU access counters
+    track_frequency: bool,
+    /// Sizes under which values written from now on get a compact encoding
+    encoding_limits: RwLock<EncodingLimits>,
+    /// Held while a key's old value is read and its replacement written, so the
+    /// aggregate deltas computed from the old value stay exact
+    key_locks: Vec<Mutex<()>>,
+    clock: Arc<dyn Clock>,
+    scan_cursors: ScanCursors,
 }
 
 impl RocksDBStorage {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let mut opts = Options::default();
+        Self::with_config(path, &Config::default())
+    }
+    
+    /// Open the database tuned by the config's storage profile and overrides
+    pub fn with_config<P: AsRef<Path>>(path: P, config: &Config) -> Result<Self> {
+        let mut opts = tuned_options(config);
         opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        opts.set_merge_operator(INCR_MERGE_OPERATOR, incr_full_merge, incr_partial_merge);
         
         // Clean up existing database for tests
         let path_ref = path.as_ref();
@@ -21,71 +92,1229 @@ impl RocksDBStorage {
             std::fs::remove_dir_all(path_ref).ok();
         }
         
-        let db = DB::open(&opts, path)?;
+        let mut cfs = vec![
+            ColumnFamilyDescriptor::new(EXPIRES_CF, tuned_options(config)),
+            ColumnFamilyDescriptor::new(FIELD_EXPIRES_CF, tuned_options(config)),
+            ColumnFamilyDescriptor::new(LFU_CF, tuned_options(config)),
+            ColumnFamilyDescriptor::new(FUNCTIONS_CF, tuned_options(config)),
+            ColumnFamilyDescriptor::new(STATS_CF, stats_options()),
+            ColumnFamilyDescriptor::new(TYPES_CF, tuned_options(config)),
+        ];
+        for (type_name, cf) in VALUE_CFS {
+            let mut cf_opts = tuned_options(config);
+            if *type_name == "string" {
+                cf_opts.set_merge_operator(INCR_MERGE_OPERATOR, incr_full_merge, incr_partial_merge);
+            }
+            cfs.push(ColumnFamilyDescriptor::new(*cf, cf_opts));
+        }
+        let db = DB::open_cf_descriptors(&opts, path_ref, cfs)
+            .context(|| format!("opening database at {}", path_ref.display()))?;
         
-        Ok(Self {
+        let storage = Self {
             db: Arc::new(db),
-        })
+            compression: config.compression,
+            track_frequency: config.maxmemory_policy.is_lfu(),
+            encoding_limits: RwLock::new(EncodingLimits {
+                list_max_listpack_entries: config.list_max_listpack_entries,
+                list_max_listpack_bytes: config.list_max_listpack_bytes,
+                hash_max_listpack_entries: config.hash_max_listpack_entries,
+                set_max_intset_entries: config.set_max_intset_entries,
+                zset_max_listpack_entries: config.zset_max_listpack_entries,
+            }),
+            key_locks: (0..KEY_LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+            clock: Arc::new(SystemClock),
+            scan_cursors: ScanCursors::default(),
+        };
+        storage.migrate_default_cf()?;
+        storage.ensure_aggregates()?;
+        Ok(storage)
     }
-}
-
-#[async_trait]
-impl Storage for RocksDBStorage {
-    async fn get(&self, key: &str) -> Result<Option<DataType>> {
-        match self.db.get(key.as_bytes())? {
-            Some(value) => {
+    
+    /// Measure expiry against `clock` instead of the wall clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Move values written before each type had its own column family out of the
+    /// default column family. A database already upgraded has nothing left there,
+    /// so after the first open this is a single empty seek.
+    fn migrate_default_cf(&self) -> Result<usize> {
+        let types_cf = self.types_cf()?;
+        let mut batch = WriteBatch::default();
+        let mut migrated = 0;
+        for item in self.db.iterator(IteratorMode::Start) {
+            let (key, value) = item.context(|| "migrating the default column family".to_string())?;
+            let type_name = DataType::peek_type(&value)
+                .ok_or_else(|| DiskDBError::Database("Corrupt value header".to_string()))?;
+            batch.put_cf(types_cf, &key, type_name);
+            batch.put_cf(self.value_cf(type_name)?, &key, &value);
+            batch.delete(&key);
+            migrated += 1;
+            if migrated % MIGRATION_BATCH == 0 {
+                self.db.write(std::mem::take(&mut batch)).context(|| "migrating the default column family".to_string())?;
+            }
+        }
+        if !batch.is_empty() {
+            self.db.write(batch).context(|| "migrating the default column family".to_string())?;
+        }
+        if migrated > 0 {
+            info!("Moved {} keys into per-type column families", migrated);
+        }
+        Ok(migrated)
+    }
+    
+    fn expires_cf(&self) -> Result<&ColumnFamily> {
+        self.db.cf_handle(EXPIRES_CF)
+            .ok_or_else(|| DiskDBError::Database(format!("Missing column family: {}", EXPIRES_CF)))
+    }
+    
+    fn field_expires_cf(&self) -> Result<&ColumnFamily> {
+        self.db.cf_handle(FIELD_EXPIRES_CF)
+            .ok_or_else(|| DiskDBError::Database(format!("Missing column family: {}", FIELD_EXPIRES_CF)))
+    }
+    
+    fn lfu_cf(&self) -> Result<&ColumnFamily> {
+        self.db.cf_handle(LFU_CF)
+            .ok_or_else(|| DiskDBError::Database(format!("Missing column family: {}", LFU_CF)))
+    }
+    
+    fn functions_cf(&self) -> Result<&ColumnFamily> {
+        self.db.cf_handle(FUNCTIONS_CF)
+            .ok_or_else(|| DiskDBError::Database(format!("Missing column family: {}", FUNCTIONS_CF)))
+    }
+    
+    fn stats_cf(&self) -> Result<&ColumnFamily> {
+        self.db.cf_handle(STATS_CF)
+            .ok_or_else(|| DiskDBError::Database(format!("Missing column family: {}", STATS_CF)))
+    }
+    
+    fn types_cf(&self) -> Result<&ColumnFamily> {
+        self.db.cf_handle(TYPES_CF)
+            .ok_or_else(|| DiskDBError::Database(format!("Missing column family: {}", TYPES_CF)))
+    }
+    
+    /// Column family holding the values of the given type
+    fn value_cf(&self, type_name: &str) -> Result<&ColumnFamily> {
+        let name = value_cf_name(type_name)
+            .ok_or_else(|| DiskDBError::Database(format!("Unknown value type: {}", type_name)))?;
+        self.db.cf_handle(name)
+            .ok_or_else(|| DiskDBError::Database(format!("Missing column family: {}", name)))
+    }
+    
+    /// Type of the value under `key`, from the type index
+    fn locate(&self, key: &str) -> Result<Option<&'static str>> {
+        match self.db.get_cf(self.types_cf()?, key.as_bytes()).context(|| format!("reading the type of key '{}'", key))? {
+            Some(name) => VALUE_CFS.iter()
+                .map(|(type_name, _)| *type_name)
+                .find(|type_name| type_name.as_bytes() == name.as_slice())
+                .map(Some)
+                .ok_or_else(|| DiskDBError::Database("Corrupt type index".to_string())),
+            None => Ok(None),
+        }
+    }
+    
+    /// Type and encoded value under `key`
+    fn read_value(&self, key: &str) -> Result<Option<(&'static str, Vec<u8>)>> {
+        let Some(type_name) = self.locate(key)? else {
+            return Ok(None);
+        };
+        let value = self.db.get_cf(self.value_cf(type_name)?, key.as_bytes())
+            .context(|| format!("reading key '{}'", key))?;
+        Ok(value.map(|value| (type_name, value)))
+    }
+    
+    /// Name of the column family the value under `key` is stored in
+    pub fn value_column_family(&self, key: &str) -> Result<Option<&'static str>> {
+        Ok(self.read_value(key)?.and_then(|(type_name, _)| value_cf_name(type_name)))
+    }
+    
+    /// Add storing `value` of `type_name` under `key` to `batch`, moving the key
+    /// out of the column family of its previous type, if any. Field deadlines go
+    /// with the hash they belonged to.
+    fn queue_put(&self, batch: &mut WriteBatch, key: &str, old_type: Option<&str>, type_name: &str, value: &[u8]) -> Result<()> {
+        if old_type != Some(type_name) {
+            if let Some(old_type) = old_type {
+                batch.delete_cf(self.value_cf(old_type)?, key.as_bytes());
+            }
+            if old_type == Some("hash") {
+                batch.delete_cf(self.field_expires_cf()?, key.as_bytes());
+            }
+            batch.put_cf(self.types_cf()?, key.as_bytes(), type_name);
+        }
+        batch.put_cf(self.value_cf(type_name)?, key.as_bytes(), value);
+        Ok(())
+    }
+    
+    fn stripe(&self, key: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        hasher.finish() as usize % self.key_locks.len()
+    }
+    
+    fn lock_key(&self, key: &str) -> MutexGuard<'_, ()> {
+        self.key_locks[self.stripe(key)].lock().unwrap_or_else(|e| e.into_inner())
+    }
+    
+    /// Lock every key's stripe, in stripe order so concurrent callers cannot deadlock
+    fn lock_keys<'a>(&self, keys: impl Iterator<Item = &'a str>) -> Vec<MutexGuard<'_, ()>> {
+        let mut stripes: Vec<usize> = keys.map(|key| self.stripe(key)).collect();
+        stripes.sort_unstable();
+        stripes.dedup();
+        stripes.into_iter()
+            .map(|stripe| self.key_locks[stripe].lock().unwrap_or_else(|e| e.into_inner()))
+            .collect()
+    }
+    
+    /// Add the aggregate changes of replacing `old` with `new` under `key` to `batch`
+    fn record_change(&self, batch: &mut WriteBatch, key: &str, old: Option<&[u8]>, new: Option<&[u8]>) -> Result<()> {
+        let cf = self.stats_cf()?;
+        let mut adjust = |stat: &[u8], delta: i64| {
+            if delta != 0 {
+                batch.merge_cf(cf, stat, delta.to_le_bytes());
+            }
+        };
+        let size = |value: Option<&[u8]>| value.map_or(0, |v| (key.len() + v.len()) as i64);
+        
+        adjust(STAT_KEYS, new.is_some() as i64 - old.is_some() as i64);
+        adjust(STAT_BYTES, size(new) - size(old));
+        let (old_type, new_type) = (old.and_then(DataType::peek_type), new.and_then(DataType::peek_type));
+        if old_type != new_type {
+            if let Some(type_name) = old_type {
+                adjust(format!("{}{}", STAT_TYPE_PREFIX, type_name).as_bytes(), -1);
+            }
+            if let Some(type_name) = new_type {
+                adjust(format!("{}{}", STAT_TYPE_PREFIX, type_name).as_bytes(), 1);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Count keys, bytes and types with a full scan of the keyspace
+    pub fn recount_aggregates(&self) -> Result<Aggregates> {
+        let mut aggregates = Aggregates::default();
+        for (type_name, _) in VALUE_CFS {
+            let mut count = 0;
+            for item in self.db.iterator_cf(self.value_cf(type_name)?, IteratorMode::Start) {
+                let (key, value) = item.context(|| "counting aggregates".to_string())?;
+                count += 1;
+                aggregates.bytes += (key.len() + value.len()) as u64;
+            }
+            aggregates.keys += count;
+            if count > 0 {
+                aggregates.keys_by_type.insert(type_name.to_string(), count);
+            }
+        }
+        Ok(aggregates)
+    }
+    
+    /// Record the aggregates from a full scan if they are missing, as in a
+    /// database written before they were maintained
+    fn ensure_aggregates(&self) -> Result<()> {
+        let cf = self.stats_cf()?;
+        if self.db.get_cf(cf, STAT_KEYS).context(|| "reading aggregates".to_string())?.is_some() {
+            return Ok(());
+        }
+        let counted = self.recount_aggregates()?;
+        let mut batch = WriteBatch::default();
+        batch.put_cf(cf, STAT_KEYS, (counted.keys as i64).to_le_bytes());
+        batch.put_cf(cf, STAT_BYTES, (counted.bytes as i64).to_le_bytes());
+        for (type_name, count) in &counted.keys_by_type {
+            batch.put_cf(cf, format!("{}{}", STAT_TYPE_PREFIX, type_name), (*count as i64).to_le_bytes());
+        }
+        self.db.write(batch).context(|| "recording aggregates".to_string())?;
+        Ok(())
+    }
+    
+    fn read_stat(&self, stat: &[u8]) -> Result<u64> {
+        let value = self.db.get_cf(self.stats_cf()?, stat).context(|| "reading aggregates".to_string())?
+            .map_or(0, |bytes| decode_stat(&bytes));
+        Ok(value.max(0) as u64)
+    }
+    
+    /// Queue `operand` on the counter at `key` and return the value it merges to.
+    /// The result is computed with the merge operator itself while the key is locked,
+    /// so it is what RocksDB stores and the aggregates can be updated in the same batch.
+    fn merge_counter(&self, key: &str, operand: &[u8]) -> Result<Option<Vec<u8>>> {
+        let _guard = self.lock_key(key);
+        let old = self.read_value(key)?;
+        self.write_counter(key, old, operand)
+    }
+    
+    /// Queue `operand` as a merge against `old`, the value read under the key's
+    /// lock, which the caller still holds, and return the merged value
+    fn write_counter(&self, key: &str, old: Option<(&'static str, Vec<u8>)>, operand: &[u8]) -> Result<Option<Vec<u8>>> {
+        let old_value = old.as_ref().map(|(_, value)| value.as_slice());
+        let new = apply_deltas(old_value, std::iter::once(operand));
+        
+        match old.as_ref().map(|(type_name, _)| *type_name) {
+            // The merge leaves other types unchanged, so there is nothing to write
+            Some(type_name) if type_name != "string" => {}
+            found => {
+                let mut batch = WriteBatch::default();
+                if found.is_none() {
+                    batch.put_cf(self.types_cf()?, key.as_bytes(), "string");
+                }
+                batch.merge_cf(self.value_cf("string")?, key.as_bytes(), operand);
+                self.record_change(&mut batch, key, old_value, new.as_deref())?;
+                self.db.write(batch).context(|| format!("updating counter '{}'", key))?;
+            }
+        }
+        
+        Ok(new)
+    }
+    
+    /// The key's LFU counter with decay applied; keys never counted start at the initial value
+    fn read_frequency(&self, key: &str, now_minutes: u16) -> Result<LfuCounter> {
+        let counter = self.db.get_cf(self.lfu_cf()?, key.as_bytes())
+            .context(|| format!("reading the access frequency of key '{}'", key))?
+            .and_then(|bytes| LfuCounter::decode(&bytes))
+            .unwrap_or_else(|| LfuCounter::new(now_minutes));
+        Ok(counter.decayed(now_minutes))
+    }
+    
+    /// Record an access to the key in its LFU counter
+    fn touch_frequency(&self, key: &str) -> Result<()> {
+        let counter = self.read_frequency(key, lfu::now_minutes(self.clock.now_millis()))?.incremented();
+        self.db.put_cf(self.lfu_cf()?, key.as_bytes(), counter.encode())
+            .context(|| format!("recording an access to key '{}'", key))?;
+        Ok(())
+    }
+    
+    fn read_expiry(&self, key: &str) -> Result<Option<u64>> {
+        match self.db.get_cf(self.expires_cf()?, key.as_bytes()).context(|| format!("reading the expiry of key '{}'", key))? {
+            Some(bytes) => {
+                let bytes: [u8; 8] = bytes.as_slice().try_into()
+                    .map_err(|_| DiskDBError::Database("Corrupt expiry metadata".to_string()))?;
+                Ok(Some(u64::from_be_bytes(bytes)))
+            }
+            None => Ok(None),
+        }
+    }
+    
+    /// Delete the key and its expiry metadata
+    fn remove_key(&self, key: &str) -> Result<()> {
+        let _guard = self.lock_key(key);
+        let mut batch = WriteBatch::default();
+        self.queue_removal(&mut batch, key)?;
+        self.db.write(batch).context(|| format!("deleting key '{}'", key))?;
+        Ok(())
+    }
+    
+    /// Add the deletion of a key and its metadata to `batch`; the caller holds the key's lock
+    fn queue_removal(&self, batch: &mut WriteBatch, key: &str) -> Result<()> {
+        let old = self.read_value(key)?;
+        if let Some((type_name, _)) = &old {
+            batch.delete_cf(self.value_cf(type_name)?, key.as_bytes());
+            batch.delete_cf(self.types_cf()?, key.as_bytes());
+        }
+        batch.delete_cf(self.expires_cf()?, key.as_bytes());
+        batch.delete_cf(self.field_expires_cf()?, key.as_bytes());
+        batch.delete_cf(self.lfu_cf()?, key.as_bytes());
+        self.record_change(batch, key, old.as_ref().map(|(_, value)| value.as_slice()), None)
+    }
+    
+    /// Lazily delete a key whose deadline has passed. Returns true if it was removed.
+    fn purge_if_expired(&self, key: &str) -> Result<bool> {
+        match self.read_expiry(key)? {
+            Some(deadline) if deadline <= self.clock.now_millis() => {
+                self.remove_key(key)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+    
+    /// Decoded live value under `key`; all of `get`, none of which waits
+    fn read_data(&self, key: &str) -> Result<Option<DataType>> {
+        if self.purge_if_expired(key)? {
+            return Ok(None);
+        }
+        match self.read_value(key)? {
+            Some((_, value)) => {
                 let data: DataType = bincode::deserialize(&value)
                     .map_err(|e| DiskDBError::Database(format!("Deserialization error: {}", e)))?;
+                if self.track_frequency {
+                    self.touch_frequency(key)?;
+                }
                 Ok(Some(data))
             }
             None => Ok(None),
         }
     }
+    
+    /// `value` as written to the database, in the encoding this storage's limits allow
+    fn serialize(&self, value: &DataType) -> Result<Vec<u8>> {
+        let limits = self.encoding_limits();
+        bincode::serialize(&value.encoded(&limits))
+            .map_err(|e| DiskDBError::Database(format!("Serialization error: {}", e)))
+    }
+    
+    /// Store `value` under `key`, dropping its expiry in the same batch when `clear_expiry` is set
+    fn write_data(&self, key: &str, value: &DataType, clear_expiry: bool) -> Result<()> {
+        let serialized = self.serialize(value)?;
+        let _guard = self.lock_key(key);
+        let old = self.read_value(key)?;
+        let mut batch = WriteBatch::default();
+        self.queue_put(&mut batch, key, old.as_ref().map(|(type_name, _)| *type_name), value.type_name(), &serialized)?;
+        self.record_change(&mut batch, key, old.as_ref().map(|(_, value)| value.as_slice()), Some(&serialized))?;
+        if clear_expiry && self.read_expiry(key)?.is_some() {
+            batch.delete_cf(self.expires_cf()?, key.as_bytes());
+        }
+        self.db.write(batch).context(|| format!("writing key '{}'", key))?;
+        Ok(())
+    }
+}
+
+/// Build RocksDB options for the configured storage profile.
+///
+/// WriteHeavy favours large memtables and universal compaction, ReadHeavy a large
+/// cache with point-lookup indexes, LowMemory small buffers and few open files.
+/// `block_cache_mb` and `write_buffer_mb` override the profile's sizes.
+fn tuned_options(config: &Config) -> Options {
+    let (write_buffer_mb, block_cache_mb) = match config.storage_profile {
+        StorageProfile::Balanced => (64, 128),
+        StorageProfile::WriteHeavy => (256, 64),
+        StorageProfile::ReadHeavy => (32, 512),
+        StorageProfile::LowMemory => (8, 8),
+    };
+    let write_buffer_mb = config.write_buffer_mb.unwrap_or(write_buffer_mb).max(1);
+    let block_cache_mb = config.block_cache_mb.unwrap_or(block_cache_mb).max(1);
+    
+    let mut opts = Options::default();
+    opts.set_write_buffer_size(write_buffer_mb * 1024 * 1024);
+    opts.set_compression_type(match config.compression {
+        Compression::None => DBCompressionType::None,
+        Compression::Lz4 => DBCompressionType::Lz4,
+        Compression::Zstd => DBCompressionType::Zstd,
+        Compression::Snappy => DBCompressionType::Snappy,
+    });
+    
+    match config.storage_profile {
+        StorageProfile::Balanced => {
+            opts.increase_parallelism(num_cpus::get() as i32);
+            opts.optimize_level_style_compaction(write_buffer_mb * 4 * 1024 * 1024);
+        }
+        StorageProfile::WriteHeavy => {
+            opts.increase_parallelism(num_cpus::get() as i32);
+            opts.set_max_write_buffer_number(4);
+            opts.set_compaction_style(DBCompactionStyle::Universal);
+        }
+        StorageProfile::ReadHeavy => {
+            opts.increase_parallelism(num_cpus::get() as i32);
+            opts.set_level_compaction_dynamic_level_bytes(true);
+            // Installs its own table factory with a bloom filter and hash index
+            opts.optimize_for_point_lookup(block_cache_mb as u64);
+            return opts;
+        }
+        StorageProfile::LowMemory => {
+            opts.set_max_write_buffer_number(2);
+            opts.set_max_open_files(64);
+        }
+    }
+    
+    let mut table = BlockBasedOptions::default();
+    table.set_block_cache(&Cache::new_lru_cache(block_cache_mb * 1024 * 1024));
+    table.set_bloom_filter(10.0, false);
+    table.set_cache_index_and_filter_blocks(config.storage_profile == StorageProfile::LowMemory);
+    opts.set_block_based_table_factory(&table);
+    
+    opts
+}
+
+/// A random byte string ordered between `low` and `high`: their common prefix
+/// followed by eight bytes drawn uniformly between the next eight bytes of each
+fn random_between(low: &[u8], high: &[u8]) -> Vec<u8> {
+    let common = low.iter().zip(high).take_while(|(a, b)| a == b).count();
+    let word = |key: &[u8]| {
+        let mut bytes = [0u8; 8];
+        for (slot, byte) in bytes.iter_mut().zip(&key[common..]) {
+            *slot = *byte;
+        }
+        u64::from_be_bytes(bytes)
+    };
+    let (lo, hi) = (word(low), word(high));
+    let offset = match (hi - lo).checked_add(1) {
+        Some(span) => random_u64() % span,
+        None => random_u64(),
+    };
+    let mut target = low[..common].to_vec();
+    target.extend_from_slice(&(lo + offset).to_be_bytes());
+    target
+}
+
+/// Tag byte in front of the little-endian f64 written by `incr_by_float`
+const FLOAT_DELTA_TAG: u8 = b'f';
+/// Tag byte in front of the little-endian u64 length and the bytes written by `append`
+const APPEND_TAG: u8 = b'a';
+
+/// A queued change to a string: `incr_by` writes a bare little-endian i64,
+/// `incr_by_float` a tagged little-endian f64 and `append` a tagged,
+/// length-prefixed suffix, which is never 8 bytes long so it cannot pass for an i64
+enum Delta<'a> {
+    Int(i64),
+    Float(f64),
+    Append(&'a str),
+}
+
+fn decode_delta(operand: &[u8]) -> Option<Delta<'_>> {
+    match operand.split_first() {
+        Some((&FLOAT_DELTA_TAG, rest)) if rest.len() == 8 => {
+            rest.try_into().ok().map(f64::from_le_bytes).map(Delta::Float)
+        }
+        Some((&APPEND_TAG, rest)) if rest.len() >= 8 => {
+            let (len, tail) = rest.split_at(8);
+            let len = u64::from_le_bytes(len.try_into().ok()?);
+            if len != tail.len() as u64 {
+                return None;
+            }
+            std::str::from_utf8(tail).ok().map(Delta::Append)
+        }
+        _ => operand.try_into().ok().map(i64::from_le_bytes).map(Delta::Int),
+    }
+}
+
+fn append_operand(value: &str) -> Vec<u8> {
+    let mut operand = Vec::with_capacity(9 + value.len());
+    operand.push(APPEND_TAG);
+    operand.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    operand.extend_from_slice(value.as_bytes());
+    operand
+}
+
+fn incr_full_merge(_key: &[u8], existing: Option<&[u8]>, operands: &MergeOperands) -> Option<Vec<u8>> {
+    apply_deltas(existing, operands.iter())
+}
+
+/// Apply queued increments and appends to the stored string, in order. A
+/// missing key counts as "0" for increments and as empty for appends.
+///
+/// An operand that does not apply to the current value (e.g. an integer delta on
+/// a float string) is skipped; `incr_by` and the executor check the type before
+/// merging, so this only happens if the key is overwritten in between.
+fn apply_deltas<'a>(existing: Option<&[u8]>, operands: impl Iterator<Item = &'a [u8]>) -> Option<Vec<u8>> {
+    let operands: Vec<&[u8]> = operands.collect();
+    // Integer deltas on an int-encoded counter never go through the text form
+    if let Some(start) = existing.map_or(Some(0), DataType::peek_int) {
+        let deltas: Option<Vec<i64>> = operands.iter()
+            .map(|operand| match decode_delta(operand) {
+                Some(Delta::Int(delta)) => Some(delta),
+                _ => None,
+            })
+            .collect();
+        if let Some(deltas) = deltas {
+            let total = deltas.into_iter().fold(start, |total, delta| total.checked_add(delta).unwrap_or(total));
+            return Some(DataType::encode_int(total));
+        }
+    }
+    
+    let mut value = match existing {
+        Some(bytes) => match bincode::deserialize::<DataType>(bytes) {
+            Ok(value @ DataType::String(_)) => Some(value),
+            _ => return Some(bytes.to_vec()),
+        },
+        None => None,
+    };
+    let zero = || DataType::String("0".to_string());
+    
+    for operand in operands {
+        match decode_delta(operand) {
+            Some(Delta::Int(delta)) => {
+                if let DataType::String(s) = value.get_or_insert_with(zero) {
+                    if let Some(next) = s.parse::<i64>().ok().and_then(|v| v.checked_add(delta)) {
+                        *s = next.to_string();
+                    }
+                }
+            }
+            Some(Delta::Float(delta)) => {
+                let _ = value.get_or_insert_with(zero).incrbyfloat(delta);
+            }
+            Some(Delta::Append(tail)) => {
+                if let DataType::String(s) = value.get_or_insert_with(|| DataType::String(String::new())) {
+                    s.push_str(tail);
+                }
+            }
+            None => {}
+        }
+    }
+    
+    bincode::serialize(&value.unwrap_or_else(zero)).ok()
+}
+
+/// Collapse consecutive integer operands into a single delta, and consecutive
+/// appends into one. Float operands are kept apart because floating-point
+/// addition does not regroup exactly.
+fn incr_partial_merge(_key: &[u8], _existing: Option<&[u8]>, operands: &MergeOperands) -> Option<Vec<u8>> {
+    let mut operands = operands.iter().map(decode_delta);
+    match operands.next()?? {
+        Delta::Int(first) => {
+            let mut total = first;
+            for operand in operands {
+                match operand? {
+                    Delta::Int(delta) => total = total.checked_add(delta)?,
+                    _ => return None,
+                }
+            }
+            Some(total.to_le_bytes().to_vec())
+        }
+        Delta::Append(first) => {
+            let mut joined = first.to_string();
+            for operand in operands {
+                match operand? {
+                    Delta::Append(tail) => joined.push_str(tail),
+                    _ => return None,
+                }
+            }
+            Some(append_operand(&joined))
+        }
+        Delta::Float(_) => None,
+    }
+}
+
+/// Column family name for values of the given type
+fn value_cf_name(type_name: &str) -> Option<&'static str> {
+    VALUE_CFS.iter().find(|(name, _)| *name == type_name).map(|(_, cf)| *cf)
+}
+
+/// Column families holding a key's value, type or metadata: everything a range
+/// delete clears, leaving function libraries and the aggregates
+fn keyspace_cfs(db: &DB) -> Result<Vec<&ColumnFamily>> {
+    [TYPES_CF, EXPIRES_CF, FIELD_EXPIRES_CF, LFU_CF].into_iter()
+        .chain(VALUE_CFS.iter().map(|(_, cf)| *cf))
+        .map(|name| db.cf_handle(name)
+            .ok_or_else(|| DiskDBError::Database(format!("Missing column family: {}", name))))
+        .collect()
+}
+
+/// Drop the files lying wholly inside `start..end`; the tombstones covering the
+/// range must already be written
+fn drop_files_in_range(db: &DB, start: &[u8], end: &[u8]) -> Result<()> {
+    for cf in keyspace_cfs(db)? {
+        db.delete_file_in_range_cf(cf, start, end)
+            .context(|| "dropping files in the deleted range".to_string())?;
+    }
+    Ok(())
+}
+
+fn decode_stat(bytes: &[u8]) -> i64 {
+    bytes.try_into().map(i64::from_le_bytes).unwrap_or(0)
+}
+
+/// Sum little-endian i64 deltas into an aggregate
+fn sum_merge(_key: &[u8], existing: Option<&[u8]>, operands: &MergeOperands) -> Option<Vec<u8>> {
+    let total = operands.iter()
+        .map(decode_stat)
+        .fold(existing.map_or(0, decode_stat), i64::wrapping_add);
+    Some(total.to_le_bytes().to_vec())
+}
+
+fn stats_options() -> Options {
+    let mut opts = Options::default();
+    opts.set_merge_operator_associative(STATS_MERGE_OPERATOR, sum_merge);
+    opts
+}
+
+#[async_trait]
+impl Storage for RocksDBStorage {
+    fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+    
+    async fn get(&self, key: &str) -> Result<Option<DataType>> {
+        self.read_data(key)
+    }
 
     async fn set(&self, key: &str, value: DataType) -> Result<()> {
-        let serialized = bincode::serialize(&value)
-            .map_err(|e| DiskDBError::Database(format!("Serialization error: {}", e)))?;
-        self.db.put(key.as_bytes(), serialized)?;
-        Ok(())
+        self.write_data(key, &value, false)
+    }
+    
+    fn get_now(&self, key: &str) -> Result<Option<DataType>> {
+        self.read_data(key)
+    }
+    
+    fn set_now(&self, key: &str, value: &DataType) -> Result<()> {
+        self.write_data(key, value, true)
+    }
+
+    async fn incr_by(&self, key: &str, delta: i64) -> Result<i64> {
+        // Validate up front so type and overflow errors reach the client
+        let current = match self.purge_if_expired(key)? {
+            true => None,
+            false => self.read_value(key)?,
+        };
+        if let Some((type_name, bytes)) = current {
+            let value = match DataType::peek_int(&bytes) {
+                Some(value) => value,
+                None if type_name == "string" => match bincode::deserialize(&bytes) {
+                    Ok(DataType::String(s)) => s.parse::<i64>()
+                        .map_err(|_| DiskDBError::Database("Value is not an integer".to_string()))?,
+                    _ => return Err(DiskDBError::Database("Corrupt string value".to_string())),
+                },
+                None => return Err(DiskDBError::Database("Operation not supported on this type".to_string())),
+            };
+            if self.track_frequency {
+                self.touch_frequency(key)?;
+            }
+            value.checked_add(delta)
+                .ok_or_else(|| DiskDBError::Database("Increment or decrement would overflow".to_string()))?;
+        }
+        
+        match self.merge_counter(key, &delta.to_le_bytes())?.as_deref().and_then(DataType::peek_int) {
+            Some(value) => Ok(value),
+            None => Err(DiskDBError::Database("Value is not an integer".to_string())),
+        }
+    }
+
+    async fn incr_by_float(&self, key: &str, delta: f64) -> Result<std::result::Result<String, String>> {
+        self.purge_if_expired(key)?;
+        let _guard = self.lock_key(key);
+        let old = self.read_value(key)?;
+        // Checked under the lock, as the merge leaves values that are not floats unchanged
+        let mut current = match &old {
+            Some((_, bytes)) => bincode::deserialize(bytes)
+                .map_err(|e| DiskDBError::Database(format!("Deserialization error: {}", e)))?,
+            None => DataType::String("0".to_string()),
+        };
+        if let Err(e) = current.incrbyfloat(delta) {
+            return Ok(Err(e));
+        }
+        
+        let mut operand = Vec::with_capacity(9);
+        operand.push(FLOAT_DELTA_TAG);
+        operand.extend_from_slice(&delta.to_le_bytes());
+        let merged = self.write_counter(key, old, &operand)?
+            .ok_or_else(|| DiskDBError::Database("Counter missing after merge".to_string()))?;
+        match bincode::deserialize(&merged) {
+            Ok(DataType::String(s)) => Ok(Ok(s)),
+            _ => Err(DiskDBError::Database("Counter missing after merge".to_string())),
+        }
+    }
+
+    async fn append(&self, key: &str, value: &str) -> Result<usize> {
+        self.purge_if_expired(key)?;
+        let _guard = self.lock_key(key);
+        let current = match self.locate(key)? {
+            Some("string") => match self.db.get_pinned_cf(self.value_cf("string")?, key.as_bytes()).context(|| format!("reading key '{}'", key))? {
+                Some(bytes) => Some(DataType::peek_len(&bytes)
+                    .ok_or_else(|| DiskDBError::Database("Corrupt value header".to_string()))?.1),
+                None => None,
+            },
+            Some(_) => return Err(DiskDBError::Database("Operation not supported on this type".to_string())),
+            None => None,
+        };
+        
+        let mut batch = WriteBatch::default();
+        match current {
+            Some(_) => {
+                batch.merge_cf(self.value_cf("string")?, key.as_bytes(), append_operand(value));
+                // The encoded string grows by exactly the appended bytes
+                if !value.is_empty() {
+                    batch.merge_cf(self.stats_cf()?, STAT_BYTES, (value.len() as i64).to_le_bytes());
+                }
+            }
+            None => {
+                let serialized = bincode::serialize(&DataType::String(value.to_string()))
+                    .map_err(|e| DiskDBError::Database(format!("Serialization error: {}", e)))?;
+                self.queue_put(&mut batch, key, None, "string", &serialized)?;
+                self.record_change(&mut batch, key, None, Some(&serialized))?;
+            }
+        }
+        self.db.write(batch).context(|| format!("appending to key '{}'", key))?;
+        Ok(current.unwrap_or(0) + value.len())
     }
 
     async fn delete(&self, key: &str) -> Result<bool> {
         let exists = self.exists(key).await?;
         if exists {
-            self.db.delete(key.as_bytes())?;
+            self.remove_key(key)?;
         }
         Ok(exists)
     }
 
     async fn exists(&self, key: &str) -> Result<bool> {
-        Ok(self.db.get(key.as_bytes())?.is_some())
+        if self.purge_if_expired(key)? {
+            return Ok(false);
+        }
+        Ok(self.locate(key)?.is_some())
     }
 
     async fn get_type(&self, key: &str) -> Result<Option<String>> {
-        match self.get(key).await? {
-            Some(data) => Ok(Some(data.type_name().to_string())),
+        if self.purge_if_expired(key)? {
+            return Ok(None);
+        }
+        let type_name = self.locate(key)?;
+        if type_name.is_some() && self.track_frequency {
+            self.touch_frequency(key)?;
+        }
+        Ok(type_name.map(str::to_string))
+    }
+    
+    async fn collection_len(&self, key: &str) -> Result<Option<(&'static str, usize)>> {
+        if self.purge_if_expired(key)? {
+            return Ok(None);
+        }
+        let Some(type_name) = self.locate(key)? else {
+            return Ok(None);
+        };
+        // Pinned reads borrow RocksDB's buffer instead of copying the value
+        match self.db.get_pinned_cf(self.value_cf(type_name)?, key.as_bytes())? {
+            Some(value) => DataType::peek_len(&value)
+                .map(Some)
+                .ok_or_else(|| DiskDBError::Database("Corrupt value header".to_string())),
             None => Ok(None),
         }
     }
     
+    async fn expire_at(&self, key: &str, deadline_ms: u64) -> Result<bool> {
+        if !self.exists(key).await? {
+            return Ok(false);
+        }
+        if deadline_ms <= self.clock.now_millis() {
+            self.remove_key(key)?;
+        } else {
+            self.db.put_cf(self.expires_cf()?, key.as_bytes(), deadline_ms.to_be_bytes())
+                .context(|| format!("setting the expiry of key '{}'", key))?;
+        }
+        Ok(true)
+    }
+    
+    async fn expiry(&self, key: &str) -> Result<Option<u64>> {
+        if self.purge_if_expired(key)? {
+            return Ok(None);
+        }
+        self.read_expiry(key)
+    }
+    
+    async fn persist(&self, key: &str) -> Result<bool> {
+        if self.read_expiry(key)?.is_none() {
+            return Ok(false);
+        }
+        self.db.delete_cf(self.expires_cf()?, key.as_bytes())
+            .context(|| format!("removing the expiry of key '{}'", key))?;
+        Ok(true)
+    }
+    
+    async fn scan_expiring(&self, after: Option<&str>, count: usize) -> Result<Vec<(String, u64)>> {
+        let mode = match after {
+            Some(key) => IteratorMode::From(key.as_bytes(), Direction::Forward),
+            None => IteratorMode::Start,
+        };
+        
+        let mut entries = Vec::with_capacity(count);
+        for item in self.db.iterator_cf(self.expires_cf()?, mode) {
+            let (key, value) = item.context(|| "scanning expiry metadata".to_string())?;
+            if after.map(|a| a.as_bytes() == key.as_ref()).unwrap_or(false) {
+                continue;
+            }
+            let deadline: [u8; 8] = value.as_ref().try_into()
+                .map_err(|_| DiskDBError::Database("Corrupt expiry metadata".to_string()))?;
+            entries.push((String::from_utf8_lossy(&key).into_owned(), u64::from_be_bytes(deadline)));
+            if entries.len() >= count {
+                break;
+            }
+        }
+        Ok(entries)
+    }
+    
+    async fn field_deadlines(&self, key: &str) -> Result<HashMap<String, u64>> {
+        match self.db.get_cf(self.field_expires_cf()?, key.as_bytes()).context(|| format!("reading field expiries of key '{}'", key))? {
+            Some(bytes) => bincode::deserialize(&bytes)
+                .map_err(|e| DiskDBError::Database(format!("Corrupt field expiry metadata: {}", e))),
+            None => Ok(HashMap::new()),
+        }
+    }
+    
+    async fn set_field_deadlines(&self, key: &str, deadlines: &HashMap<String, u64>) -> Result<()> {
+        if deadlines.is_empty() {
+            self.db.delete_cf(self.field_expires_cf()?, key.as_bytes())
+                .context(|| format!("clearing field expiries of key '{}'", key))?;
+        } else {
+            let serialized = bincode::serialize(deadlines)
+                .map_err(|e| DiskDBError::Database(format!("Serialization error: {}", e)))?;
+            self.db.put_cf(self.field_expires_cf()?, key.as_bytes(), serialized)
+                .context(|| format!("writing field expiries of key '{}'", key))?;
+        }
+        Ok(())
+    }
+    
+    /// Cursors stand for the key the next page starts at; see `scan_cursor`
+    async fn scan(&self, cursor: usize, count: usize, type_filter: Option<&str>) -> Result<(usize, Vec<String>)> {
+        let (start, exact) = match cursor {
+            0 => (Vec::new(), true),
+            cursor => self.scan_cursors.resume(cursor),
+        };
+        
+        let now = self.clock.now_millis();
+        let mut keys = Vec::new();
+        let mut visited = 0;
+        let mut iter = self.db.iterator_cf(self.types_cf()?, IteratorMode::From(&start, Direction::Forward));
+        
+        loop {
+            let (key, type_name) = match iter.next() {
+                Some(item) => item.context(|| "scanning keys".to_string())?,
+                None => return Ok((0, keys)),
+            };
+            // A page started from a cursor's leading bytes runs on past the keys
+            // sharing them, so that the next cursor moves on from them, but only so
+            // far; the cursor it ends with then resumes at the exact key
+            let capped = visited >= count.saturating_mul(INEXACT_SCAN_PAGES);
+            if visited >= count && (exact || capped || !scan_cursor::shares_prefix(cursor, &key)) {
+                return Ok((self.scan_cursors.register(key.to_vec()), keys));
+            }
+            visited += 1;
+            
+            if let Some(wanted) = type_filter {
+                if type_name.as_ref() != wanted.as_bytes() {
+                    continue;
+                }
+            }
+            let key = String::from_utf8_lossy(&key).into_owned();
+            if self.read_expiry(&key)?.is_some_and(|deadline| deadline <= now) {
+                continue;
+            }
+            keys.push(key);
+        }
+    }
+    
+    /// Seek to a random point between the first and last key, then pick uniformly
+    /// among the next `RANDOM_KEY_WINDOW` live keys, wrapping around to the start.
+    /// A keyspace no larger than the window is sampled exactly uniformly.
+    async fn random_key(&self) -> Result<Option<String>> {
+        let cf = self.types_cf()?;
+        let edge = |mode| -> Result<Option<Box<[u8]>>> {
+            match self.db.iterator_cf(cf, mode).next() {
+                Some(item) => Ok(Some(item.context(|| "reading the key range".to_string())?.0)),
+                None => Ok(None),
+            }
+        };
+        let (Some(first), Some(last)) = (edge(IteratorMode::Start)?, edge(IteratorMode::End)?) else {
+            return Ok(None);
+        };
+        let target = random_between(&first, &last);
+        
+        let now = self.clock.now_millis();
+        let after = self.db.iterator_cf(cf, IteratorMode::From(&target, Direction::Forward));
+        let before = self.db.iterator_cf(cf, IteratorMode::Start)
+            .take_while(|item| item.as_ref().map_or(true, |(key, _)| **key < *target));
+        let mut chosen = None;
+        let mut live = 0;
+        for item in after.chain(before) {
+            let (key, _) = item.context(|| "sampling a random key".to_string())?;
+            let key = String::from_utf8_lossy(&key).into_owned();
+            if self.read_expiry(&key)?.is_some_and(|deadline| deadline <= now) {
+                continue;
+            }
+            // Reservoir sampling: the n-th live key replaces the pick with probability 1/n
+            live += 1;
+            if random_u64().is_multiple_of(live) {
+                chosen = Some(key);
+            }
+            if live == RANDOM_KEY_WINDOW as u64 {
+                break;
+            }
+        }
+        Ok(chosen)
+    }
+    
+    async fn save_function_library(&self, name: &str, code: &str) -> Result<()> {
+        self.db.put_cf(self.functions_cf()?, name.as_bytes(), code.as_bytes())
+            .context(|| format!("saving function library '{}'", name))?;
+        Ok(())
+    }
+    
+    async fn function_libraries(&self) -> Result<Vec<(String, String)>> {
+        let mut libraries = Vec::new();
+        for item in self.db.iterator_cf(self.functions_cf()?, IteratorMode::Start) {
+            let (name, code) = item.context(|| "reading function libraries".to_string())?;
+            let code = String::from_utf8(code.into_vec())
+                .map_err(|_| DiskDBError::Database("Corrupt function library".to_string()))?;
+            libraries.push((String::from_utf8_lossy(&name).into_owned(), code));
+        }
+        Ok(libraries)
+    }
+    
+    async fn dbsize(&self) -> Result<usize> {
+        Ok(self.read_stat(STAT_KEYS)? as usize)
+    }
+    
+    async fn write_offset(&self) -> Result<u64> {
+        Ok(self.db.latest_sequence_number())
+    }
+    
+    async fn aggregates(&self) -> Result<Aggregates> {
+        let mut aggregates = Aggregates {
+            keys: self.read_stat(STAT_KEYS)?,
+            bytes: self.read_stat(STAT_BYTES)?,
+            keys_by_type: BTreeMap::new(),
+        };
+        let prefix = STAT_TYPE_PREFIX.as_bytes();
+        let mode = IteratorMode::From(prefix, Direction::Forward);
+        for item in self.db.iterator_cf(self.stats_cf()?, mode) {
+            let (stat, value) = item.context(|| "reading aggregates".to_string())?;
+            let Some(type_name) = stat.strip_prefix(prefix) else { break };
+            let count = decode_stat(&value);
+            if count > 0 {
+                aggregates.keys_by_type.insert(String::from_utf8_lossy(type_name).into_owned(), count as u64);
+            }
+        }
+        Ok(aggregates)
+    }
+    
+    async fn compression_stats(&self) -> Result<CompressionStats> {
+        // e.g. "# entries=10; raw key size=40; raw value size=9000; data block size=812; ..."
+        let property = |properties: &str, name: &str| -> u64 {
+            properties.split(';')
+                .filter_map(|entry| entry.split_once('='))
+                .find(|(key, _)| key.trim() == name)
+                .and_then(|(_, value)| value.trim().parse().ok())
+                .unwrap_or(0)
+        };
+        
+        let mut stats = CompressionStats { codec: self.compression.name(), raw_bytes: 0, stored_bytes: 0 };
+        for (type_name, _) in VALUE_CFS {
+            let properties = self.db.property_value_cf(self.value_cf(type_name)?, "rocksdb.aggregated-table-properties")
+                .context(|| "reading table properties".to_string())?
+                .unwrap_or_default();
+            stats.raw_bytes += property(&properties, "raw key size") + property(&properties, "raw value size");
+            stats.stored_bytes += property(&properties, "data block size");
+        }
+        Ok(stats)
+    }
+    
+    fn tracks_access_frequency(&self) -> bool {
+        self.track_frequency
+    }
+    
+    fn encoding_limits(&self) -> EncodingLimits {
+        *self.encoding_limits.read().unwrap()
+    }
+    
+    fn set_encoding_limits(&self, limits: EncodingLimits) {
+        *self.encoding_limits.write().unwrap() = limits;
+    }
+    
+    async fn access_frequency(&self, key: &str) -> Result<Option<u8>> {
+        if !self.track_frequency || !self.exists(key).await? {
+            return Ok(None);
+        }
+        Ok(Some(self.read_frequency(key, lfu::now_minutes(self.clock.now_millis()))?.counter))
+    }
+    
     async fn delete_multiple(&self, keys: &[String]) -> Result<usize> {
+        let mut unique: Vec<&str> = keys.iter().map(String::as_str).collect();
+        unique.sort_unstable();
+        unique.dedup();
+        
+        let _guards = self.lock_keys(unique.iter().copied());
+        let now = self.clock.now_millis();
         let mut batch = WriteBatch::default();
         let mut deleted = 0;
-        
-        for key in keys {
-            if self.exists(key).await? {
-                batch.delete(key.as_bytes());
+        for key in unique {
+            if self.locate(key)?.is_none() {
+                continue;
+            }
+            // Expired keys are reclaimed too, but they no longer count as existing
+            if self.read_expiry(key)?.is_none_or(|deadline| deadline > now) {
                 deleted += 1;
             }
+            self.queue_removal(&mut batch, key)?;
         }
         
-        if deleted > 0 {
-            self.db.write(batch)?;
+        if !batch.is_empty() {
+            self.db.write(batch).context(|| format!("deleting {} keys", deleted))?;
         }
         
         Ok(deleted)
     }
     
+    async fn insert_missing(&self, entries: Vec<(String, DataType)>) -> Result<usize> {
+        let _guards = self.lock_keys(entries.iter().map(|(key, _)| key.as_str()));
+        let now = self.clock.now_millis();
+        let mut batch = WriteBatch::default();
+        let mut inserted = HashSet::new();
+        for (key, value) in &entries {
+            if inserted.contains(key.as_str()) {
+                continue;
+            }
+            if self.locate(key)?.is_some() {
+                if self.read_expiry(key)?.is_none_or(|deadline| deadline > now) {
+                    continue;
+                }
+                self.queue_removal(&mut batch, key)?;
+            }
+            let serialized = self.serialize(value)?;
+            self.queue_put(&mut batch, key, None, value.type_name(), &serialized)?;
+            self.record_change(&mut batch, key, None, Some(&serialized))?;
+            inserted.insert(key.as_str());
+        }
+        
+        if !batch.is_empty() {
+            self.db.write(batch).context(|| format!("inserting {} keys", inserted.len()))?;
+        }
+        
+        Ok(inserted.len())
+    }
+    
+    async fn copy(&self, source: &str, destination: &str, replace: bool) -> Result<bool> {
+        if self.purge_if_expired(source)? {
+            return Ok(false);
+        }
+        let _guards = self.lock_keys([source, destination].into_iter());
+        let Some((type_name, value)) = self.read_value(source)? else {
+            return Ok(false);
+        };
+        // The deadline may have passed since the purge above
+        let now = self.clock.now_millis();
+        let deadline = self.read_expiry(source)?;
+        if deadline.is_some_and(|deadline| deadline <= now) {
+            return Ok(false);
+        }
+        let field_deadlines = self.db.get_cf(self.field_expires_cf()?, source.as_bytes())
+            .context(|| format!("reading field expiries of key '{}'", source))?;
+        
+        let mut batch = WriteBatch::default();
+        if self.locate(destination)?.is_some() {
+            let live = self.read_expiry(destination)?.is_none_or(|deadline| deadline > now);
+            if live && !replace {
+                return Ok(false);
+            }
+            // Nothing of the old destination survives, its metadata included
+            self.queue_removal(&mut batch, destination)?;
+        }
+        self.queue_put(&mut batch, destination, None, type_name, &value)?;
+        self.record_change(&mut batch, destination, None, Some(&value))?;
+        if let Some(deadline) = deadline {
+            batch.put_cf(self.expires_cf()?, destination.as_bytes(), deadline.to_be_bytes());
+        }
+        if let Some(field_deadlines) = field_deadlines {
+            batch.put_cf(self.field_expires_cf()?, destination.as_bytes(), field_deadlines);
+        }
+        self.db.write(batch).context(|| format!("copying key '{}' to '{}'", source, destination))?;
+        Ok(true)
+    }
+    
+    async fn delete_range(&self, start: &[u8], end: &[u8]) -> Result<usize> {
+        // Hold every stripe so no write lands in the range between counting and deleting,
+        // which keeps the aggregates exact
+        let _guards: Vec<_> = self.key_locks.iter()
+            .map(|lock| lock.lock().unwrap_or_else(|e| e.into_inner()))
+            .collect();
+        
+        let mut batch = WriteBatch::default();
+        let stats = self.stats_cf()?;
+        let mut removed = 0;
+        let mut bytes = 0;
+        for (type_name, _) in VALUE_CFS {
+            let cf = self.value_cf(type_name)?;
+            let mut count = 0;
+            for item in self.db.iterator_cf(cf, IteratorMode::From(start, Direction::Forward)) {
+                let (key, value) = item.context(|| "scanning the range to delete".to_string())?;
+                if key.as_ref() >= end {
+                    break;
+                }
+                count += 1;
+                bytes += key.len() + value.len();
+            }
+            if count > 0 {
+                batch.merge_cf(stats, format!("{}{}", STAT_TYPE_PREFIX, type_name), (-(count as i64)).to_le_bytes());
+                removed += count;
+            }
+        }
+        if removed == 0 {
+            return Ok(0);
+        }
+        batch.merge_cf(stats, STAT_KEYS, (-(removed as i64)).to_le_bytes());
+        batch.merge_cf(stats, STAT_BYTES, (-(bytes as i64)).to_le_bytes());
+        
+        for cf in keyspace_cfs(&self.db)? {
+            batch.delete_range_cf(cf, start, end);
+        }
+        self.db.write(batch).context(|| format!("deleting {} keys by range", removed))?;
+        // Whole files inside the range are dropped outright once the tombstones
+        // covering them are written, so a failure here leaves no key half deleted
+        drop_files_in_range(&self.db, start, end)?;
+        Ok(removed)
+    }
+    
+    async fn flush_db(&self, index: usize, lazy: bool) -> Result<usize> {
+        if index != 0 {
+            return Err(DiskDBError::Database(format!("DB index {} is out of range", index)));
+        }
+        
+        let _guards: Vec<_> = self.key_locks.iter()
+            .map(|lock| lock.lock().unwrap_or_else(|e| e.into_inner()))
+            .collect();
+        
+        // The whole keyspace is the range from the empty key to just past the largest key
+        let mut end: Option<Vec<u8>> = None;
+        for cf in keyspace_cfs(&self.db)? {
+            if let Some(item) = self.db.iterator_cf(cf, IteratorMode::End).next() {
+                let (key, _) = item.context(|| "finding the last key to flush".to_string())?;
+                if end.as_deref().is_none_or(|end| key.as_ref() > end) {
+                    end = Some(key.to_vec());
+                }
+            }
+        }
+        let Some(mut end) = end else { return Ok(0) };
+        end.push(0);
+        
+        // Nothing is left once the range is deleted, so the aggregates restart
+        // from zero instead of counting every key on the way out
+        let removed = self.read_stat(STAT_KEYS)? as usize;
+        let stats = self.stats_cf()?;
+        let mut batch = WriteBatch::default();
+        batch.put_cf(stats, STAT_KEYS, 0i64.to_le_bytes());
+        batch.put_cf(stats, STAT_BYTES, 0i64.to_le_bytes());
+        for (type_name, _) in VALUE_CFS {
+            batch.put_cf(stats, format!("{}{}", STAT_TYPE_PREFIX, type_name), 0i64.to_le_bytes());
+        }
+        for cf in keyspace_cfs(&self.db)? {
+            batch.delete_range_cf(cf, [].as_slice(), end.as_slice());
+        }
+        self.db.write(batch).context(|| "flushing the keyspace".to_string())?;
+        
+        // The keys are gone once the tombstones are written. Without ASYNC their
+        // files are dropped before returning, while no write can land in the range.
+        // With it, compaction frees the space in the background; unlike dropping
+        // files, it keeps whatever is written in the range afterwards.
+        if lazy {
+            let db = self.db.clone();
+            tokio::task::spawn_blocking(move || {
+                if let Ok(cfs) = keyspace_cfs(&db) {
+                    for cf in cfs {
+                        db.compact_range_cf(cf, None::<&[u8]>, Some(end.as_slice()));
+                    }
+                }
+            });
+        } else {
+            drop_files_in_range(&self.db, &[], &end)?;
+        }
+        Ok(removed)
+    }
+    
+    async fn unlink(&self, keys: &[String]) -> Result<usize> {
+        let mut located = Vec::new();
+        for key in keys {
+            if let Some(cf) = self.locate(key)?.and_then(value_cf_name) {
+                located.push((key.clone(), cf));
+            }
+        }
+        let unlinked = self.delete_multiple(keys).await?;
+        
+        // The deletes are tombstones; compacting over them is what frees the space
+        if unlinked > 0 {
+            let db = self.db.clone();
+            tokio::task::spawn_blocking(move || {
+                for (key, cf) in located {
+                    if let Some(cf) = db.cf_handle(cf) {
+                        db.compact_range_cf(cf, Some(key.as_bytes()), Some(key.as_bytes()));
+                    }
+                }
+            });
+        }
+        Ok(unlinked)
+    }
+    
     async fn exists_multiple(&self, keys: &[String]) -> Result<usize> {
         let mut count = 0;
         for key in keys {
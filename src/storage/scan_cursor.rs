@@ -0,0 +1,82 @@
+//! SCAN cursors.
+//!
+//! A cursor stands for the key the next SCAN page starts at. Its value carries
+//! the leading bytes of that key above a 16-bit tag, so any cursor can be resumed
+//! without server state: the page seeks to the first key with at least those
+//! leading bytes. The exact key is also remembered for the most recent cursors,
+//! and a page started from one of those resumes right at it.
+//!
+//! Unlike an offset into the keyspace, a resume point cannot be shifted by keys
+//! written or deleted elsewhere, so every key that exists for the whole iteration
+//! is returned. From remembered cursors it is returned exactly once; a forgotten
+//! or invented cursor may repeat keys that share its leading bytes.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Low bits of a cursor that tell apart cursors sharing their leading key bytes
+const TAG_BITS: u32 = 16;
+/// Leading key bytes a cursor carries above its tag
+const PREFIX_LEN: usize = std::mem::size_of::<usize>() - TAG_BITS as usize / 8;
+/// Cursors whose exact key is remembered; beyond that the oldest are forgotten
+const MAX_CURSORS: usize = 4096;
+
+#[derive(Default)]
+pub struct ScanCursors {
+    inner: Mutex<Cursors>,
+}
+
+#[derive(Default)]
+struct Cursors {
+    last_tag: u16,
+    /// Exact key each remembered cursor starts at
+    resume: HashMap<usize, Vec<u8>>,
+    /// Remembered cursors, oldest first
+    order: VecDeque<usize>,
+}
+
+impl ScanCursors {
+    /// A new cursor for a page starting at `key`; never 0, which marks the end of
+    /// an iteration. Tags only repeat after far more cursors than are remembered,
+    /// so a remembered cursor is never handed out twice.
+    pub fn register(&self, key: Vec<u8>) -> usize {
+        let mut cursors = self.inner.lock().unwrap();
+        cursors.last_tag = cursors.last_tag.checked_add(1).unwrap_or(1);
+        let cursor = prefix(&key) | cursors.last_tag as usize;
+        cursors.resume.insert(cursor, key);
+        cursors.order.push_back(cursor);
+        if cursors.order.len() > MAX_CURSORS {
+            if let Some(oldest) = cursors.order.pop_front() {
+                cursors.resume.remove(&oldest);
+            }
+        }
+        cursor
+    }
+
+    /// The key to seek to for the page `cursor` starts, and whether it is the exact
+    /// key the cursor was handed out for rather than its leading bytes. Cursors
+    /// can be used any number of times, so a page can be retried.
+    pub fn resume(&self, cursor: usize) -> (Vec<u8>, bool) {
+        if let Some(key) = self.inner.lock().unwrap().resume.get(&cursor) {
+            return (key.clone(), true);
+        }
+        let bytes = cursor.to_be_bytes();
+        // Keys shorter than the prefix were padded with zeros, which must not
+        // place the seek past them
+        let len = bytes[..PREFIX_LEN].iter().rposition(|&b| b != 0).map_or(0, |last| last + 1);
+        (bytes[..len].to_vec(), false)
+    }
+}
+
+/// Whether `key` has the leading bytes `cursor` carries
+pub fn shares_prefix(cursor: usize, key: &[u8]) -> bool {
+    prefix(key) == cursor & !((1 << TAG_BITS) - 1)
+}
+
+/// The leading bytes of `key`, zero-padded, in the bits above the tag
+fn prefix(key: &[u8]) -> usize {
+    let mut bytes = [0u8; std::mem::size_of::<usize>()];
+    let len = key.len().min(PREFIX_LEN);
+    bytes[..len].copy_from_slice(&key[..len]);
+    usize::from_be_bytes(bytes)
+}
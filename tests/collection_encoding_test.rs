@@ -0,0 +1,172 @@
+use diskdb::commands::CommandExecutor;
+use diskdb::protocol::{Request, Response};
+use diskdb::storage::rocksdb_storage::RocksDBStorage;
+use diskdb::Config;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+// Low enough that a few members cross every threshold
+const MAX_ENTRIES: usize = 3;
+
+fn setup() -> (TempDir, CommandExecutor) {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = Config::new();
+    config.hash_max_listpack_entries = MAX_ENTRIES;
+    config.set_max_intset_entries = MAX_ENTRIES;
+    config.zset_max_listpack_entries = MAX_ENTRIES;
+    let storage = Arc::new(RocksDBStorage::with_config(temp_dir.path(), &config).unwrap());
+    (temp_dir, CommandExecutor::new(storage))
+}
+
+async fn run(executor: &CommandExecutor, cmd: &str) -> Response {
+    executor.execute(Request::parse(cmd).unwrap()).await.unwrap()
+}
+
+async fn encoding(executor: &CommandExecutor, key: &str) -> String {
+    match run(executor, &format!("OBJECT ENCODING {}", key)).await {
+        Response::String(Some(encoding)) => encoding,
+        other => panic!("unexpected reply {:?}", other),
+    }
+}
+
+async fn hgetall(executor: &CommandExecutor, key: &str) -> HashMap<String, String> {
+    match run(executor, &format!("HGETALL {}", key)).await {
+        Response::Array(items) => items.chunks(2)
+            .map(|pair| match pair {
+                [Response::String(Some(field)), Response::String(Some(value))] => (field.clone(), value.clone()),
+                other => panic!("unexpected pair {:?}", other),
+            })
+            .collect(),
+        other => panic!("unexpected reply {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_hash_switches_encoding_past_threshold() {
+    let (_dir, executor) = setup();
+    let mut model = HashMap::new();
+
+    for i in 0..MAX_ENTRIES {
+        run(&executor, &format!("HSET h field{} value{}", i, i)).await;
+        model.insert(format!("field{}", i), format!("value{}", i));
+        assert_eq!(encoding(&executor, "h").await, "listpack");
+        assert_eq!(hgetall(&executor, "h").await, model);
+    }
+
+    run(&executor, "HSET h extra \"with spaces:and 3:colons\"").await;
+    model.insert("extra".to_string(), "with spaces:and 3:colons".to_string());
+    assert_eq!(encoding(&executor, "h").await, "hashtable");
+    assert_eq!(hgetall(&executor, "h").await, model);
+
+    // Shrinking back under the threshold and growing past it again keeps every field
+    run(&executor, "HDEL h field0 field1").await;
+    model.remove("field0");
+    model.remove("field1");
+    assert_eq!(encoding(&executor, "h").await, "listpack");
+    assert_eq!(hgetall(&executor, "h").await, model);
+    run(&executor, "HMSET h a 1 b 2").await;
+    model.insert("a".to_string(), "1".to_string());
+    model.insert("b".to_string(), "2".to_string());
+    assert_eq!(encoding(&executor, "h").await, "hashtable");
+    assert_eq!(hgetall(&executor, "h").await, model);
+}
+
+#[tokio::test]
+async fn test_thresholds_belong_to_each_storage() {
+    let (_dir, lowered) = setup();
+    let default_dir = TempDir::new().unwrap();
+    let default = CommandExecutor::new(Arc::new(RocksDBStorage::new(default_dir.path()).unwrap()));
+
+    for executor in [&lowered, &default] {
+        run(executor, "HMSET h a 1 b 2 c 3 d 4").await;
+        run(executor, "SADD s 1 2 3 4").await;
+        run(executor, "ZADD z 1 a 2 b 3 c 4 d").await;
+    }
+    assert_eq!(encoding(&lowered, "h").await, "hashtable");
+    assert_eq!(encoding(&lowered, "s").await, "hashtable");
+    assert_eq!(encoding(&lowered, "z").await, "skiplist");
+    assert_eq!(encoding(&default, "h").await, "listpack");
+    assert_eq!(encoding(&default, "s").await, "intset");
+    assert_eq!(encoding(&default, "z").await, "listpack");
+}
+
+#[tokio::test]
+async fn test_set_uses_intset_for_few_integers() {
+    let (_dir, executor) = setup();
+
+    run(&executor, "SADD s 3 -1 10").await;
+    assert_eq!(encoding(&executor, "s").await, "intset");
+    // A member that is not a canonical integer, or one member too many, needs a hashtable
+    run(&executor, "SADD t 1 01").await;
+    assert_eq!(encoding(&executor, "t").await, "hashtable");
+    run(&executor, "SADD s 7").await;
+    assert_eq!(encoding(&executor, "s").await, "hashtable");
+    run(&executor, "SREM s 10").await;
+    assert_eq!(encoding(&executor, "s").await, "intset");
+
+    let mut members = match run(&executor, "SMEMBERS s").await {
+        Response::Array(items) => items,
+        other => panic!("unexpected reply {:?}", other),
+    };
+    members.sort_by_key(|member| format!("{:?}", member));
+    let expected: Vec<Response> = ["-1", "3", "7"].iter().map(|m| Response::String(Some(m.to_string()))).collect();
+    assert_eq!(members, expected);
+    assert_eq!(run(&executor, "SISMEMBER t 01").await, Response::Integer(1));
+}
+
+#[tokio::test]
+async fn test_sorted_set_switches_encoding_past_threshold() {
+    let (_dir, executor) = setup();
+    let mut model = BTreeMap::new();
+
+    for (score, member) in [(2.5, "b"), (-0.0, "a"), (f64::INFINITY, "c")] {
+        run(&executor, &format!("ZADD z {} {}", score, member)).await;
+        model.insert(member, score);
+    }
+    assert_eq!(encoding(&executor, "z").await, "listpack");
+    let with_scores = |model: &BTreeMap<&str, f64>| {
+        let mut items: Vec<(&str, f64)> = model.iter().map(|(member, score)| (*member, *score)).collect();
+        items.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(b.0)));
+        items.into_iter()
+            .flat_map(|(member, score)| [member.to_string(), score.to_string()])
+            .collect::<Vec<_>>()
+    };
+    let zrange = |reply: Response| match reply {
+        Response::Array(items) => items.into_iter()
+            .map(|item| match item {
+                Response::String(Some(s)) => s,
+                other => panic!("unexpected item {:?}", other),
+            })
+            .map(|s| s.parse::<f64>().map_or(s.clone(), |score| score.to_string()))
+            .collect::<Vec<_>>(),
+        other => panic!("unexpected reply {:?}", other),
+    };
+    assert_eq!(zrange(run(&executor, "ZRANGE z 0 -1 WITHSCORES").await), with_scores(&model));
+
+    run(&executor, "ZADD z 1 d").await;
+    model.insert("d", 1.0);
+    assert_eq!(encoding(&executor, "z").await, "skiplist");
+    assert_eq!(zrange(run(&executor, "ZRANGE z 0 -1 WITHSCORES").await), with_scores(&model));
+
+    run(&executor, "ZREM z c").await;
+    model.remove("c");
+    assert_eq!(encoding(&executor, "z").await, "listpack");
+    assert_eq!(zrange(run(&executor, "ZRANGE z 0 -1 WITHSCORES").await), with_scores(&model));
+    assert_eq!(run(&executor, "ZCARD z").await, Response::Integer(3));
+}
+
+#[test]
+fn test_packed_lengths_are_not_trusted() {
+    use diskdb::data_types::DataType;
+    
+    for payload in [
+        r#"{"HashPack":[18446744073709551615,""]}"#,
+        r#"{"ZSetPack":[9223372036854775808,""]}"#,
+        r#"{"HashPack":[4611686018427387904,"1:a1:b"]}"#,
+    ] {
+        assert!(serde_json::from_str::<DataType>(payload).is_err(), "{}", payload);
+    }
+    let hash: DataType = serde_json::from_str(r#"{"HashPack":[1,"1:a1:b"]}"#).unwrap();
+    assert!(matches!(hash, DataType::Hash(h) if h.len() == 1 && h["a"] == "b"));
+}
@@ -0,0 +1,52 @@
+use diskdb::commands::CommandExecutor;
+use diskdb::data_types::DataType;
+use diskdb::protocol::{Request, Response};
+use diskdb::storage::rocksdb_storage::RocksDBStorage;
+use diskdb::storage::Storage;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+// Count allocations to tell a header read apart from decoding every element
+struct AllocCounter;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for AllocCounter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: AllocCounter = AllocCounter;
+
+#[tokio::test]
+async fn test_llen_reads_only_the_header() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    let executor = CommandExecutor::new(storage.clone());
+    
+    let items = 100_000;
+    let list: Vec<String> = (0..items).map(|i| format!("item_{}", i)).collect();
+    storage.set("big", DataType::List(list.into())).await.unwrap();
+    
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let len = executor.execute(Request::LLen { key: "big".to_string() }).await.unwrap();
+    let llen_allocations = ALLOCATIONS.load(Ordering::SeqCst) - before;
+    assert_eq!(len, Response::Integer(items as i64));
+    
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    storage.get("big").await.unwrap();
+    let decode_allocations = ALLOCATIONS.load(Ordering::SeqCst) - before;
+    
+    // Decoding allocates once per element; the header read is independent of size
+    assert!(decode_allocations >= items);
+    assert!(llen_allocations < 100, "LLEN made {} allocations", llen_allocations);
+}
@@ -0,0 +1,2333 @@
+use diskdb::clock::{Clock, MockClock};
+use diskdb::commands::{dump, CommandExecutor};
+use diskdb::data_types::{DataType, StreamEntry};
+use diskdb::protocol::{ParseErrorKind, Request, Response};
+use diskdb::storage::rocksdb_storage::RocksDBStorage;
+use diskdb::storage::Storage;
+use diskdb::{Config, MaxMemoryPolicy};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::SystemTime;
+use tempfile::TempDir;
+
+fn setup() -> (TempDir, Arc<CommandExecutor>) {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    (temp_dir, Arc::new(CommandExecutor::new(storage)))
+}
+
+async fn run(executor: &CommandExecutor, cmd: &str) -> Response {
+    let request = Request::parse(cmd).unwrap();
+    executor.execute(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_concurrent_incr_is_atomic() {
+    let (_dir, executor) = setup();
+    let tasks = 16;
+    let per_task = 50;
+    
+    let mut handles = Vec::new();
+    for _ in 0..tasks {
+        let executor = executor.clone();
+        handles.push(tokio::spawn(async move {
+            for _ in 0..per_task {
+                run(&executor, "INCR counter").await;
+            }
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    
+    assert_eq!(
+        run(&executor, "GET counter").await,
+        Response::String(Some((tasks * per_task).to_string()))
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn test_concurrent_lpush_keeps_every_value() {
+    let (_dir, executor) = setup();
+    let tasks = 16;
+    let per_task = 50;
+    
+    let mut handles = Vec::new();
+    for task in 0..tasks {
+        let executor = executor.clone();
+        handles.push(tokio::spawn(async move {
+            for i in 0..per_task {
+                run(&executor, &format!("LPUSH shared v{}_{}", task, i)).await;
+                run(&executor, &format!("SADD members v{}_{}", task, i)).await;
+            }
+        }));
+    }
+    for handle in handles {
+        handle.await.unwrap();
+    }
+    
+    assert_eq!(run(&executor, "LLEN shared").await, Response::Integer((tasks * per_task) as i64));
+    assert_eq!(run(&executor, "SCARD members").await, Response::Integer((tasks * per_task) as i64));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+async fn test_concurrent_hsetnx_creates_field_once() {
+    let (_dir, executor) = setup();
+    for round in 0..20 {
+        let key = format!("config{}", round);
+        let first = tokio::spawn({
+            let (executor, key) = (executor.clone(), key.clone());
+            async move { run(&executor, &format!("HSETNX {} owner a", key)).await }
+        });
+        let second = tokio::spawn({
+            let (executor, key) = (executor.clone(), key.clone());
+            async move { run(&executor, &format!("HSETNX {} owner b", key)).await }
+        });
+        let replies = [first.await.unwrap(), second.await.unwrap()];
+        let created = replies.iter().filter(|reply| **reply == Response::Integer(1)).count();
+        assert_eq!(created, 1, "{:?}", replies);
+        
+        let winner = if replies[0] == Response::Integer(1) { "a" } else { "b" };
+        assert_eq!(run(&executor, &format!("HGET {} owner", key)).await, Response::String(Some(winner.to_string())));
+    }
+    
+    run(&executor, "SET plain v").await;
+    assert_eq!(
+        run(&executor, "HSETNX plain f v").await,
+        Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_hmset_and_hstrlen() {
+    let (_dir, executor) = setup();
+    
+    assert_eq!(run(&executor, "HMSET user name héllo city 東京 age 30").await, Response::Ok);
+    assert_eq!(run(&executor, "HSTRLEN user name").await, Response::Integer(6));
+    assert_eq!(run(&executor, "HSTRLEN user city").await, Response::Integer(6));
+    assert_eq!(run(&executor, "HSTRLEN user missing").await, Response::Integer(0));
+    assert_eq!(run(&executor, "HSTRLEN nokey name").await, Response::Integer(0));
+    
+    // All fields land in one write, overwriting existing ones
+    assert_eq!(run(&executor, "HMSET user age 31 zip 100").await, Response::Ok);
+    match run(&executor, "HGETALL user").await {
+        Response::Array(items) => assert_eq!(items.len(), 8),
+        other => panic!("unexpected HGETALL reply {:?}", other),
+    }
+    assert_eq!(run(&executor, "HGET user age").await, Response::String(Some("31".to_string())));
+    
+    assert!(Request::parse("HMSET user name").is_err());
+    assert!(Request::parse("HMSET user a 1 b").is_err());
+    run(&executor, "SET plain v").await;
+    assert!(matches!(run(&executor, "HSTRLEN plain f").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+    assert_eq!(
+        run(&executor, "HMSET plain f v").await,
+        Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_incr_variants_share_counter() {
+    let (_dir, executor) = setup();
+    
+    assert_eq!(run(&executor, "SET counter 10").await, Response::Ok);
+    assert_eq!(run(&executor, "INCR counter").await, Response::Integer(11));
+    assert_eq!(run(&executor, "DECR counter").await, Response::Integer(10));
+    assert_eq!(run(&executor, "INCRBY counter 5").await, Response::Integer(15));
+    assert_eq!(run(&executor, "INCR fresh").await, Response::Integer(1));
+    
+    run(&executor, "SET text hello").await;
+    let request = Request::parse("INCR text").unwrap();
+    assert!(executor.execute(request).await.is_err());
+    assert_eq!(run(&executor, "GET text").await, Response::String(Some("hello".to_string())));
+}
+
+#[tokio::test]
+async fn test_incrbyfloat() {
+    let (_dir, executor) = setup();
+    let s = |v: &str| Response::String(Some(v.to_string()));
+    
+    run(&executor, "SET metric 10.5").await;
+    assert_eq!(run(&executor, "INCRBYFLOAT metric 0.1").await, s("10.6"));
+    assert_eq!(run(&executor, "INCRBYFLOAT metric -5.6").await, s("5"));
+    assert_eq!(run(&executor, "GET metric").await, s("5"));
+    assert_eq!(run(&executor, "INCRBYFLOAT fresh 2.5e2").await, s("250"));
+    
+    // Integer counters keep working once a float has been added
+    assert_eq!(run(&executor, "INCR metric").await, Response::Integer(6));
+    
+    run(&executor, "SET text hello").await;
+    assert_eq!(run(&executor, "INCRBYFLOAT text 1").await, Response::Error("ERR value is not a valid float".to_string()));
+    assert_eq!(run(&executor, "GET text").await, s("hello"));
+    run(&executor, "LPUSH items a").await;
+    assert!(matches!(run(&executor, "INCRBYFLOAT items 1").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+}
+
+#[tokio::test]
+async fn test_sort_numeric_and_alpha() {
+    let (_dir, executor) = setup();
+    run(&executor, "RPUSH nums 10 2 33 1").await;
+    
+    let strings = |values: &[&str]| {
+        Response::Array(values.iter().map(|v| Response::String(Some(v.to_string()))).collect())
+    };
+    
+    assert_eq!(run(&executor, "SORT nums").await, strings(&["1", "2", "10", "33"]));
+    assert_eq!(run(&executor, "SORT nums DESC").await, strings(&["33", "10", "2", "1"]));
+    assert_eq!(run(&executor, "SORT nums ALPHA").await, strings(&["1", "10", "2", "33"]));
+    
+    run(&executor, "SADD words pear apple fig").await;
+    assert_eq!(run(&executor, "SORT words ALPHA").await, strings(&["apple", "fig", "pear"]));
+    assert_eq!(
+        run(&executor, "SORT words").await,
+        Response::Error("ERR One or more scores can't be converted into double".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_sort_limit_by_and_get() {
+    let (_dir, executor) = setup();
+    run(&executor, "RPUSH ids 1 2 3 4 5").await;
+    
+    let strings = |values: &[&str]| {
+        Response::Array(values.iter().map(|v| Response::String(Some(v.to_string()))).collect())
+    };
+    
+    assert_eq!(run(&executor, "SORT ids LIMIT 0 2").await, strings(&["1", "2"]));
+    assert_eq!(run(&executor, "SORT ids LIMIT 2 2").await, strings(&["3", "4"]));
+    assert_eq!(run(&executor, "SORT ids LIMIT 4 10").await, strings(&["5"]));
+    
+    for (id, weight) in [("1", "50"), ("2", "40"), ("3", "30"), ("4", "20"), ("5", "10")] {
+        run(&executor, &format!("SET weight_{} {}", id, weight)).await;
+        run(&executor, &format!("HSET user:{} name user{}", id, id)).await;
+    }
+    assert_eq!(run(&executor, "SORT ids BY weight_* LIMIT 0 3").await, strings(&["5", "4", "3"]));
+    assert_eq!(
+        run(&executor, "SORT ids BY weight_* LIMIT 0 2 GET # GET user:*->name").await,
+        strings(&["5", "user5", "4", "user4"])
+    );
+    assert_eq!(
+        run(&executor, "SORT ids BY nosort GET missing_*").await,
+        Response::Array((0..5).map(|_| Response::Null).collect())
+    );
+}
+
+#[tokio::test]
+async fn test_sort_get_reads_expired_fields_without_purging() {
+    let temp_dir = TempDir::new().unwrap();
+    let clock = Arc::new(MockClock::new(1_700_000_000_000));
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap().with_clock(clock.clone()));
+    let executor = CommandExecutor::new(storage.clone());
+    run(&executor, "RPUSH ids 1 2").await;
+    run(&executor, "HSET user:1 name alice").await;
+    run(&executor, "HSET user:2 name bob").await;
+    run(&executor, "HEXPIRE user:1 1 FIELDS 1 name").await;
+    clock.advance(1100);
+    
+    assert_eq!(
+        run(&executor, "SORT ids GET user:*->name").await,
+        Response::Array(vec![Response::Null, Response::String(Some("bob".to_string()))])
+    );
+    // SORT only reads, so the expired field is left for the next write or HGET
+    assert!(storage.field_deadlines("user:1").await.unwrap().contains_key("name"));
+    assert_eq!(run(&executor, "HGET user:1 name").await, Response::Null);
+    assert!(storage.field_deadlines("user:1").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_expireat_in_past_deletes_key() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET session abc").await;
+    
+    assert_eq!(run(&executor, "EXPIREAT session 1").await, Response::Integer(1));
+    assert_eq!(run(&executor, "GET session").await, Response::Null);
+    assert_eq!(run(&executor, "TTL session").await, Response::Integer(-2));
+    assert_eq!(run(&executor, "EXPIREAT session 1").await, Response::Integer(0));
+}
+
+#[tokio::test]
+async fn test_pttl_is_finer_than_ttl() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET job pending").await;
+    
+    assert_eq!(run(&executor, "PTTL job").await, Response::Integer(-1));
+    assert_eq!(run(&executor, "PEXPIRE job 1500").await, Response::Integer(1));
+    
+    match run(&executor, "PTTL job").await {
+        Response::Integer(ms) => assert!(ms > 1000 && ms <= 1500, "unexpected PTTL {}", ms),
+        other => panic!("unexpected response {:?}", other),
+    }
+    match run(&executor, "TTL job").await {
+        Response::Integer(secs) => assert!(secs == 1 || secs == 2, "unexpected TTL {}", secs),
+        other => panic!("unexpected response {:?}", other),
+    }
+    
+    let deadline = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() + 100;
+    assert_eq!(run(&executor, &format!("EXPIREAT job {}", deadline)).await, Response::Integer(1));
+    match run(&executor, "TTL job").await {
+        Response::Integer(secs) => assert!(secs > 90 && secs <= 100),
+        other => panic!("unexpected response {:?}", other),
+    }
+    
+    run(&executor, "SET job done").await;
+    assert_eq!(run(&executor, "TTL job").await, Response::Integer(-1));
+}
+
+#[tokio::test]
+async fn test_getex_updates_ttl_without_touching_value() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET session token-1").await;
+    let value = Response::String(Some("token-1".to_string()));
+    
+    assert_eq!(run(&executor, "GETEX session").await, value);
+    assert_eq!(run(&executor, "TTL session").await, Response::Integer(-1));
+    
+    assert_eq!(run(&executor, "GETEX session EX 100").await, value);
+    match run(&executor, "TTL session").await {
+        Response::Integer(secs) => assert!(secs > 90 && secs <= 100),
+        other => panic!("unexpected response {:?}", other),
+    }
+    
+    assert_eq!(run(&executor, "GETEX session PX 5000").await, value);
+    match run(&executor, "PTTL session").await {
+        Response::Integer(ms) => assert!(ms > 4000 && ms <= 5000),
+        other => panic!("unexpected response {:?}", other),
+    }
+    
+    assert_eq!(run(&executor, "GETEX session PERSIST").await, value);
+    assert_eq!(run(&executor, "TTL session").await, Response::Integer(-1));
+    assert_eq!(run(&executor, "GET session").await, value);
+    
+    assert_eq!(run(&executor, "GETEX missing EX 10").await, Response::Null);
+    assert_eq!(run(&executor, "TTL missing").await, Response::Integer(-2));
+}
+
+#[tokio::test]
+async fn test_append_returns_new_byte_length() {
+    let (_dir, executor) = setup();
+    let append = |key: &str, value: &str| Request::Append { key: key.to_string(), value: value.to_string() };
+    
+    // Missing key: created with the appended value
+    assert_eq!(run(&executor, "APPEND greeting héllo").await, Response::Integer(6));
+    assert_eq!(run(&executor, "STRLEN greeting").await, Response::Integer(6));
+    
+    // Existing key: total length after the append, multi-word values included
+    assert_eq!(run(&executor, "APPEND greeting big world").await, Response::Integer(15));
+    assert_eq!(run(&executor, "GET greeting").await, Response::String(Some("héllobig world".to_string())));
+    
+    // Empty strings leave the length unchanged, and create an empty key when missing
+    assert_eq!(executor.execute(append("greeting", "")).await.unwrap(), Response::Integer(15));
+    assert_eq!(executor.execute(append("empty", "")).await.unwrap(), Response::Integer(0));
+    assert_eq!(run(&executor, "EXISTS empty").await, Response::Integer(1));
+    assert_eq!(run(&executor, "STRLEN empty").await, Response::Integer(0));
+    
+    // Wrong type
+    run(&executor, "LPUSH items a").await;
+    assert!(matches!(run(&executor, "APPEND items b").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+    assert!(matches!(run(&executor, "STRLEN items").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+    assert_eq!(run(&executor, "STRLEN missing").await, Response::Integer(0));
+}
+
+#[tokio::test]
+async fn test_getrange_and_substr_alias() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET text This_is_a_string").await;
+    let s = |v: &str| Response::String(Some(v.to_string()));
+    
+    assert_eq!(run(&executor, "GETRANGE text 0 3").await, s("This"));
+    assert_eq!(run(&executor, "GETRANGE text -3 -1").await, s("ing"));
+    assert_eq!(run(&executor, "GETRANGE text 0 -1").await, s("This_is_a_string"));
+    assert_eq!(run(&executor, "GETRANGE text 10 100").await, s("string"));
+    assert_eq!(run(&executor, "GETRANGE text 5 2").await, s(""));
+    assert_eq!(run(&executor, "GETRANGE missing 0 -1").await, s(""));
+    
+    assert_eq!(run(&executor, "SUBSTR text -3 -1").await, run(&executor, "GETRANGE text -3 -1").await);
+}
+
+#[tokio::test]
+async fn test_lcs() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET key1 ohmytext").await;
+    run(&executor, "SET key2 mynewtext").await;
+    let s = |v: &str| Response::String(Some(v.to_string()));
+    let int = Response::Integer;
+    let range = |start: i64, end: i64| Response::Array(vec![int(start), int(end)]);
+    let idx_reply = |matches: Vec<Response>, len: i64| Response::Array(vec![
+        s("matches"),
+        Response::Array(matches),
+        s("len"),
+        int(len),
+    ]);
+
+    assert_eq!(run(&executor, "LCS key1 key2").await, s("mytext"));
+    assert_eq!(run(&executor, "LCS key1 key2 LEN").await, int(6));
+    assert_eq!(run(&executor, "LCS key1 key2 IDX").await, idx_reply(vec![
+        Response::Array(vec![range(4, 7), range(5, 8)]),
+        Response::Array(vec![range(2, 3), range(0, 1)]),
+    ], 6));
+    assert_eq!(run(&executor, "LCS key1 key2 IDX MINMATCHLEN 4 WITHMATCHLEN").await, idx_reply(vec![
+        Response::Array(vec![range(4, 7), range(5, 8), int(4)]),
+    ], 6));
+
+    // A missing key is an empty string
+    assert_eq!(run(&executor, "LCS key1 missing").await, s(""));
+    assert_eq!(run(&executor, "LCS missing key2 LEN").await, int(0));
+    assert_eq!(run(&executor, "LCS missing key2 IDX").await, idx_reply(vec![], 0));
+
+    // The linear-space subsequence agrees with the length from the full table
+    let mut seed = 7u32;
+    for _ in 0..20 {
+        let mut word = |len: usize| (0..len).map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (b'a' + (seed >> 16) as u8 % 4) as char
+        }).collect::<String>();
+        let (a, b) = (word(23), word(31));
+        run(&executor, &format!("SET a {}", a)).await;
+        run(&executor, &format!("SET b {}", b)).await;
+        let Response::String(Some(common)) = run(&executor, "LCS a b").await else { panic!("LCS a b") };
+        let is_subsequence = |of: &str| {
+            let mut rest = of.chars();
+            common.chars().all(|c| rest.any(|r| r == c))
+        };
+        assert!(is_subsequence(&a) && is_subsequence(&b), "{} of {} and {}", common, a, b);
+        assert_eq!(run(&executor, "LCS a b LEN").await, int(common.len() as i64));
+        let Response::Array(reply) = run(&executor, "LCS a b IDX").await else { panic!("LCS a b IDX") };
+        assert_eq!(reply[3], int(common.len() as i64));
+    }
+
+    run(&executor, "RPUSH items a").await;
+    assert!(matches!(run(&executor, "LCS key1 items").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+    assert!(Request::parse("LCS key1 key2 LEN IDX").is_err());
+    assert!(Request::parse("LCS key1 key2 MINMATCHLEN x").is_err());
+    assert!(Request::parse("LCS key1").is_err());
+}
+
+#[tokio::test]
+async fn test_bitop_and_or_patterns() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET foo foo").await;
+    run(&executor, "SET bar bar").await;
+    let s = |v: &str| Response::String(Some(v.to_string()));
+    
+    // 'f' 0x66 & 'b' 0x62 = 0x62, 'o' 0x6f & 'a' 0x61 = 0x61, 'o' 0x6f & 'r' 0x72 = 0x62
+    assert_eq!(run(&executor, "BITOP AND dest foo bar").await, Response::Integer(3));
+    assert_eq!(run(&executor, "GET dest").await, s("bab"));
+    
+    assert_eq!(run(&executor, "BITOP OR dest foo bar").await, Response::Integer(3));
+    assert_eq!(run(&executor, "GET dest").await, s("fo\x7f"));
+    
+    // Shorter and missing sources are padded with zero bytes
+    run(&executor, "SET short b").await;
+    assert_eq!(run(&executor, "BITOP AND dest foo short").await, Response::Integer(3));
+    assert_eq!(run(&executor, "GET dest").await, s("b\0\0"));
+    assert_eq!(run(&executor, "BITOP OR dest short missing").await, Response::Integer(1));
+    assert_eq!(run(&executor, "GET dest").await, s("b"));
+    
+    assert_eq!(run(&executor, "BITOP OR dest missing other").await, Response::Integer(0));
+    assert_eq!(run(&executor, "EXISTS dest").await, Response::Integer(0));
+    
+    // NOT of ASCII sets the high bit, which string values cannot hold yet
+    assert!(matches!(run(&executor, "BITOP NOT dest foo").await, Response::Error(_)));
+}
+
+#[tokio::test]
+async fn test_bitpos_multi_byte() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET one a").await;
+    run(&executor, "SET two aa").await;
+    // XOR leaves a zero byte followed by 'a' 0x61
+    assert_eq!(run(&executor, "BITOP XOR bits two one").await, Response::Integer(2));
+    
+    assert_eq!(run(&executor, "BITPOS bits 1").await, Response::Integer(9));
+    assert_eq!(run(&executor, "BITPOS bits 0").await, Response::Integer(0));
+    assert_eq!(run(&executor, "BITPOS bits 1 0 0").await, Response::Integer(-1));
+    assert_eq!(run(&executor, "BITPOS bits 1 -1").await, Response::Integer(9));
+    assert_eq!(run(&executor, "BITPOS bits 0 1").await, Response::Integer(8));
+    
+    run(&executor, "SET foo foo").await;
+    assert_eq!(run(&executor, "BITPOS foo 1 2").await, Response::Integer(17));
+    
+    assert_eq!(run(&executor, "BITPOS missing 0").await, Response::Integer(0));
+    assert_eq!(run(&executor, "BITPOS missing 1").await, Response::Integer(-1));
+}
+
+#[tokio::test]
+async fn test_smismember_preserves_order() {
+    let (_dir, executor) = setup();
+    run(&executor, "SADD fruits apple banana cherry").await;
+    let flags = |v: &[i64]| Response::Array(v.iter().map(|&i| Response::Integer(i)).collect());
+    
+    assert_eq!(run(&executor, "SMISMEMBER fruits cherry kiwi apple apple grape banana").await, flags(&[1, 0, 1, 1, 0, 1]));
+    assert_eq!(run(&executor, "SMISMEMBER missing apple banana").await, flags(&[0, 0]));
+    
+    run(&executor, "SET plain value").await;
+    assert!(matches!(run(&executor, "SMISMEMBER plain apple").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+}
+
+#[tokio::test]
+async fn test_hash_field_expiry() {
+    let temp_dir = TempDir::new().unwrap();
+    let clock = Arc::new(MockClock::new(1_700_000_000_000));
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap().with_clock(clock.clone()));
+    let executor = CommandExecutor::new(storage.clone());
+    run(&executor, "HSET session token abc").await;
+    run(&executor, "HSET session user alice").await;
+    let codes = |v: &[i64]| Response::Array(v.iter().map(|&i| Response::Integer(i)).collect());
+    
+    assert_eq!(run(&executor, "HEXPIRE session 1 FIELDS 2 token missing").await, codes(&[1, 0]));
+    assert_eq!(run(&executor, "HEXPIRE nokey 1 FIELDS 1 token").await, codes(&[2]));
+    assert_eq!(run(&executor, "HTTL session FIELDS 3 token user missing").await, codes(&[1, -1, -2]));
+    
+    clock.advance(1100);
+    
+    assert_eq!(run(&executor, "HGET session token").await, Response::Null);
+    assert_eq!(run(&executor, "HGET session user").await, Response::String(Some("alice".to_string())));
+    assert_eq!(run(&executor, "HLEN session").await, Response::Integer(1));
+    assert_eq!(run(&executor, "HGETALL session").await, Response::Array(vec![
+        Response::String(Some("user".to_string())),
+        Response::String(Some("alice".to_string())),
+    ]));
+    
+    // Persisting or overwriting a field removes its TTL
+    run(&executor, "HEXPIRE session 100 FIELDS 1 user").await;
+    assert_eq!(run(&executor, "HPERSIST session FIELDS 2 user token").await, codes(&[1, -2]));
+    assert_eq!(run(&executor, "HPERSIST session FIELDS 1 user").await, codes(&[-1]));
+    run(&executor, "HEXPIRE session 100 FIELDS 1 user").await;
+    run(&executor, "HSET session user bob").await;
+    assert_eq!(run(&executor, "HTTL session FIELDS 1 user").await, codes(&[-1]));
+    
+    // Expiring the last field removes the key
+    assert_eq!(run(&executor, "HEXPIRE session 0 FIELDS 1 user").await, codes(&[1]));
+    assert_eq!(run(&executor, "EXISTS session").await, Response::Integer(0));
+    
+    run(&executor, "SET plain v").await;
+    assert_eq!(
+        run(&executor, "HSET plain f v").await,
+        Response::Error("WRONGTYPE Operation against a key holding the wrong kind of value".to_string())
+    );
+    
+    // Overwriting a hash with another type drops its field deadlines, so a hash
+    // written there later does not expire on them
+    run(&executor, "HSET profile plan pro").await;
+    run(&executor, "HEXPIRE profile 1 FIELDS 1 plan").await;
+    run(&executor, "SET profile v").await;
+    assert!(storage.field_deadlines("profile").await.unwrap().is_empty());
+    let hash = HashMap::from([("plan".to_string(), "free".to_string())]);
+    storage.set("profile", DataType::Hash(hash)).await.unwrap();
+    clock.advance(1100);
+    assert_eq!(run(&executor, "HGET profile plan").await, Response::String(Some("free".to_string())));
+    assert_eq!(run(&executor, "HTTL profile FIELDS 1 plan").await, codes(&[-1]));
+}
+
+/// Split a SCAN-family reply into its next cursor and elements
+fn scan_reply(response: Response) -> (String, Vec<String>) {
+    match response {
+        Response::Array(mut parts) if parts.len() == 2 => {
+            let elements = match parts.pop() {
+                Some(Response::Array(items)) => items.into_iter().map(|item| match item {
+                    Response::String(Some(s)) => s,
+                    other => panic!("unexpected element {:?}", other),
+                }).collect(),
+                other => panic!("unexpected elements {:?}", other),
+            };
+            match parts.pop() {
+                Some(Response::String(Some(cursor))) => (cursor, elements),
+                other => panic!("unexpected cursor {:?}", other),
+            }
+        }
+        other => panic!("unexpected scan reply {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_hscan_visits_every_field_once() {
+    let (_dir, executor) = setup();
+    for i in 0..1000 {
+        run(&executor, &format!("HSET big field{} value{}", i, i)).await;
+    }
+    
+    let mut seen = std::collections::HashMap::new();
+    let mut cursor = "0".to_string();
+    loop {
+        let (next, elements) = scan_reply(run(&executor, &format!("HSCAN big {} COUNT 37", cursor)).await);
+        assert!(elements.len() <= 74);
+        for pair in elements.chunks(2) {
+            assert_eq!(pair[1], pair[0].replace("field", "value"));
+            *seen.entry(pair[0].clone()).or_insert(0) += 1;
+        }
+        if next == "0" {
+            break;
+        }
+        cursor = next;
+    }
+    assert_eq!(seen.len(), 1000);
+    assert!(seen.values().all(|&count| count == 1));
+}
+
+#[tokio::test]
+async fn test_sscan_and_zscan_match() {
+    let (_dir, executor) = setup();
+    run(&executor, "SADD tags red green blue rose").await;
+    run(&executor, "ZADD board 1 alice 2 bob 3 anna").await;
+    
+    let (cursor, members) = scan_reply(run(&executor, "SSCAN tags 0 MATCH r* COUNT 100").await);
+    assert_eq!(cursor, "0");
+    assert_eq!(members, vec!["red", "rose"]);
+    
+    let (cursor, entries) = scan_reply(run(&executor, "ZSCAN board 0 MATCH a*").await);
+    assert_eq!(cursor, "0");
+    assert_eq!(entries, vec!["alice", "1", "anna", "3"]);
+    
+    assert_eq!(scan_reply(run(&executor, "SSCAN missing 0").await), ("0".to_string(), vec![]));
+    assert!(matches!(run(&executor, "HSCAN tags 0").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+}
+
+#[tokio::test]
+async fn test_scan_type_filter() {
+    let (_dir, executor) = setup();
+    for i in 0..5 {
+        run(&executor, &format!("SET str{} v", i)).await;
+        run(&executor, &format!("RPUSH list{} a", i)).await;
+        run(&executor, &format!("HSET hash{} f v", i)).await;
+    }
+    
+    let (cursor, keys) = scan_reply(run(&executor, "SCAN 0 TYPE list COUNT 100").await);
+    assert_eq!(cursor, "0");
+    let mut keys = keys;
+    keys.sort();
+    assert_eq!(keys, vec!["list0", "list1", "list2", "list3", "list4"]);
+    
+    // Small pages still advance over keys of other types
+    let mut cursor = "0".to_string();
+    let mut hashes = HashSet::new();
+    for _ in 0..100 {
+        let (next, page) = scan_reply(run(&executor, &format!("SCAN {} COUNT 2 TYPE hash MATCH hash*", cursor)).await);
+        assert!(page.len() <= 2);
+        hashes.extend(page);
+        cursor = next;
+        if cursor == "0" {
+            break;
+        }
+    }
+    assert_eq!(cursor, "0");
+    assert_eq!(hashes, (0..5).map(|i| format!("hash{}", i)).collect::<HashSet<_>>());
+    
+    assert!(Request::parse("SCAN 0 TYPE widget").is_err());
+}
+
+#[tokio::test]
+async fn test_lmpop_pops_from_first_non_empty_list() {
+    let (_dir, executor) = setup();
+    run(&executor, "RPUSH q2 a b c").await;
+    run(&executor, "RPUSH q3 z").await;
+    
+    let text = |s: &str| Response::String(Some(s.to_string()));
+    assert_eq!(
+        run(&executor, "LMPOP 3 q1 q2 q3 LEFT COUNT 2").await,
+        Response::Array(vec![text("q2"), Response::Array(vec![text("a"), text("b")])])
+    );
+    // A count larger than the list drains it and the key is removed
+    assert_eq!(
+        run(&executor, "LMPOP 3 q1 q2 q3 RIGHT COUNT 5").await,
+        Response::Array(vec![text("q2"), Response::Array(vec![text("c")])])
+    );
+    assert_eq!(run(&executor, "EXISTS q2").await, Response::Integer(0));
+    assert_eq!(run(&executor, "LMPOP 2 q1 q2 LEFT").await, Response::NullArray);
+    
+    run(&executor, "SET q1 text").await;
+    assert!(matches!(run(&executor, "LMPOP 2 q1 q3 LEFT").await, Response::Error(e) if e.contains("WRONGTYPE")));
+}
+
+#[tokio::test]
+async fn test_lpop_and_rpop_with_count() {
+    let (_dir, executor) = setup();
+    run(&executor, "RPUSH q a b c d e").await;
+    
+    let texts = |items: &[&str]| Response::Array(items.iter().map(|s| Response::String(Some(s.to_string()))).collect());
+    assert_eq!(run(&executor, "LPOP q 2").await, texts(&["a", "b"]));
+    // Popped from the tail, the last element comes first
+    assert_eq!(run(&executor, "RPOP q 2").await, texts(&["e", "d"]));
+    assert_eq!(run(&executor, "LPOP q 0").await, texts(&[]));
+    // Without a count the reply is the element alone
+    run(&executor, "RPUSH q f").await;
+    assert_eq!(run(&executor, "RPOP q").await, Response::String(Some("f".to_string())));
+    
+    // A count larger than the list drains it and the key is removed
+    assert_eq!(run(&executor, "LPOP q 10").await, texts(&["c"]));
+    assert_eq!(run(&executor, "EXISTS q").await, Response::Integer(0));
+    assert_eq!(run(&executor, "LPOP q 3").await, Response::NullArray);
+    assert_eq!(run(&executor, "RPOP q").await, Response::Null);
+    
+    assert!(Request::parse("LPOP q -1").is_err());
+    assert!(Request::parse("RPOP q 1 2").is_err());
+}
+
+#[tokio::test]
+async fn test_zmpop_min_and_max() {
+    let (_dir, executor) = setup();
+    run(&executor, "ZADD scores 1 a 2 b 3 c").await;
+    
+    let text = |s: &str| Response::String(Some(s.to_string()));
+    let entry = |m: &str, s: &str| Response::Array(vec![text(m), text(s)]);
+    assert_eq!(
+        run(&executor, "ZMPOP 2 missing scores MIN").await,
+        Response::Array(vec![text("scores"), Response::Array(vec![entry("a", "1")])])
+    );
+    assert_eq!(
+        run(&executor, "ZMPOP 1 scores MAX COUNT 2").await,
+        Response::Array(vec![text("scores"), Response::Array(vec![entry("c", "3"), entry("b", "2")])])
+    );
+    assert_eq!(run(&executor, "EXISTS scores").await, Response::Integer(0));
+    assert!(Request::parse("ZMPOP 0 scores MIN").is_err());
+    assert!(Request::parse("ZMPOP 1 scores SIDEWAYS").is_err());
+}
+
+#[tokio::test]
+async fn test_zadd_nx_xx() {
+    let (_dir, executor) = setup();
+    run(&executor, "ZADD board 1 alice").await;
+    
+    assert_eq!(run(&executor, "ZADD board NX 5 alice 2 bob").await, Response::Integer(1));
+    assert_eq!(run(&executor, "ZSCORE board alice").await, Response::String(Some("1".to_string())));
+    
+    assert_eq!(run(&executor, "ZADD board XX 7 alice 3 carol").await, Response::Integer(0));
+    assert_eq!(run(&executor, "ZSCORE board alice").await, Response::String(Some("7".to_string())));
+    assert_eq!(run(&executor, "ZSCORE board carol").await, Response::Null);
+    
+    // XX against a missing key must not create it
+    assert_eq!(run(&executor, "ZADD ghost XX 1 a").await, Response::Integer(0));
+    assert_eq!(run(&executor, "EXISTS ghost").await, Response::Integer(0));
+}
+
+#[tokio::test]
+async fn test_zadd_gt_lt_ch() {
+    let (_dir, executor) = setup();
+    run(&executor, "ZADD board 10 alice 10 bob").await;
+    
+    // GT only raises scores but still adds new members
+    assert_eq!(run(&executor, "ZADD board GT CH 5 alice 15 bob 1 carol").await, Response::Integer(2));
+    assert_eq!(run(&executor, "ZSCORE board alice").await, Response::String(Some("10".to_string())));
+    assert_eq!(run(&executor, "ZSCORE board bob").await, Response::String(Some("15".to_string())));
+    
+    assert_eq!(run(&executor, "ZADD board LT 20 alice 4 bob").await, Response::Integer(0));
+    assert_eq!(run(&executor, "ZSCORE board alice").await, Response::String(Some("10".to_string())));
+    assert_eq!(run(&executor, "ZSCORE board bob").await, Response::String(Some("4".to_string())));
+    
+    // CH does not count members whose score is unchanged
+    assert_eq!(run(&executor, "ZADD board CH 10 alice 5 bob").await, Response::Integer(1));
+}
+
+#[tokio::test]
+async fn test_zadd_incr() {
+    let (_dir, executor) = setup();
+    assert_eq!(run(&executor, "ZADD board INCR 5 alice").await, Response::String(Some("5".to_string())));
+    assert_eq!(run(&executor, "ZADD board INCR 2.5 alice").await, Response::String(Some("7.5".to_string())));
+    
+    // A blocked condition replies nil and leaves the score alone
+    assert_eq!(run(&executor, "ZADD board INCR NX 1 alice").await, Response::Null);
+    assert_eq!(run(&executor, "ZADD board INCR GT -1 alice").await, Response::Null);
+    assert_eq!(run(&executor, "ZSCORE board alice").await, Response::String(Some("7.5".to_string())));
+}
+
+fn members(items: &[&str]) -> Response {
+    Response::Array(items.iter().map(|m| Response::String(Some(m.to_string()))).collect())
+}
+
+#[tokio::test]
+async fn test_zrangebylex_and_zlexcount_with_equal_scores() {
+    let (_dir, executor) = setup();
+    run(&executor, "ZADD words 0 apple 0 banana 0 cherry 0 date 0 elder").await;
+    
+    assert_eq!(run(&executor, "ZRANGEBYLEX words - +").await, members(&["apple", "banana", "cherry", "date", "elder"]));
+    assert_eq!(run(&executor, "ZRANGEBYLEX words [banana [date").await, members(&["banana", "cherry", "date"]));
+    assert_eq!(run(&executor, "ZRANGEBYLEX words (banana (date").await, members(&["cherry"]));
+    assert_eq!(run(&executor, "ZRANGEBYLEX words [c +").await, members(&["cherry", "date", "elder"]));
+    assert_eq!(run(&executor, "ZRANGEBYLEX words - (b").await, members(&["apple"]));
+    assert_eq!(run(&executor, "ZRANGEBYLEX words - + LIMIT 1 2").await, members(&["banana", "cherry"]));
+    assert_eq!(run(&executor, "ZRANGEBYLEX words - + LIMIT 3 -1").await, members(&["date", "elder"]));
+    assert_eq!(run(&executor, "ZRANGEBYLEX words + -").await, members(&[]));
+    assert_eq!(run(&executor, "ZRANGEBYLEX missing - +").await, members(&[]));
+    
+    assert_eq!(run(&executor, "ZLEXCOUNT words - +").await, Response::Integer(5));
+    assert_eq!(run(&executor, "ZLEXCOUNT words [banana (date").await, Response::Integer(2));
+    assert_eq!(run(&executor, "ZLEXCOUNT missing - +").await, Response::Integer(0));
+    
+    // Mixed scores are outside the contract: members are still filtered by name alone
+    run(&executor, "ZADD mixed 2 a 1 b").await;
+    assert_eq!(run(&executor, "ZRANGE mixed 0 -1").await, members(&["b", "a"]));
+    assert_eq!(run(&executor, "ZRANGEBYLEX mixed - +").await, members(&["a", "b"]));
+    
+    run(&executor, "SET plain v").await;
+    assert!(matches!(run(&executor, "ZLEXCOUNT plain - +").await, Response::Error(_)));
+    for cmd in ["ZRANGEBYLEX words a +", "ZLEXCOUNT words - c", "ZRANGEBYLEX words - + LIMIT 1", "ZRANGEBYLEX words - + COUNT 1 2"] {
+        assert!(Request::parse(cmd).is_err(), "{} should be rejected", cmd);
+    }
+}
+
+fn scored(items: &[(&str, &str)]) -> Response {
+    Response::Array(items.iter()
+        .flat_map(|(m, s)| [Response::String(Some(m.to_string())), Response::String(Some(s.to_string()))])
+        .collect())
+}
+
+#[tokio::test]
+async fn test_zunionstore_weights_and_aggregates() {
+    let (_dir, executor) = setup();
+    run(&executor, "ZADD clicks 1 a 2 b").await;
+    run(&executor, "ZADD likes 10 b 20 c").await;
+    
+    assert_eq!(run(&executor, "ZUNIONSTORE out 2 clicks likes").await, Response::Integer(3));
+    assert_eq!(run(&executor, "ZRANGE out 0 -1 WITHSCORES").await, scored(&[("a", "1"), ("b", "12"), ("c", "20")]));
+    
+    // Weights scale each input before the scores are combined
+    assert_eq!(run(&executor, "ZUNIONSTORE out 2 clicks likes WEIGHTS 3 0.5").await, Response::Integer(3));
+    assert_eq!(run(&executor, "ZRANGE out 0 -1 WITHSCORES").await, scored(&[("a", "3"), ("c", "10"), ("b", "11")]));
+    
+    assert_eq!(run(&executor, "ZUNIONSTORE out 2 clicks likes AGGREGATE MAX").await, Response::Integer(3));
+    assert_eq!(run(&executor, "ZSCORE out b").await, Response::String(Some("10".to_string())));
+    assert_eq!(run(&executor, "ZUNIONSTORE out 2 clicks likes WEIGHTS 1 -1 AGGREGATE MIN").await, Response::Integer(3));
+    assert_eq!(run(&executor, "ZRANGE out 0 -1 WITHSCORES").await, scored(&[("c", "-20"), ("b", "-10"), ("a", "1")]));
+    
+    // A missing input contributes nothing; an empty result removes the destination
+    assert_eq!(run(&executor, "ZUNIONSTORE out 2 clicks nothing").await, Response::Integer(2));
+    assert_eq!(run(&executor, "ZUNIONSTORE out 1 nothing").await, Response::Integer(0));
+    assert_eq!(run(&executor, "EXISTS out").await, Response::Integer(0));
+    
+    run(&executor, "SET plain v").await;
+    assert!(matches!(run(&executor, "ZUNIONSTORE out 2 clicks plain").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+    for cmd in ["ZUNIONSTORE out 3 a b", "ZUNIONSTORE out 0 a", "ZUNIONSTORE out 2 a b WEIGHTS 1", "ZUNIONSTORE out 1 a AGGREGATE AVG", "ZUNIONSTORE out 1 a WEIGHTS x"] {
+        assert!(Request::parse(cmd).is_err(), "{} should be rejected", cmd);
+    }
+}
+
+#[tokio::test]
+async fn test_zinterstore_keeps_common_members_and_reads_sets() {
+    let (_dir, executor) = setup();
+    run(&executor, "ZADD scores 5 a 7 b 9 c").await;
+    run(&executor, "SADD picked b c d").await;
+    
+    // Plain set members score 1
+    assert_eq!(run(&executor, "ZINTERSTORE out 2 scores picked").await, Response::Integer(2));
+    assert_eq!(run(&executor, "ZRANGE out 0 -1 WITHSCORES").await, scored(&[("b", "8"), ("c", "10")]));
+    
+    assert_eq!(run(&executor, "ZINTERSTORE out 2 scores picked WEIGHTS 2 100 AGGREGATE MAX").await, Response::Integer(2));
+    assert_eq!(run(&executor, "ZRANGE out 0 -1 WITHSCORES").await, scored(&[("b", "100"), ("c", "100")]));
+    assert_eq!(run(&executor, "ZINTERSTORE out 2 scores picked AGGREGATE MIN").await, Response::Integer(2));
+    assert_eq!(run(&executor, "ZRANGE out 0 -1 WITHSCORES").await, scored(&[("b", "1"), ("c", "1")]));
+    
+    // The destination may be one of the inputs
+    assert_eq!(run(&executor, "ZINTERSTORE scores 2 scores picked WEIGHTS 1 0").await, Response::Integer(2));
+    assert_eq!(run(&executor, "ZRANGE scores 0 -1 WITHSCORES").await, scored(&[("b", "7"), ("c", "9")]));
+    
+    assert_eq!(run(&executor, "ZINTERSTORE out 2 scores nothing").await, Response::Integer(0));
+    assert_eq!(run(&executor, "EXISTS out").await, Response::Integer(0));
+}
+
+#[tokio::test]
+async fn test_zdiff_keeps_members_only_in_the_first_set() {
+    let (_dir, executor) = setup();
+    run(&executor, "ZADD first 3 A 1 B 2 C").await;
+    run(&executor, "ZADD second 10 B").await;
+    
+    // Scores come from the first set, whatever the others hold
+    assert_eq!(run(&executor, "ZDIFF 2 first second WITHSCORES").await, scored(&[("C", "2"), ("A", "3")]));
+    assert_eq!(run(&executor, "ZDIFF 2 first second").await, Response::Array(vec![
+        Response::String(Some("C".to_string())),
+        Response::String(Some("A".to_string())),
+    ]));
+    // Missing keys are empty, and plain set members count as well
+    assert_eq!(run(&executor, "ZDIFF 2 first nothing").await, Response::Array(vec![
+        Response::String(Some("B".to_string())),
+        Response::String(Some("C".to_string())),
+        Response::String(Some("A".to_string())),
+    ]));
+    assert_eq!(run(&executor, "ZDIFF 2 nothing first").await, Response::Array(vec![]));
+    run(&executor, "SADD picked A").await;
+    assert_eq!(run(&executor, "ZDIFF 3 first second picked WITHSCORES").await, scored(&[("C", "2")]));
+    
+    assert_eq!(run(&executor, "ZDIFFSTORE out 2 first second").await, Response::Integer(2));
+    assert_eq!(run(&executor, "ZRANGE out 0 -1 WITHSCORES").await, scored(&[("C", "2"), ("A", "3")]));
+    assert_eq!(run(&executor, "ZDIFFSTORE out 2 second first").await, Response::Integer(0));
+    assert_eq!(run(&executor, "EXISTS out").await, Response::Integer(0));
+    
+    run(&executor, "SET plain v").await;
+    assert!(matches!(run(&executor, "ZDIFF 2 first plain").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+    assert!(matches!(run(&executor, "ZDIFFSTORE out 2 first plain").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+    for cmd in ["ZDIFF 0 first", "ZDIFF 2 first", "ZDIFF 1 first SCORES", "ZDIFF 1", "ZDIFFSTORE out 1 first second", "ZDIFFSTORE out 1"] {
+        assert!(Request::parse(cmd).is_err(), "{} should be rejected", cmd);
+    }
+}
+
+#[test]
+fn test_zadd_rejects_conflicting_flags() {
+    for cmd in [
+        "ZADD board NX XX 1 a",
+        "ZADD board GT NX 1 a",
+        "ZADD board GT LT 1 a",
+        "ZADD board INCR 1 a 2 b",
+        "ZADD board NX",
+    ] {
+        assert!(Request::parse(cmd).is_err(), "{} should be rejected", cmd);
+    }
+}
+
+#[test]
+fn test_parse_detailed_reports_kind_and_position() {
+    let err = Request::parse_detailed("INCRBY k notanumber").unwrap_err();
+    assert_eq!(err.command, "INCRBY");
+    assert_eq!(err.kind, ParseErrorKind::InvalidInteger);
+    assert_eq!(err.arg_index, Some(2));
+    
+    let err = Request::parse_detailed("zadd k").unwrap_err();
+    assert_eq!(err.command, "ZADD");
+    assert_eq!(err.kind, ParseErrorKind::WrongArity);
+    
+    let err = Request::parse_detailed("ZADD k NX 1 a oops b").unwrap_err();
+    assert_eq!((err.kind, err.arg_index), (ParseErrorKind::InvalidFloat, Some(5)));
+    
+    let err = Request::parse_detailed("FROBNICATE k").unwrap_err();
+    assert_eq!((err.kind, err.arg_index), (ParseErrorKind::UnknownCommand, Some(0)));
+    assert_eq!(Request::parse_detailed("   ").unwrap_err().kind, ParseErrorKind::Empty);
+    
+    // The legacy entrypoint keeps reporting the same text
+    assert_eq!(Request::parse_rust("INCRBY k notanumber").unwrap_err().to_string(), "Protocol error: Invalid integer");
+}
+
+#[test]
+fn test_parse_detailed_never_panics_on_mangled_input() {
+    let commands = ["GET", "SET", "INCRBY", "GETRANGE", "BITPOS", "BITOP", "LRANGE", "LMPOP", "ZADD",
+        "ZMPOP", "ZRANGE", "XRANGE", "SORT", "HEXPIRE", "HTTL", "SSCAN", "HELLO", "GETEX", "CLIENT"];
+    let args = ["", "k", "-1", "1.5", "nan", "NX", "COUNT", "FIELDS", "LIMIT", "0", "99999999999999999999"];
+    for command in commands {
+        for a in args {
+            for b in args {
+                for c in args {
+                    let line = format!("{} {} {} {}", command, a, b, c);
+                    if let Err(e) = Request::parse_detailed(&line) {
+                        assert_eq!(e.command, command);
+                        assert!(e.arg_index.is_none_or(|i| i < line.split_whitespace().count()), "{:?} for {}", e, line);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_decrby_parses_with_both_parsers() {
+    let is_expected = |request: Request| {
+        matches!(request, Request::DecrBy { ref key, delta: 3 } if key == "counter")
+    };
+    assert!(is_expected(Request::parse_rust("DECRBY counter 3").unwrap()));
+    // Goes through the C parser when the c_parser feature is enabled
+    assert!(is_expected(Request::parse("DECRBY counter 3").unwrap()));
+    assert!(Request::parse_rust("DECRBY counter").is_err());
+    assert!(Request::parse_rust("DECRBY counter three").is_err());
+}
+
+#[tokio::test]
+async fn test_decrby() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET counter 10").await;
+    assert_eq!(run(&executor, "DECRBY counter 3").await, Response::Integer(7));
+    assert_eq!(run(&executor, "DECRBY counter -5").await, Response::Integer(12));
+    assert_eq!(run(&executor, "DECRBY fresh 4").await, Response::Integer(-4));
+    
+    let err = executor.execute(Request::parse("DECRBY counter -9223372036854775808").unwrap()).await.unwrap_err();
+    assert!(err.to_string().contains("would overflow"));
+    assert_eq!(run(&executor, "GET counter").await, Response::String(Some("12".to_string())));
+}
+
+#[tokio::test]
+async fn test_execute_batch_keeps_going_after_errors() {
+    let (_dir, executor) = setup();
+    let requests = ["SET counter 1", "INCR counter", "LPUSH counter x", "GET counter", "GET missing"]
+        .iter()
+        .map(|cmd| Request::parse(cmd).unwrap())
+        .collect();
+    
+    let responses = executor.execute_batch(requests).await;
+    assert_eq!(responses.len(), 5);
+    assert_eq!(*responses[0].as_ref().unwrap(), Response::Ok);
+    assert_eq!(*responses[1].as_ref().unwrap(), Response::Integer(2));
+    assert!(responses[2].as_ref().unwrap_err().to_string().contains("WRONGTYPE"));
+    assert_eq!(*responses[3].as_ref().unwrap(), Response::String(Some("2".to_string())));
+    assert_eq!(*responses[4].as_ref().unwrap(), Response::Null);
+}
+
+#[tokio::test]
+async fn test_object_freq_tracks_hot_keys() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = Config::new();
+    config.maxmemory_policy = MaxMemoryPolicy::AllKeysLfu;
+    let storage = Arc::new(RocksDBStorage::with_config(temp_dir.path(), &config).unwrap());
+    let executor = CommandExecutor::new(storage);
+    
+    run(&executor, "SET hot 1").await;
+    run(&executor, "SET cold 1").await;
+    for _ in 0..500 {
+        run(&executor, "GET hot").await;
+    }
+    run(&executor, "GET cold").await;
+    
+    let freq = |response: Response| match response {
+        Response::Integer(freq) => freq,
+        other => panic!("unexpected OBJECT FREQ reply {:?}", other),
+    };
+    let hot = freq(run(&executor, "OBJECT FREQ hot").await);
+    let cold = freq(run(&executor, "OBJECT FREQ cold").await);
+    assert!(hot > cold, "hot {} should exceed cold {}", hot, cold);
+    // The counter is logarithmic, far below the number of accesses
+    assert!(hot < 50);
+    assert_eq!(run(&executor, "OBJECT FREQ missing").await, Response::Null);
+}
+
+#[tokio::test]
+async fn test_object_encoding_follows_list_size() {
+    let (_dir, executor) = setup();
+    run(&executor, "RPUSH small a b c").await;
+    assert_eq!(run(&executor, "OBJECT ENCODING small").await, Response::String(Some("listpack".to_string())));
+    
+    // Growing past the entry limit converts the list to the full representation
+    let values: Vec<String> = (0..200).map(|i| format!("item{}", i)).collect();
+    run(&executor, &format!("RPUSH small {}", values.join(" "))).await;
+    assert_eq!(run(&executor, "OBJECT ENCODING small").await, Response::String(Some("quicklist".to_string())));
+    assert_eq!(run(&executor, "LLEN small").await, Response::Integer(203));
+    assert_eq!(
+        run(&executor, "LRANGE small 0 3").await,
+        Response::Array(["a", "b", "c", "item0"].iter().map(|v| Response::String(Some(v.to_string()))).collect()),
+    );
+    
+    run(&executor, "SET n 12345").await;
+    run(&executor, "SET s hello").await;
+    assert_eq!(run(&executor, "OBJECT ENCODING n").await, Response::String(Some("int".to_string())));
+    assert_eq!(run(&executor, "OBJECT ENCODING s").await, Response::String(Some("embstr".to_string())));
+    assert_eq!(run(&executor, "OBJECT ENCODING missing").await, Response::Null);
+}
+
+#[tokio::test]
+async fn test_integer_encoded_strings() {
+    let (_dir, executor) = setup();
+    let text = |s: &str| Response::String(Some(s.to_string()));
+    
+    run(&executor, "SET counter 41").await;
+    assert_eq!(run(&executor, "OBJECT ENCODING counter").await, text("int"));
+    assert_eq!(run(&executor, "INCR counter").await, Response::Integer(42));
+    assert_eq!(run(&executor, "DECRBY counter 100").await, Response::Integer(-58));
+    assert_eq!(run(&executor, "GET counter").await, text("-58"));
+    assert_eq!(run(&executor, "STRLEN counter").await, Response::Integer(3));
+    assert_eq!(run(&executor, "OBJECT ENCODING counter").await, text("int"));
+    
+    // Only the canonical form is int-encoded; anything else must read back as written
+    for value in ["007", "+5", "-0", "99999999999999999999"] {
+        run(&executor, &format!("SET k {}", value)).await;
+        assert_eq!(run(&executor, "GET k").await, text(value));
+        assert_eq!(run(&executor, "OBJECT ENCODING k").await, text("embstr"), "{}", value);
+    }
+    run(&executor, "SET k 007").await;
+    assert_eq!(run(&executor, "INCR k").await, Response::Integer(8));
+    assert_eq!(run(&executor, "OBJECT ENCODING k").await, text("int"));
+    assert_eq!(run(&executor, "APPEND k 1").await, Response::Integer(2));
+    assert_eq!(run(&executor, "GET k").await, text("81"));
+}
+
+#[tokio::test]
+async fn test_debug_stringmatch_len() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    let executor = CommandExecutor::new(storage).with_debug_command(true);
+    assert_eq!(run(&executor, "DEBUG STRINGMATCH-LEN user:* user:1").await, Response::Integer(1));
+    assert_eq!(run(&executor, "DEBUG STRINGMATCH-LEN a\\* ab").await, Response::Integer(0));
+    
+    let (_dir, disabled) = setup();
+    assert!(matches!(run(&disabled, "DEBUG STRINGMATCH-LEN * x").await, Response::Error(e) if e.contains("not allowed")));
+}
+
+fn stream(ids: &[&str]) -> DataType {
+    DataType::Stream(ids.iter()
+        .map(|id| StreamEntry { id: id.to_string(), timestamp: SystemTime::UNIX_EPOCH, fields: HashMap::new() })
+        .collect())
+}
+
+#[test]
+fn test_verify_invariants_reports_the_first_inconsistency() {
+    assert_eq!(stream(&["1-0", "1-1", "2-0"]).verify_invariants(), Ok(()));
+    let err = stream(&["1-0", "5-0", "3-0", "2-0"]).verify_invariants().unwrap_err();
+    assert!(err.contains("entry 2") && err.contains("3-0") && err.contains("5-0"), "{}", err);
+    assert!(stream(&["1-0", "1-0"]).verify_invariants().is_err());
+    assert!(stream(&["oops"]).verify_invariants().unwrap_err().contains("malformed"));
+    
+    let scores: BTreeMap<String, f64> = [("a".to_string(), 1.0), ("b".to_string(), f64::NAN)].into_iter().collect();
+    assert!(DataType::SortedSet(scores).verify_invariants().unwrap_err().contains("'b'"));
+    assert_eq!(DataType::String("v".to_string()).verify_invariants(), Ok(()));
+}
+
+#[tokio::test]
+async fn test_debug_object_check() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    let executor = CommandExecutor::new(storage.clone()).with_debug_command(true);
+    
+    run(&executor, "XADD events 1-0 kind login").await;
+    run(&executor, "XADD events 2-0 kind logout").await;
+    run(&executor, "ZADD board 1 a 2 b").await;
+    run(&executor, "RPUSH small a b c").await;
+    for key in ["events", "board", "small"] {
+        assert_eq!(run(&executor, &format!("DEBUG OBJECT-CHECK {}", key)).await, Response::Ok, "{}", key);
+    }
+    
+    // A stream written with an ID behind its last entry
+    storage.set("broken", stream(&["5-0", "4-0"])).await.unwrap();
+    assert!(matches!(run(&executor, "DEBUG OBJECT-CHECK broken").await, Response::Error(e) if e.contains("4-0")));
+    assert!(matches!(run(&executor, "DEBUG OBJECT-CHECK missing").await, Response::Error(e) if e.contains("no such key")));
+    
+    let (_dir, disabled) = setup();
+    assert!(matches!(run(&disabled, "DEBUG OBJECT-CHECK events").await, Response::Error(e) if e.contains("not allowed")));
+}
+
+#[tokio::test]
+async fn test_latency_records_slow_commands() {
+    let temp_dir = TempDir::new().unwrap();
+    let clock = Arc::new(MockClock::new(1_700_000_000_000));
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap().with_clock(clock.clone()));
+    let executor = CommandExecutor::new(storage).with_debug_command(true).with_latency_threshold(50);
+    
+    run(&executor, "SET fast value").await;
+    assert_eq!(run(&executor, "LATENCY LATEST").await, Response::Array(Vec::new()));
+    
+    assert_eq!(run(&executor, "DEBUG SLEEP 0.1").await, Response::Ok);
+    let latest = match run(&executor, "LATENCY LATEST").await {
+        Response::Array(events) => events,
+        other => panic!("unexpected LATENCY LATEST reply {:?}", other),
+    };
+    match latest.as_slice() {
+        [Response::Array(event)] => match event.as_slice() {
+            [Response::String(Some(name)), Response::Integer(at), Response::Integer(ms), Response::Integer(max)] => {
+                assert_eq!(name, "command");
+                assert_eq!(*at, 1_700_000_000);
+                assert!(*ms >= 100 && max == ms, "{} {}", ms, max);
+            }
+            other => panic!("unexpected event {:?}", other),
+        },
+        other => panic!("unexpected events {:?}", other),
+    }
+    
+    // A second spike in the same second folds into one sample
+    run(&executor, "DEBUG SLEEP 0.06").await;
+    clock.advance(2_000);
+    run(&executor, "DEBUG SLEEP 0.06").await;
+    match run(&executor, "LATENCY HISTORY command").await {
+        Response::Array(samples) => {
+            assert_eq!(samples.len(), 2);
+            assert!(matches!(&samples[1], Response::Array(sample) if sample[0] == Response::Integer(1_700_000_002)));
+        }
+        other => panic!("unexpected LATENCY HISTORY reply {:?}", other),
+    }
+    assert_eq!(run(&executor, "LATENCY HISTORY fork").await, Response::Array(Vec::new()));
+    
+    assert_eq!(run(&executor, "LATENCY RESET").await, Response::Integer(1));
+    assert_eq!(run(&executor, "LATENCY LATEST").await, Response::Array(Vec::new()));
+    assert!(matches!(run(&executor, "LATENCY DOCTOR").await, Response::Error(e) if e.contains("unknown subcommand")));
+    
+    // Without a threshold nothing is recorded
+    let plain_dir = TempDir::new().unwrap();
+    let unmonitored = CommandExecutor::new(Arc::new(RocksDBStorage::new(plain_dir.path()).unwrap())).with_debug_command(true);
+    run(&unmonitored, "DEBUG SLEEP 0.06").await;
+    assert_eq!(run(&unmonitored, "LATENCY LATEST").await, Response::Array(Vec::new()));
+}
+
+#[tokio::test]
+async fn test_expire_condition_flags() {
+    let temp_dir = TempDir::new().unwrap();
+    let clock = Arc::new(MockClock::new(1_000_000));
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap().with_clock(clock));
+    let executor = CommandExecutor::new(storage);
+    run(&executor, "SET k v").await;
+    
+    // Without an expiry: XX and GT never apply, NX and LT do
+    assert_eq!(run(&executor, "EXPIRE k 100 XX").await, Response::Integer(0));
+    assert_eq!(run(&executor, "EXPIRE k 100 GT").await, Response::Integer(0));
+    assert_eq!(run(&executor, "TTL k").await, Response::Integer(-1));
+    assert_eq!(run(&executor, "EXPIRE k 100 LT").await, Response::Integer(1));
+    assert_eq!(run(&executor, "TTL k").await, Response::Integer(100));
+    run(&executor, "SET k v").await;
+    assert_eq!(run(&executor, "PEXPIRE k 50000 NX").await, Response::Integer(1));
+    assert_eq!(run(&executor, "PTTL k").await, Response::Integer(50_000));
+    
+    // With one: NX never applies, XX always does, GT and LT compare deadlines
+    assert_eq!(run(&executor, "EXPIRE k 10 NX").await, Response::Integer(0));
+    assert_eq!(run(&executor, "EXPIRE k 10 GT").await, Response::Integer(0));
+    assert_eq!(run(&executor, "EXPIRE k 50 GT").await, Response::Integer(0));
+    assert_eq!(run(&executor, "EXPIRE k 60 gt").await, Response::Integer(1));
+    assert_eq!(run(&executor, "EXPIRE k 70 LT").await, Response::Integer(0));
+    assert_eq!(run(&executor, "PEXPIREAT k 1030000 LT").await, Response::Integer(1));
+    assert_eq!(run(&executor, "TTL k").await, Response::Integer(30));
+    assert_eq!(run(&executor, "EXPIREAT k 2000 XX GT").await, Response::Integer(1));
+    assert_eq!(run(&executor, "TTL k").await, Response::Integer(1000));
+    assert_eq!(run(&executor, "EXPIRE k 5 XX").await, Response::Integer(1));
+    assert_eq!(run(&executor, "TTL k").await, Response::Integer(5));
+    
+    // A missing key is never changed
+    assert_eq!(run(&executor, "EXPIRE missing 10 LT").await, Response::Integer(0));
+    assert_eq!(run(&executor, "EXPIRE missing 10").await, Response::Integer(0));
+    
+    for cmd in ["EXPIRE k 10 NX XX", "EXPIRE k 10 NX GT", "EXPIRE k 10 GT LT", "EXPIRE k 10 SOON"] {
+        assert!(Request::parse(cmd).is_err(), "{} should be rejected", cmd);
+    }
+    assert!(Request::parse("EXPIRE k 10 XX LT").is_ok());
+}
+
+#[tokio::test]
+async fn test_mock_clock_expires_keys_without_sleeping() {
+    let temp_dir = TempDir::new().unwrap();
+    let clock = Arc::new(MockClock::new(1_700_000_000_000));
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap().with_clock(clock.clone()));
+    let executor = CommandExecutor::new(storage).with_debug_command(true);
+    
+    run(&executor, "SET session token").await;
+    assert_eq!(run(&executor, "EXPIRE session 10").await, Response::Integer(1));
+    assert_eq!(run(&executor, "DEBUG ADVANCE-TIME 9999").await, Response::Integer(1_700_000_009_999));
+    assert_eq!(run(&executor, "PTTL session").await, Response::Integer(1));
+    assert_eq!(run(&executor, "GET session").await, Response::String(Some("token".to_string())));
+    run(&executor, "DEBUG ADVANCE-TIME 1").await;
+    assert_eq!(run(&executor, "GET session").await, Response::Null);
+    
+    // Stream IDs come from the same clock
+    assert_eq!(run(&executor, "DEBUG SET-TIME 5000").await, Response::Ok);
+    assert_eq!(clock.now_millis(), 5000);
+    assert_eq!(run(&executor, "XADD events * kind login").await, Response::String(Some("5000-0".to_string())));
+    
+    // The wall clock cannot be moved
+    let wall_dir = TempDir::new().unwrap();
+    let wall = CommandExecutor::new(Arc::new(RocksDBStorage::new(wall_dir.path()).unwrap())).with_debug_command(true);
+    assert!(matches!(run(&wall, "DEBUG ADVANCE-TIME 1").await, Response::Error(e) if e.contains("mock clock")));
+}
+
+#[tokio::test]
+async fn test_lset_linsert_ltrim() {
+    let (_dir, executor) = setup();
+    let items = |values: &[&str]| Response::Array(values.iter().map(|v| Response::String(Some(v.to_string()))).collect());
+    
+    assert_eq!(run(&executor, "LSET l 0 x").await, Response::Error("ERR no such key".to_string()));
+    assert_eq!(run(&executor, "LINSERT l BEFORE a x").await, Response::Integer(0));
+    run(&executor, "RPUSH l a b c").await;
+    
+    assert_eq!(run(&executor, "LSET l -1 z").await, Response::Ok);
+    assert_eq!(run(&executor, "LSET l 3 z").await, Response::Error("ERR index out of range".to_string()));
+    assert_eq!(run(&executor, "LINSERT l BEFORE b x").await, Response::Integer(4));
+    assert_eq!(run(&executor, "LINSERT l AFTER z y").await, Response::Integer(5));
+    assert_eq!(run(&executor, "LINSERT l AFTER missing y").await, Response::Integer(-1));
+    assert_eq!(run(&executor, "LRANGE l 0 -1").await, items(&["a", "x", "b", "z", "y"]));
+    
+    assert_eq!(run(&executor, "LTRIM l 1 -2").await, Response::Ok);
+    assert_eq!(run(&executor, "LRANGE l 0 -1").await, items(&["x", "b", "z"]));
+    // Trimming everything away deletes the key
+    assert_eq!(run(&executor, "LTRIM l 2 1").await, Response::Ok);
+    assert_eq!(run(&executor, "EXISTS l").await, Response::Integer(0));
+}
+
+#[tokio::test]
+async fn test_object_freq_requires_lfu_policy() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET k v").await;
+    assert_eq!(
+        run(&executor, "OBJECT FREQ k").await,
+        Response::Error("ERR An LFU maxmemory policy is not selected, access frequency not tracked".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_wait_without_replicas() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET k v").await;
+    assert_eq!(run(&executor, "WAIT 0 0").await, Response::Integer(0));
+    
+    // No replica can ever be reached, so asking for one is refused at once
+    let started = std::time::Instant::now();
+    for cmd in ["WAIT 1 5000", "WAIT 1 0"] {
+        match run(&executor, cmd).await {
+            Response::Error(e) => assert!(e.contains("WAIT is not supported"), "{}: {}", cmd, e),
+            other => panic!("{}: expected an error, got {:?}", cmd, other),
+        }
+    }
+    assert!(started.elapsed() < std::time::Duration::from_millis(1000));
+}
+
+#[tokio::test]
+async fn test_waitaof_without_aof() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET k v").await;
+    let started = std::time::Instant::now();
+    for cmd in ["WAITAOF 0 0 0", "WAITAOF 0 1 100"] {
+        assert_eq!(
+            run(&executor, cmd).await,
+            Response::Array(vec![Response::Integer(0), Response::Integer(0)]),
+            "{}", cmd,
+        );
+    }
+    assert!(started.elapsed() < std::time::Duration::from_millis(1000));
+    
+    // Local acks need an append-only file
+    for cmd in ["WAITAOF 1 0 100", "WAITAOF 1 1 0"] {
+        match run(&executor, cmd).await {
+            Response::Error(e) => assert!(e.contains("appendonly is disabled"), "{}: {}", cmd, e),
+            other => panic!("{}: expected an error, got {:?}", cmd, other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_write_offset_token() {
+    let (_dir, executor) = setup();
+    let offset = |response: Response| match response {
+        Response::Integer(n) => n,
+        other => panic!("expected an offset, got {:?}", other),
+    };
+    
+    let mut last = offset(run(&executor, "GETWRITEOFFSET").await);
+    for cmd in ["SET k v1", "SET k v2", "LPUSH list a", "DEL k"] {
+        run(&executor, cmd).await;
+        let current = offset(run(&executor, "GETWRITEOFFSET").await);
+        assert!(current > last, "{} did not advance the offset: {} -> {}", cmd, last, current);
+        last = current;
+    }
+    
+    run(&executor, "SET k v3").await;
+    assert_eq!(run(&executor, &format!("MINOFFSET {} GET k", last)).await, Response::String(Some("v3".to_string())));
+    assert_eq!(run(&executor, "MINOFFSET 0 LLEN list").await, Response::Integer(1));
+    match run(&executor, &format!("MINOFFSET {} GET k", last + 1000)).await {
+        Response::Error(e) => assert!(e.starts_with("LOADING")),
+        other => panic!("expected LOADING, got {:?}", other),
+    }
+    
+    let err = Request::parse_detailed("MINOFFSET 1 SET k v").unwrap_err();
+    assert_eq!(err.arg_index, Some(2));
+    let err = Request::parse_detailed("MINOFFSET 1 GET").unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::WrongArity);
+}
+
+#[tokio::test]
+async fn test_unlink_removes_keys_at_once() {
+    let (_dir, executor) = setup();
+    let elements: Vec<String> = (0..100_000).map(|i| i.to_string()).collect();
+    run(&executor, &format!("RPUSH big {}", elements.join(" "))).await;
+    run(&executor, "SET a 1").await;
+    run(&executor, "SADD s x y").await;
+    
+    let started = std::time::Instant::now();
+    assert_eq!(run(&executor, "UNLINK big a s missing").await, Response::Integer(3));
+    assert!(started.elapsed() < std::time::Duration::from_secs(1));
+    assert_eq!(run(&executor, "EXISTS big a s").await, Response::Integer(0));
+    assert_eq!(run(&executor, "DBSIZE").await, Response::Integer(0));
+    assert!(Request::parse("UNLINK k").unwrap().is_write());
+}
+
+#[tokio::test]
+async fn test_repeated_append_merges() {
+    let (_dir, executor) = setup();
+    let started = std::time::Instant::now();
+    for i in 0..10_000 {
+        let piece = format!("{:04}", i);
+        assert_eq!(run(&executor, &format!("APPEND grown {}", piece)).await, Response::Integer((i + 1) * 4));
+    }
+    assert!(started.elapsed() < std::time::Duration::from_secs(20));
+    
+    let expected: String = (0..10_000).map(|i| format!("{:04}", i)).collect();
+    assert_eq!(run(&executor, "GET grown").await, Response::String(Some(expected)));
+    assert_eq!(run(&executor, "STRLEN grown").await, Response::Integer(40_000));
+    
+    // Increments still merge on top of an appended number
+    run(&executor, "APPEND n 1").await;
+    run(&executor, "APPEND n 2").await;
+    assert_eq!(run(&executor, "INCR n").await, Response::Integer(13));
+    assert_eq!(run(&executor, "APPEND n 0").await, Response::Integer(3));
+    assert_eq!(run(&executor, "GET n").await, Response::String(Some("130".to_string())));
+}
+
+#[tokio::test]
+async fn test_cluster_stub_reports_a_single_node() {
+    let (_dir, executor) = setup();
+    match run(&executor, "CLUSTER INFO").await {
+        Response::String(Some(info)) => {
+            assert!(info.lines().any(|line| line == "cluster_enabled:0"), "{}", info);
+            assert!(info.lines().any(|line| line == "cluster_state:ok"), "{}", info);
+        }
+        other => panic!("unexpected CLUSTER INFO reply {:?}", other),
+    }
+    assert_eq!(run(&executor, "CLUSTER SLOTS").await, Response::Array(Vec::new()));
+    assert_eq!(run(&executor, "cluster shards").await, Response::Array(Vec::new()));
+    
+    let id = run(&executor, "CLUSTER MYID").await;
+    assert!(matches!(&id, Response::String(Some(id)) if id.len() == 40 && id.chars().all(|c| c.is_ascii_hexdigit())));
+    assert_eq!(run(&executor, "CLUSTER MYID").await, id);
+    
+    assert!(matches!(run(&executor, "CLUSTER NODES").await, Response::Error(e) if e.contains("unknown subcommand")));
+    assert!(matches!(run(&executor, "CLUSTER HELP").await, Response::Array(lines) if !lines.is_empty()));
+}
+
+#[tokio::test]
+async fn test_delprefix_removes_only_prefixed_keys() {
+    let (_dir, executor) = setup();
+    for i in 0..1000 {
+        let request = match i % 3 {
+            0 => format!("SET tenant:42:{} v", i),
+            1 => format!("RPUSH tenant:42:{} a b", i),
+            _ => format!("HSET tenant:42:{} f v", i),
+        };
+        run(&executor, &request).await;
+    }
+    run(&executor, "EXPIRE tenant:42:0 100").await;
+    for key in ["tenant:42", "tenant:43:1", "tenant:4", "tenant:420", "user:1"] {
+        run(&executor, &format!("SET {} keep", key)).await;
+    }
+    
+    assert_eq!(run(&executor, "DELPREFIX tenant:42:").await, Response::Integer(1000));
+    assert_eq!(run(&executor, "DBSIZE").await, Response::Integer(5));
+    assert_eq!(run(&executor, "EXISTS tenant:42:0 tenant:42:1 tenant:42:999").await, Response::Integer(0));
+    assert_eq!(run(&executor, "TTL tenant:42:0").await, Response::Integer(-2));
+    for key in ["tenant:42", "tenant:43:1", "tenant:4", "tenant:420", "user:1"] {
+        assert_eq!(run(&executor, &format!("GET {}", key)).await, Response::String(Some("keep".to_string())), "{}", key);
+    }
+    
+    // Recreated keys start fresh
+    run(&executor, "SET tenant:42:0 new").await;
+    assert_eq!(run(&executor, "TTL tenant:42:0").await, Response::Integer(-1));
+    assert_eq!(run(&executor, "DELPREFIX nothing:").await, Response::Integer(0));
+    assert!(Request::parse("DELPREFIX").is_err());
+}
+
+async fn dump(executor: &CommandExecutor, key: &str) -> String {
+    match run(executor, &format!("DUMP {}", key)).await {
+        Response::String(Some(payload)) => payload,
+        other => panic!("unexpected DUMP reply {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_dump_restore_round_trip() {
+    let (_dir, executor) = setup();
+    run(&executor, "RPUSH list a b c").await;
+    run(&executor, "HSET hash field value").await;
+    run(&executor, "SET counter 42").await;
+    
+    for key in ["list", "hash", "counter"] {
+        let payload = dump(&executor, key).await;
+        assert_eq!(run(&executor, &format!("RESTORE {} 0 {}", key, payload)).await,
+            Response::Error("BUSYKEY Target key name already exists.".to_string()));
+        assert_eq!(run(&executor, &format!("RESTORE {}:copy 0 {}", key, payload)).await, Response::Ok);
+        assert_eq!(dump(&executor, &format!("{}:copy", key)).await, payload);
+    }
+    assert_eq!(run(&executor, "LRANGE list:copy 0 -1").await, Response::Array(
+        ["a", "b", "c"].iter().map(|v| Response::String(Some(v.to_string()))).collect()));
+    assert_eq!(run(&executor, "INCR counter:copy").await, Response::Integer(43));
+    
+    // REPLACE overwrites, and the TTL applies to the restored key
+    let payload = dump(&executor, "hash").await;
+    assert_eq!(run(&executor, &format!("RESTORE list 5000 {} REPLACE", payload)).await, Response::Ok);
+    assert_eq!(run(&executor, "HGET list field").await, Response::String(Some("value".to_string())));
+    assert!(matches!(run(&executor, "PTTL list").await, Response::Integer(ttl) if ttl > 0 && ttl <= 5000));
+    assert_eq!(run(&executor, "DUMP missing").await, Response::Null);
+}
+
+#[tokio::test]
+async fn test_restore_rejects_tampered_or_newer_payloads() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET greeting hello").await;
+    let payload = dump(&executor, "greeting").await;
+    let bad = Response::Error(dump::BAD_PAYLOAD.to_string());
+    let mut bytes: Vec<u8> = (0..payload.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&payload[i..i + 2], 16).unwrap())
+        .collect();
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    
+    // A flipped checksum byte
+    let mut tampered = bytes.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert_eq!(run(&executor, &format!("RESTORE a 0 {}", hex(&tampered))).await, bad);
+    
+    // A newer version, correctly signed, is still refused
+    let body_end = bytes.len() - dump::CHECKSUM_LEN;
+    bytes[0] = dump::DUMP_VERSION + 1;
+    let sum = dump::checksum(&bytes[..body_end]);
+    bytes[body_end..].copy_from_slice(&sum);
+    assert_eq!(run(&executor, &format!("RESTORE b 0 {}", hex(&bytes))).await, bad);
+    
+    assert_eq!(run(&executor, "RESTORE c 0 nothex").await, bad);
+    assert_eq!(run(&executor, "EXISTS a b c").await, Response::Integer(0));
+}
+
+#[tokio::test]
+async fn test_copy_carries_the_remaining_ttl() {
+    let temp_dir = TempDir::new().unwrap();
+    let clock = Arc::new(MockClock::new(1_700_000_000_000));
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap().with_clock(clock.clone()));
+    let executor = CommandExecutor::new(storage);
+    
+    run(&executor, "SET session token").await;
+    run(&executor, "PEXPIRE session 10000").await;
+    clock.advance(4_000);
+    
+    // The copy keeps what is left of the TTL rather than starting it over
+    assert_eq!(run(&executor, "COPY session backup DB 0").await, Response::Integer(1));
+    assert_eq!(run(&executor, "GET backup").await, Response::String(Some("token".to_string())));
+    assert_eq!(run(&executor, "PTTL backup").await, Response::Integer(6_000));
+    assert_eq!(run(&executor, "PTTL session").await, Response::Integer(6_000));
+    
+    // Hash field deadlines come along, and REPLACE drops the old destination's TTL
+    run(&executor, "HMSET profile name ada plan pro").await;
+    run(&executor, "HEXPIRE profile 30 FIELDS 1 plan").await;
+    assert_eq!(run(&executor, "COPY profile backup").await, Response::Integer(0));
+    assert_eq!(run(&executor, "COPY profile backup REPLACE").await, Response::Integer(1));
+    assert_eq!(run(&executor, "TTL backup").await, Response::Integer(-1));
+    assert_eq!(run(&executor, "HTTL backup FIELDS 2 plan name").await,
+        Response::Array(vec![Response::Integer(30), Response::Integer(-1)]));
+    
+    // A source past its deadline is as good as missing
+    clock.advance(6_000);
+    assert_eq!(run(&executor, "COPY session other").await, Response::Integer(0));
+    assert_eq!(run(&executor, "EXISTS session other").await, Response::Integer(0));
+    
+    // Only database 0 exists
+    assert_eq!(run(&executor, "COPY profile elsewhere DB 1").await, Response::Error("ERR DB index is out of range".to_string()));
+    assert!(matches!(run(&executor, "COPY profile profile").await, Response::Error(e) if e.contains("same")));
+    for cmd in ["COPY profile", "COPY a b DB", "COPY a b DB x", "COPY a b KEEPTTL"] {
+        assert!(Request::parse(cmd).is_err(), "{} should be rejected", cmd);
+    }
+}
+
+#[tokio::test]
+async fn test_flushdb_and_flushall_clear_every_key() {
+    let (_dir, executor) = setup();
+    let code = "#!lua name=kept\nredis.register_function('one', function() return 1 end)";
+    executor.execute(Request::FunctionLoad { code: code.to_string(), replace: false }).await.unwrap();
+    let (mailbox, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+    executor.tracking().enable(3, mailbox);
+    
+    for flush in ["FLUSHDB", "FLUSHALL ASYNC", "flushdb sync"] {
+        run(&executor, "SET greeting hello").await;
+        run(&executor, "EXPIRE greeting 100").await;
+        run(&executor, "RPUSH queue a b").await;
+        run(&executor, "HMSET profile name ada").await;
+        run(&executor, "ZADD scores 1 m").await;
+        executor.tracking().remember(3, &["greeting"]);
+        
+        assert_eq!(run(&executor, flush).await, Response::Ok, "{}", flush);
+        assert_eq!(run(&executor, "DBSIZE").await, Response::Integer(0), "{}", flush);
+        assert_eq!(run(&executor, "EXISTS greeting queue profile scores").await, Response::Integer(0));
+        assert_eq!(run(&executor, "TTL greeting").await, Response::Integer(-2));
+        // Tracking clients hear that everything changed
+        assert_eq!(inbox.try_recv().unwrap(), Response::Array(vec![
+            Response::String(Some("invalidate".to_string())),
+            Response::Null,
+        ]));
+    }
+    
+    // Keys written afterwards are unaffected, and functions are not data
+    assert_eq!(run(&executor, "FLUSHALL").await, Response::Ok);
+    run(&executor, "SET fresh 1").await;
+    assert_eq!(run(&executor, "DBSIZE").await, Response::Integer(1));
+    assert_eq!(run(&executor, "FCALL one 0").await, Response::Integer(1));
+    
+    // ASYNC only leaves freeing the space for later; what is written next is kept
+    assert_eq!(run(&executor, "FLUSHALL ASYNC").await, Response::Ok);
+    run(&executor, "SET fresh 2").await;
+    tokio::task::yield_now().await;
+    assert_eq!(run(&executor, "GET fresh").await, Response::String(Some("2".to_string())));
+    assert_eq!(run(&executor, "DBSIZE").await, Response::Integer(1));
+    
+    assert!(matches!(Request::parse("FLUSHALL ASYNC"), Ok(Request::FlushAll { lazy: true })));
+    assert!(matches!(Request::parse("flushdb sync"), Ok(Request::FlushDb { lazy: false })));
+    assert!(matches!(Request::parse("FLUSHDB"), Ok(Request::FlushDb { lazy: false })));
+    for cmd in ["FLUSHDB LAZY", "FLUSHALL ASYNC SYNC"] {
+        assert!(Request::parse(cmd).is_err(), "{} should be rejected", cmd);
+    }
+}
+
+#[tokio::test]
+async fn test_memory_usage() {
+    let (_dir, executor) = setup();
+    let usage = |response: Response| match response {
+        Response::Integer(n) => n,
+        other => panic!("expected a size, got {:?}", other),
+    };
+    
+    run(&executor, "SET short hi").await;
+    let elements: Vec<String> = (0..1000).map(|i| format!("element-{}", i)).collect();
+    run(&executor, &format!("RPUSH long {}", elements.join(" "))).await;
+    let short = usage(run(&executor, "MEMORY USAGE short").await);
+    let long = usage(run(&executor, "MEMORY USAGE long").await);
+    assert!(long > short * 100, "{} vs {}", long, short);
+    assert_eq!(run(&executor, "MEMORY USAGE missing").await, Response::Null);
+    
+    // Only the sampled elements are sized: one big element first skews a small sample
+    let big = "x".repeat(10_000);
+    run(&executor, &format!("RPUSH skewed {} {}", big, vec!["y"; 999].join(" "))).await;
+    let sampled = usage(run(&executor, "MEMORY USAGE skewed SAMPLES 1").await);
+    let exact = usage(run(&executor, "MEMORY USAGE skewed SAMPLES 0").await);
+    assert!(sampled > exact * 100, "{} vs {}", sampled, exact);
+    assert!(matches!(run(&executor, "MEMORY USAGE skewed SAMPLES x").await, Response::Error(_)));
+    
+    match run(&executor, "MEMORY STATS").await {
+        Response::Array(stats) => {
+            assert_eq!(stats[0], Response::String(Some("keys.count".to_string())));
+            assert_eq!(stats[1], Response::Integer(3));
+            assert!(stats.contains(&Response::String(Some("keys.list".to_string()))));
+        }
+        other => panic!("expected stats, got {:?}", other),
+    }
+    assert!(matches!(run(&executor, "MEMORY DOCTOR").await, Response::String(Some(report)) if report.contains("3 keys")));
+    assert!(matches!(run(&executor, "MEMORY FREE").await, Response::Error(e) if e.contains("MEMORY HELP")));
+}
+
+#[cfg(not(feature = "memory_pool"))]
+#[tokio::test]
+async fn test_memory_pool_without_the_feature() {
+    let (_dir, executor) = setup();
+    assert_eq!(run(&executor, "MEMORY POOL").await, Response::Array(vec![
+        Response::String(Some("pool.enabled".to_string())),
+        Response::Integer(0),
+    ]));
+    assert!(matches!(run(&executor, "MEMORY POOL now").await, Response::Error(e) if e.contains("wrong number")));
+}
+
+#[cfg(feature = "memory_pool")]
+#[tokio::test]
+async fn test_memory_pool_reports_hit_rate() {
+    use diskdb::ffi::memory::PooledString;
+    
+    let (_dir, executor) = setup();
+    run(&executor, "SET greeting hello").await;
+    run(&executor, "RPUSH list a b c").await;
+    // Freed allocations go back to the pool, so later ones can hit
+    for i in 0..100 {
+        PooledString::from_str(&format!("value-{}", i)).unwrap();
+    }
+    
+    let stats = match run(&executor, "MEMORY POOL").await {
+        Response::Array(stats) => stats,
+        other => panic!("expected pool stats, got {:?}", other),
+    };
+    let field = |name: &str| stats.chunks(2)
+        .find(|pair| pair[0] == Response::String(Some(name.to_string())))
+        .map(|pair| &pair[1])
+        .unwrap_or_else(|| panic!("{} missing from {:?}", name, stats));
+    assert_eq!(field("pool.enabled"), &Response::Integer(1));
+    assert!(matches!(field("pool.allocations"), Response::Integer(n) if *n >= 100));
+    match field("pool.hit-rate") {
+        Response::String(Some(rate)) => {
+            let rate: f64 = rate.parse().unwrap();
+            assert!((0.0..=100.0).contains(&rate), "hit rate {}", rate);
+        }
+        other => panic!("unexpected hit rate {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_version_reports_build_metadata() {
+    let (_dir, executor) = setup();
+    let fields = match run(&executor, "VERSION").await {
+        Response::Array(fields) => fields,
+        other => panic!("unexpected VERSION reply {:?}", other),
+    };
+    
+    assert_eq!(fields[0], Response::String(Some("version".to_string())));
+    assert!(matches!(&fields[1], Response::String(Some(v)) if !v.is_empty()));
+    assert_eq!(fields[6], Response::String(Some("features".to_string())));
+    match &fields[7] {
+        Response::Array(features) => {
+            let expected = format!("c_parser={}", if cfg!(feature = "c_parser") { "on" } else { "off" });
+            assert!(features.contains(&Response::String(Some(expected))));
+        }
+        other => panic!("unexpected features {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_cardinality_commands_check_type() {
+    let (_dir, executor) = setup();
+    run(&executor, "SADD s a b c").await;
+    run(&executor, "HSET h f v").await;
+    run(&executor, "ZADD z 1 a 2 b").await;
+    run(&executor, "XADD x * f v").await;
+    run(&executor, "SET str hello").await;
+    
+    assert_eq!(run(&executor, "SCARD s").await, Response::Integer(3));
+    assert_eq!(run(&executor, "HLEN h").await, Response::Integer(1));
+    assert_eq!(run(&executor, "ZCARD z").await, Response::Integer(2));
+    assert_eq!(run(&executor, "XLEN x").await, Response::Integer(1));
+    assert_eq!(run(&executor, "STRLEN str").await, Response::Integer(5));
+    assert_eq!(run(&executor, "LLEN missing").await, Response::Integer(0));
+    assert!(matches!(run(&executor, "LLEN s").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+    assert!(matches!(run(&executor, "HLEN str").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+}
+
+#[tokio::test]
+async fn test_max_value_size_rejects_large_writes() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    let executor = CommandExecutor::new(storage).with_max_value_size(16);
+    let too_large = Response::Error(diskdb::commands::VALUE_TOO_LARGE.to_string());
+    
+    assert_eq!(run(&executor, "SET k 0123456789abcdef").await, Response::Ok);
+    assert_eq!(run(&executor, "SET big 0123456789abcdefg").await, too_large);
+    assert_eq!(run(&executor, "GET big").await, Response::Null);
+    
+    // APPEND counts the existing value towards the limit
+    assert_eq!(run(&executor, "SET a 0123456789").await, Response::Ok);
+    assert_eq!(run(&executor, "APPEND a 0123456").await, too_large);
+    assert_eq!(run(&executor, "GET a").await, Response::String(Some("0123456789".to_string())));
+    
+    assert_eq!(run(&executor, "RPUSH l small 0123456789abcdefg").await, too_large);
+    assert_eq!(run(&executor, "LLEN l").await, Response::Integer(0));
+}
+
+#[tokio::test]
+async fn test_eval_set_and_get_through_redis_call() {
+    let (_dir, executor) = setup();
+    let eval = Request::Eval {
+        script: "redis.call('set', KEYS[1], ARGV[1]) return redis.call('get', KEYS[1])".to_string(),
+        keys: vec!["greeting".to_string()],
+        args: vec!["hello world".to_string()],
+    };
+    let reply = executor.execute(eval).await.unwrap();
+    assert_eq!(reply, Response::String(Some("hello world".to_string())));
+    assert_eq!(run(&executor, "GET greeting").await, Response::String(Some("hello world".to_string())));
+    
+    // Error replies from redis.call reach the client unchanged
+    run(&executor, "LPUSH list a").await;
+    let eval = Request::Eval {
+        script: "return redis.call('get', KEYS[1])".to_string(),
+        keys: vec!["list".to_string()],
+        args: vec![],
+    };
+    assert!(matches!(executor.execute(eval).await.unwrap(), Response::Error(e) if e.starts_with("WRONGTYPE")));
+}
+
+#[tokio::test]
+async fn test_script_load_and_evalsha() {
+    let (_dir, executor) = setup();
+    let sha = match run(&executor, "SCRIPT LOAD return(redis.call('incr',KEYS[1]))").await {
+        Response::String(Some(sha)) => sha,
+        other => panic!("unexpected SCRIPT LOAD reply {:?}", other),
+    };
+    assert_eq!(sha.len(), 40);
+    
+    let evalsha = format!("EVALSHA {} 1 counter", sha);
+    assert_eq!(run(&executor, &evalsha).await, Response::Integer(1));
+    let evalsha_upper = format!("EVALSHA {} 1 counter", sha.to_uppercase());
+    assert_eq!(run(&executor, &evalsha_upper).await, Response::Integer(2));
+    
+    let missing = format!("EVALSHA {} 0", "0".repeat(40));
+    assert!(matches!(run(&executor, &missing).await, Response::Error(e) if e.starts_with("NOSCRIPT")));
+    
+    // Lua tables become arrays, false becomes null and status tables become OK
+    assert_eq!(
+        run(&executor, "EVAL return({1,'two',false}) 0").await,
+        Response::Array(vec![Response::Integer(1), Response::String(Some("two".to_string())), Response::Null])
+    );
+    assert_eq!(run(&executor, "EVAL return(redis.call('set',KEYS[1],ARGV[1])) 1 k v").await, Response::Ok);
+}
+
+#[tokio::test]
+async fn test_looping_script_is_stopped_at_time_limit() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    let executor = CommandExecutor::new(storage).with_lua_time_limit(100);
+    
+    let started = std::time::Instant::now();
+    let eval = Request::Eval {
+        script: "redis.call('set', KEYS[1], 'before') while true do end".to_string(),
+        keys: vec!["k".to_string()],
+        args: vec![],
+    };
+    match executor.execute(eval).await.unwrap() {
+        Response::Error(e) => assert!(e.contains("more than 100 ms"), "{}", e),
+        other => panic!("expected the script to be stopped, got {:?}", other),
+    }
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    
+    // The lock is released and writes made before the limit stay
+    assert_eq!(run(&executor, "GET k").await, Response::String(Some("before".to_string())));
+    assert_eq!(run(&executor, "EVAL return(1) 0").await, Response::Integer(1));
+    
+    // Loading a library runs its body under the same limit
+    let load = Request::FunctionLoad {
+        code: "#!lua name=spin\nwhile true do end".to_string(),
+        replace: false,
+    };
+    assert!(matches!(executor.execute(load).await.unwrap(), Response::Error(e) if e.contains("more than 100 ms")));
+}
+
+#[tokio::test]
+async fn test_function_library_survives_restart() {
+    let temp_dir = TempDir::new().unwrap();
+    let code = "#!lua name=counters\n\
+        redis.register_function('bump', function(keys, args)\n\
+            return redis.call('incrby', keys[1], args[1])\n\
+        end)";
+    
+    {
+        let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+        let executor = CommandExecutor::new(storage);
+        let load = Request::FunctionLoad { code: code.to_string(), replace: false };
+        assert_eq!(executor.execute(load).await.unwrap(), Response::String(Some("counters".to_string())));
+        
+        // Loading the same library again needs REPLACE
+        let again = Request::FunctionLoad { code: code.to_string(), replace: false };
+        assert!(matches!(executor.execute(again).await.unwrap(), Response::Error(e) if e.contains("already exists")));
+        assert_eq!(run(&executor, "FCALL bump 1 hits 5").await, Response::Integer(5));
+    }
+    
+    // A fresh executor over the reopened database knows nothing until restored
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    let executor = CommandExecutor::new(storage);
+    assert!(matches!(run(&executor, "FCALL bump 1 hits 1").await, Response::Error(e) if e.contains("not found")));
+    assert_eq!(executor.restore_functions().await.unwrap(), 1);
+    assert_eq!(run(&executor, "FCALL bump 1 hits 2").await, Response::Integer(7));
+    
+    let text = |s: &str| Response::String(Some(s.to_string()));
+    assert_eq!(run(&executor, "FUNCTION LIST").await, Response::Array(vec![Response::Array(vec![
+        text("library_name"), text("counters"),
+        text("engine"), text("LUA"),
+        text("functions"), Response::Array(vec![text("bump")]),
+    ])]));
+}
+
+#[tokio::test]
+async fn test_function_load_rejects_bad_libraries() {
+    let (_dir, executor) = setup();
+    let load = |code: &str| Request::FunctionLoad { code: code.to_string(), replace: false };
+    
+    let reply = executor.execute(load("return 1")).await.unwrap();
+    assert!(matches!(reply, Response::Error(e) if e.contains("metadata")));
+    let reply = executor.execute(load("#!lua name=empty\nlocal x = 1")).await.unwrap();
+    assert!(matches!(reply, Response::Error(e) if e.contains("No functions")));
+    
+    // Function names are unique across libraries
+    let first = "#!lua name=first\nredis.register_function('shared', function() return 1 end)";
+    let second = "#!lua name=second\nredis.register_function('shared', function() return 2 end)";
+    assert!(matches!(executor.execute(load(first)).await.unwrap(), Response::String(_)));
+    let reply = executor.execute(load(second)).await.unwrap();
+    assert!(matches!(reply, Response::Error(e) if e.contains("already exists")));
+    assert_eq!(run(&executor, "FCALL shared 0").await, Response::Integer(1));
+}
+
+#[tokio::test]
+async fn test_command_introspection() {
+    let (_dir, executor) = setup();
+    let text = |s: &str| Response::String(Some(s.to_string()));
+    
+    let count = match run(&executor, "COMMAND COUNT").await {
+        Response::Integer(n) => n,
+        other => panic!("unexpected COMMAND COUNT reply {:?}", other),
+    };
+    assert!(count > 0);
+    match run(&executor, "COMMAND").await {
+        Response::Array(entries) => assert_eq!(entries.len() as i64, count),
+        other => panic!("unexpected COMMAND reply {:?}", other),
+    }
+    
+    assert_eq!(run(&executor, "COMMAND DOCS GET").await, Response::Array(vec![
+        text("get"),
+        Response::Array(vec![
+            text("arity"), Response::Integer(2),
+            text("flags"), Response::Array(vec![text("readonly"), text("fast")]),
+        ]),
+    ]));
+    assert!(matches!(run(&executor, "COMMAND BOGUS").await, Response::Error(_)));
+}
+
+#[tokio::test]
+async fn test_command_getkeys() {
+    use diskdb::commands::table::CommandSpec;
+    let (_dir, executor) = setup();
+    let keys = |names: &[&str]| Response::Array(names.iter().map(|k| Response::String(Some(k.to_string()))).collect());
+    
+    assert_eq!(run(&executor, "COMMAND GETKEYS SET k v").await, keys(&["k"]));
+    assert_eq!(run(&executor, "COMMAND GETKEYS DEL a b c").await, keys(&["a", "b", "c"]));
+    assert_eq!(run(&executor, "COMMAND GETKEYS BLPOP a b 5").await, keys(&["a", "b"]));
+    assert_eq!(run(&executor, "COMMAND GETKEYS BITOP AND dest a b").await, keys(&["dest", "a", "b"]));
+    // Keys counted by a numkeys argument come from parsing the command
+    assert_eq!(run(&executor, "COMMAND GETKEYS ZUNIONSTORE out 2 a b WEIGHTS 1 2").await, keys(&["out", "a", "b"]));
+    assert_eq!(run(&executor, "COMMAND GETKEYS EVAL script 1 k arg").await, keys(&["k"]));
+    
+    let error = |message: &str| Response::Error(message.to_string());
+    assert_eq!(run(&executor, "COMMAND GETKEYS GET").await, error("ERR Invalid number of arguments specified for command"));
+    assert_eq!(run(&executor, "COMMAND GETKEYS GET a b").await, error("ERR Invalid number of arguments specified for command"));
+    assert_eq!(run(&executor, "COMMAND GETKEYS PING").await, error("ERR The command has no key arguments"));
+    assert_eq!(run(&executor, "COMMAND GETKEYS NOPE k").await, error("ERR Invalid command specified"));
+    assert_eq!(run(&executor, "COMMAND GETKEYS ZUNIONSTORE out 3 a b").await, error("ERR Invalid arguments specified for command"));
+    
+    // No command here interleaves keys with values, so check a step of 2 on an MSET-like spec
+    let mset = CommandSpec { name: "mset", arity: -3, flags: &[], first_key: 1, last_key: -1, step: 2 };
+    let args: Vec<String> = ["MSET", "a", "1", "b", "2"].iter().map(|s| s.to_string()).collect();
+    assert_eq!(mset.key_args(&args), vec!["a", "b"]);
+}
+
+#[tokio::test]
+async fn test_subcommand_help() {
+    use diskdb::commands::table::SUBCOMMAND_HELP;
+    let (_dir, executor) = setup();
+    
+    match run(&executor, "OBJECT HELP").await {
+        Response::Array(lines) => {
+            assert!(lines.len() > 1);
+            for line in &lines {
+                let Response::String(Some(line)) = line else { panic!("unexpected help line {:?}", line) };
+                assert!(["ENCODING", "FREQ", "HELP"].iter().any(|sub| line.starts_with(sub)), "{}", line);
+            }
+        }
+        other => panic!("unexpected OBJECT HELP reply {:?}", other),
+    }
+    
+    // Every command with a help table answers HELP in any case, and lists HELP itself
+    for (command, lines) in SUBCOMMAND_HELP {
+        let reply = run(&executor, &format!("{} help", command)).await;
+        let expected: Vec<Response> = lines.iter().map(|l| Response::String(Some(l.to_string()))).collect();
+        assert_eq!(reply, Response::Array(expected), "{} HELP", command);
+        assert!(lines.iter().any(|l| l.starts_with("HELP")), "{} HELP does not list itself", command);
+    }
+}
+
+#[tokio::test]
+async fn test_debug_reload_round_trips_every_type() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    let executor = CommandExecutor::new(storage).with_debug_command(true);
+    
+    run(&executor, "SET str hello").await;
+    run(&executor, "EXPIRE str 1000").await;
+    run(&executor, "RPUSH list a b c").await;
+    run(&executor, "SADD set x y z").await;
+    run(&executor, "HSET hash f1 v1").await;
+    run(&executor, "HSET hash f2 v2").await;
+    run(&executor, "HEXPIRE hash 1000 FIELDS 1 f2").await;
+    run(&executor, "ZADD zset 1.5 one 2 two").await;
+    run(&executor, r#"JSON.SET doc $ {"a":[1,2,{"b":null}]}"#).await;
+    let id = run(&executor, "XADD stream * field value").await;
+    assert_eq!(run(&executor, "DBSIZE").await, Response::Integer(7));
+    
+    assert_eq!(run(&executor, "DEBUG RELOAD").await, Response::Ok);
+    
+    assert_eq!(run(&executor, "GET str").await, Response::String(Some("hello".to_string())));
+    assert!(matches!(run(&executor, "TTL str").await, Response::Integer(t) if t > 0));
+    assert_eq!(run(&executor, "LRANGE list 0 -1").await, Response::Array(
+        ["a", "b", "c"].iter().map(|s| Response::String(Some(s.to_string()))).collect()));
+    assert_eq!(run(&executor, "SCARD set").await, Response::Integer(3));
+    assert_eq!(run(&executor, "HGET hash f1").await, Response::String(Some("v1".to_string())));
+    assert!(matches!(run(&executor, "HTTL hash FIELDS 1 f2").await, Response::Array(ttls) if ttls[0] != Response::Integer(-1)));
+    assert_eq!(run(&executor, "ZSCORE zset one").await, Response::String(Some("1.5".to_string())));
+    assert!(matches!(run(&executor, "JSON.GET doc $").await, Response::String(Some(json)) if json.contains("\"b\":null")));
+    assert_eq!(run(&executor, "XLEN stream").await, Response::Integer(1));
+    let Response::String(Some(id)) = id else { panic!("unexpected XADD reply") };
+    assert_eq!(run(&executor, &format!("XRANGE stream {} {}", id, id)).await, Response::Array(
+        [id.as_str(), "field", "value"].iter().map(|s| Response::String(Some(s.to_string()))).collect()));
+    assert_eq!(run(&executor, "DBSIZE").await, Response::Integer(7));
+}
+
+#[tokio::test]
+async fn test_debug_requires_config() {
+    let (_dir, executor) = setup();
+    run(&executor, "SET k v").await;
+    assert!(matches!(run(&executor, "DEBUG RELOAD").await, Response::Error(e) if e.contains("not allowed")));
+    assert_eq!(run(&executor, "GET k").await, Response::String(Some("v".to_string())));
+}
+
+#[tokio::test]
+async fn test_blocking_pop() {
+    let (_dir, executor) = setup();
+    let pair = |key: &str, value: &str| Response::Array(vec![
+        Response::String(Some(key.to_string())),
+        Response::String(Some(value.to_string())),
+    ]);
+    
+    // Ready elements are popped at once from the first non-empty key
+    run(&executor, "RPUSH second a b").await;
+    assert_eq!(run(&executor, "BLPOP first second 1").await, pair("second", "a"));
+    assert_eq!(run(&executor, "BRPOP first second 1").await, pair("second", "b"));
+    assert_eq!(run(&executor, "BLPOP first 0.05").await, Response::NullArray);
+    run(&executor, "SET text v").await;
+    assert!(matches!(run(&executor, "BLPOP text 1").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+    
+    // Blocked clients are served oldest first
+    let mut waiters = Vec::new();
+    for _ in 0..3 {
+        let executor = executor.clone();
+        waiters.push(tokio::spawn(async move { run(&executor, "BLPOP queue 5").await }));
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert_eq!(run(&executor, "RPUSH queue one two three").await, Response::Integer(3));
+    for (waiter, expected) in waiters.into_iter().zip(["one", "two", "three"]) {
+        assert_eq!(waiter.await.unwrap(), pair("queue", expected));
+    }
+    assert_eq!(run(&executor, "LLEN queue").await, Response::Integer(0));
+    
+    // A client that timed out no longer takes elements
+    assert_eq!(run(&executor, "BRPOP late 0.05").await, Response::NullArray);
+    run(&executor, "LPUSH late x").await;
+    assert_eq!(run(&executor, "LLEN late").await, Response::Integer(1));
+    
+    assert!(Request::parse("BLPOP k -1").is_err());
+    assert!(Request::parse("BLPOP k").is_err());
+}
+
+#[tokio::test]
+async fn test_missing_replies_keep_their_resp2_shape() {
+    use diskdb::resp::{encode_reply, RespVersion};
+    let (_dir, executor) = setup();
+    
+    // A missing string, a missing array and an empty array are three replies in RESP2
+    for (cmd, wire) in [
+        ("GET missing", "$-1\r\n"),
+        ("LPOP missing", "$-1\r\n"),
+        ("LPOP missing 2", "*-1\r\n"),
+        ("LRANGE missing 0 -1", "*0\r\n"),
+        ("LMPOP 1 missing LEFT", "*-1\r\n"),
+        ("ZMPOP 1 missing MIN", "*-1\r\n"),
+        ("BLPOP missing 0.05", "*-1\r\n"),
+        ("BZPOPMIN missing 0.05", "*-1\r\n"),
+    ] {
+        let request = Request::parse(cmd).unwrap();
+        let name = request.command_name();
+        let response = executor.execute(request).await.unwrap();
+        let mut out = Vec::new();
+        encode_reply(name, response, RespVersion::Resp2, &mut out);
+        assert_eq!(String::from_utf8(out).unwrap(), wire, "{}", cmd);
+    }
+}
+
+#[tokio::test]
+async fn test_tracking_falls_back_to_broadcast() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    let executor = CommandExecutor::new(storage).with_tracking_limit(2);
+    let (mailbox, mut inbox) = tokio::sync::mpsc::unbounded_channel();
+    let invalidated = |message: Response| match message {
+        Response::Array(mut parts) => parts.pop().unwrap(),
+        other => panic!("unexpected message {:?}", other),
+    };
+    let keys = |names: &[&str]| Response::Array(names.iter().map(|k| Response::String(Some(k.to_string()))).collect());
+    
+    executor.tracking().enable(7, mailbox);
+    executor.tracking().remember(7, &["a", "b"]);
+    run(&executor, "SET a 1").await;
+    run(&executor, "SET unread 1").await;
+    assert_eq!(invalidated(inbox.try_recv().unwrap()), keys(&["a"]));
+    assert!(inbox.try_recv().is_err());
+    
+    // Writes made by scripts are reported too
+    run(&executor, "EVAL \"redis.call('SET', KEYS[1], 'x')\" 1 b").await;
+    assert_eq!(invalidated(inbox.try_recv().unwrap()), keys(&["b"]));
+    
+    // Over the cap, every write is reported
+    executor.tracking().remember(7, &["c", "d", "e"]);
+    assert!(executor.tracking().is_broadcast(7));
+    run(&executor, "DEL anything").await;
+    assert_eq!(invalidated(inbox.try_recv().unwrap()), keys(&["anything"]));
+    
+    executor.tracking().disable(7);
+    run(&executor, "SET a 2").await;
+    assert!(inbox.try_recv().is_err());
+}
+
+#[test]
+fn test_command_table_matches_parser() {
+    use diskdb::commands::table::COMMAND_TABLE;
+    
+    // Every listed command is known to the parser, even if these arguments are wrong
+    for spec in COMMAND_TABLE {
+        if let Err(e) = Request::parse_detailed(spec.name) {
+            assert_ne!(e.kind, ParseErrorKind::UnknownCommand, "{} is listed but not parsed", spec.name);
+        }
+    }
+    
+    assert!(Request::parse("SET k v").unwrap().is_write());
+    assert!(Request::parse("DEL k").unwrap().is_write());
+    assert!(!Request::parse("GET k").unwrap().is_write());
+    assert!(!Request::parse("PING").unwrap().is_write());
+}
+
+#[test]
+fn test_quoted_arguments() {
+    use diskdb::protocol::split_args;
+    
+    match Request::parse_detailed("SET \"my key\" \"value with spaces\"").unwrap() {
+        Request::Set { key, value, .. } => {
+            assert_eq!(key, "my key");
+            assert_eq!(value, "value with spaces");
+        }
+        other => panic!("unexpected request {:?}", other),
+    }
+    
+    assert_eq!(split_args(r#"ECHO "tab\there" 'it\'s' "" plain"#).unwrap(), vec!["ECHO", "tab\there", "it's", "", "plain"]);
+    assert_eq!(split_args("GET don't\r").unwrap(), vec!["GET", "don't"]);
+    
+    let unbalanced = Request::parse_detailed("SET k \"open").unwrap_err();
+    assert_eq!((unbalanced.command.as_str(), unbalanced.kind, unbalanced.arg_index), ("SET", ParseErrorKind::Syntax, Some(2)));
+    let glued = Request::parse_detailed("SET k \"a\"b").unwrap_err();
+    assert_eq!(glued.kind, ParseErrorKind::Syntax);
+}
+
+#[tokio::test]
+async fn test_quoted_values_round_trip_through_commands() {
+    let (_dir, executor) = setup();
+    
+    assert_eq!(run(&executor, "HSET h f \"a b c\"").await, Response::Integer(1));
+    assert_eq!(run(&executor, "HGET h f").await, Response::String(Some("a b c".to_string())));
+    
+    run(&executor, r#"SADD s "two words" 'single quoted'"#).await;
+    assert_eq!(run(&executor, "SISMEMBER s \"two words\"").await, Response::Integer(1));
+    assert_eq!(run(&executor, "SCARD s").await, Response::Integer(2));
+    
+    run(&executor, r#"SET quote "say \"hi\"\tnow \xc3\xa9""#).await;
+    assert_eq!(run(&executor, "GET quote").await, Response::String(Some("say \"hi\"\tnow é".to_string())));
+    
+    // A lone byte that is not UTF-8 cannot become a String argument
+    let invalid = Request::parse_detailed(r#"SET k "\xff""#).unwrap_err();
+    assert_eq!((invalid.kind, invalid.arg_index), (ParseErrorKind::Syntax, Some(2)));
+    // An incomplete hex escape keeps the x
+    assert_eq!(diskdb::protocol::split_args(r#""\xZ1""#).unwrap(), vec!["xZ1"]);
+}
+
+#[tokio::test]
+async fn test_randomkey_picks_live_keys() {
+    let temp_dir = TempDir::new().unwrap();
+    let clock = Arc::new(MockClock::new(1_000_000));
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap().with_clock(clock.clone()));
+    let executor = CommandExecutor::new(storage);
+    
+    assert_eq!(run(&executor, "RANDOMKEY").await, Response::Null);
+    
+    let keys = ["apple", "banana", "cherry", "date"];
+    for key in keys {
+        run(&executor, &format!("SET {} v", key)).await;
+    }
+    let mut seen = HashSet::new();
+    for _ in 0..200 {
+        match run(&executor, "RANDOMKEY").await {
+            Response::String(Some(key)) => {
+                assert!(keys.contains(&key.as_str()), "unexpected key {}", key);
+                seen.insert(key);
+            }
+            other => panic!("unexpected reply {:?}", other),
+        }
+    }
+    assert_eq!(seen.len(), keys.len());
+    
+    // Expired keys are never returned, and a keyspace of only expired keys is empty
+    run(&executor, "DEL apple banana cherry").await;
+    run(&executor, "PEXPIRE date 10").await;
+    assert_eq!(run(&executor, "RANDOMKEY").await, Response::String(Some("date".to_string())));
+    clock.advance(10);
+    assert_eq!(run(&executor, "RANDOMKEY").await, Response::Null);
+    assert!(Request::parse("RANDOMKEY extra").is_err());
+}
+
+#[tokio::test]
+async fn test_set_conditions_get_and_expiry() {
+    let temp_dir = TempDir::new().unwrap();
+    let clock = Arc::new(MockClock::new(1_000_000));
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap().with_clock(clock.clone()));
+    let executor = CommandExecutor::new(storage);
+    let text = |s: &str| Response::String(Some(s.to_string()));
+    
+    // NX only sets an absent key, XX only an existing one
+    assert_eq!(run(&executor, "SET k first NX").await, Response::Ok);
+    assert_eq!(run(&executor, "SET k second NX").await, Response::Null);
+    assert_eq!(run(&executor, "GET k").await, text("first"));
+    assert_eq!(run(&executor, "SET missing v XX").await, Response::Null);
+    assert_eq!(run(&executor, "EXISTS missing").await, Response::Integer(0));
+    assert_eq!(run(&executor, "SET k third XX").await, Response::Ok);
+    
+    // GET replies with the prior value, even when a condition stops the write
+    assert_eq!(run(&executor, "SET k fourth GET").await, text("third"));
+    assert_eq!(run(&executor, "SET fresh v GET").await, Response::Null);
+    assert_eq!(run(&executor, "SET k fifth NX GET").await, text("fourth"));
+    assert_eq!(run(&executor, "GET k").await, text("fourth"));
+    
+    // GET against another type fails without writing
+    run(&executor, "LPUSH list a").await;
+    assert!(matches!(run(&executor, "SET list v GET").await, Response::Error(e) if e.starts_with("WRONGTYPE")));
+    assert_eq!(run(&executor, "LLEN list").await, Response::Integer(1));
+    
+    // Expiry options set the TTL in the same command; KEEPTTL keeps it, a plain SET clears it
+    run(&executor, "SET k v EX 10").await;
+    assert_eq!(run(&executor, "PTTL k").await, Response::Integer(10_000));
+    run(&executor, "SET k v2 KEEPTTL").await;
+    assert_eq!(run(&executor, "PTTL k").await, Response::Integer(10_000));
+    run(&executor, "SET k v3 PXAT 1000500").await;
+    assert_eq!(run(&executor, "PTTL k").await, Response::Integer(500));
+    run(&executor, "SET k v4").await;
+    assert_eq!(run(&executor, "PTTL k").await, Response::Integer(-1));
+    run(&executor, "SET k v5 PX 100 NX").await;
+    assert_eq!(run(&executor, "PTTL k").await, Response::Integer(-1));
+    
+    // Without a leading option, the unquoted tail is still the value
+    run(&executor, "SET phrase hello world NX").await;
+    assert_eq!(run(&executor, "GET phrase").await, text("hello world NX"));
+    
+    assert!(Request::parse("SET k v NX XX").is_err());
+    assert!(Request::parse("SET k v NX world").is_err());
+    assert!(Request::parse("SET k v EX 0").is_err());
+    assert!(Request::parse("SET k v EX 10 KEEPTTL").is_err());
+    assert!(Request::parse("SET k v PX").is_err());
+}
+
+#[tokio::test]
+async fn test_storage_fast_paths_match_async_calls() {
+    let temp_dir = TempDir::new().unwrap();
+    let clock = Arc::new(MockClock::new(1_000_000));
+    let storage = RocksDBStorage::new(temp_dir.path()).unwrap().with_clock(clock.clone());
+    let value = DataType::String("v".to_string());
+    
+    assert!(storage.get_now("k").unwrap().is_none());
+    storage.set_now("k", &value).unwrap();
+    assert!(matches!(storage.get_now("k").unwrap(), Some(DataType::String(s)) if s == "v"));
+    assert!(matches!(storage.get("k").await.unwrap(), Some(DataType::String(s)) if s == "v"));
+    assert_eq!(storage.dbsize().await.unwrap(), 1);
+    
+    // set_now clears the expiry as set followed by persist does
+    storage.expire_at("k", 1_000_500).await.unwrap();
+    storage.set_now("k", &value).unwrap();
+    assert_eq!(storage.expiry("k").await.unwrap(), None);
+    
+    // get_now treats an expired key as missing, as get does
+    storage.expire_at("k", 1_000_500).await.unwrap();
+    clock.advance(500);
+    assert!(storage.get_now("k").unwrap().is_none());
+    assert!(!storage.exists("k").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_debug_populate() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    let executor = CommandExecutor::new(storage.clone()).with_debug_command(true);
+    
+    run(&executor, "SET key:7 mine").await;
+    assert_eq!(run(&executor, "DEBUG POPULATE 1000").await, Response::Ok);
+    assert_eq!(run(&executor, "DBSIZE").await, Response::Integer(1000));
+    assert_eq!(run(&executor, "GET key:999").await, Response::String(Some("value:999".to_string())));
+    // Existing keys are left alone
+    assert_eq!(run(&executor, "GET key:7").await, Response::String(Some("mine".to_string())));
+    
+    run(&executor, "DEBUG POPULATE 3 item 10").await;
+    assert_eq!(run(&executor, "GET item:2").await, Response::String(Some("value:2\0\0\0".to_string())));
+    run(&executor, "DEBUG POPULATE 3 short 3").await;
+    assert_eq!(run(&executor, "GET short:2").await, Response::String(Some("val".to_string())));
+    assert_eq!(run(&executor, "DBSIZE").await, Response::Integer(1006));
+    
+    let disabled = CommandExecutor::new(storage);
+    assert!(matches!(run(&disabled, "DEBUG POPULATE 10 other").await, Response::Error(e) if e.contains("DEBUG command not allowed")));
+    assert!(Request::parse("DEBUG POPULATE many").is_err());
+}
+
+#[tokio::test]
+async fn test_scan_returns_stable_keys_once_under_concurrent_writes() {
+    let (_dir, executor) = setup();
+    let stable: Vec<String> = (0..200).map(|i| format!("key:{:03}", i)).collect();
+    for key in &stable {
+        run(&executor, &format!("SET {} v", key)).await;
+    }
+    
+    // Churn keys that sort between the stable ones while the scan runs
+    let writer = {
+        let executor = executor.clone();
+        tokio::spawn(async move {
+            for round in 0..50 {
+                for i in (0..200).step_by(7) {
+                    run(&executor, &format!("SET key:{:03}:churn{} v", i, round)).await;
+                    if round > 0 {
+                        run(&executor, &format!("DEL key:{:03}:churn{}", i, round - 1)).await;
+                    }
+                }
+                tokio::task::yield_now().await;
+            }
+        })
+    };
+    
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut cursor = "0".to_string();
+    let mut pages = 0;
+    loop {
+        let (next, page) = scan_reply(run(&executor, &format!("SCAN {} COUNT 5", cursor)).await);
+        for key in page {
+            *seen.entry(key).or_default() += 1;
+        }
+        pages += 1;
+        assert!(pages < 10_000, "scan did not terminate");
+        tokio::task::yield_now().await;
+        if next == "0" {
+            break;
+        }
+        cursor = next;
+    }
+    writer.await.unwrap();
+    
+    for key in &stable {
+        assert_eq!(seen.get(key), Some(&1), "{} returned {:?} times", key, seen.get(key));
+    }
+    assert!(seen.values().all(|&times| times == 1));
+    
+    // Reusing a cursor retries its page
+    let (next, _) = scan_reply(run(&executor, "SCAN 0 COUNT 5").await);
+    let (_, page) = scan_reply(run(&executor, &format!("SCAN {} COUNT 5", next)).await);
+    assert_eq!(scan_reply(run(&executor, &format!("SCAN {} COUNT 5", next)).await).1, page);
+    
+    // Cursors that were forgotten or never handed out still resume from the
+    // leading key bytes they carry, without missing a key
+    let scan_from = |cursor: String| {
+        let executor = executor.clone();
+        async move {
+            let mut cursor = cursor;
+            let mut seen = HashSet::new();
+            for _ in 0..10_000 {
+                let (next, page) = scan_reply(run(&executor, &format!("SCAN {} COUNT 5", cursor)).await);
+                seen.extend(page);
+                cursor = next;
+                if cursor == "0" {
+                    return seen;
+                }
+            }
+            panic!("scan did not terminate");
+        }
+    };
+    let seen = scan_from("987654".to_string()).await;
+    assert!(stable.iter().all(|key| seen.contains(key)));
+    
+    let (forgotten, page) = scan_reply(run(&executor, "SCAN 0 COUNT 50").await);
+    for _ in 0..5000 {
+        run(&executor, "SCAN 0 COUNT 1").await;
+    }
+    let seen = scan_from(forgotten).await;
+    assert!(stable.iter().filter(|key| !page.contains(key)).all(|key| seen.contains(key)));
+}
+
+#[tokio::test]
+async fn test_scan_from_leading_bytes_keeps_to_count() {
+    let (_dir, executor) = setup();
+    let keys: HashSet<String> = (0..300).map(|i| format!("shared:{:03}", i)).collect();
+    for key in &keys {
+        run(&executor, &format!("SET {} v", key)).await;
+    }
+    
+    // A cursor never handed out, carrying the leading bytes every key shares
+    let mut bytes = [0u8; std::mem::size_of::<usize>()];
+    bytes[..6].copy_from_slice(b"shared");
+    let mut cursor = usize::from_be_bytes(bytes).to_string();
+    let mut seen = HashSet::new();
+    for _ in 0..1000 {
+        let (next, page) = scan_reply(run(&executor, &format!("SCAN {} COUNT 5", cursor)).await);
+        assert!(page.len() <= 50, "page of {} keys", page.len());
+        seen.extend(page);
+        cursor = next;
+        if cursor == "0" {
+            break;
+        }
+    }
+    assert_eq!(cursor, "0");
+    assert_eq!(seen, keys);
+}
@@ -0,0 +1,100 @@
+use diskdb::clock::MockClock;
+use diskdb::protocol::{Request, Response};
+use diskdb::storage::rocksdb_storage::RocksDBStorage;
+use diskdb::{CommandExecutor, EmbeddedDB};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn setup() -> (TempDir, EmbeddedDB, CommandExecutor) {
+    let temp_dir = TempDir::new().unwrap();
+    let db = EmbeddedDB::open(temp_dir.path()).unwrap();
+    let executor = CommandExecutor::new(db.storage().clone());
+    (temp_dir, db, executor)
+}
+
+async fn run(executor: &CommandExecutor, command: &str) -> Response {
+    executor.execute(Request::parse(command).unwrap()).await.unwrap()
+}
+
+fn strings(items: &[&str]) -> Vec<String> {
+    items.iter().map(|s| s.to_string()).collect()
+}
+
+#[tokio::test]
+async fn test_typed_strings_and_lists_match_commands() {
+    let (_dir, db, executor) = setup();
+
+    db.set_string("name", "disk").await.unwrap();
+    assert_eq!(run(&executor, "GET name").await, Response::String(Some("disk".to_string())));
+    run(&executor, "SET greeting hello").await;
+    assert_eq!(db.get_string("greeting").await.unwrap(), Some("hello".to_string()));
+    assert_eq!(db.get_string("missing").await.unwrap(), None);
+
+    // SET through either path clears an expiry
+    run(&executor, "EXPIRE name 100").await;
+    db.set_string("name", "db").await.unwrap();
+    assert_eq!(run(&executor, "TTL name").await, Response::Integer(-1));
+
+    assert_eq!(db.rpush("jobs", strings(&["b", "c"])).await.unwrap(), 2);
+    assert_eq!(db.lpush("jobs", strings(&["a"])).await.unwrap(), 3);
+    assert_eq!(run(&executor, "RPUSH jobs d").await, Response::Integer(4));
+    assert_eq!(db.get_list("jobs").await.unwrap(), strings(&["a", "b", "c", "d"]));
+    assert_eq!(db.lrange("jobs", -2, -1).await.unwrap(), strings(&["c", "d"]));
+    let expected: Vec<Response> = ["a", "b", "c", "d"].iter().map(|s| Response::String(Some(s.to_string()))).collect();
+    assert_eq!(run(&executor, "LRANGE jobs 0 -1").await, Response::Array(expected));
+
+    // Popping the last element deletes the list, as LPOP does
+    assert_eq!(db.lpop("jobs").await.unwrap(), Some("a".to_string()));
+    assert_eq!(db.rpop("jobs").await.unwrap(), Some("d".to_string()));
+    db.lpop("jobs").await.unwrap();
+    db.lpop("jobs").await.unwrap();
+    assert!(!db.exists("jobs").await.unwrap());
+    assert_eq!(run(&executor, "EXISTS jobs").await, Response::Integer(0));
+
+    // Wrong types fail the same way as their commands
+    assert!(matches!(db.lpush("name", strings(&["x"])).await, Err(e) if e.to_string().contains("WRONGTYPE")));
+    assert!(matches!(db.get_list("name").await, Err(e) if e.to_string().contains("WRONGTYPE")));
+
+    assert_eq!(db.delete(&strings(&["name", "greeting", "missing"])).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn test_typed_sets_hashes_and_sorted_sets_match_commands() {
+    let temp_dir = TempDir::new().unwrap();
+    let clock = Arc::new(MockClock::new(1_000_000));
+    let storage = RocksDBStorage::new(temp_dir.path()).unwrap().with_clock(clock.clone());
+    let db = EmbeddedDB::from_storage(Arc::new(storage));
+    let executor = CommandExecutor::new(db.storage().clone());
+
+    assert_eq!(db.sadd("tags", strings(&["a", "b", "a"])).await.unwrap(), 2);
+    assert_eq!(run(&executor, "SADD tags c b").await, Response::Integer(1));
+    let mut members = db.smembers("tags").await.unwrap();
+    members.sort();
+    assert_eq!(members, strings(&["a", "b", "c"]));
+    assert!(db.sismember("tags", "c").await.unwrap());
+    assert!(!db.sismember("tags", "z").await.unwrap());
+
+    assert!(db.hset("user", "name", "ann").await.unwrap());
+    assert!(!db.hset("user", "name", "bob").await.unwrap());
+    run(&executor, "HSET user age 30").await;
+    assert_eq!(db.hget("user", "name").await.unwrap(), Some("bob".to_string()));
+    assert_eq!(run(&executor, "HGET user name").await, Response::String(Some("bob".to_string())));
+    let expected: HashMap<String, String> =
+        [("name", "bob"), ("age", "30")].iter().map(|(f, v)| (f.to_string(), v.to_string())).collect();
+    assert_eq!(db.hgetall("user").await.unwrap(), expected);
+
+    // A field past its TTL is gone on both paths
+    run(&executor, "HSET user temp x").await;
+    run(&executor, "HEXPIRE user 1 FIELDS 1 temp").await;
+    clock.advance(1_000);
+    assert_eq!(db.hget("user", "temp").await.unwrap(), None);
+    assert!(!db.hgetall("user").await.unwrap().contains_key("temp"));
+    assert_eq!(run(&executor, "HGET user temp").await, Response::Null);
+
+    assert_eq!(db.zadd("board", vec![(10.0, "ann".to_string()), (7.5, "bob".to_string())]).await.unwrap(), 2);
+    assert_eq!(db.zscore("board", "bob").await.unwrap(), Some(7.5));
+    assert_eq!(run(&executor, "ZSCORE board ann").await, Response::String(Some("10".to_string())));
+    assert_eq!(db.zscore("board", "cat").await.unwrap(), None);
+    assert!(matches!(db.zscore("tags", "a").await, Err(e) if e.to_string().contains("WRONGTYPE")));
+}
@@ -0,0 +1,55 @@
+use diskdb::glob::glob_match;
+use std::time::{Duration, Instant};
+
+fn matches(pattern: &str, text: &str) -> bool {
+    glob_match(pattern.as_bytes(), text.as_bytes())
+}
+
+#[test]
+fn test_wildcards() {
+    assert!(matches("*", ""));
+    assert!(matches("*", "anything"));
+    assert!(matches("user:*", "user:42"));
+    assert!(matches("*:42", "user:42"));
+    assert!(matches("u*r*2", "user:42"));
+    assert!(!matches("user:*", "users"));
+    assert!(matches("h?llo", "hello"));
+    assert!(!matches("h?llo", "hllo"));
+    assert!(matches("a**b", "ab"));
+    assert!(!matches("", "a"));
+    assert!(matches("", ""));
+}
+
+#[test]
+fn test_classes() {
+    assert!(matches("h[ae]llo", "hallo"));
+    assert!(!matches("h[ae]llo", "hillo"));
+    assert!(matches("h[a-z]llo", "hqllo"));
+    assert!(matches("h[z-a]llo", "hqllo"));
+    assert!(!matches("h[a-z]llo", "hQllo"));
+    assert!(matches("h[^e]llo", "hallo"));
+    assert!(!matches("h[^e]llo", "hello"));
+    assert!(matches("x[\\]]", "x]"));
+    // An unterminated class runs to the end of the pattern
+    assert!(matches("x[ab", "xb"));
+}
+
+#[test]
+fn test_escaped_metacharacters() {
+    assert!(matches("a\\*b", "a*b"));
+    assert!(!matches("a\\*b", "axb"));
+    assert!(matches("a\\?", "a?"));
+    assert!(!matches("a\\?", "ab"));
+    assert!(matches("\\[x]", "[x]"));
+    assert!(matches("a\\", "a\\"));
+}
+
+#[test]
+fn test_pathological_pattern_is_linear() {
+    let text = "a".repeat(10_000);
+    let started = Instant::now();
+    assert!(!matches("a*a*a*a*a*a*a*a*a*a*x", &text));
+    assert!(!matches("*a*a*a*a*a*a*a*a*b*", &text));
+    assert!(matches("*a*a*a*a*a*a*a*a*a", &text));
+    assert!(started.elapsed() < Duration::from_secs(1), "took {:?}", started.elapsed());
+}
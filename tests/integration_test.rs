@@ -1,10 +1,10 @@
-use diskdb::{Config, Server};
+use diskdb::{Config, OptimizedClient, OptimizedServer, Request, Response, Server};
 use diskdb::storage::rocksdb_storage::RocksDBStorage;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::TcpStream;
-use tokio::time::sleep;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpSocket, TcpStream};
+use tokio::time::{sleep, timeout, Instant};
 
 #[tokio::test]
 async fn test_server_basic_operations() {
@@ -139,4 +139,1099 @@ async fn test_multiple_clients() {
     
     // Cleanup
     std::fs::remove_dir_all("./test_db3").ok();
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_active_expiry_reclaims_unread_keys() {
+    let mut config = Config::new();
+    config.server_port = 16383;
+    config.database_path = std::path::PathBuf::from("./test_db4");
+    config.active_expire_interval_ms = 50;
+    
+    let storage = Arc::new(RocksDBStorage::new(&config.database_path).unwrap());
+    let server = Server::new(config, storage).unwrap();
+    
+    tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    sleep(Duration::from_millis(100)).await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16383").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut response = String::new();
+    
+    for i in 0..100 {
+        for command in [format!("SET temp_{} value\n", i), format!("EXPIRE temp_{} 1\n", i)] {
+            writer.write_all(command.as_bytes()).await.unwrap();
+            response.clear();
+            reader.read_line(&mut response).await.unwrap();
+        }
+    }
+    
+    writer.write_all(b"DBSIZE\n").await.unwrap();
+    response.clear();
+    reader.read_line(&mut response).await.unwrap();
+    assert_eq!(response.trim(), "100");
+    
+    // Only DBSIZE is issued from here on, which never touches individual keys
+    sleep(Duration::from_millis(1000)).await;
+    let mut size = String::new();
+    for _ in 0..40 {
+        writer.write_all(b"DBSIZE\n").await.unwrap();
+        size.clear();
+        reader.read_line(&mut size).await.unwrap();
+        if size.trim() == "0" {
+            break;
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(size.trim(), "0");
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db4").ok();
+}
+
+async fn start_optimized_server(port: u16, db_path: &str) {
+    let mut config = Config::new();
+    config.server_port = port;
+    config.database_path = std::path::PathBuf::from(db_path);
+    start_optimized_server_with(config).await;
+}
+
+async fn start_optimized_server_with(config: Config) {
+    let storage = Arc::new(RocksDBStorage::new(&config.database_path).unwrap());
+    let server = OptimizedServer::new(config, storage).unwrap();
+    
+    tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    
+    sleep(Duration::from_millis(100)).await;
+}
+
+async fn roundtrip(
+    writer: &mut tokio::net::tcp::OwnedWriteHalf,
+    reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
+    cmd: &str,
+) -> String {
+    writer.write_all(format!("{}\n", cmd).as_bytes()).await.unwrap();
+    let mut response = String::new();
+    reader.read_line(&mut response).await.unwrap();
+    response.trim().to_string()
+}
+
+#[tokio::test]
+async fn test_client_setname_and_list() {
+    start_optimized_server(16384, "./test_db5").await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16384").await.unwrap();
+    let local_addr = stream.local_addr().unwrap().to_string();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    assert_eq!(roundtrip(&mut writer, &mut reader, "CLIENT GETNAME").await, "(nil)");
+    assert_eq!(roundtrip(&mut writer, &mut reader, "CLIENT SETNAME worker-1").await, "OK");
+    assert_eq!(roundtrip(&mut writer, &mut reader, "CLIENT GETNAME").await, "worker-1");
+    
+    let id = roundtrip(&mut writer, &mut reader, "CLIENT ID").await;
+    assert!(id.parse::<u64>().is_ok());
+    
+    let listing = roundtrip(&mut writer, &mut reader, "CLIENT LIST").await;
+    assert!(listing.contains(&format!("id={}", id)));
+    assert!(listing.contains(&format!("addr={}", local_addr)));
+    assert!(listing.contains("name=worker-1"));
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db5").ok();
+}
+
+#[tokio::test]
+async fn test_client_kill_by_id() {
+    start_optimized_server(16385, "./test_db6").await;
+    
+    let victim = TcpStream::connect("127.0.0.1:16385").await.unwrap();
+    let (victim_reader, mut victim_writer) = victim.into_split();
+    let mut victim_reader = BufReader::new(victim_reader);
+    let victim_id = roundtrip(&mut victim_writer, &mut victim_reader, "CLIENT ID").await;
+    
+    let admin = TcpStream::connect("127.0.0.1:16385").await.unwrap();
+    let (admin_reader, mut admin_writer) = admin.into_split();
+    let mut admin_reader = BufReader::new(admin_reader);
+    let killed = roundtrip(&mut admin_writer, &mut admin_reader, &format!("CLIENT KILL ID {}", victim_id)).await;
+    assert_eq!(killed, "1");
+    
+    // The victim's connection is closed by the server
+    let mut line = String::new();
+    let read = tokio::time::timeout(Duration::from_secs(2), victim_reader.read_line(&mut line)).await;
+    assert_eq!(read.unwrap().unwrap(), 0);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db6").ok();
+}
+
+/// Read a POOL STATS reply into label/value pairs
+async fn pool_stats(
+    writer: &mut tokio::net::tcp::OwnedWriteHalf,
+    reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
+) -> std::collections::HashMap<String, i64> {
+    writer.write_all(b"POOL STATS\n").await.unwrap();
+    let mut lines = Vec::new();
+    while lines.len() < 14 {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        if !line.trim().is_empty() {
+            lines.push(line.trim().to_string());
+        }
+    }
+    lines.chunks(2)
+        .map(|pair| (pair[0].clone(), pair[1].parse().unwrap()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_pool_stats_track_buffer_reuse() {
+    start_optimized_server(16386, "./test_db7").await;
+    
+    let admin = TcpStream::connect("127.0.0.1:16386").await.unwrap();
+    let (admin_reader, mut admin_writer) = admin.into_split();
+    let mut admin_reader = BufReader::new(admin_reader);
+    let before = pool_stats(&mut admin_writer, &mut admin_reader).await;
+    assert!(before["connected_clients"] >= 1);
+    
+    // Each connection borrows a response buffer and hands it back on close
+    for _ in 0..3 {
+        let stream = TcpStream::connect("127.0.0.1:16386").await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        assert_eq!(roundtrip(&mut writer, &mut reader, "PING").await, "PONG");
+        drop(writer);
+        sleep(Duration::from_millis(50)).await;
+    }
+    
+    let after = pool_stats(&mut admin_writer, &mut admin_reader).await;
+    let requests = |s: &std::collections::HashMap<String, i64>| s["buffer_pool_hits"] + s["buffer_pool_misses"];
+    assert!(requests(&after) >= requests(&before) + 3);
+    assert!(after["buffer_pool_hits"] > before["buffer_pool_hits"]);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db7").ok();
+}
+
+#[tokio::test]
+async fn test_large_pipeline_flushes_in_order() {
+    let mut config = Config::new();
+    config.server_port = 16388;
+    config.database_path = std::path::PathBuf::from("./test_db8");
+    config.max_pipeline_response_bytes = 4096;
+    start_optimized_server_with(config).await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16388").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    // 100 values of ~600 bytes is far more than the 4KB response cap
+    let value = |i: usize| format!("{}-{}", i, "x".repeat(600));
+    let sets: String = (0..100).map(|i| format!("SET pipe{} {}\n", i, value(i))).collect();
+    writer.write_all(sets.as_bytes()).await.unwrap();
+    for _ in 0..100 {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line.trim(), "OK");
+    }
+    
+    let gets: String = (0..100).map(|i| format!("GET pipe{}\n", i)).collect();
+    writer.write_all(gets.as_bytes()).await.unwrap();
+    for i in 0..100 {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line.trim(), value(i));
+    }
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db8").ok();
+}
+
+/// Read `count` non-blank lines, e.g. the items of an array reply
+async fn read_lines(reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>, count: usize) -> Vec<String> {
+    let mut lines = Vec::with_capacity(count);
+    while lines.len() < count {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        if !line.trim().is_empty() {
+            lines.push(line.trim().to_string());
+        }
+    }
+    lines
+}
+
+#[tokio::test]
+async fn test_request_response_client_gets_each_reply_before_sending_more() {
+    start_optimized_server(16413, "./test_db24").await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16413").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let mut reply = String::new();
+    
+    // More commands than one pipeline holds, each sent only after the last reply
+    for i in 0..150 {
+        writer.write_all(format!("SET key{} {}\n", i, i).as_bytes()).await.unwrap();
+        reply.clear();
+        tokio::time::timeout(Duration::from_secs(2), reader.read_line(&mut reply)).await.unwrap().unwrap();
+        assert_eq!(reply.trim(), "OK", "reply to SET #{}", i);
+    }
+    
+    // The start of the next request is already buffered, but not all of it
+    writer.write_all(b"SET first 1\nSET sec").await.unwrap();
+    reply.clear();
+    tokio::time::timeout(Duration::from_secs(2), reader.read_line(&mut reply)).await.unwrap().unwrap();
+    assert_eq!(reply.trim(), "OK");
+    writer.write_all(b"ond 2\n").await.unwrap();
+    reply.clear();
+    tokio::time::timeout(Duration::from_secs(2), reader.read_line(&mut reply)).await.unwrap().unwrap();
+    assert_eq!(reply.trim(), "OK");
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db24").ok();
+}
+
+#[tokio::test]
+async fn test_pipeline_replies_match_requests_one_to_one() {
+    start_optimized_server(16414, "./test_db25").await;
+    let mut client = OptimizedClient::connect("127.0.0.1:16414").await.unwrap();
+    // Array replies span several lines in the text protocol
+    client.set_resp3(true);
+    
+    let text = |s: &str| Response::String(Some(s.to_string()));
+    let requests = vec![
+        Request::Set { key: "name".to_string(), value: "disk".to_string(), options: Default::default() },
+        Request::RPush { key: "list".to_string(), values: vec!["a".to_string(), "b".to_string(), "c".to_string()] },
+        // Serialized as a bare GET, which the server rejects as unparseable
+        Request::Get { key: String::new() },
+        Request::LRange { key: "list".to_string(), start: 0, stop: -1 },
+        Request::LPush { key: "name".to_string(), values: vec!["x".to_string()] },
+        Request::LRange { key: "missing".to_string(), start: 0, stop: -1 },
+        Request::Get { key: "name".to_string() },
+    ];
+    let replies = client.execute_pipeline(requests).await.unwrap();
+    
+    assert_eq!(replies.len(), 7);
+    assert_eq!(replies[0], Response::Ok);
+    assert_eq!(replies[1], Response::Integer(3));
+    assert!(matches!(&replies[2], Response::Error(_)), "{:?}", replies[2]);
+    assert_eq!(replies[3], Response::Array(vec![text("a"), text("b"), text("c")]));
+    assert!(matches!(&replies[4], Response::Error(e) if e.contains("WRONGTYPE")), "{:?}", replies[4]);
+    assert_eq!(replies[5], Response::Array(vec![]));
+    assert_eq!(replies[6], text("disk"));
+    
+    // Single commands on the same pooled connections get their own replies
+    assert_eq!(client.execute(Request::LLen { key: "list".to_string() }).await.unwrap(), Response::Integer(3));
+    assert_eq!(client.get("name").await.unwrap(), Some("disk".to_string()));
+    assert!(client.ping().await.unwrap());
+    
+    // get_or falls back without storing the default
+    assert_eq!(client.get_or("name", "anon").await.unwrap(), "disk");
+    assert_eq!(client.get_or("nobody", "anon").await.unwrap(), "anon");
+    assert_eq!(client.get("nobody").await.unwrap(), None);
+    
+    // Concurrent commands batched into one implicit pipeline each get their own reply
+    client.set_max_pipeline_size(4);
+    let client = std::sync::Arc::new(client);
+    for i in 0..10 {
+        client.set(&format!("key{}", i), &format!("value{}", i)).await.unwrap();
+    }
+    let gets: Vec<_> = (0..10).map(|i| {
+        let client = client.clone();
+        tokio::spawn(async move { client.get(&format!("key{}", i)).await.unwrap() })
+    }).collect();
+    for (i, get) in gets.into_iter().enumerate() {
+        assert_eq!(get.await.unwrap(), Some(format!("value{}", i)));
+    }
+    
+    // Without pipelining each command goes out on its own
+    let mut client = std::sync::Arc::try_unwrap(client).ok().unwrap();
+    client.set_pipeline_enabled(false);
+    assert_eq!(client.get("key3").await.unwrap(), Some("value3".to_string()));
+    client.close().await.unwrap();
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db25").ok();
+}
+
+#[tokio::test]
+async fn test_subscriber_mode_restricts_commands() {
+    start_optimized_server(16389, "./test_db9").await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16389").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    let publisher = TcpStream::connect("127.0.0.1:16389").await.unwrap();
+    let (pub_reader, mut pub_writer) = publisher.into_split();
+    let mut pub_reader = BufReader::new(pub_reader);
+    
+    // Plain commands are answered once the pipeline is flushed, which PING forces
+    pub_writer.write_all(b"SET greeting hello\nPING\n").await.unwrap();
+    assert_eq!(read_lines(&mut pub_reader, 2).await, vec!["OK", "PONG"]);
+    
+    writer.write_all(b"SUBSCRIBE news\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 3).await, vec!["subscribe", "news", "1"]);
+    
+    writer.write_all(b"GET greeting\n").await.unwrap();
+    assert_eq!(
+        read_lines(&mut reader, 1).await[0],
+        "ERROR: ERR Can't execute 'get': only (P)SUBSCRIBE / (P)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context"
+    );
+    writer.write_all(b"PING\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["PONG"]);
+    
+    // Published messages are pushed without a request
+    pub_writer.write_all(b"PUBLISH news extra extra\nPING\n").await.unwrap();
+    assert_eq!(read_lines(&mut pub_reader, 2).await, vec!["1", "PONG"]);
+    assert_eq!(read_lines(&mut reader, 3).await, vec!["message", "news", "extra extra"]);
+    
+    writer.write_all(b"UNSUBSCRIBE\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 3).await, vec!["unsubscribe", "news", "0"]);
+    
+    writer.write_all(b"GET greeting\nPING\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 2).await, vec!["hello", "PONG"]);
+    pub_writer.write_all(b"PUBLISH news again\nPING\n").await.unwrap();
+    assert_eq!(read_lines(&mut pub_reader, 2).await, vec!["0", "PONG"]);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db9").ok();
+}
+
+#[tokio::test]
+async fn test_reset_clears_connection_state() {
+    start_optimized_server(16398, "./test_db10").await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16398").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    writer.write_all(b"CLIENT SETNAME worker\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["OK"]);
+    writer.write_all(b"SUBSCRIBE news\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 3).await, vec!["subscribe", "news", "1"]);
+    
+    // RESET is accepted in subscriber mode and leaves it
+    writer.write_all(b"RESET\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["RESET"]);
+    
+    writer.write_all(b"SET after reset\nGET after\nPING\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 3).await, vec!["OK", "reset", "PONG"]);
+    writer.write_all(b"CLIENT GETNAME\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["(nil)"]);
+    writer.write_all(b"PUBLISH news hello\nPING\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 2).await, vec!["0", "PONG"]);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db10").ok();
+}
+
+#[tokio::test]
+async fn test_hello_handshake() {
+    start_optimized_server(16399, "./test_db11").await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16399").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    writer.write_all(b"CLIENT ID\n").await.unwrap();
+    let id = read_lines(&mut reader, 1).await.remove(0);
+    
+    writer.write_all(b"HELLO 2\n").await.unwrap();
+    let fields = read_lines(&mut reader, 12).await;
+    let value = |name: &str| {
+        let pos = fields.iter().position(|f| f == name).unwrap();
+        fields[pos + 1].clone()
+    };
+    assert_eq!(value("server"), "diskdb");
+    assert_eq!(value("proto"), "2");
+    assert_eq!(value("id"), id);
+    
+    writer.write_all(b"HELLO 4\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["ERROR: NOPROTO unsupported protocol version 4"]);
+    writer.write_all(b"HELLO 2 AUTH default secret\n").await.unwrap();
+    assert!(read_lines(&mut reader, 1).await[0].starts_with("ERROR: ERR AUTH"));
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db11").ok();
+}
+
+#[tokio::test]
+async fn test_idle_connections_are_closed() {
+    let mut config = Config::new();
+    config.server_port = 16401;
+    config.database_path = std::path::PathBuf::from("./test_db12");
+    config.idle_timeout_ms = 200;
+    start_optimized_server_with(config).await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16401").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    // Activity keeps resetting the idle clock
+    for _ in 0..3 {
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(roundtrip(&mut writer, &mut reader, "PING").await, "PONG");
+    }
+    
+    // Staying silent past the limit gets a final error, then EOF
+    sleep(Duration::from_millis(400)).await;
+    let mut line = String::new();
+    reader.read_line(&mut line).await.unwrap();
+    assert!(line.contains("idle timeout"), "unexpected goodbye {:?}", line);
+    line.clear();
+    assert_eq!(reader.read_line(&mut line).await.unwrap(), 0);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db12").ok();
+}
+
+/// Read until the server closes the connection, returning the bytes received
+async fn read_until_closed(stream: &mut TcpStream) -> usize {
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut total = 0;
+    loop {
+        match stream.read(&mut buf).await {
+            Ok(0) | Err(_) => return total,
+            Ok(n) => total += n,
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_slow_reader_is_closed_past_output_buffer_limits() {
+    let mut config = Config::new();
+    config.server_port = 16415;
+    config.database_path = std::path::PathBuf::from("./test_db26");
+    config.client_output_buffer_soft_limit = 64 * 1024;
+    config.client_output_buffer_soft_ms = 200;
+    config.client_output_buffer_hard_limit = 4 * 1024 * 1024;
+    start_optimized_server_with(config).await;
+    
+    let value = "x".repeat(100 * 1024);
+    let stream = TcpStream::connect("127.0.0.1:16415").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    assert_eq!(roundtrip(&mut writer, &mut reader, &format!("SET big {}", value)).await, "OK");
+    assert_eq!(roundtrip(&mut writer, &mut reader, &format!("SET huge {}", value.repeat(50))).await, "OK");
+    
+    // Ask for 20MB of replies through a tiny receive window and stop reading
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.set_recv_buffer_size(4096).unwrap();
+    let mut slow = socket.connect("127.0.0.1:16415".parse().unwrap()).await.unwrap();
+    let requests = 200;
+    slow.write_all("GET big\r\n".repeat(requests).as_bytes()).await.unwrap();
+    let started = Instant::now();
+    sleep(Duration::from_millis(1000)).await;
+    
+    // The server gave up long before its write timeout, without sending everything
+    let received = timeout(Duration::from_secs(5), read_until_closed(&mut slow)).await
+        .expect("slow reader was not disconnected");
+    assert!(received < requests * value.len(), "received all {} bytes", received);
+    assert!(started.elapsed() < Duration::from_secs(6));
+    
+    // A single reply past the hard limit closes the connection without being sent
+    let mut greedy = TcpStream::connect("127.0.0.1:16415").await.unwrap();
+    greedy.write_all(b"GET huge\r\n").await.unwrap();
+    let received = timeout(Duration::from_secs(5), read_until_closed(&mut greedy)).await.unwrap();
+    assert_eq!(received, 0);
+    
+    // Clients within the limits are unaffected
+    assert_eq!(roundtrip(&mut writer, &mut reader, "GET big").await, value);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db26").ok();
+}
+
+#[tokio::test]
+async fn test_oversized_line_is_rejected_without_closing() {
+    let mut config = Config::new();
+    config.server_port = 16403;
+    config.database_path = std::path::PathBuf::from("./test_db14");
+    config.max_value_size = 1024;
+    start_optimized_server_with(config).await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16403").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    // Far past the line limit, so the server must discard it rather than buffer it
+    let huge = format!("SET big {}", "x".repeat(1024 * 1024));
+    let reply = roundtrip(&mut writer, &mut reader, &huge).await;
+    assert!(reply.contains("max-value-size"), "unexpected reply {:?}", reply);
+    
+    // The connection stays usable afterwards
+    assert_eq!(roundtrip(&mut writer, &mut reader, "PING").await, "PONG");
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db14").ok();
+}
+
+#[tokio::test]
+async fn test_crlf_and_unterminated_final_command() {
+    let mut config = Config::new();
+    config.server_port = 16404;
+    config.database_path = std::path::PathBuf::from("./test_db15");
+    start_optimized_server_with(config).await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16404").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    // CRLF terminators and a quoted value; the last command has no newline at all
+    writer.write_all(b"SET greeting \"hello world\"\r\nGET greeting\r\nPING").await.unwrap();
+    writer.shutdown().await.unwrap();
+    
+    let mut replies = Vec::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line).await.unwrap() > 0 {
+        replies.push(line.trim_end().to_string());
+        line.clear();
+    }
+    assert_eq!(replies, vec!["OK", "hello world", "PONG"]);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db15").ok();
+}
+
+#[tokio::test]
+async fn test_blpop_receives_push_from_another_client() {
+    let mut config = Config::new();
+    config.server_port = 16405;
+    config.database_path = std::path::PathBuf::from("./test_db16");
+    start_optimized_server_with(config).await;
+    
+    let consumer = TcpStream::connect("127.0.0.1:16405").await.unwrap();
+    let (reader, mut writer) = consumer.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"BLPOP jobs 5\n").await.unwrap();
+    
+    // Let the consumer block before anything is pushed
+    sleep(Duration::from_millis(200)).await;
+    let mut producer = TcpStream::connect("127.0.0.1:16405").await.unwrap();
+    producer.write_all(b"RPUSH jobs work\n").await.unwrap();
+    let mut pushed = [0u8; 64];
+    let n = producer.read(&mut pushed).await.unwrap();
+    assert_eq!(String::from_utf8_lossy(&pushed[..n]).trim(), "1");
+    
+    let mut reply = Vec::new();
+    let mut line = String::new();
+    while reply.len() < 2 {
+        line.clear();
+        tokio::time::timeout(Duration::from_secs(2), reader.read_line(&mut line)).await.unwrap().unwrap();
+        if !line.trim().is_empty() {
+            reply.push(line.trim().to_string());
+        }
+    }
+    assert_eq!(reply, vec!["jobs", "work"]);
+    
+    // The element went to the blocked client, not back into the list
+    producer.write_all(b"LLEN jobs\n").await.unwrap();
+    let n = producer.read(&mut pushed).await.unwrap();
+    assert_eq!(String::from_utf8_lossy(&pushed[..n]).trim(), "0");
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db16").ok();
+}
+
+#[tokio::test]
+async fn test_blpop_of_departed_client_leaves_pushes_in_the_list() {
+    start_optimized_server(16421, "./test_db32").await;
+    
+    // One consumer disconnects while blocked, another is killed
+    let mut gone = TcpStream::connect("127.0.0.1:16421").await.unwrap();
+    gone.write_all(b"BLPOP jobs 0\n").await.unwrap();
+    let killed = TcpStream::connect("127.0.0.1:16421").await.unwrap();
+    let (killed_reader, mut killed_writer) = killed.into_split();
+    let mut killed_reader = BufReader::new(killed_reader);
+    let killed_id = roundtrip(&mut killed_writer, &mut killed_reader, "CLIENT ID").await;
+    killed_writer.write_all(b"BLPOP jobs 0\n").await.unwrap();
+    sleep(Duration::from_millis(200)).await;
+    drop(gone);
+    
+    let producer = TcpStream::connect("127.0.0.1:16421").await.unwrap();
+    let (reader, mut writer) = producer.into_split();
+    let mut reader = BufReader::new(reader);
+    assert_eq!(roundtrip(&mut writer, &mut reader, &format!("CLIENT KILL ID {}", killed_id)).await, "1");
+    sleep(Duration::from_millis(200)).await;
+    
+    assert_eq!(roundtrip(&mut writer, &mut reader, "RPUSH jobs work").await, "1");
+    assert_eq!(roundtrip(&mut writer, &mut reader, "LLEN jobs").await, "1");
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db32").ok();
+}
+
+#[tokio::test]
+async fn test_bzpopmin_receives_zadd_from_another_client() {
+    let mut config = Config::new();
+    config.server_port = 16412;
+    config.database_path = std::path::PathBuf::from("./test_db23");
+    start_optimized_server_with(config).await;
+    
+    let consumer = TcpStream::connect("127.0.0.1:16412").await.unwrap();
+    let (reader, mut writer) = consumer.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"BZPOPMIN schedule 5\n").await.unwrap();
+    
+    // Let the consumer block before any job is scheduled
+    sleep(Duration::from_millis(200)).await;
+    let mut producer = TcpStream::connect("127.0.0.1:16412").await.unwrap();
+    producer.write_all(b"ZADD schedule 300 later 100 soon\n").await.unwrap();
+    let mut added = [0u8; 64];
+    let n = producer.read(&mut added).await.unwrap();
+    assert_eq!(String::from_utf8_lossy(&added[..n]).trim(), "2");
+    
+    let mut reply = Vec::new();
+    let mut line = String::new();
+    while reply.len() < 3 {
+        line.clear();
+        tokio::time::timeout(Duration::from_secs(2), reader.read_line(&mut line)).await.unwrap().unwrap();
+        if !line.trim().is_empty() {
+            reply.push(line.trim().to_string());
+        }
+    }
+    assert_eq!(reply, vec!["schedule", "soon", "100"]);
+    
+    // Only the lowest-scored member was taken
+    producer.write_all(b"ZCARD schedule\n").await.unwrap();
+    let n = producer.read(&mut added).await.unwrap();
+    assert_eq!(String::from_utf8_lossy(&added[..n]).trim(), "1");
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db23").ok();
+}
+
+#[tokio::test]
+async fn test_hello_3_switches_replies_to_resp3() {
+    start_optimized_server(16406, "./test_db17").await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16406").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    writer.write_all(b"HELLO 3\n").await.unwrap();
+    let hello = read_lines(&mut reader, 23).await;
+    assert_eq!(hello[0], "%6");
+    let proto = hello.iter().position(|l| l == "proto").unwrap();
+    assert_eq!(hello[proto + 1], ":3");
+    
+    writer.write_all(b"HSET h field value\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec![":1"]);
+    writer.write_all(b"HGETALL h\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 5).await, vec!["%1", "$5", "field", "$5", "value"]);
+    writer.write_all(b"GET missing\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["_"]);
+    
+    // Subscription confirmations and messages arrive as pushes
+    writer.write_all(b"SUBSCRIBE news\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 6).await, vec![">3", "$9", "subscribe", "$4", "news", ":1"]);
+    let mut publisher = TcpStream::connect("127.0.0.1:16406").await.unwrap();
+    publisher.write_all(b"PUBLISH news hi\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 7).await, vec![">3", "$7", "message", "$4", "news", "$2", "hi"]);
+    
+    // RESET and HELLO 2 both return to the line protocol
+    writer.write_all(b"RESET\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["RESET"]);
+    writer.write_all(b"HELLO 3\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 23).await[0], "%6");
+    writer.write_all(b"HELLO 2\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 2).await, vec!["server", "diskdb"]);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db17").ok();
+}
+
+#[tokio::test]
+async fn test_client_tracking_invalidation() {
+    start_optimized_server(16407, "./test_db18").await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16407").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    // Tracking needs RESP3 pushes
+    writer.write_all(b"CLIENT TRACKING ON\n").await.unwrap();
+    assert!(read_lines(&mut reader, 1).await[0].starts_with("ERROR: ERR CLIENT TRACKING requires RESP3"));
+    
+    writer.write_all(b"HELLO 3\n").await.unwrap();
+    read_lines(&mut reader, 23).await;
+    writer.write_all(b"CLIENT TRACKING ON\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["+OK"]);
+    writer.write_all(b"GET cached\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["_"]);
+    
+    let mut other = TcpStream::connect("127.0.0.1:16407").await.unwrap();
+    other.write_all(b"SET cached fresh\n").await.unwrap();
+    let expected = vec![">2", "$10", "invalidate", "*1", "$6", "cached"];
+    let pushed = tokio::time::timeout(Duration::from_secs(2), read_lines(&mut reader, 6)).await.unwrap();
+    assert_eq!(pushed, expected);
+    
+    // The key is forgotten until it is read again
+    other.write_all(b"SET cached again\n").await.unwrap();
+    assert!(tokio::time::timeout(Duration::from_millis(200), read_lines(&mut reader, 1)).await.is_err());
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db18").ok();
+}
+
+#[tokio::test]
+async fn test_tracking_client_outlives_idle_timeout() {
+    let mut config = Config::new();
+    config.server_port = 16422;
+    config.database_path = std::path::PathBuf::from("./test_db33");
+    config.idle_timeout_ms = 200;
+    start_optimized_server_with(config).await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16422").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"HELLO 3\n").await.unwrap();
+    read_lines(&mut reader, 23).await;
+    writer.write_all(b"CLIENT TRACKING ON\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["+OK"]);
+    writer.write_all(b"GET cached\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["_"]);
+    
+    // Waiting on invalidations is not idling
+    sleep(Duration::from_millis(400)).await;
+    let mut other = TcpStream::connect("127.0.0.1:16422").await.unwrap();
+    other.write_all(b"SET cached fresh\n").await.unwrap();
+    let expected = vec![">2", "$10", "invalidate", "*1", "$6", "cached"];
+    let pushed = tokio::time::timeout(Duration::from_secs(2), read_lines(&mut reader, 6)).await.unwrap();
+    assert_eq!(pushed, expected);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db33").ok();
+}
+
+#[tokio::test]
+async fn test_multibulk_request_split_across_segments() {
+    start_optimized_server(16408, "./test_db19").await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16408").await.unwrap();
+    stream.set_nodelay(true).unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    let request = b"*3\r\n$3\r\nSET\r\n$5\r\nsplit\r\n$11\r\nhello world\r\n";
+    for chunk in request.chunks(3) {
+        writer.write_all(chunk).await.unwrap();
+        writer.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["OK"]);
+    
+    // A multibulk request and a text line in the same segment
+    writer.write_all(b"*2\r\n$3\r\nGET\r\n$5\r\nsplit\r\nEXISTS split\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 2).await, vec!["hello world", "1"]);
+    
+    // A bogus length closes the connection at once
+    writer.write_all(b"*1\r\n$999999999999\r\n").await.unwrap();
+    let reply = tokio::time::timeout(Duration::from_secs(2), read_lines(&mut reader, 1)).await.unwrap();
+    assert!(reply[0].contains("invalid bulk length"), "{:?}", reply);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db19").ok();
+}
+
+#[tokio::test]
+async fn test_pubsub_introspection() {
+    start_optimized_server(16409, "./test_db20").await;
+    
+    let mut subscribers = Vec::new();
+    for subscription in ["SUBSCRIBE news\n", "SUBSCRIBE news sports\n"] {
+        let stream = TcpStream::connect("127.0.0.1:16409").await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        writer.write_all(subscription.as_bytes()).await.unwrap();
+        let confirmations = subscription.split_whitespace().count() - 1;
+        read_lines(&mut reader, confirmations * 3).await;
+        subscribers.push((reader, writer));
+    }
+    let (pattern_reader, mut pattern_writer) = TcpStream::connect("127.0.0.1:16409").await.unwrap().into_split();
+    let mut pattern_reader = BufReader::new(pattern_reader);
+    pattern_writer.write_all(b"PSUBSCRIBE n*\n").await.unwrap();
+    read_lines(&mut pattern_reader, 3).await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16409").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    writer.write_all(b"PUBSUB NUMSUB news sports weather\nPING\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 7).await, vec!["news", "2", "sports", "1", "weather", "0", "PONG"]);
+    writer.write_all(b"PUBSUB CHANNELS\nPUBSUB CHANNELS n*\nPUBSUB NUMPAT\nPING\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 5).await, vec!["news", "sports", "news", "1", "PONG"]);
+    
+    // Channels disappear with their last subscriber
+    drop(subscribers);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    writer.write_all(b"PUBSUB NUMSUB news\nPING\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 3).await, vec!["news", "0", "PONG"]);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db20").ok();
+}
+
+#[tokio::test]
+async fn test_quit_closes_after_replying() {
+    start_optimized_server(16410, "./test_db21").await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16410").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    // Requests pipelined after QUIT are not run
+    writer.write_all(b"PING\nQUIT\nSET after quit\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 2).await, vec!["PONG", "OK"]);
+    let mut rest = String::new();
+    let closed = tokio::time::timeout(Duration::from_secs(2), reader.read_line(&mut rest)).await.unwrap().unwrap();
+    assert_eq!(closed, 0, "expected EOF, got {:?}", rest);
+    
+    // QUIT also ends a subscription
+    let stream = TcpStream::connect("127.0.0.1:16410").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"SUBSCRIBE news\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 3).await, vec!["subscribe", "news", "1"]);
+    writer.write_all(b"QUIT\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["OK"]);
+    let mut rest = String::new();
+    let closed = tokio::time::timeout(Duration::from_secs(2), reader.read_line(&mut rest)).await.unwrap().unwrap();
+    assert_eq!(closed, 0, "expected EOF, got {:?}", rest);
+    
+    let stream = TcpStream::connect("127.0.0.1:16410").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"EXISTS after\nPUBSUB NUMSUB news\nPING\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 4).await, vec!["0", "news", "0", "PONG"]);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db21").ok();
+}
+
+/// Whether the server answers a PING on a fresh connection, false if it hangs up
+async fn ping_accepted(port: u16) -> bool {
+    let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    if writer.write_all(b"PING\n").await.is_err() {
+        return false;
+    }
+    let mut response = String::new();
+    match tokio::time::timeout(Duration::from_secs(2), reader.read_line(&mut response)).await {
+        Ok(Ok(_)) => response.trim() == "PONG",
+        _ => false,
+    }
+}
+
+#[tokio::test]
+async fn test_connection_rate_limit_per_ip() {
+    let mut config = Config::new();
+    config.server_port = 16411;
+    config.database_path = std::path::PathBuf::from("./test_db22");
+    config.max_connections_per_sec = 5;
+    config.connection_burst = 3;
+    start_optimized_server_with(config).await;
+    
+    // A flood gets the burst and is then refused
+    let mut accepted = 0;
+    for _ in 0..10 {
+        if ping_accepted(16411).await {
+            accepted += 1;
+        }
+    }
+    assert!((3..=4).contains(&accepted), "accepted {} of 10", accepted);
+    
+    // A connector staying under the rate is always let in
+    for _ in 0..3 {
+        sleep(Duration::from_millis(300)).await;
+        assert!(ping_accepted(16411).await);
+    }
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db22").ok();
+}
+
+#[tokio::test]
+async fn test_monitor_streams_commands_from_other_clients() {
+    start_optimized_server(16416, "./test_db27").await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16416").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"MONITOR\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 1).await, vec!["OK"]);
+    
+    let mut other = TcpStream::connect("127.0.0.1:16416").await.unwrap();
+    let other_addr = other.local_addr().unwrap();
+    other.write_all(b"SET greeting \"hello world\"\n").await.unwrap();
+    other.write_all(b"*3\r\n$3\r\nSET\r\n$3\r\nbin\r\n$3\r\na\"\n\r\n").await.unwrap();
+    
+    let fed = tokio::time::timeout(Duration::from_secs(2), read_lines(&mut reader, 2)).await.unwrap();
+    let expected = [
+        format!("[0 {}] \"SET\" \"greeting\" \"hello world\"", other_addr),
+        format!("[0 {}] \"SET\" \"bin\" \"a\\\"\\n\"", other_addr),
+    ];
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs_f64();
+    for (line, expected) in fed.iter().zip(&expected) {
+        let (timestamp, command) = line.split_once(' ').unwrap();
+        assert!((now - timestamp.parse::<f64>().unwrap()).abs() < 60.0, "{}", line);
+        assert_eq!(command, expected);
+    }
+    
+    // Monitoring takes over the connection until RESET
+    writer.write_all(b"GET greeting\n").await.unwrap();
+    assert_eq!(
+        read_lines(&mut reader, 1).await,
+        vec!["ERROR: ERR Can't execute 'get': only QUIT / RESET are allowed while monitoring"]
+    );
+    writer.write_all(b"RESET\nGET greeting\n").await.unwrap();
+    assert_eq!(read_lines(&mut reader, 2).await, vec!["RESET", "hello world"]);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db27").ok();
+}
+
+#[tokio::test]
+async fn test_protocol_length_limits() {
+    let mut config = Config::new();
+    config.server_port = 16417;
+    config.database_path = std::path::PathBuf::from("./test_db28");
+    config.proto_max_bulk_len = 1024;
+    config.proto_max_multibulk_len = 8;
+    start_optimized_server_with(config).await;
+    
+    let stream = TcpStream::connect("127.0.0.1:16417").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    
+    // A text line with too many arguments is refused, but the line protocol can carry on
+    let reply = roundtrip(&mut writer, &mut reader, "DEL a b c d e f g h").await;
+    assert!(reply.contains("more than 8 arguments"), "unexpected reply {:?}", reply);
+    assert_eq!(roundtrip(&mut writer, &mut reader, "DEL a b c d e f g").await, "0");
+    
+    // Oversized RESP headers close the connection before any payload is read
+    for header in [&b"*100000000\r\n"[..], b"*1\r\n$999999999\r\n", b"*2\r\n$1025\r\n"] {
+        let mut stream = TcpStream::connect("127.0.0.1:16417").await.unwrap();
+        stream.write_all(header).await.unwrap();
+        let mut reply = Vec::new();
+        timeout(Duration::from_secs(2), stream.read_to_end(&mut reply)).await.unwrap().unwrap();
+        let reply = String::from_utf8(reply).unwrap();
+        assert!(reply.contains("invalid"), "unexpected reply {:?}", reply);
+    }
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db28").ok();
+}
+
+#[tokio::test]
+async fn test_servers_apply_custom_socket_options() {
+    let tuned = |port: u16, db_path: &str| {
+        let mut config = Config::new();
+        config.server_port = port;
+        config.database_path = std::path::PathBuf::from(db_path);
+        config.tcp_backlog = 16;
+        config.so_rcvbuf = 64 * 1024;
+        config.so_sndbuf = 32 * 1024;
+        config.tcp_keepalive_secs = 0;
+        config.tcp_nodelay = false;
+        config
+    };
+    
+    let config = tuned(16418, "./test_db29");
+    let storage = Arc::new(RocksDBStorage::new(&config.database_path).unwrap());
+    let server = Server::new(config, storage).unwrap();
+    tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    start_optimized_server_with(tuned(16419, "./test_db30")).await;
+    
+    // Both servers bind with the options and serve requests over tuned connections
+    for port in [16418, 16419] {
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        assert_eq!(roundtrip(&mut writer, &mut reader, "SET tuned yes").await, "OK");
+        assert_eq!(roundtrip(&mut writer, &mut reader, "GET tuned").await, "yes");
+    }
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db29").ok();
+    std::fs::remove_dir_all("./test_db30").ok();
+}
+
+#[tokio::test]
+async fn test_slow_subscriber_does_not_hold_up_publishers() {
+    let mut config = Config::new();
+    config.server_port = 16420;
+    config.database_path = std::path::PathBuf::from("./test_db31");
+    config.so_sndbuf = 16 * 1024;
+    config.pubsub_queue_limit = 16;
+    start_optimized_server_with(config).await;
+    
+    // A subscriber that stops reading, through a tiny receive window
+    let socket = TcpSocket::new_v4().unwrap();
+    socket.set_recv_buffer_size(4096).unwrap();
+    let (slow_reader, mut slow_writer) = socket.connect("127.0.0.1:16420".parse().unwrap()).await.unwrap().into_split();
+    let mut slow_reader = BufReader::new(slow_reader);
+    let slow_id = roundtrip(&mut slow_writer, &mut slow_reader, "CLIENT ID").await;
+    slow_writer.write_all(b"SUBSCRIBE news\n").await.unwrap();
+    read_lines(&mut slow_reader, 3).await;
+    
+    // ...and one that keeps up, reading on its own task
+    let (fast_reader, mut fast_writer) = TcpStream::connect("127.0.0.1:16420").await.unwrap().into_split();
+    let mut fast_reader = BufReader::new(fast_reader);
+    fast_writer.write_all(b"SUBSCRIBE news\n").await.unwrap();
+    read_lines(&mut fast_reader, 3).await;
+    let count = 300;
+    let payload = "x".repeat(16 * 1024);
+    let fast = tokio::spawn(async move { read_lines(&mut fast_reader, count * 3).await });
+    
+    let (reader, mut writer) = TcpStream::connect("127.0.0.1:16420").await.unwrap().into_split();
+    let mut reader = BufReader::new(reader);
+    let published = timeout(Duration::from_secs(5), async {
+        for i in 0..count {
+            let reply = roundtrip(&mut writer, &mut reader, &format!("PUBLISH news {}-{}", i, payload)).await;
+            // Dropping the slow subscriber's oldest messages still counts as delivered
+            assert_eq!(reply, "2", "message {}", i);
+        }
+    }).await;
+    assert!(published.is_ok(), "the publisher was held up");
+    
+    let lines = timeout(Duration::from_secs(5), fast).await.expect("the fast subscriber was held up").unwrap();
+    for (i, message) in lines.chunks(3).enumerate() {
+        assert_eq!(message[2], format!("{}-{}", i, payload));
+    }
+    
+    // The slow subscriber holds no more than its limit, and the rest shows as lag
+    writer.write_all(b"CLIENT LIST\nPING\n").await.unwrap();
+    let mut listing = Vec::new();
+    loop {
+        let line = read_lines(&mut reader, 1).await.remove(0);
+        if line == "PONG" {
+            break;
+        }
+        listing.push(line);
+    }
+    let slow_entry = listing.iter().find(|line| line.starts_with(&format!("id={} ", slow_id))).unwrap();
+    let field = |name: &str| -> usize {
+        slow_entry.split(' ')
+            .find_map(|field| field.strip_prefix(&format!("{}=", name)))
+            .unwrap()
+            .parse()
+            .unwrap()
+    };
+    assert_eq!(field("pubsub-queued"), 16);
+    assert!(field("pubsub-dropped") > 0, "{}", slow_entry);
+    assert!(field("pubsub-dropped") < count, "{}", slow_entry);
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db31").ok();
+}
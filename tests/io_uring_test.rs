@@ -0,0 +1,40 @@
+#![cfg(all(target_os = "linux", feature = "io_uring"))]
+
+use diskdb::commands::CommandExecutor;
+use diskdb::network::io_uring_server::IoUringServer;
+use diskdb::storage::rocksdb_storage::RocksDBStorage;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_command_split_across_segments() {
+    let storage = Arc::new(RocksDBStorage::new("./test_db_uring").unwrap());
+    let executor = Arc::new(CommandExecutor::new(storage));
+    let server = IoUringServer::new("127.0.0.1:16387", executor).unwrap();
+    thread::spawn(move || server.serve());
+    thread::sleep(Duration::from_millis(200));
+    
+    let mut stream = TcpStream::connect("127.0.0.1:16387").unwrap();
+    stream.set_nodelay(true).unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    
+    // Send the command in two segments so the server sees two separate reads
+    stream.write_all(b"SET split_key spl").unwrap();
+    thread::sleep(Duration::from_millis(100));
+    stream.write_all(b"it_value\n").unwrap();
+    
+    let mut response = String::new();
+    reader.read_line(&mut response).unwrap();
+    assert_eq!(response.trim(), "OK");
+    
+    stream.write_all(b"GET split_key\n").unwrap();
+    response.clear();
+    reader.read_line(&mut response).unwrap();
+    assert_eq!(response.trim(), "split_value");
+    
+    // Cleanup
+    std::fs::remove_dir_all("./test_db_uring").ok();
+}
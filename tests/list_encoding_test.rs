@@ -0,0 +1,190 @@
+use diskdb::commands::CommandExecutor;
+use diskdb::protocol::{Request, Response};
+use diskdb::storage::rocksdb_storage::RocksDBStorage;
+use proptest::prelude::*;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+// Small enough that a handful of operations crosses the listpack/quicklist boundary
+const MAX_ENTRIES: usize = 4;
+const MAX_BYTES: usize = 48;
+
+#[derive(Debug, Clone)]
+enum Op {
+    LPush(Vec<String>),
+    RPush(Vec<String>),
+    LPop,
+    RPop,
+    /// LPOP or RPOP with a count
+    PopCount(bool, usize),
+    LRange(i64, i64),
+    LSet(i64, String),
+    /// Pivot is picked by position in the current list, so it is usually present
+    LInsert(bool, usize, String),
+    LTrim(i64, i64),
+}
+
+fn value() -> impl Strategy<Value = String> {
+    // Mostly short values, sometimes one long enough to trip the byte limit alone
+    prop_oneof![4 => "[a-c]{1,3}", 1 => "[a-z]{20,40}"]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        prop::collection::vec(value(), 1..4).prop_map(Op::LPush),
+        prop::collection::vec(value(), 1..4).prop_map(Op::RPush),
+        Just(Op::LPop),
+        Just(Op::RPop),
+        (any::<bool>(), 0usize..6).prop_map(|(from_left, count)| Op::PopCount(from_left, count)),
+        (-6i64..6, -6i64..6).prop_map(|(start, stop)| Op::LRange(start, stop)),
+        (-6i64..6, value()).prop_map(|(index, value)| Op::LSet(index, value)),
+        (any::<bool>(), 0usize..8, value()).prop_map(|(before, pivot, value)| Op::LInsert(before, pivot, value)),
+        (-6i64..6, -6i64..6).prop_map(|(start, stop)| Op::LTrim(start, stop)),
+    ]
+}
+
+/// Apply `op` to the reference list, returning the request for DiskDB and the reply it must give
+fn apply(model: &mut Vec<String>, op: Op) -> (Request, Response) {
+    let key = "list".to_string();
+    let resolve = |index: i64, len: usize| {
+        let index = if index < 0 { len as i64 + index } else { index };
+        usize::try_from(index).ok().filter(|&i| i < len)
+    };
+    let bounds = |start: i64, stop: i64, len: usize| {
+        let len = len as i64;
+        let from = if start < 0 { (len + start).max(0) } else { start.min(len) } as usize;
+        let to = if stop < 0 { (len + stop + 1).max(0) } else { (stop + 1).min(len) } as usize;
+        (from, to)
+    };
+    let strings = |items: &[String]| Response::Array(items.iter().map(|item| Response::String(Some(item.clone()))).collect());
+    match op {
+        Op::LPush(values) => {
+            for value in &values {
+                model.insert(0, value.clone());
+            }
+            (Request::LPush { key, values }, Response::Integer(model.len() as i64))
+        }
+        Op::RPush(values) => {
+            model.extend(values.iter().cloned());
+            (Request::RPush { key, values }, Response::Integer(model.len() as i64))
+        }
+        Op::LPop => {
+            let popped = (!model.is_empty()).then(|| model.remove(0));
+            (Request::LPop { key, count: None }, popped.map_or(Response::Null, |v| Response::String(Some(v))))
+        }
+        Op::RPop => (Request::RPop { key, count: None }, model.pop().map_or(Response::Null, |v| Response::String(Some(v)))),
+        Op::PopCount(from_left, count) => {
+            let reply = if model.is_empty() {
+                Response::NullArray
+            } else {
+                let count = count.min(model.len());
+                let mut popped: Vec<String> = if from_left {
+                    model.drain(..count).collect()
+                } else {
+                    model.drain(model.len() - count..).collect()
+                };
+                if !from_left {
+                    popped.reverse();
+                }
+                strings(&popped)
+            };
+            let count = Some(count);
+            let request = if from_left { Request::LPop { key, count } } else { Request::RPop { key, count } };
+            (request, reply)
+        }
+        Op::LRange(start, stop) => {
+            let (from, to) = bounds(start, stop, model.len());
+            let items = if from < to { &model[from..to] } else { &[] };
+            (Request::LRange { key, start, stop }, strings(items))
+        }
+        Op::LSet(index, value) => {
+            let reply = if model.is_empty() {
+                Response::Error("ERR no such key".to_string())
+            } else if let Some(i) = resolve(index, model.len()) {
+                model[i] = value.clone();
+                Response::Ok
+            } else {
+                Response::Error("ERR index out of range".to_string())
+            };
+            (Request::LSet { key, index, value }, reply)
+        }
+        Op::LInsert(before, pivot, value) => {
+            let pivot = model.get(pivot).cloned().unwrap_or_else(|| "missing".to_string());
+            let reply = if model.is_empty() {
+                Response::Integer(0)
+            } else if let Some(at) = model.iter().position(|item| *item == pivot) {
+                model.insert(if before { at } else { at + 1 }, value.clone());
+                Response::Integer(model.len() as i64)
+            } else {
+                Response::Integer(-1)
+            };
+            (Request::LInsert { key, before, pivot, value }, reply)
+        }
+        Op::LTrim(start, stop) => {
+            let (from, to) = bounds(start, stop, model.len());
+            *model = if from < to { model[from..to].to_vec() } else { Vec::new() };
+            (Request::LTrim { key, start, stop }, Response::Ok)
+        }
+    }
+}
+
+fn check_ops(ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+        let executor = CommandExecutor::new(storage).with_debug_command(true);
+        let limits = Request::DebugListpackLimits { entries: MAX_ENTRIES, bytes: MAX_BYTES };
+        prop_assert_eq!(executor.execute(limits).await.unwrap(), Response::Ok);
+
+        let mut model = Vec::new();
+        for op in ops {
+            let (request, expected) = apply(&mut model, op.clone());
+            prop_assert_eq!(executor.execute(request).await.unwrap(), expected, "{:?}", op);
+
+            let range = executor.execute(Request::LRange { key: "list".to_string(), start: 0, stop: -1 }).await.unwrap();
+            let items = model.iter().map(|item| Response::String(Some(item.clone()))).collect();
+            prop_assert_eq!(range, Response::Array(items), "after {:?}", op);
+            let len = executor.execute(Request::LLen { key: "list".to_string() }).await.unwrap();
+            prop_assert_eq!(len, Response::Integer(model.len() as i64), "after {:?}", op);
+
+            let encoding = executor.execute(Request::ObjectEncoding { key: "list".to_string() }).await.unwrap();
+            let expected = if model.is_empty() {
+                Response::Null
+            } else if model.len() <= MAX_ENTRIES && model.iter().map(String::len).sum::<usize>() <= MAX_BYTES {
+                Response::String(Some("listpack".to_string()))
+            } else {
+                Response::String(Some("quicklist".to_string()))
+            };
+            prop_assert_eq!(encoding, expected, "after {:?}", op);
+        }
+        Ok(())
+    })
+}
+
+#[tokio::test]
+async fn test_listpack_limits_apply_to_one_storage() {
+    let (dir_a, dir_b) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let lowered = CommandExecutor::new(Arc::new(RocksDBStorage::new(dir_a.path()).unwrap())).with_debug_command(true);
+    let other = CommandExecutor::new(Arc::new(RocksDBStorage::new(dir_b.path()).unwrap()));
+    let limits = Request::DebugListpackLimits { entries: MAX_ENTRIES, bytes: MAX_BYTES };
+    assert_eq!(lowered.execute(limits).await.unwrap(), Response::Ok);
+
+    let values: Vec<String> = (0..MAX_ENTRIES + 1).map(|i| i.to_string()).collect();
+    for executor in [&lowered, &other] {
+        let push = Request::RPush { key: "list".to_string(), values: values.clone() };
+        assert_eq!(executor.execute(push).await.unwrap(), Response::Integer(values.len() as i64));
+    }
+    let encoding = || Request::ObjectEncoding { key: "list".to_string() };
+    assert_eq!(lowered.execute(encoding()).await.unwrap(), Response::String(Some("quicklist".to_string())));
+    assert_eq!(other.execute(encoding()).await.unwrap(), Response::String(Some("listpack".to_string())));
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn list_matches_reference_across_encodings(ops in prop::collection::vec(op(), 1..40)) {
+        check_ops(ops)?;
+    }
+}
@@ -0,0 +1,61 @@
+use diskdb::data_types::{DataType, EncodingLimits};
+use diskdb::storage::rocksdb_storage::RocksDBStorage;
+use diskdb::storage::Storage;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tempfile::TempDir;
+
+// Count allocations to compare decoding a listpack with decoding a full list
+struct AllocCounter;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for AllocCounter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: AllocCounter = AllocCounter;
+
+#[tokio::test]
+async fn test_small_list_decodes_with_fewer_allocations() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = RocksDBStorage::new(temp_dir.path()).unwrap();
+    
+    let items: Vec<String> = (0..64).map(|i| format!("item_{}", i)).collect();
+    // Written with the compact encoding turned off, as older versions stored every list
+    storage.set_encoding_limits(EncodingLimits { list_max_listpack_entries: 0, ..EncodingLimits::default() });
+    storage.set("full", DataType::List(items.clone().into())).await.unwrap();
+    storage.set_encoding_limits(EncodingLimits::default());
+    storage.set("packed", DataType::List(items.clone().into())).await.unwrap();
+    
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let full = storage.get("full").await.unwrap();
+    let full_allocations = ALLOCATIONS.load(Ordering::SeqCst) - before;
+    
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let packed = storage.get("packed").await.unwrap();
+    let packed_allocations = ALLOCATIONS.load(Ordering::SeqCst) - before;
+    
+    // Both encodings read back to the same list
+    assert!(matches!(&full, Some(DataType::List(list)) if *list == items));
+    match &packed {
+        Some(DataType::ListPack(pack)) => assert_eq!(pack.unpack(), items),
+        other => panic!("expected a listpack, got {:?}", other),
+    }
+    assert_eq!(packed.unwrap().encoding(&storage.encoding_limits()), "listpack");
+    
+    // A full list allocates once per element; a listpack once for its buffer
+    assert!(full_allocations >= items.len());
+    assert!(
+        packed_allocations + items.len() / 2 < full_allocations,
+        "listpack made {} allocations, full list {}", packed_allocations, full_allocations,
+    );
+}
@@ -114,7 +114,7 @@ mod memory_leak_tests {
         for _ in 0..10000 {
             let requests = vec![
                 Request::Get { key: "key1".to_string() },
-                Request::Set { key: "key2".to_string(), value: "x".repeat(1000) },
+                Request::Set { key: "key2".to_string(), value: "x".repeat(1000), options: Default::default() },
                 Request::LPush { 
                     key: "list1".to_string(), 
                     values: (0..100).map(|i| format!("item{}", i)).collect() 
@@ -212,7 +212,7 @@ mod memory_leak_tests {
         for _ in 0..1000 {
             let input = "SET key value_with_some_content";
             match Request::parse(input) {
-                Ok(Request::Set { key, value }) => {
+                Ok(Request::Set { key, value, .. }) => {
                     let _pooled_key = PooledString::from_str(&key).unwrap();
                     let _pooled_value = PooledString::from_str(&value).unwrap();
                 }
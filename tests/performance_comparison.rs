@@ -152,6 +152,7 @@ mod performance_comparison {
                 requests.push(Request::Set {
                     key: format!("pipeline_key{}", idx),
                     value: format!("pipeline_value{}", idx),
+                    options: Default::default(),
                 });
             }
             
@@ -240,7 +240,7 @@ mod performance_tests {
             for cmd in &commands {
                 let request = Request::parse(cmd).unwrap();
                 match request {
-                    Request::Set { key, value } => {
+                    Request::Set { key, value, .. } => {
                         let _ = PooledStorageOps::create_string(&value);
                     }
                     Request::LPush { key, values } => {
@@ -0,0 +1,55 @@
+use diskdb::network::PubSub;
+use diskdb::{PubSubOverflow, Response};
+use std::sync::Arc;
+
+fn message(channel: &str, text: &str) -> Response {
+    Response::Array(vec![
+        Response::String(Some("message".to_string())),
+        Response::String(Some(channel.to_string())),
+        Response::String(Some(text.to_string())),
+    ])
+}
+
+#[tokio::test]
+async fn test_full_queue_drops_the_oldest_messages() {
+    let broker = Arc::new(PubSub::new().with_queue_limit(8, PubSubOverflow::DropOldest));
+    let mut slow = broker.subscriber();
+    let mut fast = broker.subscriber();
+    slow.subscribe(&["news".to_string()]);
+    fast.subscribe(&["news".to_string()]);
+    
+    // The subscriber that keeps up gets everything, whatever the other one does
+    for i in 0..100 {
+        assert_eq!(broker.publish("news", &i.to_string()), 2);
+        assert_eq!(fast.recv().await, Some(message("news", &i.to_string())));
+    }
+    
+    // The one that never read keeps the newest messages and counts the rest as lag
+    let queue = slow.queue();
+    assert_eq!((queue.queued(), queue.dropped()), (8, 92));
+    for i in 92..100 {
+        assert_eq!(slow.recv().await, Some(message("news", &i.to_string())));
+    }
+    assert_eq!(queue.queued(), 0);
+}
+
+#[tokio::test]
+async fn test_full_queue_disconnects_the_subscriber() {
+    let broker = Arc::new(PubSub::new().with_queue_limit(8, PubSubOverflow::Disconnect));
+    let mut slow = broker.subscriber();
+    let mut fast = broker.subscriber();
+    slow.subscribe(&["news".to_string()]);
+    fast.subscribe(&["news".to_string()]);
+    
+    for i in 0..8 {
+        assert_eq!(broker.publish("news", &i.to_string()), 2);
+        fast.recv().await;
+    }
+    // The message that does not fit closes the slow subscriber's queue
+    for i in 8..20 {
+        assert_eq!(broker.publish("news", &i.to_string()), 1);
+        assert_eq!(fast.recv().await, Some(message("news", &i.to_string())));
+    }
+    assert_eq!(slow.recv().await, None);
+    assert_eq!(slow.queue().queued(), 0);
+}
@@ -0,0 +1,130 @@
+use diskdb::resp::{decode_command, decode_reply, encode_reply, Decoded, Frame, RespVersion};
+use diskdb::Config;
+use diskdb::Response;
+
+fn encode(command: &str, response: Response, version: RespVersion) -> String {
+    let mut out = Vec::new();
+    encode_reply(command, response, version, &mut out);
+    String::from_utf8(out).unwrap()
+}
+
+fn text(s: &str) -> Response {
+    Response::String(Some(s.to_string()))
+}
+
+#[test]
+fn test_hgetall_is_a_flat_array_in_resp2_and_a_map_in_resp3() {
+    let reply = || Response::Array(vec![text("name"), text("ada"), text("age"), text("36")]);
+    
+    assert_eq!(
+        encode("hgetall", reply(), RespVersion::Resp2),
+        "*4\r\n$4\r\nname\r\n$3\r\nada\r\n$3\r\nage\r\n$2\r\n36\r\n",
+    );
+    assert_eq!(
+        encode("hgetall", reply(), RespVersion::Resp3),
+        "%2\r\n$4\r\nname\r\n$3\r\nada\r\n$3\r\nage\r\n$2\r\n36\r\n",
+    );
+    // Other arrays stay arrays
+    assert_eq!(encode("lrange", reply(), RespVersion::Resp3).lines().next(), Some("*4"));
+}
+
+#[test]
+fn test_resp3_types_fall_back_in_resp2() {
+    let cases = [
+        (Frame::Null, "$-1\r\n", "_\r\n"),
+        (Frame::NullArray, "*-1\r\n", "_\r\n"),
+        (Frame::Double(1.5), "$3\r\n1.5\r\n", ",1.5\r\n"),
+        (Frame::Double(f64::NEG_INFINITY), "$4\r\n-inf\r\n", ",-inf\r\n"),
+        (Frame::Boolean(true), ":1\r\n", "#t\r\n"),
+        (Frame::BigNumber("12345678901234567890".to_string()), "$20\r\n12345678901234567890\r\n", "(12345678901234567890\r\n"),
+        (Frame::Set(vec![Frame::Integer(1)]), "*1\r\n:1\r\n", "~1\r\n:1\r\n"),
+        (Frame::Push(vec![Frame::Simple("x".to_string())]), "*1\r\n+x\r\n", ">1\r\n+x\r\n"),
+    ];
+    for (frame, resp2, resp3) in cases {
+        assert_eq!(String::from_utf8(frame.to_bytes(RespVersion::Resp2)).unwrap(), resp2, "{:?}", frame);
+        assert_eq!(String::from_utf8(frame.to_bytes(RespVersion::Resp3)).unwrap(), resp3, "{:?}", frame);
+    }
+}
+
+#[test]
+fn test_reply_shapes() {
+    assert_eq!(Frame::reply("zscore", text("2.5")), Frame::Double(2.5));
+    assert_eq!(Frame::reply("get", text("2.5")), Frame::Bulk("2.5".to_string()));
+    assert!(matches!(Frame::reply("smembers", Response::Array(vec![text("a")])), Frame::Set(_)));
+    
+    // Errors always carry a code
+    assert_eq!(encode("get", Response::Error("WRONGTYPE bad".to_string()), RespVersion::Resp3), "-WRONGTYPE bad\r\n");
+    assert_eq!(encode("client", Response::Error("No such client".to_string()), RespVersion::Resp3), "-ERR No such client\r\n");
+    
+    // Two subscriptions are confirmed with two pushes
+    let confirmations = Response::Array(vec![
+        text("subscribe"), text("a"), Response::Integer(1),
+        text("subscribe"), text("b"), Response::Integer(2),
+    ]);
+    assert_eq!(encode("subscribe", confirmations, RespVersion::Resp3).matches(">3\r\n").count(), 2);
+}
+
+#[test]
+fn test_multibulk_command_is_reassembled_byte_by_byte() {
+    let frame = b"*3\r\n$3\r\nSET\r\n$5\r\nmykey\r\n$11\r\nhello world\r\n*1\r\n";
+    let end = frame.len() - 4;
+    
+    for cut in 0..end {
+        assert_eq!(decode_command(&frame[..cut], 1024, 1024).unwrap(), Decoded::Incomplete, "cut at {}", cut);
+    }
+    let args = vec!["SET".to_string(), "mykey".to_string(), "hello world".to_string()];
+    assert_eq!(decode_command(&frame[..end], 1024, 1024).unwrap(), Decoded::Command(args.clone(), end));
+    // Bytes of the next request are left alone
+    assert_eq!(decode_command(frame, 1024, 1024).unwrap(), Decoded::Command(args, end));
+}
+
+#[test]
+fn test_oversized_bulk_length_is_rejected_before_the_payload() {
+    assert!(decode_command(b"*2\r\n$3\r\nGET\r\n$999999999999\r\n", 1024, 1024).is_err());
+    assert!(decode_command(b"*1\r\n$1025\r\n", 1024, 1024).is_err());
+    assert!(decode_command(b"*1\r\n$-5\r\n", 1024, 1024).is_err());
+    assert!(decode_command(b"*99999999999\r\n", 1024, 1024).is_err());
+    assert!(decode_command(b"*1\r\n$3\r\nGETxx", 1024, 1024).is_err());
+    assert_eq!(decode_command(b"*1\r\n$1024\r\n", 1024, 1024).unwrap(), Decoded::Incomplete);
+}
+
+#[test]
+fn test_default_limits_reject_huge_headers_without_allocating() {
+    let config = Config::default();
+    let (max_bulk, max_multibulk) = (config.proto_max_bulk_len, config.proto_max_multibulk_len);
+    assert!(decode_command(b"*100000000\r\n", max_bulk, max_multibulk).is_err());
+    assert!(decode_command(b"*1\r\n$999999999\r\n", max_bulk, max_multibulk).is_err());
+    
+    // Exactly at a limit is fine and waits for the rest
+    assert_eq!(decode_command(b"*3\r\n", 1024, 3).unwrap(), Decoded::Incomplete);
+    assert!(decode_command(b"*4\r\n", 1024, 3).is_err());
+}
+
+#[test]
+fn test_replies_decode_whole_and_flatten_resp3_types() {
+    let hash = Response::Array(vec![text("f1"), text("v1"), text("f2"), Response::Integer(2)]);
+    let mut buf = Vec::new();
+    encode_reply("hgetall", hash, RespVersion::Resp3, &mut buf);
+    encode_reply("zscore", text("1.5"), RespVersion::Resp3, &mut buf);
+    encode_reply("get", Response::Null, RespVersion::Resp3, &mut buf);
+    
+    // The map comes back as the flat array it was built from
+    let (first, used) = decode_reply(&buf).unwrap().unwrap();
+    assert_eq!(first, Response::Array(vec![text("f1"), text("v1"), text("f2"), Response::Integer(2)]));
+    let (second, used2) = decode_reply(&buf[used..]).unwrap().unwrap();
+    assert_eq!(second, text("1.5"));
+    let (third, used3) = decode_reply(&buf[used + used2..]).unwrap().unwrap();
+    assert_eq!(third, Response::Null);
+    assert_eq!(used + used2 + used3, buf.len());
+    
+    // Every proper prefix is incomplete rather than a shorter reply
+    for end in 0..used {
+        assert_eq!(decode_reply(&buf[..end]).unwrap(), None, "prefix of {} bytes", end);
+    }
+    assert_eq!(decode_reply(b"+OK\r\n").unwrap(), Some((Response::Ok, 5)));
+    assert_eq!(decode_reply(b"$-1\r\n").unwrap(), Some((Response::Null, 5)));
+    assert_eq!(decode_reply(b"*-1\r\n").unwrap(), Some((Response::NullArray, 5)));
+    assert_eq!(decode_reply(b"*0\r\n").unwrap(), Some((Response::Array(Vec::new()), 4)));
+    assert_eq!(decode_reply(b"-ERR bad\r\n").unwrap(), Some((Response::Error("ERR bad".to_string()), 10)));
+    assert!(decode_reply(b"?\r\n").is_err());
+}
@@ -0,0 +1,31 @@
+use diskdb::Response;
+use serde_json::json;
+
+fn text(s: &str) -> Response {
+    Response::String(Some(s.to_string()))
+}
+
+#[test]
+fn test_scalars_render_as_json() {
+    assert_eq!(Response::Ok.to_json(), json!("OK"));
+    assert_eq!(Response::Integer(-7).to_json(), json!(-7));
+    assert_eq!(text("héllo \"quoted\"").to_json(), json!("héllo \"quoted\""));
+    assert_eq!(Response::String(None).to_json(), json!(null));
+    assert_eq!(Response::Null.to_json(), json!(null));
+    assert_eq!(Response::Error("WRONGTYPE bad".to_string()).to_json(), json!({ "error": "WRONGTYPE bad" }));
+}
+
+#[test]
+fn test_arrays_render_as_nested_json_arrays() {
+    let reply = Response::Array(vec![Response::Integer(1), text("x"), Response::Null]);
+    assert_eq!(reply.to_json(), json!([1, "x", null]));
+    
+    let nested = Response::Array(vec![
+        Response::Array(vec![text("field"), text("value")]),
+        Response::Array(Vec::new()),
+    ]);
+    assert_eq!(nested.to_json(), json!([["field", "value"], []]));
+    
+    // The text format is unchanged
+    assert_eq!(Response::Integer(1).to_string(), "1\n");
+}
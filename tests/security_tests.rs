@@ -28,7 +28,7 @@ mod security_tests {
         let long_value = "B".repeat(10_000_000);
         let cmd = format!("SET key {}", long_value);
         match Request::parse(&cmd) {
-            Ok(Request::Set { key: _, value }) => {
+            Ok(Request::Set { key: _, value, .. }) => {
                 assert_eq!(value.len(), 10_000_000);
             }
             Err(_) => {
@@ -0,0 +1,228 @@
+use diskdb::data_types::DataType;
+use diskdb::storage::rocksdb_storage::RocksDBStorage;
+use diskdb::{Compression, Config, DiskDBError, Storage, StorageProfile};
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_every_storage_profile_opens() {
+    let profiles = [
+        StorageProfile::Balanced,
+        StorageProfile::WriteHeavy,
+        StorageProfile::ReadHeavy,
+        StorageProfile::LowMemory,
+    ];
+    
+    for profile in profiles {
+        for (block_cache_mb, write_buffer_mb) in [(None, None), (Some(16), Some(4))] {
+            let temp_dir = TempDir::new().unwrap();
+            let mut config = Config::new();
+            config.storage_profile = profile;
+            config.block_cache_mb = block_cache_mb;
+            config.write_buffer_mb = write_buffer_mb;
+            
+            let storage = RocksDBStorage::with_config(temp_dir.path(), &config)
+                .unwrap_or_else(|e| panic!("{:?} failed to open: {}", profile, e));
+            storage.set("key", DataType::String("value".to_string())).await.unwrap();
+            match storage.get("key").await.unwrap() {
+                Some(DataType::String(value)) => assert_eq!(value, "value"),
+                other => panic!("{:?} returned {:?}", profile, other),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_storage_profile_from_str() {
+    assert_eq!("read-heavy".parse(), Ok(StorageProfile::ReadHeavy));
+    assert_eq!("WRITE_HEAVY".parse(), Ok(StorageProfile::WriteHeavy));
+    assert_eq!("lowmemory".parse(), Ok(StorageProfile::LowMemory));
+    assert!("fast".parse::<StorageProfile>().is_err());
+}
+
+#[tokio::test]
+async fn test_zstd_round_trips_large_values() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = Config::new();
+    config.compression = Compression::Zstd;
+    let storage = RocksDBStorage::with_config(temp_dir.path(), &config).unwrap();
+    
+    let document = r#"{"user":"alice","roles":["admin","editor"],"active":true}"#.repeat(2000);
+    storage.set("doc", DataType::String(document.clone())).await.unwrap();
+    
+    match storage.get("doc").await.unwrap() {
+        Some(DataType::String(value)) => assert_eq!(value, document),
+        other => panic!("unexpected value {:?}", other.map(|v| v.type_name().to_string())),
+    }
+    assert_eq!(storage.compression_stats().await.unwrap().codec, "zstd");
+}
+
+#[tokio::test]
+async fn test_aggregates_match_a_full_recount() {
+    use diskdb::storage::now_millis;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+    
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    let text = |s: &str| DataType::String(s.to_string());
+    
+    storage.set("s1", text("a")).await.unwrap();
+    storage.set("s1", text("a much longer value")).await.unwrap();
+    storage.set("l1", DataType::List(vec!["x".to_string(), "y".to_string()].into())).await.unwrap();
+    storage.set("set1", DataType::Set(HashSet::from(["m".to_string()]))).await.unwrap();
+    storage.set("h1", DataType::Hash(HashMap::from([("f".to_string(), "v".to_string())]))).await.unwrap();
+    // Overwriting with another type moves the key between type counts
+    storage.set("l1", text("now a string")).await.unwrap();
+    storage.incr_by("counter", 5).await.unwrap();
+    storage.incr_by("counter", 99_995).await.unwrap();
+    storage.incr_by_float("float", 1.5).await.unwrap().unwrap();
+    storage.delete("set1").await.unwrap();
+    storage.delete("missing").await.unwrap();
+    storage.delete_multiple(&["h1".to_string(), "h1".to_string(), "nope".to_string()]).await.unwrap();
+    storage.set("gone", text("soon")).await.unwrap();
+    storage.expire_at("gone", now_millis() - 1).await.unwrap();
+    
+    // Concurrent writers racing on the same keys
+    let mut tasks = Vec::new();
+    for i in 0..8 {
+        let storage = storage.clone();
+        tasks.push(tokio::spawn(async move {
+            for j in 0..50 {
+                let key = format!("race{}", j % 5);
+                if (i + j) % 3 == 0 {
+                    storage.delete(&key).await.unwrap();
+                } else {
+                    storage.set(&key, DataType::String("v".repeat(i + j))).await.unwrap();
+                }
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+    
+    let maintained = storage.aggregates().await.unwrap();
+    assert_eq!(maintained, storage.recount_aggregates().unwrap());
+    assert_eq!(maintained.keys_by_type.get("list"), None);
+    assert_eq!(storage.dbsize().await.unwrap() as u64, maintained.keys);
+    
+    // The aggregates persist rather than being rebuilt on open
+    drop(storage);
+    let reopened = RocksDBStorage::new(temp_dir.path()).unwrap();
+    assert_eq!(reopened.aggregates().await.unwrap(), maintained);
+}
+
+#[tokio::test]
+async fn test_incr_by_float_checks_the_value_it_writes() {
+    use std::sync::Arc;
+    
+    let temp_dir = TempDir::new().unwrap();
+    let storage = Arc::new(RocksDBStorage::new(temp_dir.path()).unwrap());
+    
+    storage.set("text", DataType::String("abc".to_string())).await.unwrap();
+    assert_eq!(storage.incr_by_float("text", 1.0).await.unwrap(), Err("ERR value is not a valid float".to_string()));
+    assert!(matches!(storage.get("text").await.unwrap(), Some(DataType::String(s)) if s == "abc"));
+    
+    // A value swapped to text between the check and the write is never reported as incremented
+    let mut tasks = Vec::new();
+    for i in 0..4 {
+        let storage = storage.clone();
+        tasks.push(tokio::spawn(async move {
+            for j in 0..50 {
+                if i % 2 == 0 {
+                    let value = if j % 2 == 0 { "abc" } else { "1" };
+                    storage.set("race", DataType::String(value.to_string())).await.unwrap();
+                } else if let Ok(value) = storage.incr_by_float("race", 0.5).await.unwrap() {
+                    assert!(value.parse::<f64>().is_ok(), "{}", value);
+                }
+            }
+        }));
+    }
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_values_live_in_their_types_column_family() {
+    use std::collections::HashMap;
+    
+    let temp_dir = TempDir::new().unwrap();
+    let storage = RocksDBStorage::new(temp_dir.path()).unwrap();
+    
+    storage.set("s", DataType::String("v".to_string())).await.unwrap();
+    storage.set("l", DataType::List(vec!["x".to_string()].into())).await.unwrap();
+    storage.set("h", DataType::Hash(HashMap::from([("f".to_string(), "v".to_string())]))).await.unwrap();
+    storage.incr_by("n", 3).await.unwrap();
+    assert_eq!(storage.value_column_family("s").unwrap(), Some("strings"));
+    assert_eq!(storage.value_column_family("l").unwrap(), Some("lists"));
+    assert_eq!(storage.value_column_family("h").unwrap(), Some("hashes"));
+    assert_eq!(storage.value_column_family("n").unwrap(), Some("strings"));
+    
+    // Changing a key's type moves it, leaving nothing behind in the old family
+    storage.set("s", DataType::List(vec!["y".to_string()].into())).await.unwrap();
+    assert_eq!(storage.value_column_family("s").unwrap(), Some("lists"));
+    assert_eq!(storage.get_type("s").await.unwrap().as_deref(), Some("list"));
+    assert!(matches!(storage.get("s").await.unwrap(), Some(DataType::ListPack(pack)) if pack.unpack() == ["y"]));
+    
+    // INCR on another type leaves it where it is
+    assert!(storage.incr_by("l", 1).await.is_err());
+    assert_eq!(storage.value_column_family("l").unwrap(), Some("lists"));
+    
+    assert!(storage.delete("h").await.unwrap());
+    assert_eq!(storage.value_column_family("h").unwrap(), None);
+    assert!(!storage.exists("h").await.unwrap());
+    
+    let (_, keys) = storage.scan(0, 100, Some("list")).await.unwrap();
+    assert_eq!(keys, vec!["l".to_string(), "s".to_string()]);
+    assert_eq!(storage.aggregates().await.unwrap(), storage.recount_aggregates().unwrap());
+}
+
+#[tokio::test]
+async fn test_default_column_family_is_migrated_on_open() {
+    let temp_dir = TempDir::new().unwrap();
+    {
+        // A database from before values were split by type
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        let db = rocksdb::DB::open(&opts, temp_dir.path()).unwrap();
+        let list = DataType::List(vec!["a".to_string(), "b".to_string()].into());
+        db.put(b"old-list", bincode::serialize(&list).unwrap()).unwrap();
+        db.put(b"old-string", bincode::serialize(&DataType::String("kept".to_string())).unwrap()).unwrap();
+    }
+    
+    let storage = RocksDBStorage::new(temp_dir.path()).unwrap();
+    assert_eq!(storage.value_column_family("old-list").unwrap(), Some("lists"));
+    assert!(matches!(storage.get("old-string").await.unwrap(), Some(DataType::String(s)) if s == "kept"));
+    assert_eq!(storage.get_type("old-list").await.unwrap().as_deref(), Some("list"));
+    assert_eq!(storage.dbsize().await.unwrap(), 2);
+    
+    // Nothing is left to move the second time
+    drop(storage);
+    let reopened = RocksDBStorage::new(temp_dir.path()).unwrap();
+    assert_eq!(reopened.aggregates().await.unwrap(), reopened.recount_aggregates().unwrap());
+    assert_eq!(reopened.value_column_family("old-string").unwrap(), Some("strings"));
+}
+
+#[tokio::test]
+async fn test_opening_a_locked_database_is_a_clean_error() {
+    let temp_dir = TempDir::new().unwrap();
+    let storage = RocksDBStorage::new(temp_dir.path()).unwrap();
+    storage.set("key", DataType::String("value".to_string())).await.unwrap();
+    
+    // RocksDB holds a lock on the directory while it is open
+    match RocksDBStorage::new(temp_dir.path()) {
+        Err(DiskDBError::Database(msg)) => {
+            assert!(msg.contains("opening database at"), "{}", msg);
+            assert!(msg.contains(&temp_dir.path().display().to_string()), "{}", msg);
+        }
+        Err(other) => panic!("unexpected error: {}", other),
+        Ok(_) => panic!("opened a database that is already open"),
+    }
+    
+    // The first handle is unaffected, and the path opens again once it is released
+    assert!(matches!(storage.get("key").await.unwrap(), Some(DataType::String(v)) if v == "value"));
+    drop(storage);
+    let reopened = RocksDBStorage::new(temp_dir.path()).unwrap();
+    assert!(reopened.exists("key").await.unwrap());
+}
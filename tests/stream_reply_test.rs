@@ -0,0 +1,83 @@
+use bytes::BytesMut;
+use diskdb::network::optimized_connection::{write_array_reply, OutputGuard};
+use diskdb::protocol::Response;
+use diskdb::resp::{self, RespVersion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Track live heap bytes and their high-water mark while a reply is written
+struct AllocCounter;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for AllocCounter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let live = LIVE.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+        PEAK.fetch_max(live, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: AllocCounter = AllocCounter;
+
+fn items(count: usize, size: usize) -> Vec<Response> {
+    (0..count).map(|i| Response::String(Some(format!("{:0width$}", i, width = size)))).collect()
+}
+
+async fn streamed(resp: Option<RespVersion>, command: &str, items: Vec<Response>, chunk_bytes: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut buffer = BytesMut::new();
+    write_array_reply(&mut out, resp, command, items, &mut buffer, chunk_bytes, &mut OutputGuard::default()).await.unwrap();
+    out.extend_from_slice(&buffer);
+    out
+}
+
+#[tokio::test]
+async fn test_streamed_array_matches_whole_encoding() {
+    let mixed = || {
+        let mut mixed = items(50, 8);
+        mixed.push(Response::Null);
+        mixed.push(Response::Integer(7));
+        mixed.push(Response::Ok);
+        mixed.push(Response::Array(items(3, 2)));
+        mixed
+    };
+
+    for command in ["lrange", "smembers", "hgetall", "subscribe"] {
+        for version in [RespVersion::Resp2, RespVersion::Resp3] {
+            let mut whole = Vec::new();
+            resp::encode_reply(command, Response::Array(mixed()), version, &mut whole);
+            assert_eq!(streamed(Some(version), command, mixed(), 64).await, whole, "{} {:?}", command, version);
+        }
+    }
+
+    let text = Response::Array(mixed()).to_string().into_bytes();
+    assert_eq!(streamed(None, "lrange", mixed(), 64).await, text);
+    let empty = Response::Array(Vec::new()).to_string().into_bytes();
+    assert_eq!(streamed(None, "lrange", Vec::new(), 64).await, empty);
+}
+
+#[tokio::test]
+async fn test_streamed_array_is_not_buffered_whole() {
+    let items = items(20_000, 200);
+    let payload: usize = 20_000 * 200;
+    let chunk_bytes = 64 * 1024;
+    let mut buffer = BytesMut::new();
+    let mut sink = tokio::io::sink();
+
+    let baseline = LIVE.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+    let mut output = OutputGuard::default();
+    write_array_reply(&mut sink, Some(RespVersion::Resp2), "lrange", items, &mut buffer, chunk_bytes, &mut output).await.unwrap();
+    let growth = PEAK.load(Ordering::SeqCst).saturating_sub(baseline);
+
+    // Only about one chunk is ever held encoded, never the whole 4MB reply
+    assert!(growth < payload / 8, "peak grew by {} bytes for a {} byte reply", growth, payload);
+}
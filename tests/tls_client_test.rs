@@ -0,0 +1,52 @@
+use diskdb::client::TlsClientConfig;
+use diskdb::storage::rocksdb_storage::RocksDBStorage;
+use diskdb::{Config, OptimizedClient, Server};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name)
+}
+
+async fn start_tls_server(port: u16, db_path: &std::path::Path) {
+    let mut config = Config::new();
+    config.server_port = port;
+    config.database_path = db_path.to_path_buf();
+    config.use_tls = true;
+    config.cert_path = Some(fixture("cert.pem"));
+    config.key_path = Some(fixture("key.pem"));
+
+    let storage = Arc::new(RocksDBStorage::new(&config.database_path).unwrap());
+    let server = Server::new(config, storage).unwrap();
+
+    tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+
+    sleep(Duration::from_millis(100)).await;
+}
+
+#[tokio::test]
+async fn test_pooled_tls_client_roundtrip() {
+    let dir = tempfile::tempdir().unwrap();
+    start_tls_server(16400, dir.path()).await;
+
+    // The self-signed fixture certificate acts as its own root
+    let tls = TlsClientConfig::new("localhost").with_root_certificate(fixture("cert.pem"));
+    let client = OptimizedClient::connect_tls("127.0.0.1:16400", &tls).await.unwrap();
+    client.set("tls_key", "tls_value").await.unwrap();
+    assert_eq!(client.get("tls_key").await.unwrap(), Some("tls_value".to_string()));
+
+    // Without trusting the certificate the handshake is refused
+    let untrusted = OptimizedClient::connect_tls("127.0.0.1:16400", &TlsClientConfig::new("localhost"))
+        .await
+        .unwrap();
+    assert!(untrusted.get("tls_key").await.is_err());
+
+    // The insecure option skips verification, including the hostname check
+    let insecure = TlsClientConfig::new("not-the-server").insecure();
+    let client = OptimizedClient::connect_tls("127.0.0.1:16400", &insecure).await.unwrap();
+    assert_eq!(client.get("tls_key").await.unwrap(), Some("tls_value".to_string()));
+}
@@ -0,0 +1,85 @@
+#![cfg(feature = "tracing")]
+
+use diskdb::storage::rocksdb_storage::RocksDBStorage;
+use diskdb::{Config, OptimizedServer};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::sleep;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// Records every span as `name field=value ...`
+struct CapturingSubscriber {
+    spans: Arc<Mutex<Vec<String>>>,
+    next_id: AtomicU64,
+}
+
+struct FieldWriter<'a>(&'a mut String);
+
+impl Visit for FieldWriter<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.push_str(&format!(" {}={:?}", field.name(), value));
+    }
+}
+
+impl Subscriber for CapturingSubscriber {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+        let mut line = attrs.metadata().name().to_string();
+        attrs.record(&mut FieldWriter(&mut line));
+        self.spans.lock().unwrap().push(line);
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+    fn event(&self, _: &Event<'_>) {}
+    fn enter(&self, _: &Id) {}
+    fn exit(&self, _: &Id) {}
+}
+
+#[tokio::test]
+async fn test_connection_and_command_spans_are_recorded() {
+    let spans = Arc::new(Mutex::new(Vec::new()));
+    tracing::subscriber::set_global_default(CapturingSubscriber {
+        spans: spans.clone(),
+        next_id: AtomicU64::new(1),
+    })
+    .unwrap();
+
+    let mut config = Config::new();
+    config.server_port = 16402;
+    config.database_path = std::path::PathBuf::from("./test_db13");
+    let storage = Arc::new(RocksDBStorage::new(&config.database_path).unwrap());
+    let server = OptimizedServer::new(config, storage).unwrap();
+    tokio::spawn(async move {
+        server.start().await.unwrap();
+    });
+    sleep(Duration::from_millis(100)).await;
+
+    let stream = TcpStream::connect("127.0.0.1:16402").await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(b"SET traced yes\nPING\n").await.unwrap();
+    for expected in ["OK", "PONG"] {
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        assert_eq!(line.trim(), expected);
+    }
+
+    let spans = spans.lock().unwrap().clone();
+    assert!(spans.iter().any(|s| s.starts_with("connection addr=")), "{:?}", spans);
+    assert!(spans.iter().any(|s| s == "command name=SET"), "{:?}", spans);
+    assert!(spans.iter().any(|s| s == "command name=PING"), "{:?}", spans);
+
+    // Cleanup
+    std::fs::remove_dir_all("./test_db13").ok();
+}